@@ -0,0 +1,25 @@
+#!/bin/sh
+# Copyright (c) 2022 by Rivos Inc.
+# Licensed under the Apache License, Version 2.0, see LICENSE for details.
+# SPDX-License-Identifier: Apache-2.0
+
+# Builds the crate and the examples/feature_smoke.rs consumer with no features, with each feature
+# declared in Cargo.toml enabled in isolation, and with all features enabled. The features are
+# read from Cargo.toml so that new ones are covered without changes here.
+
+set -eu
+
+cd "$(dirname "$0")/.."
+
+features=$(sed -n '/^\[features\]/,/^\[/{/^[A-Za-z0-9_-]* *=/s/ *=.*//p}' Cargo.toml | grep -vx default || true)
+
+check() {
+    echo "feature-matrix: $*"
+    cargo check --quiet --lib --examples "$@"
+}
+
+check --no-default-features
+for feature in $features; do
+    check --no-default-features --features "$feature"
+done
+check --all-features
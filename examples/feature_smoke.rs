@@ -0,0 +1,33 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A representative consumer of the crate, built by `ci/feature-matrix.sh` with every feature
+//! combination to catch items in shared modules that are missing a `cfg` gate.
+
+use sbi_rs::*;
+
+fn main() {
+    for ext in SbiExtension::ALL {
+        let mut regs = [0; 8];
+        regs[7] = ext.eid();
+        // Decode a call to every extension the build knows about, touching the shared constants,
+        // message dispatch and error types.
+        #[cfg(feature = "handler")]
+        match SbiMessage::from_regs(&regs).map(|msg| msg.to_regs()) {
+            Ok(regs) => println!("{ext}: {regs:x?}"),
+            Err(e) => println!("{ext}: {e}"),
+        }
+        #[cfg(not(feature = "handler"))]
+        println!("{ext}: {regs:x?}");
+    }
+    let all: ExtensionSet = SbiExtension::ALL.into_iter().collect();
+    println!("{all}");
+
+    // Take the address of a caller-side wrapper so the `api` module is built.
+    #[cfg(feature = "caller")]
+    println!(
+        "{:p}",
+        sbi_rs::api::base::get_specification_version as fn() -> Result<u64>
+    );
+}
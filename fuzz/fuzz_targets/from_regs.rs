@@ -0,0 +1,22 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Decodes arbitrary register frames as a hypervisor decodes a guest's `ecall`s. Decoding must
+//! never panic, and any message it produces must display, validate and re-encode to registers
+//! that decode to the same message.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sbi_rs::SbiMessage;
+
+fuzz_target!(|regs: [u64; 8]| {
+    let Ok(msg) = SbiMessage::from_regs(&regs) else {
+        return;
+    };
+    let _ = msg.to_string();
+    let _ = SbiMessage::from_regs_strict(&regs);
+    let _ = msg.result(regs[0], regs[1]);
+    assert_eq!(SbiMessage::from_regs(&msg.to_regs()), Ok(msg));
+});
@@ -0,0 +1,58 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Decodes arbitrary register frames for a platform with a vendor and a firmware extension,
+//! exercising the decoders `define_vendor_extension!` and `define_firmware_extension!` generate
+//! alongside the standard ones.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sbi_rs::*;
+
+define_vendor_extension! {
+    /// A vendor extension with a function of each return kind.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum FuzzVendor(0x0900_0001) {
+        /// A function taking every argument register.
+        Copy(copy) = 0 {
+            /// a0.
+            a => a0,
+            /// a1.
+            b => a1,
+            /// a2.
+            c => a2,
+            /// a3.
+            d => a3,
+            /// a4.
+            e => a4,
+            /// a5.
+            f => a5,
+        },
+        /// A function without a return value.
+        Stop(stop) = 1 => Empty,
+    }
+}
+
+define_firmware_extension! {
+    /// A firmware extension with a single function.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum FuzzFirmware(0x0a00_0001) {
+        /// A function taking one argument.
+        Log(log) = 0 {
+            /// a0.
+            level => a0,
+        },
+    }
+}
+
+fuzz_target!(|regs: [u64; 8]| {
+    let Ok(msg) = PlatformSbiMessage::<FuzzVendor, FuzzFirmware>::from_regs(&regs) else {
+        return;
+    };
+    let _ = msg.to_string();
+    let _ = msg.result(regs[0], regs[1]);
+    let decoded = PlatformSbiMessage::<FuzzVendor, FuzzFirmware>::from_regs(&msg.to_regs());
+    assert_eq!(decoded, Ok(msg));
+});
@@ -0,0 +1,87 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A test kernel booted by OpenSBI on QEMU's virt machine, exercising the caller-side API through
+//! real `ecall`s with the `api::test_kernel` runtime. Each test's result is printed over the
+//! Debug Console, and the kernel exits with a System Reset call whose reason, and so QEMU's exit
+//! status, reports whether every test passed.
+//!
+//! Run with `cargo run --release` from this directory, with `qemu-system-riscv64` installed.
+
+#![no_std]
+#![no_main]
+
+use sbi_rs::api::test_kernel::{check, check_eq, check_ok, Test, TestResult};
+use sbi_rs::api::{base, hsm, ipi, probe, rfence, time};
+use sbi_rs::*;
+
+const TESTS: &[(&str, Test)] = &[
+    ("spec_version", spec_version),
+    ("probe_base", probe_base),
+    ("probe_unknown", probe_unknown),
+    ("capabilities", capabilities),
+    ("set_timer", set_timer),
+    ("hart_status", hart_status),
+    ("hart_start_invalid", hart_start_invalid),
+    ("send_ipi_empty", send_ipi_empty),
+    ("remote_fence_i", remote_fence_i),
+];
+
+fn spec_version(_hart_id: u64) -> TestResult {
+    let version = check_ok(base::get_specification_version(), "call failed")?;
+    // Every implementation with a Base extension implements at least SBI v0.2.
+    check(version >= 2, "version older than v0.2")
+}
+
+fn probe_base(_hart_id: u64) -> TestResult {
+    check(
+        base::probe_sbi_extension(EXT_BASE).is_ok(),
+        "Base not reported",
+    )
+}
+
+fn probe_unknown(_hart_id: u64) -> TestResult {
+    check_eq(
+        base::probe_sbi_extension(EXT_FIRMWARE_END),
+        Err(Error::NotSupported),
+        "unused extension ID reported",
+    )
+}
+
+fn capabilities(_hart_id: u64) -> TestResult {
+    let caps = probe::capabilities();
+    check(caps.supports(SbiExtension::Base), "Base missing")?;
+    check_eq(probe::capabilities(), caps, "cached capabilities differ")
+}
+
+fn set_timer(_hart_id: u64) -> TestResult {
+    check_ok(time::set_timer(u64::MAX), "set_timer failed")?;
+    check_ok(time::clear_timer(), "clear_timer failed")
+}
+
+fn hart_status(hart_id: u64) -> TestResult {
+    let status = check_ok(hsm::hart_status(hart_id), "call failed")?;
+    check_eq(status, HartState::Started, "running hart not started")
+}
+
+fn hart_start_invalid(_hart_id: u64) -> TestResult {
+    // Safety: The start fails as there is no such hart, so no code is run at the address.
+    let result = unsafe { api::state::hart_start(u64::MAX - 1, 0x8020_0000, 0) };
+    check_eq(
+        result,
+        Err(Error::InvalidParam),
+        "start of missing hart not rejected",
+    )
+}
+
+fn send_ipi_empty(_hart_id: u64) -> TestResult {
+    check_ok(ipi::send_ipi(HartMask::new()), "empty mask rejected")
+}
+
+fn remote_fence_i(hart_id: u64) -> TestResult {
+    let mask = check_ok(HartMask::from_hart_ids([hart_id]), "hart ID out of range")?;
+    check_ok(rfence::remote_fence_i(mask), "fence failed")
+}
+
+sbi_test_kernel!(TESTS);
@@ -0,0 +1,189 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Types for the physical addresses and lengths passed to SBI calls that refer to memory.
+//!
+//! Checked constructors enforce the alignment and range requirements of the calls taking them, so
+//! a value that made it into a message is known to describe a well-formed region. The raw
+//! constructors accept any value, as decoding must represent whatever a caller passed.
+//!
+//! ```
+//! use sbi_rs::{Error, Len, PageSize, PhysAddr};
+//!
+//! let base = PhysAddr::new_aligned(0x8020_0000, PageSize::Size2M).unwrap();
+//! let len = Len::from_pages(2, PageSize::Size4k).unwrap();
+//! assert_eq!(base.checked_add(len).unwrap().bits(), 0x8020_2000);
+//! assert_eq!(
+//!     PhysAddr::new_aligned(0x8020_0800, PageSize::Size4k),
+//!     Err(Error::InvalidAddress)
+//! );
+//! assert_eq!(Len::new_aligned(0x1800, PageSize::Size4k), Err(Error::InvalidParam));
+//! assert_eq!(
+//!     PhysAddr::new(u64::MAX).checked_add(len),
+//!     Err(Error::BadRange)
+//! );
+//! ```
+
+use core::fmt;
+
+use crate::error::*;
+
+/// The sizes of the pages referred to by SBI calls.
+#[repr(u64)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageSize {
+    /// 4KiB pages.
+    Size4k = 1 << 12,
+    /// 2MiB pages.
+    Size2M = 1 << 21,
+    /// 1GiB pages.
+    Size1G = 1 << 30,
+    /// 512GiB pages.
+    Size512G = 1 << 39,
+}
+
+impl PageSize {
+    /// Returns the size of the page in bytes.
+    pub const fn bytes(&self) -> u64 {
+        *self as u64
+    }
+
+    /// Returns the number of low address bits selecting a byte within the page.
+    pub const fn shift(&self) -> u32 {
+        self.bytes().trailing_zeros()
+    }
+}
+
+/// A physical address passed to an SBI call.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysAddr(u64);
+
+impl PhysAddr {
+    /// Creates a physical address from its raw value.
+    pub const fn new(addr: u64) -> Self {
+        Self(addr)
+    }
+
+    /// Creates a physical address aligned to `size`. Returns `Error::InvalidAddress` if `addr`
+    /// isn't.
+    pub const fn new_aligned(addr: u64, size: PageSize) -> Result<Self> {
+        let addr = Self(addr);
+        if !addr.is_aligned(size) {
+            return Err(Error::InvalidAddress);
+        }
+        Ok(addr)
+    }
+
+    /// Returns the raw value of the address.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns if the address is aligned to `size`.
+    pub const fn is_aligned(&self, size: PageSize) -> bool {
+        self.0 & (size.bytes() - 1) == 0
+    }
+
+    /// Returns the number of the `size` page containing the address.
+    pub const fn page_number(&self, size: PageSize) -> u64 {
+        self.0 >> size.shift()
+    }
+
+    /// Returns the address `len` bytes past this one. Returns `Error::BadRange` if the region
+    /// starting at the address wraps around the address space.
+    pub const fn checked_add(&self, len: Len) -> Result<Self> {
+        match self.0.checked_add(len.bytes()) {
+            Some(end) => Ok(Self(end)),
+            None => Err(Error::BadRange),
+        }
+    }
+}
+
+impl From<PhysAddr> for u64 {
+    fn from(addr: PhysAddr) -> u64 {
+        addr.bits()
+    }
+}
+
+impl fmt::Debug for PhysAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// The length in bytes of a region of memory passed to an SBI call.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Len(u64);
+
+impl Len {
+    /// Creates a length from its raw value in bytes.
+    pub const fn new(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    /// Creates the length of `num_pages` pages of `size`. Returns `Error::BadRange` if it doesn't
+    /// fit in 64 bits.
+    pub const fn from_pages(num_pages: u64, size: PageSize) -> Result<Self> {
+        match num_pages.checked_mul(size.bytes()) {
+            Some(bytes) => Ok(Self(bytes)),
+            None => Err(Error::BadRange),
+        }
+    }
+
+    /// Creates a length that is a whole number of `size` pages. Returns `Error::InvalidParam` if
+    /// `bytes` isn't.
+    pub const fn new_aligned(bytes: u64, size: PageSize) -> Result<Self> {
+        let len = Self(bytes);
+        if !len.is_aligned(size) {
+            return Err(Error::InvalidParam);
+        }
+        Ok(len)
+    }
+
+    /// Returns the length in bytes.
+    pub const fn bytes(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns if the length is zero.
+    pub const fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns if the length is a whole number of `size` pages.
+    pub const fn is_aligned(&self, size: PageSize) -> bool {
+        self.0 & (size.bytes() - 1) == 0
+    }
+}
+
+impl From<Len> for u64 {
+    fn from(len: Len) -> u64 {
+        len.bytes()
+    }
+}
+
+impl fmt::Debug for Len {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Returns the address of the `num_pages` pages of `page_size` at `addr`, or an error if `addr` is
+// misaligned or the pages run past the end of the address space.
+#[cfg(all(feature = "caller", feature = "tee"))]
+pub(crate) fn pages(addr: u64, page_size: PageSize, num_pages: u64) -> Result<PhysAddr> {
+    let addr = PhysAddr::new_aligned(addr, page_size)?;
+    addr.checked_add(Len::from_pages(num_pages, page_size)?)?;
+    Ok(addr)
+}
+
+// Returns the address and length of the page-aligned region of `len` bytes at `addr`, or an error
+// if either is misaligned or the region runs past the end of the address space.
+#[cfg(all(feature = "caller", feature = "tee"))]
+pub(crate) fn region(addr: u64, len: u64) -> Result<(PhysAddr, Len)> {
+    let addr = PhysAddr::new_aligned(addr, PageSize::Size4k)?;
+    let len = Len::new_aligned(len, PageSize::Size4k)?;
+    addr.checked_add(len)?;
+    Ok((addr, len))
+}
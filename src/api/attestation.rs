@@ -4,9 +4,11 @@
 
 use arrayvec::ArrayVec;
 
+use crate::api::probe;
+use crate::api::shared_buffer::with_shared_buffer;
 use crate::{
     ecall_send, AttestationCapabilities, AttestationFunction, Error, EvidenceFormat, Result,
-    SbiMessage, EVIDENCE_DATA_BLOB_SIZE, MAX_HASH_SIZE,
+    SbiExtension, SbiMessage, EVIDENCE_DATA_BLOB_SIZE, MAX_HASH_SIZE,
 };
 
 /// Maximum supported size for the attestation evidence certificate.
@@ -19,6 +21,7 @@ pub fn get_capabilities() -> Result<AttestationCapabilities> {
         caps_addr_out: (&caps as *const AttestationCapabilities) as u64,
         caps_size: core::mem::size_of::<AttestationCapabilities>() as u64,
     });
+    probe::require(SbiExtension::Attestation)?;
 
     // Safety: &caps is the single reference to a variable defined in this scope.
     unsafe { ecall_send(&msg) }?;
@@ -34,10 +37,10 @@ pub fn get_capabilities() -> Result<AttestationCapabilities> {
 ///
 /// * `cert_request` - The [Certificate Signing Request](https://datatracker.ietf.org/doc/html/rfc2986) buffer.
 /// * `request_data` - A data blob that will be included in the generated
-///                    certificate, as [UserNotice](https://datatracker.ietf.org/doc/html/rfc2986)
-///                    X.509 certificate extension. This is typically used to
-///                    pass a cryptographic nonce.
-/// * `evidence_format` - The format of the attestation evidence as defined by [`EvidenceFormat`](crate::EvidenceFormat).
+///   certificate, as [UserNotice](https://datatracker.ietf.org/doc/html/rfc2986)
+///   X.509 certificate extension. This is typically used to
+///   pass a cryptographic nonce.
+/// * `evidence_format` - The format of the attestation evidence as defined by [`EvidenceFormat`].
 pub fn get_evidence(
     cert_request: &[u8],
     request_data: &[u8],
@@ -46,31 +49,32 @@ pub fn get_evidence(
     if request_data.len() != EVIDENCE_DATA_BLOB_SIZE {
         return Err(Error::InvalidParam);
     }
+    probe::require(SbiExtension::Attestation)?;
 
-    let mut cert_bytes = ArrayVec::<u8, MAX_CERT_SIZE>::new();
-    let msg = SbiMessage::Attestation(AttestationFunction::GetEvidence {
-        cert_request_addr: cert_request.as_ptr() as u64,
-        cert_request_size: cert_request.len() as u64,
-        request_data_addr: request_data.as_ptr() as u64,
-        evidence_format: evidence_format as u64,
-        cert_addr_out: (cert_bytes.as_ptr()) as u64,
-        cert_size: MAX_CERT_SIZE as u64,
-    });
-
+    let mut cert_bytes: ArrayVec<u8, MAX_CERT_SIZE> = [0; MAX_CERT_SIZE].into();
     // Safety: GetEvidence only reads the pages pointed to by `cert_request` and
     // `request_data`. This is safe because they're owned by the borrowed slices
     // passed as arguments.
-    // GetEvidence writes to a single reference to `cert_bytes``, which is
-    // defined in this scope.
-    let len = unsafe { ecall_send(&msg) }?;
-
-    // Safety: cert_bytes is backed by a MAX_CERT_SIZE array.
-    unsafe {
-        if len as usize > MAX_CERT_SIZE {
-            return Err(Error::Failed);
-        }
-        cert_bytes.set_len(len as usize);
-    };
+    // GetEvidence writes to the buffer passed to the message builder, which is
+    // `cert_bytes`, defined in this scope.
+    let len = unsafe {
+        with_shared_buffer(
+            |cert| {
+                SbiMessage::Attestation(AttestationFunction::GetEvidence {
+                    cert_request_addr: cert_request.as_ptr() as u64,
+                    cert_request_size: cert_request.len() as u64,
+                    request_data_addr: request_data.as_ptr() as u64,
+                    evidence_format: evidence_format as u64,
+                    cert_addr_out: cert.addr(),
+                    cert_size: cert.len(),
+                })
+            },
+            &mut cert_bytes,
+        )
+    }?
+    .written()?
+    .len();
+    cert_bytes.truncate(len);
 
     Ok(cert_bytes)
 }
@@ -86,6 +90,7 @@ pub fn extend_measurement(digest: &[u8], index: usize) -> Result<()> {
         measurement_data_size: digest.len() as u64,
         measurement_index: index as u64,
     });
+    probe::require(SbiExtension::Attestation)?;
 
     // Safety: ExtendMeasurement only reads the pages pointed to by `digest`.
     // This is safe because they're owned by the borrowed slice passed as an
@@ -100,25 +105,61 @@ pub fn extend_measurement(digest: &[u8], index: usize) -> Result<()> {
 ///
 /// * `index` - The measurement register TCG PCR index.
 pub fn read_measurement(index: usize) -> Result<ArrayVec<u8, MAX_HASH_SIZE>> {
-    let mut msmt_bytes = ArrayVec::<u8, MAX_HASH_SIZE>::new();
+    probe::require(SbiExtension::Attestation)?;
+    let mut msmt_bytes: ArrayVec<u8, MAX_HASH_SIZE> = [0; MAX_HASH_SIZE].into();
+    // Safety: ReadMeasurement writes into the buffer passed to the message builder,
+    // which is `msmt_bytes`, defined in this scope.
+    let len = unsafe {
+        with_shared_buffer(
+            |msmt| {
+                SbiMessage::Attestation(AttestationFunction::ReadMeasurement {
+                    measurement_data_addr_out: msmt.addr(),
+                    measurement_data_size: msmt.len(),
+                    measurement_index: index as u64,
+                })
+            },
+            &mut msmt_bytes,
+        )
+    }?
+    .written()?
+    .len();
+    msmt_bytes.truncate(len);
 
-    let msg = SbiMessage::Attestation(AttestationFunction::ReadMeasurement {
-        measurement_data_addr_out: msmt_bytes.as_ptr() as u64,
-        measurement_data_size: MAX_HASH_SIZE as u64,
-        measurement_index: index as u64,
-    });
-
-    // Safety: ReadMeasurement writes into a single reference to `msmt_bytes`,
-    // which is defined in this scope.
-    let len = unsafe { ecall_send(&msg) }?;
+    Ok(msmt_bytes)
+}
 
-    // Safety: msmt_bytes is backed by a MAX_HASH_SIZE array.
-    unsafe {
-        if len as usize > MAX_HASH_SIZE {
-            return Err(Error::Failed);
-        }
-        msmt_bytes.set_len(len as usize);
-    };
+/// Extend a measurement register, after checking `digest` and `index` against the given
+/// attestation capabilities.
+/// # Arguments
+///
+/// * `caps` - The attestation capabilities returned by [`get_capabilities`].
+/// * `digest` - The digest to extend the measurement register with. Must be the size of the
+///   hash algorithm reported in `caps`.
+/// * `index` - The TCG PCR index of one of the runtime measurement registers reported in `caps`.
+pub fn extend_measurement_checked(
+    caps: &AttestationCapabilities,
+    digest: &[u8],
+    index: usize,
+) -> Result<()> {
+    caps.check_extend_measurement(digest.len() as u64, index as u64)?;
+    extend_measurement(digest, index)
+}
 
+/// Read a measurement register data, after checking `index` against the given attestation
+/// capabilities. Returns an error if the length of the returned data doesn't match the hash
+/// algorithm reported in `caps`.
+/// # Arguments
+///
+/// * `caps` - The attestation capabilities returned by [`get_capabilities`].
+/// * `index` - The TCG PCR index of one of the runtime measurement registers reported in `caps`.
+pub fn read_measurement_checked(
+    caps: &AttestationCapabilities,
+    index: usize,
+) -> Result<ArrayVec<u8, MAX_HASH_SIZE>> {
+    caps.check_read_measurement(MAX_HASH_SIZE as u64, index as u64)?;
+    let msmt_bytes = read_measurement(index)?;
+    if msmt_bytes.len() != caps.hash_algorithm.size() {
+        return Err(Error::Failed);
+    }
     Ok(msmt_bytes)
 }
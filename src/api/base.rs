@@ -2,7 +2,7 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 use crate::ecall_send;
-use crate::{BaseFunction::*, Error as SbiError, Result, SbiMessage};
+use crate::{BaseFunction::*, Error as SbiError, Result, SbiMessage, EXT_PUT_CHAR};
 
 /// Returns the implemented version of the SBI standard.
 pub fn get_specification_version() -> Result<u64> {
@@ -39,6 +39,29 @@ pub fn probe_sbi_extension(sbi_extension_id: u64) -> Result<u64> {
     }
 }
 
+/// Returns if the legacy (v0.1) extensions, such as the legacy console, can be used.
+///
+/// Implementations of SBI v0.1 have no Base extension and implement only the legacy extensions,
+/// so they are assumed to be available. Implementations of SBI v0.2 and v0.3 are probed for the
+/// legacy PutChar extension. The legacy extensions are deprecated from SBI v1.0 on and may be
+/// absent, so they are never probed on those implementations and reported as unavailable;
+/// callers should use the replacement extensions, such as the Debug Console, instead.
+pub fn legacy_available() -> bool {
+    match get_specification_version() {
+        Err(_) => true,
+        Ok(version) if spec_version_major(version) == 0 => {
+            probe_sbi_extension(EXT_PUT_CHAR).is_ok()
+        }
+        Ok(_) => false,
+    }
+}
+
+// Returns the major number of the SBI specification version returned by
+// `get_specification_version()`.
+fn spec_version_major(version: u64) -> u64 {
+    (version >> 24) & 0x7f
+}
+
 /// Returns the vendor that produced this machine(`mvendorid`).
 pub fn get_machine_vendor_id() -> Result<u64> {
     let msg = SbiMessage::Base(GetMachineVendorID);
@@ -2,17 +2,144 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{ecall_send, DebugConsoleFunction, Result, SbiMessage};
+use core::fmt;
+
+use crate::api::fallback::Fallback;
+use crate::api::probe;
+use crate::{
+    ecall_send, DebugConsoleFunction, Error, Len, PhysAddr, Result, SbiExtension, SbiMessage,
+};
+
+/// A function printing a string to the console.
+pub type ConsolePuts = fn(&[u8]) -> Result<()>;
+
+/// A function translating the virtual address of a byte to its physical address.
+pub type VirtToPhys = fn(usize) -> u64;
+
+// The granule within which buffers are assumed to be physically contiguous: the smallest page.
+const PAGE_SIZE: usize = 4096;
 
 /// Prints the given string in a platfrom-dependent way.
 pub fn console_puts(chars: &[u8]) -> Result<()> {
-    let msg = SbiMessage::DebugConsole(DebugConsoleFunction::PutString {
-        len: chars.len() as u64,
-        addr: chars.as_ptr() as u64,
-    });
+    DebugConsoleWriter::new().write_bytes(chars)
+}
+
+/// A writer printing to the console through the Debug Console extension, for `write!` and
+/// `writeln!`. Strings are passed to the SBI implementation by physical address, a page at a time
+/// so that they needn't be physically contiguous, and writes the implementation only partly
+/// completes are retried until the whole string is printed.
+///
+/// ```no_run
+/// use core::fmt::Write;
+/// use sbi_rs::api::debug_console::DebugConsoleWriter;
+///
+/// writeln!(DebugConsoleWriter::new(), "booting hart {}", 0).unwrap();
+/// ```
+///
+/// Partial writes are retried, as seen here against a software backend that prints at most three
+/// bytes per call. The writer checks that the Debug Console extension is supported with
+/// `probe::require()` first, so the backend also answers the Base extension's probes:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use core::fmt::Write;
+/// use sbi_rs::api::debug_console::DebugConsoleWriter;
+/// use sbi_rs::*;
+/// use std::cell::RefCell;
+///
+/// struct Console(RefCell<Vec<u8>>);
+///
+/// impl EcallSender for Console {
+///     unsafe fn send(&self, regs: &[u64; 8]) -> SbiReturn {
+///         if regs[7] == EXT_BASE {
+///             return SbiReturn::success(1);
+///         }
+///         let len = regs[0].min(3) as usize;
+///         let bytes = core::slice::from_raw_parts(regs[1] as *const u8, len);
+///         self.0.borrow_mut().extend_from_slice(bytes);
+///         SbiReturn::success(len as u64)
+///     }
+/// }
+///
+/// let console = Console(RefCell::new(Vec::new()));
+/// with_ecall_sender(&console, || writeln!(DebugConsoleWriter::new(), "hello").unwrap());
+/// assert_eq!(console.0.into_inner(), b"hello\n");
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct DebugConsoleWriter {
+    virt_to_phys: Option<VirtToPhys>,
+}
+
+impl DebugConsoleWriter {
+    /// Creates a writer for a guest whose addresses are identity-mapped, as in early boot.
+    pub const fn new() -> Self {
+        Self { virt_to_phys: None }
+    }
 
-    // Safety: The sbi implementation is trusted not to write memory when printing to the console.
-    unsafe { ecall_send(&msg) }?;
+    /// Creates a writer translating the addresses of the strings it prints with `virt_to_phys`,
+    /// for guests running with address translation enabled.
+    pub const fn with_translation(virt_to_phys: VirtToPhys) -> Self {
+        Self {
+            virt_to_phys: Some(virt_to_phys),
+        }
+    }
 
+    /// Prints `bytes`, retrying until all of them are written. Returns `Error::NotSupported`
+    /// without making the call if the Debug Console extension isn't supported, or `Error::Failed`
+    /// if the SBI implementation stops making progress.
+    pub fn write_bytes(&self, mut bytes: &[u8]) -> Result<()> {
+        probe::require(SbiExtension::DebugConsole)?;
+        while !bytes.is_empty() {
+            let addr = bytes.as_ptr() as usize;
+            let len = bytes.len().min(PAGE_SIZE - addr % PAGE_SIZE);
+            let phys_addr = PhysAddr::new(self.virt_to_phys.map_or(addr as u64, |f| f(addr)));
+            // A translation ending past the top of the address space can't describe the string.
+            phys_addr.checked_add(Len::new(len as u64))?;
+            let msg = SbiMessage::DebugConsole(DebugConsoleFunction::PutString {
+                len: Len::new(len as u64),
+                addr: phys_addr,
+            });
+            // Safety: The sbi implementation is trusted not to write memory when printing to the
+            // console.
+            let written = unsafe { ecall_send(&msg) }?;
+            if written == 0 || written > len as u64 {
+                return Err(Error::Failed);
+            }
+            bytes = &bytes[written as usize..];
+        }
+        Ok(())
+    }
+}
+
+impl Default for DebugConsoleWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Write for DebugConsoleWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Prints the given string one character at a time through the legacy PutChar extension.
+pub fn legacy_console_puts(chars: &[u8]) -> Result<()> {
+    for &c in chars {
+        let msg = SbiMessage::PutChar(c as u64);
+        // Safety: PutChar doesn't touch memory.
+        unsafe { ecall_send(&msg) }?;
+    }
     Ok(())
 }
+
+/// Returns a console printing with `console_puts()` if the Debug Console extension is supported
+/// and with `legacy_console_puts()` otherwise.
+pub fn console() -> Fallback<ConsolePuts> {
+    Fallback::probed(
+        SbiExtension::DebugConsole,
+        console_puts,
+        legacy_console_puts,
+    )
+}
@@ -0,0 +1,70 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Graceful degradation for optional extensions.
+//!
+//! A `Fallback` holds a preferred implementation of a service that depends on an optional
+//! extension, such as printing through the Debug Console extension, and a fallback used when the
+//! extension isn't available, such as printing through the legacy PutChar extension. The choice
+//! is made once, when the `Fallback` is created, so code using the service calls it through a
+//! single method without checking for the extension at every call site.
+//!
+//! ```no_run
+//! use sbi_rs::api::debug_console;
+//!
+//! let puts = debug_console::console();
+//! puts.call(b"hello\n").unwrap();
+//! ```
+
+use crate::api::probe;
+use crate::SbiExtension;
+
+/// A preferred implementation of a service and the fallback used in its place, chosen once.
+#[derive(Clone, Copy, Debug)]
+pub struct Fallback<T> {
+    preferred: T,
+    fallback: T,
+    use_preferred: bool,
+}
+
+impl<T> Fallback<T> {
+    /// Creates a `Fallback` using `preferred` if `use_preferred` is true and `fallback`
+    /// otherwise, for services whose availability is determined by the caller, such as ISA
+    /// extensions discovered from the device tree.
+    pub const fn new(use_preferred: bool, preferred: T, fallback: T) -> Self {
+        Self {
+            preferred,
+            fallback,
+            use_preferred,
+        }
+    }
+
+    /// Creates a `Fallback` using `preferred` if the SBI implementation supports `ext`, as
+    /// reported by `probe::supports()`, and `fallback` otherwise.
+    pub fn probed(ext: SbiExtension, preferred: T, fallback: T) -> Self {
+        Self::new(probe::supports(ext), preferred, fallback)
+    }
+
+    /// Returns if the preferred implementation was chosen.
+    pub fn uses_preferred(&self) -> bool {
+        self.use_preferred
+    }
+
+    /// Returns the chosen implementation.
+    pub fn get(&self) -> &T {
+        if self.use_preferred {
+            &self.preferred
+        } else {
+            &self.fallback
+        }
+    }
+
+    /// Calls the chosen implementation with `arg`.
+    pub fn call<A, R>(&self, arg: A) -> R
+    where
+        T: Fn(A) -> R,
+    {
+        (self.get())(arg)
+    }
+}
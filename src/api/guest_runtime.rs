@@ -0,0 +1,124 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The SBI services needed by a minimal guest, such as a test kernel, bundled behind one type.
+//!
+//! ```no_run
+//! # use core::fmt::Write;
+//! # use sbi_rs::api::guest_runtime::GuestRuntime;
+//! let runtime = GuestRuntime::new();
+//! writeln!(runtime.console(), "running on SBI {:#x}", runtime.spec_version()).unwrap();
+//! runtime.set_timer(0).unwrap();
+//! ```
+
+use core::fmt;
+use core::panic::PanicInfo;
+
+use crate::api::debug_console::{self, ConsolePuts};
+use crate::api::fallback::Fallback;
+use crate::api::{probe, reset, time};
+use crate::{Error, ExtensionSet, ResetReason, ResetType, Result, SbiExtension};
+
+/// The SBI services available to the guest, as discovered by `GuestRuntime::new()`.
+#[derive(Clone, Copy, Debug)]
+pub struct GuestRuntime {
+    spec_version: u64,
+    extensions: ExtensionSet,
+}
+
+impl GuestRuntime {
+    /// Creates a runtime for the SBI implementation's capabilities, probing for them if they
+    /// haven't been cached by `probe::capabilities()` yet.
+    pub fn new() -> Self {
+        let caps = probe::capabilities();
+        Self {
+            spec_version: caps.spec_version,
+            extensions: caps.extensions,
+        }
+    }
+
+    /// Returns the SBI specification version implemented by the SBI implementation, or 0 if it
+    /// couldn't be determined.
+    pub fn spec_version(&self) -> u64 {
+        self.spec_version
+    }
+
+    /// Returns the extensions supported by the SBI implementation.
+    pub fn extensions(&self) -> ExtensionSet {
+        self.extensions
+    }
+
+    /// Returns if `ext` is supported by the SBI implementation.
+    pub fn supports(&self, ext: SbiExtension) -> bool {
+        self.extensions.contains(ext)
+    }
+
+    /// Returns a console for `write!` and `writeln!`, using the Debug Console extension if it is
+    /// supported and the legacy PutChar extension otherwise.
+    pub fn console(&self) -> GuestConsole {
+        GuestConsole {
+            puts: Fallback::new(
+                self.supports(SbiExtension::DebugConsole),
+                debug_console::console_puts,
+                debug_console::legacy_console_puts,
+            ),
+        }
+    }
+
+    /// Programs the timer to fire at the absolute time `stime_value`. Returns
+    /// `Error::NotSupported` if the Timer extension isn't supported.
+    pub fn set_timer(&self, stime_value: u64) -> Result<()> {
+        if !self.supports(SbiExtension::Time) {
+            return Err(Error::NotSupported);
+        }
+        time::set_timer(stime_value)
+    }
+
+    /// Shuts the system down, returning an error if the SBI implementation failed to do so.
+    pub fn shutdown(&self) -> Result<()> {
+        Err(reset::shutdown())
+    }
+
+    /// Reports `info` on the console and shuts the system down as failed. Meant to be called
+    /// from the guest's `#[panic_handler]`. With the `tiny` feature, only the location of the
+    /// panic is reported, leaving the formatting of its message out of the guest.
+    pub fn handle_panic(&self, info: &PanicInfo) -> ! {
+        use fmt::Write;
+        // Nothing more can be done if printing fails.
+        #[cfg(feature = "tiny")]
+        let _ = match info.location() {
+            Some(location) => writeln!(
+                self.console(),
+                "panicked at {}:{}",
+                location.file(),
+                location.line()
+            ),
+            None => writeln!(self.console(), "panicked"),
+        };
+        #[cfg(not(feature = "tiny"))]
+        let _ = writeln!(self.console(), "{info}");
+        let _ = reset::reset(ResetType::Shutdown, ResetReason::SystemFailure);
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl Default for GuestRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A console returned by `GuestRuntime::console()`.
+#[derive(Clone, Copy, Debug)]
+pub struct GuestConsole {
+    puts: Fallback<ConsolePuts>,
+}
+
+impl fmt::Write for GuestConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.puts.call(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
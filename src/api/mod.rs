@@ -2,32 +2,80 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+/// Helpers for SBI calls that pass a buffer in shared memory.
+pub mod shared_buffer;
+
+/// Registration of memory shared with the SBI implementation across calls.
+pub mod shared_memory;
+
 /// Debug Console for printing strings through SBI.
 pub mod debug_console;
 
+/// Timer interfaces.
+pub mod time;
+
+/// Inter-processor interrupt interfaces.
+pub mod ipi;
+
+/// Remote fence interfaces.
+pub mod rfence;
+
+/// Guest watchdog built on the timer and reset extensions.
+pub mod watchdog;
+
 /// Host interfaces for reset extension.
 pub mod reset;
 
 /// Host interfaces for hart state management.
 pub mod state;
 
+/// Hart state management interfaces, under the name of the extension in the SBI specification.
+pub use state as hsm;
+
 /// Host interfaces for nested virtualization acceleration.
+#[cfg(feature = "nacl")]
 pub mod nacl;
 
 /// Host interfaces for confidential computing.
+#[cfg(feature = "tee")]
 pub mod tee_host;
 
+/// Host helpers for building a TVM's guest memory map.
+#[cfg(feature = "tee")]
+pub mod tee_layout;
+
 /// Host interfaces for confidential computing interrupt virtualization.
+#[cfg(feature = "tee")]
 pub mod tee_interrupt;
 
 /// Guest interfaces for confidential computing.
+#[cfg(feature = "tee")]
 pub mod tee_guest;
 
 /// Host interfaces for PMU.
+#[cfg(feature = "pmu")]
 pub mod pmu;
 
+/// PMU counter virtualization for hypervisors.
+#[cfg(feature = "pmu")]
+pub mod virtual_pmu;
+
 /// Base SBI inferfaces.
 pub mod base;
 
+/// Discovery and caching of the capabilities of the SBI implementation.
+pub mod probe;
+
+/// Fallbacks for services that depend on optional extensions.
+pub mod fallback;
+
+/// Bundled SBI services for minimal guests.
+pub mod guest_runtime;
+
+/// Runtime and assertion helpers for bare-metal test kernels.
+#[cfg(feature = "test-kernel")]
+pub mod test_kernel;
+
 /// Host interfaces for attestation.
+#[cfg(feature = "attestation")]
 pub mod attestation;
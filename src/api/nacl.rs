@@ -2,9 +2,12 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use core::{marker::PhantomData, ptr};
+
+use crate::api::probe;
 use crate::NaclFunction::*;
 use crate::NaclShmem;
-use crate::{ecall_send, Result, SbiMessage};
+use crate::{ecall_send, Result, SbiExtension, SbiMessage};
 
 const PFN_SHIFT: u64 = 12;
 
@@ -23,6 +26,7 @@ pub unsafe fn register_shmem(shmem_ptr: *mut NaclShmem) -> Result<()> {
     let msg = SbiMessage::Nacl(SetShmem {
         shmem_pfn: (shmem_ptr as u64) >> PFN_SHIFT,
     });
+    probe::require(SbiExtension::Nacl)?;
     ecall_send(&msg)?;
     Ok(())
 }
@@ -32,7 +36,97 @@ pub fn unregister_shmem() -> Result<()> {
     let msg = SbiMessage::Nacl(SetShmem {
         shmem_pfn: u64::MAX,
     });
+    probe::require(SbiExtension::Nacl)?;
     // Safety: Doesn't access host memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
 }
+
+/// Synchronizes the HS or VS CSR at `csr_num` between the registered shared memory area and the
+/// host hypervisor.
+pub fn sync_csr(csr_num: u16) -> Result<()> {
+    let msg = SbiMessage::Nacl(SyncCsr {
+        csr_num: csr_num as u64,
+    });
+    probe::require(SbiExtension::Nacl)?;
+    // Safety: SyncCsr only accesses the shared memory area, which the caller registered with
+    // `register_shmem()` and must already treat as volatile.
+    unsafe { ecall_send(&msg) }?;
+    Ok(())
+}
+
+/// Synchronizes all CSRs marked dirty in the registered shared memory area with the host
+/// hypervisor.
+pub fn sync_all_csrs() -> Result<()> {
+    let msg = SbiMessage::Nacl(SyncCsr { csr_num: u64::MAX });
+    probe::require(SbiExtension::Nacl)?;
+    // Safety: SyncCsr only accesses the shared memory area, which the caller registered with
+    // `register_shmem()` and must already treat as volatile.
+    unsafe { ecall_send(&msg) }?;
+    Ok(())
+}
+
+/// Provides volatile accessors to the CSRs and dirty bitmap of a registered `NaclShmem` area.
+pub struct NaclCsrsRef<'a> {
+    ptr: *mut NaclShmem,
+    _lifetime: PhantomData<&'a NaclShmem>,
+}
+
+impl<'a> NaclCsrsRef<'a> {
+    /// Creates a new `NaclCsrsRef` from a raw pointer to a `NaclShmem`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that `ptr` is suitably aligned and points to a `NaclShmem`
+    /// structure that is valid for the lifetime `'a`.
+    pub unsafe fn new(ptr: *mut NaclShmem) -> Self {
+        Self {
+            ptr,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Reads the HS or VS CSR at `csr_num`.
+    pub fn csr(&self, csr_num: u16) -> u64 {
+        let index = NaclShmem::csr_index(csr_num);
+        // Safety: `index` is guaranteed to be a valid index into `csrs` and the caller guaranteed
+        // at construction that `ptr` points to a valid `NaclShmem`.
+        unsafe { ptr::addr_of!((*self.ptr).csrs[index]).read_volatile() }
+    }
+
+    /// Writes the HS or VS CSR at `csr_num` and marks it dirty so that it is picked up by the
+    /// next call to `sync_all_csrs()`.
+    pub fn set_csr(&self, csr_num: u16, val: u64) {
+        let index = NaclShmem::csr_index(csr_num);
+        // Safety: `index` is guaranteed to be a valid index into `csrs` and the caller guaranteed
+        // at construction that `ptr` points to a valid `NaclShmem`.
+        unsafe { ptr::addr_of_mut!((*self.ptr).csrs[index]).write_volatile(val) };
+        self.set_csr_dirty(csr_num);
+    }
+
+    /// Returns if the HS or VS CSR at `csr_num` is marked dirty.
+    pub fn is_csr_dirty(&self, csr_num: u16) -> bool {
+        let (word, bit) = NaclShmem::dirty_bitmap_bit(csr_num);
+        // Safety: `word` is guaranteed to be a valid index into `dirty_bitmap` and the caller
+        // guaranteed at construction that `ptr` points to a valid `NaclShmem`.
+        unsafe { ptr::addr_of!((*self.ptr).dirty_bitmap[word]).read_volatile() & bit != 0 }
+    }
+
+    /// Marks the HS or VS CSR at `csr_num` dirty.
+    pub fn set_csr_dirty(&self, csr_num: u16) {
+        let (word, bit) = NaclShmem::dirty_bitmap_bit(csr_num);
+        // Safety: `word` is guaranteed to be a valid index into `dirty_bitmap` and the caller
+        // guaranteed at construction that `ptr` points to a valid `NaclShmem`.
+        unsafe {
+            let p = ptr::addr_of_mut!((*self.ptr).dirty_bitmap[word]);
+            p.write_volatile(p.read_volatile() | bit);
+        }
+    }
+
+    /// Writes the HS or VS CSR at `csr_num` and immediately synchronizes it with the host
+    /// hypervisor.
+    pub fn write_and_sync_csr(&self, csr_num: u16, val: u64) -> Result<()> {
+        self.set_csr(csr_num, val);
+        sync_csr(csr_num)
+    }
+}
@@ -2,15 +2,23 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use core::{marker::PhantomData, ptr};
+
+use crate::api::probe;
+use crate::api::shared_buffer::load_acquire_u64;
 use crate::ecall_send;
+use crate::{
+    join_rv32_reg_pair, PmuCounterInfo, PmuSnapshot, Result, SbiExtension, SbiMessage,
+    PMU_SNAPSHOT_MAX_COUNTERS, XLEN,
+};
 use crate::{
     PmuCounterConfigFlags, PmuCounterStartFlags, PmuCounterStopFlags, PmuEventType, PmuFunction,
 };
-use crate::{PmuCounterInfo, Result, SbiMessage};
 
 /// Returns the number of PMU counters supported by the platform
 pub fn get_num_counters() -> Result<u64> {
     let msg = SbiMessage::Pmu(PmuFunction::GetNumCounters);
+    probe::require(SbiExtension::Pmu)?;
     // Safety: PmuFunction doesn't touch memory
     unsafe { ecall_send(&msg) }
 }
@@ -18,6 +26,7 @@ pub fn get_num_counters() -> Result<u64> {
 /// Returns information about the PMU counter specified by counter_index
 pub fn get_counter_info(counter_index: u64) -> Result<PmuCounterInfo> {
     let msg = SbiMessage::Pmu(PmuFunction::GetCounterInfo(counter_index));
+    probe::require(SbiExtension::Pmu)?;
     // Safety: PmuFunction doesn't touch memory
     let info = unsafe { ecall_send(&msg) }?;
     Ok(PmuCounterInfo::new(info))
@@ -38,6 +47,7 @@ pub fn configure_matching_counters(
         event_type,
         event_data,
     });
+    probe::require(SbiExtension::Pmu)?;
     // Safety: PmuFunction does not touch memory.
     unsafe { ecall_send(&msg) }
 }
@@ -55,6 +65,7 @@ pub fn start_counters(
         start_flags,
         initial_value,
     });
+    probe::require(SbiExtension::Pmu)?;
     // Safety: PmuFunction does not touch memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -71,6 +82,7 @@ pub fn stop_counters(
         counter_mask,
         stop_flags,
     });
+    probe::require(SbiExtension::Pmu)?;
     // Safety: PmuFunction does not touch memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -79,6 +91,68 @@ pub fn stop_counters(
 /// Reads the firmware counter specified by counter_index
 pub fn read_firmware_counter(counter_index: u64) -> Result<u64> {
     let msg = SbiMessage::Pmu(PmuFunction::ReadFirmwareCounter(counter_index));
+    probe::require(SbiExtension::Pmu)?;
     // Safety: PmuFunction does not touch memory.
-    unsafe { ecall_send(&msg) }
+    let lo = unsafe { ecall_send(&msg) }?;
+    if XLEN == 32 {
+        let msg = SbiMessage::Pmu(PmuFunction::ReadFirmwareCounterHigh(counter_index));
+        // Safety: PmuFunction does not touch memory.
+        let hi = unsafe { ecall_send(&msg) }?;
+        Ok(join_rv32_reg_pair(lo, hi))
+    } else {
+        Ok(lo)
+    }
+}
+
+/// Provides accessors to a `PmuSnapshot` area that the SBI implementation updates while the caller
+/// may be reading it, for example when counters overflow or are stopped on another hart.
+///
+/// Every read is a load with Acquire semantics (see `load_acquire_u64()`), so a counter value read
+/// after the overflow bitmap is at least as recent as the bitmap. Reading the bitmap before the
+/// counter values it describes is therefore the order to use.
+pub struct PmuSnapshotRef<'a> {
+    ptr: *const PmuSnapshot,
+    _lifetime: PhantomData<&'a PmuSnapshot>,
+}
+
+impl<'a> PmuSnapshotRef<'a> {
+    /// Creates a new `PmuSnapshotRef` from a raw pointer to a `PmuSnapshot`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that `ptr` is suitably aligned and points to a `PmuSnapshot`
+    /// structure that is valid for the lifetime `'a`, and that it is only written by the SBI
+    /// implementation for that lifetime.
+    pub unsafe fn new(ptr: *const PmuSnapshot) -> Self {
+        Self {
+            ptr,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Reads the counter overflow bitmap.
+    pub fn overflow_bitmap(&self) -> u64 {
+        // Safety: The caller guaranteed at construction that `ptr` points to a valid
+        // `PmuSnapshot`.
+        unsafe { load_acquire_u64(ptr::addr_of!((*self.ptr).counter_overflow_bitmap)) }
+    }
+
+    /// Reads the value of the counter at `index`, which must be less than
+    /// `PMU_SNAPSHOT_MAX_COUNTERS`.
+    pub fn counter_value(&self, index: usize) -> u64 {
+        assert!(index < PMU_SNAPSHOT_MAX_COUNTERS);
+        // Safety: `index` is guaranteed to be a valid index into `counter_values` and the caller
+        // guaranteed at construction that `ptr` points to a valid `PmuSnapshot`.
+        unsafe { load_acquire_u64(ptr::addr_of!((*self.ptr).counter_values[index])) }
+    }
+
+    /// Copies the snapshot into a local `PmuSnapshot`, reading the overflow bitmap first.
+    pub fn read(&self) -> PmuSnapshot {
+        let mut snapshot = PmuSnapshot::default();
+        snapshot.counter_overflow_bitmap = self.overflow_bitmap();
+        for (i, value) in snapshot.counter_values.iter_mut().enumerate() {
+            *value = self.counter_value(i);
+        }
+        snapshot
+    }
 }
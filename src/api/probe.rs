@@ -0,0 +1,163 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Discovery of what the SBI implementation supports.
+//!
+//! `probe()` queries the Base extension for the implementation's version and for each extension
+//! known to this crate. `capabilities()` caches the result of the first probe in a static shared
+//! by all harts, so wrappers can check `is_supported()` before every call without issuing any
+//! further ECALLs. On hot paths, `supports()` and `require()` read only the cached set of
+//! supported extensions, a single bitset.
+//!
+//! The wrappers in `api` for the optional extensions, Debug Console, PMU, NACL (including
+//! `SharedMemoryRegion`), the TEE extensions, Attestation and HSM suspend, call `require()` before
+//! issuing a call, so calls to an extension the implementation lacks fail with
+//! `Error::NotSupported` without an ECALL. Wrappers for the extensions every implementation
+//! provides don't check. Off-target, the cache is shared by the whole process, so the first sender
+//! installed with `with_ecall_sender()` that reaches a checking wrapper must answer the Base
+//! extension's probes, and its answers hold for the senders after it:
+//!
+//! ```rust
+//! # #[cfg(all(feature = "std", feature = "handler", feature = "pmu"))] {
+//! use sbi_rs::api::{pmu, probe};
+//! use sbi_rs::mock::{MockFirmware, MockMachine};
+//! use sbi_rs::*;
+//!
+//! let firmware = MockFirmware::new(MockMachine::new(1));
+//! firmware.run(|| {
+//!     assert_eq!(probe::require(SbiExtension::HartState), Ok(()));
+//!     assert_eq!(probe::require(SbiExtension::Pmu), Err(Error::NotSupported));
+//! });
+//!
+//! // Probing again would fail, but the extensions are only probed once.
+//! firmware
+//!     .machine_mut()
+//!     .inject_error(EXT_BASE, 3, Error::Failed, usize::MAX);
+//! assert!(firmware.run(|| probe::supports(SbiExtension::HartState)));
+//!
+//! // Calls to missing extensions never reach a sender, so none needs to be installed.
+//! assert_eq!(pmu::get_num_counters(), Err(Error::NotSupported));
+//! # }
+//! ```
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use crate::api::base;
+use crate::{Error, ExtensionSet, Result, SbiExtension};
+
+/// The SBI specification version, implementation and extensions of the SBI implementation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SbiCapabilities {
+    /// The SBI specification version implemented, or 0 if it couldn't be determined.
+    pub spec_version: u64,
+    /// The ID of the SBI implementation, or 0 if it couldn't be determined.
+    pub impl_id: u64,
+    /// The version of the SBI implementation, or 0 if it couldn't be determined.
+    pub impl_version: u64,
+    /// The extensions supported by the SBI implementation.
+    pub extensions: ExtensionSet,
+}
+
+impl SbiCapabilities {
+    /// Returns if `ext` is supported.
+    pub fn supports(&self, ext: SbiExtension) -> bool {
+        self.extensions.contains(ext)
+    }
+
+    /// Returns if the extension with ID `eid` is supported. Extensions unknown to this crate are
+    /// never reported as supported.
+    pub fn is_supported(&self, eid: u64) -> bool {
+        SbiExtension::from_eid(eid).is_some_and(|ext| self.supports(ext))
+    }
+
+    /// Returns `Error::NotSupported` unless `ext` is supported, for wrappers that check for an
+    /// extension before issuing a call to it.
+    pub fn require(&self, ext: SbiExtension) -> Result<()> {
+        require_in(self.extensions, ext)
+    }
+}
+
+fn require_in(extensions: ExtensionSet, ext: SbiExtension) -> Result<()> {
+    if extensions.contains(ext) {
+        Ok(())
+    } else {
+        Err(Error::NotSupported)
+    }
+}
+
+/// Queries the SBI implementation for its capabilities. Legacy extensions are checked with
+/// `base::legacy_available()` rather than probed.
+pub fn probe() -> SbiCapabilities {
+    let legacy = base::legacy_available();
+    let extensions = SbiExtension::ALL
+        .into_iter()
+        .filter(|ext| {
+            if ext.is_legacy() {
+                legacy
+            } else {
+                base::probe_sbi_extension(ext.eid()).is_ok()
+            }
+        })
+        .collect();
+    SbiCapabilities {
+        spec_version: base::get_specification_version().unwrap_or(0),
+        impl_id: base::get_implementation_id().unwrap_or(0),
+        impl_version: base::get_implementation_version().unwrap_or(0),
+        extensions,
+    }
+}
+
+// The capabilities cached by `capabilities()`. The values are XLEN-sized registers, so they fit
+// in a `usize`. Harts racing to fill the cache store the same values, so only `PROBED` needs to
+// order the stores.
+static PROBED: AtomicBool = AtomicBool::new(false);
+static SPEC_VERSION: AtomicUsize = AtomicUsize::new(0);
+static IMPL_ID: AtomicUsize = AtomicUsize::new(0);
+static IMPL_VERSION: AtomicUsize = AtomicUsize::new(0);
+static EXTENSIONS: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the capabilities of the SBI implementation, probing for them with `probe()` on the
+/// first call and returning the cached result afterwards.
+pub fn capabilities() -> SbiCapabilities {
+    if !PROBED.load(Ordering::Acquire) {
+        let caps = probe();
+        SPEC_VERSION.store(caps.spec_version as usize, Ordering::Relaxed);
+        IMPL_ID.store(caps.impl_id as usize, Ordering::Relaxed);
+        IMPL_VERSION.store(caps.impl_version as usize, Ordering::Relaxed);
+        EXTENSIONS.store(caps.extensions.raw(), Ordering::Relaxed);
+        PROBED.store(true, Ordering::Release);
+        return caps;
+    }
+    SbiCapabilities {
+        spec_version: SPEC_VERSION.load(Ordering::Relaxed) as u64,
+        impl_id: IMPL_ID.load(Ordering::Relaxed) as u64,
+        impl_version: IMPL_VERSION.load(Ordering::Relaxed) as u64,
+        extensions: ExtensionSet::from_raw(EXTENSIONS.load(Ordering::Relaxed)),
+    }
+}
+
+// Returns the cached set of supported extensions, probing for them on the first call.
+fn extensions() -> ExtensionSet {
+    if !PROBED.load(Ordering::Acquire) {
+        return capabilities().extensions;
+    }
+    ExtensionSet::from_raw(EXTENSIONS.load(Ordering::Relaxed))
+}
+
+/// Returns if `ext` is supported, according to the cached `capabilities()`.
+pub fn supports(ext: SbiExtension) -> bool {
+    extensions().contains(ext)
+}
+
+/// Returns if the extension with ID `eid` is supported, according to the cached
+/// `capabilities()`.
+pub fn is_supported(eid: u64) -> bool {
+    SbiExtension::from_eid(eid).is_some_and(supports)
+}
+
+/// Returns `Error::NotSupported` unless `ext` is supported, according to the cached
+/// `capabilities()`, so that wrappers can fail calls to missing extensions without issuing them.
+pub fn require(ext: SbiExtension) -> Result<()> {
+    require_in(extensions(), ext)
+}
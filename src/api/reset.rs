@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::ResetFunction;
-use crate::{ecall_send, Result, SbiMessage};
+use crate::{ecall_send, Error, Result, SbiMessage};
 use crate::{ResetReason, ResetType};
 
 /// Resets the system.
@@ -14,10 +14,26 @@ pub fn reset(reset_type: ResetType, reason: ResetReason) -> Result<()> {
     Ok(())
 }
 
-/// Shuts down the system.
-pub fn shutdown() -> Result<()> {
-    let msg = SbiMessage::Reset(ResetFunction::shutdown());
-    // Safety: This ecall doesn't touch memory and will never return.
-    unsafe { ecall_send(&msg) }?;
-    Ok(())
+// Resets the system, returning why it wasn't reset if the call returns at all.
+fn terminate(reset_type: ResetType, reason: ResetReason) -> Error {
+    match reset(reset_type, reason) {
+        Ok(()) => Error::Failed,
+        Err(e) => e,
+    }
+}
+
+/// Shuts down the system. Only returns if the SBI implementation failed to do so, with the
+/// error it reported or `Error::Failed` if the call succeeded but returned anyway.
+pub fn shutdown() -> Error {
+    terminate(ResetType::Shutdown, ResetReason::NoReason)
+}
+
+/// Powers the system down and reboots it, returning as `shutdown()` does on failure.
+pub fn cold_reboot() -> Error {
+    terminate(ResetType::ColdReset, ResetReason::NoReason)
+}
+
+/// Reboots the system without powering it down, returning as `shutdown()` does on failure.
+pub fn warm_reboot() -> Error {
+    terminate(ResetType::WarmReset, ResetReason::NoReason)
 }
@@ -0,0 +1,242 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use arrayvec::ArrayVec;
+
+use crate::RfenceFunction::*;
+use crate::{ecall_send, HartMask, Result, RfenceFunction, SbiMessage};
+
+fn send(func: RfenceFunction) -> Result<()> {
+    // Safety: Remote fences don't touch memory.
+    unsafe { ecall_send(&SbiMessage::Rfence(func)) }?;
+    Ok(())
+}
+
+/// Executes `FENCE.I` on the harts selected by `hart_mask`.
+pub fn remote_fence_i(hart_mask: HartMask) -> Result<()> {
+    send(RemoteFenceI { hart_mask })
+}
+
+/// Executes `SFENCE.VMA` for the virtual address range of `size` bytes at `start_addr` on the
+/// harts selected by `hart_mask`.
+pub fn remote_sfence_vma(hart_mask: HartMask, start_addr: u64, size: u64) -> Result<()> {
+    send(RemoteSfenceVma {
+        hart_mask,
+        start_addr,
+        size,
+    })
+}
+
+/// Executes `SFENCE.VMA` for `asid` and the virtual address range of `size` bytes at
+/// `start_addr` on the harts selected by `hart_mask`.
+pub fn remote_sfence_vma_asid(
+    hart_mask: HartMask,
+    start_addr: u64,
+    size: u64,
+    asid: u64,
+) -> Result<()> {
+    send(RemoteSfenceVmaAsid {
+        hart_mask,
+        start_addr,
+        size,
+        asid,
+    })
+}
+
+/// Executes `HFENCE.GVMA` for `vmid` and the guest physical address range of `size` bytes at
+/// `start_addr` on the harts selected by `hart_mask`.
+pub fn remote_hfence_gvma_vmid(
+    hart_mask: HartMask,
+    start_addr: u64,
+    size: u64,
+    vmid: u64,
+) -> Result<()> {
+    send(RemoteHfenceGvmaVmid {
+        hart_mask,
+        start_addr,
+        size,
+        vmid,
+    })
+}
+
+/// Executes `HFENCE.GVMA` for all VMIDs and the guest physical address range of `size` bytes at
+/// `start_addr` on the harts selected by `hart_mask`.
+pub fn remote_hfence_gvma(hart_mask: HartMask, start_addr: u64, size: u64) -> Result<()> {
+    send(RemoteHfenceGvma {
+        hart_mask,
+        start_addr,
+        size,
+    })
+}
+
+/// Executes `HFENCE.VVMA` for `asid` and the guest virtual address range of `size` bytes at
+/// `start_addr` on the harts selected by `hart_mask`.
+pub fn remote_hfence_vvma_asid(
+    hart_mask: HartMask,
+    start_addr: u64,
+    size: u64,
+    asid: u64,
+) -> Result<()> {
+    send(RemoteHfenceVvmaAsid {
+        hart_mask,
+        start_addr,
+        size,
+        asid,
+    })
+}
+
+/// Executes `HFENCE.VVMA` for all ASIDs and the guest virtual address range of `size` bytes at
+/// `start_addr` on the harts selected by `hart_mask`.
+pub fn remote_hfence_vvma(hart_mask: HartMask, start_addr: u64, size: u64) -> Result<()> {
+    send(RemoteHfenceVvma {
+        hart_mask,
+        start_addr,
+        size,
+    })
+}
+
+/// Collects the virtual address ranges to flush in a TLB shootdown, so that they can be flushed
+/// on the remote harts with as few `SFENCE.VMA` calls as possible.
+///
+/// Overlapping and adjacent ranges are merged into one. Once more than `N` separate ranges are
+/// pending, or the ranges cover `full_flush_threshold` bytes or more, the batch is flushed with a
+/// single call for the whole address space instead, as are ranges that the RFENCE extension
+/// treats as a flush of the whole address space.
+///
+/// ```rust
+/// # #[cfg(all(feature = "std", feature = "handler"))] {
+/// use sbi_rs::api::rfence::SfenceBatch;
+/// use sbi_rs::mock::{MockFirmware, MockMachine};
+/// use sbi_rs::HartMask;
+///
+/// let mut batch = SfenceBatch::<4>::new(64 * 4096);
+/// batch.add(0x1000, 0x1000);
+/// batch.add(0x3000, 0x1000);
+/// batch.add(0x2000, 0x1000);
+/// batch.add(0x8000, 0x2000);
+/// assert_eq!(batch.len(), 2);
+///
+/// let firmware = MockFirmware::new(MockMachine::new(2));
+/// firmware.run(|| batch.flush(HartMask::all())).unwrap();
+/// assert_eq!(firmware.machine_mut().hart(1).unwrap().fences(), 2);
+/// assert!(batch.is_empty());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SfenceBatch<const N: usize> {
+    // Sorted, disjoint and non-adjacent `(start, end)` ranges, with `end` exclusive.
+    ranges: ArrayVec<(u64, u64), N>,
+    full_flush: bool,
+    full_flush_threshold: u64,
+}
+
+impl<const N: usize> SfenceBatch<N> {
+    /// Creates an empty batch that falls back to flushing the whole address space once its ranges
+    /// cover `full_flush_threshold` bytes.
+    pub fn new(full_flush_threshold: u64) -> Self {
+        Self {
+            ranges: ArrayVec::new(),
+            full_flush: false,
+            full_flush_threshold,
+        }
+    }
+
+    /// Adds the range of `size` bytes at `start_addr` to the batch. As for the RFENCE calls, the
+    /// whole address space is flushed if both `start_addr` and `size` are 0, or if `size` is
+    /// `u64::MAX`. Other empty ranges are ignored.
+    pub fn add(&mut self, start_addr: u64, size: u64) {
+        if self.full_flush {
+            return;
+        }
+        if (start_addr == 0 && size == 0) || size == u64::MAX {
+            self.full_flush = true;
+            return;
+        }
+        if size == 0 {
+            return;
+        }
+        // A range running past the top of the address space is flushed along with the rest.
+        let Some(mut end) = start_addr.checked_add(size) else {
+            self.full_flush = true;
+            return;
+        };
+        let mut start = start_addr;
+        self.ranges.retain(|&mut (s, e)| {
+            if s > end || e < start {
+                return true;
+            }
+            start = start.min(s);
+            end = end.max(e);
+            false
+        });
+        let index = self.ranges.partition_point(|&(s, _)| s < start);
+        let covered: u64 = self.ranges.iter().map(|&(s, e)| e - s).sum();
+        if covered + (end - start) >= self.full_flush_threshold
+            || self.ranges.try_insert(index, (start, end)).is_err()
+        {
+            self.full_flush = true;
+            self.ranges.clear();
+        }
+    }
+
+    /// Returns if the batch will flush the whole address space.
+    pub fn is_full_flush(&self) -> bool {
+        self.full_flush
+    }
+
+    /// Returns the number of `SFENCE.VMA` calls needed to flush the batch.
+    pub fn len(&self) -> usize {
+        if self.full_flush {
+            1
+        } else {
+            self.ranges.len()
+        }
+    }
+
+    /// Returns if there is nothing to flush.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `(start_addr, size)` ranges the batch will flush, in increasing address order,
+    /// or a single range covering the whole address space.
+    pub fn ranges(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        let full = self.full_flush.then_some((0, u64::MAX));
+        full.into_iter()
+            .chain(self.ranges.iter().map(|&(start, end)| (start, end - start)))
+    }
+
+    /// Removes every range from the batch.
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+        self.full_flush = false;
+    }
+
+    /// Flushes the batch's ranges on the harts selected by `hart_mask` with
+    /// `remote_sfence_vma()`, emptying the batch. The ranges that were to be flushed after a
+    /// failing call are left in the batch.
+    pub fn flush(&mut self, hart_mask: HartMask) -> Result<()> {
+        self.flush_with(|start_addr, size| remote_sfence_vma(hart_mask, start_addr, size))
+    }
+
+    /// Flushes the batch's ranges for `asid` on the harts selected by `hart_mask` with
+    /// `remote_sfence_vma_asid()`, as `flush()` does.
+    pub fn flush_asid(&mut self, hart_mask: HartMask, asid: u64) -> Result<()> {
+        self.flush_with(|start_addr, size| {
+            remote_sfence_vma_asid(hart_mask, start_addr, size, asid)
+        })
+    }
+
+    fn flush_with(&mut self, mut fence: impl FnMut(u64, u64) -> Result<()>) -> Result<()> {
+        if self.full_flush {
+            fence(0, u64::MAX)?;
+            self.full_flush = false;
+        }
+        while let Some(&(start, end)) = self.ranges.first() {
+            fence(start, end - start)?;
+            self.ranges.remove(0);
+        }
+        Ok(())
+    }
+}
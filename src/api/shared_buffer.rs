@@ -0,0 +1,131 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use core::sync::atomic::{fence, Ordering};
+
+use crate::{ecall_send, Error, Result, SbiMessage};
+
+/// The location of a buffer shared with the SBI implementation for the duration of a call, as
+/// passed to the message builder of `with_shared_buffer()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SharedBuffer {
+    addr: u64,
+    len: u64,
+}
+
+impl SharedBuffer {
+    /// Returns the address of the buffer.
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    /// Returns the length of the buffer in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The result of a call made with `with_shared_buffer()`: the value returned by the call along
+/// with the buffer, which may now hold data written by the SBI implementation.
+pub struct SharedBufferResponse<'a> {
+    value: u64,
+    buf: &'a mut [u8],
+}
+
+impl<'a> SharedBufferResponse<'a> {
+    /// Returns the value returned by the call.
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns the whole buffer.
+    pub fn bytes(&self) -> &[u8] {
+        self.buf
+    }
+
+    /// Returns the start of the buffer, for calls which return the number of bytes they wrote to
+    /// it. Returns `Error::Failed` if the call claims to have written more bytes than the buffer
+    /// holds.
+    pub fn written(&self) -> Result<&[u8]> {
+        usize::try_from(self.value)
+            .ok()
+            .and_then(|len| self.buf.get(..len))
+            .ok_or(Error::Failed)
+    }
+
+    /// Returns the little-endian `u64` at `index`, for buffers laid out as an array of `u64`s.
+    /// Returns `None` if `index` is past the end of the buffer.
+    pub fn read_u64(&self, index: usize) -> Option<u64> {
+        let start = index.checked_mul(8)?;
+        let bytes = self.buf.get(start..start.checked_add(8)?)?;
+        // Unwrap ok: `bytes` is exactly 8 bytes long.
+        Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Consumes the response, returning the buffer.
+    pub fn into_bytes(self) -> &'a mut [u8] {
+        self.buf
+    }
+}
+
+/// Issues the SBI call built by `msg_builder` from the location of `buf`, for calls that pass
+/// their arguments in registers along with a buffer in shared memory.
+///
+/// Writes made by the caller to `buf` are ordered before the call, and writes made by the SBI
+/// implementation during the call are ordered before any subsequent reads of the returned
+/// `SharedBufferResponse`.
+///
+/// # Safety
+///
+/// The message returned by `msg_builder` must not cause the SBI implementation to access any
+/// memory other than `buf`, except for memory that the caller otherwise guarantees to be safe to
+/// access for the call.
+pub unsafe fn with_shared_buffer<F>(
+    msg_builder: F,
+    buf: &mut [u8],
+) -> Result<SharedBufferResponse<'_>>
+where
+    F: FnOnce(SharedBuffer) -> SbiMessage,
+{
+    let msg = msg_builder(SharedBuffer {
+        addr: buf.as_mut_ptr() as u64,
+        len: buf.len() as u64,
+    });
+    fence(Ordering::Release);
+    let value = ecall_send(&msg)?;
+    fence(Ordering::Acquire);
+    Ok(SharedBufferResponse { value, buf })
+}
+
+/// Loads the `u64` at `ptr` in memory that the SBI implementation may update asynchronously,
+/// such as a counter overflow bitmap, with Acquire semantics: data the SBI implementation wrote
+/// before publishing the loaded value is visible to loads after this one.
+///
+/// The load is a single atomic access where 64-bit atomics are available, so it can't observe a
+/// torn value. On RV32 it is a volatile load of the two halves followed by an Acquire fence, and
+/// the halves may come from different updates. The SBI implementation must pair its updates with
+/// a release fence (`fence rw, w`) before the store that publishes them.
+///
+/// # Safety
+///
+/// `ptr` must be aligned and valid for reads for the duration of the call.
+pub unsafe fn load_acquire_u64(ptr: *const u64) -> u64 {
+    #[cfg(target_has_atomic = "64")]
+    {
+        // Safety: The caller guarantees that `ptr` is aligned and valid. The memory is only ever
+        // accessed by this crate through volatile or atomic accesses.
+        core::sync::atomic::AtomicU64::from_ptr(ptr as *mut u64).load(Ordering::Acquire)
+    }
+    #[cfg(not(target_has_atomic = "64"))]
+    {
+        let val = ptr.read_volatile();
+        fence(Ordering::Acquire);
+        val
+    }
+}
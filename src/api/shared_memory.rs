@@ -0,0 +1,138 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use core::marker::PhantomData;
+
+use crate::api::probe;
+use crate::{ecall_send, Result, SbiExtension, SbiMessage};
+
+/// An extension that shares an area of memory with the SBI implementation across calls, such as
+/// the NACL shared memory area. The area is registered by passing its physical address in a call
+/// and deregistered by passing an address of -1 in the same call.
+pub trait ShmemRegistration {
+    /// The layout of the shared memory area.
+    type Area;
+
+    /// The extension the area is registered with, checked with `probe::require()` before
+    /// registering.
+    const EXTENSION: SbiExtension;
+
+    /// Returns the call registering the area at the physical address `addr`, or deregistering
+    /// the registered area if `addr` is `u64::MAX`.
+    fn message(addr: u64) -> SbiMessage;
+}
+
+/// The shared memory area of the Nested Virtualization Acceleration extension.
+#[cfg(feature = "nacl")]
+#[derive(Clone, Copy, Debug)]
+pub struct NaclShmemRegistration;
+
+#[cfg(feature = "nacl")]
+impl ShmemRegistration for NaclShmemRegistration {
+    type Area = crate::NaclShmem;
+
+    const EXTENSION: SbiExtension = SbiExtension::Nacl;
+
+    fn message(addr: u64) -> SbiMessage {
+        const PFN_SHIFT: u64 = 12;
+        let shmem_pfn = if addr == u64::MAX {
+            u64::MAX
+        } else {
+            addr >> PFN_SHIFT
+        };
+        SbiMessage::Nacl(crate::NaclFunction::SetShmem { shmem_pfn })
+    }
+}
+
+/// A memory area registered with the SBI implementation for the calling hart, deregistered when
+/// dropped. The area stays mutably borrowed while registered, so it can be neither freed nor
+/// accessed other than through the volatile pointer returned by `as_ptr()` while the SBI
+/// implementation may write to it.
+///
+/// Registrations are per hart, so regions can't be sent to other threads. Addresses are passed to
+/// the SBI implementation as is, which assumes memory is identity-mapped.
+///
+/// ```no_run
+/// # #[cfg(feature = "nacl")] {
+/// use sbi_rs::api::shared_memory::{NaclShmemRegistration, SharedMemoryRegion};
+/// use sbi_rs::NaclShmem;
+///
+/// # fn shmem() -> &'static mut NaclShmem { unimplemented!() }
+/// let region = SharedMemoryRegion::<NaclShmemRegistration>::register(shmem()).unwrap();
+/// let _shmem = region.as_ptr();
+/// drop(region); // Deregisters the area.
+/// # }
+/// ```
+pub struct SharedMemoryRegion<'a, R: ShmemRegistration> {
+    area: *mut R::Area,
+    _borrow: PhantomData<&'a mut R::Area>,
+    // Registrations are made for the calling hart.
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<R: ShmemRegistration> SharedMemoryRegion<'static, R> {
+    /// Registers `area` with the SBI implementation. Fails with the error the SBI implementation
+    /// returns, such as `Error::InvalidAddress` if `area` isn't suitably aligned, or with
+    /// `Error::NotSupported` without making the call if `probe::require()` finds that the
+    /// extension isn't supported:
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "std", feature = "handler", feature = "nacl"))] {
+    /// use sbi_rs::api::shared_memory::{NaclShmemRegistration, SharedMemoryRegion};
+    /// use sbi_rs::mock::{MockFirmware, MockMachine};
+    /// use sbi_rs::replay::Recorder;
+    /// use sbi_rs::*;
+    ///
+    /// // The mock firmware doesn't implement NACL.
+    /// let firmware = MockFirmware::new(MockMachine::new(1));
+    /// let recorder = Recorder::new(&firmware);
+    /// let shmem = Box::leak(Box::<NaclShmem>::default());
+    /// let region = with_ecall_sender(&recorder, || {
+    ///     SharedMemoryRegion::<NaclShmemRegistration>::register(shmem)
+    /// });
+    /// assert_eq!(region.err(), Some(Error::NotSupported));
+    /// // Only the Base extension's probes were issued.
+    /// assert!(recorder.records().iter().all(|r| r.regs[7] == EXT_BASE));
+    /// # }
+    /// ```
+    pub fn register(area: &'static mut R::Area) -> Result<Self> {
+        // Safety: Leaking the region leaves a 'static area registered, which stays valid.
+        unsafe { Self::register_unchecked(area) }
+    }
+}
+
+impl<'a, R: ShmemRegistration> SharedMemoryRegion<'a, R> {
+    /// Registers `area` with the SBI implementation, as `register()` does, for an area that
+    /// doesn't live for the rest of the program.
+    ///
+    /// # Safety
+    ///
+    /// The region must be dropped, on the hart that created it, before `area` goes out of scope:
+    /// leaking it with `core::mem::forget()` would leave the SBI implementation writing to memory
+    /// that may be reused.
+    pub unsafe fn register_unchecked(area: &'a mut R::Area) -> Result<Self> {
+        let area = area as *mut R::Area;
+        probe::require(R::EXTENSION)?;
+        ecall_send(&R::message(area as u64))?;
+        Ok(Self {
+            area,
+            _borrow: PhantomData,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Returns a pointer to the area, which must be accessed with volatile reads and writes as
+    /// the SBI implementation may access it at any time.
+    pub fn as_ptr(&self) -> *mut R::Area {
+        self.area
+    }
+}
+
+impl<R: ShmemRegistration> Drop for SharedMemoryRegion<'_, R> {
+    fn drop(&mut self) {
+        // Safety: Deregistering doesn't touch memory. Nothing more can be done if it fails. The
+        // extension was checked when the area was registered.
+        let _ = unsafe { ecall_send(&R::message(u64::MAX)) };
+    }
+}
@@ -2,8 +2,9 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::api::probe;
 use crate::StateFunction::*;
-use crate::{ecall_send, Result, SbiMessage};
+use crate::{ecall_send, Error, HartState, Result, SbiExtension, SbiMessage, SuspendType};
 
 /// Starts the given cpu executing at `start_addr` with `opaque` in register a1.
 ///
@@ -22,3 +23,59 @@ pub unsafe fn hart_start(hart_id: u64, start_addr: u64, opaque: u64) -> Result<(
     ecall_send(&msg)?;
     Ok(())
 }
+
+/// Stops the calling hart. Only returns if the SBI implementation failed to stop it, with the
+/// error it reported or `Error::Failed` if the call succeeded but returned anyway.
+pub fn hart_stop() -> Error {
+    let msg = SbiMessage::HartState(HartStop);
+    // Safety: HartStop doesn't touch memory.
+    match unsafe { ecall_send(&msg) } {
+        Ok(_) => Error::Failed,
+        Err(e) => e,
+    }
+}
+
+/// Returns the state of the hart with ID `hart_id`.
+pub fn hart_status(hart_id: u64) -> Result<HartState> {
+    let msg = SbiMessage::HartState(HartStatus { hart_id });
+    // Safety: HartStatus doesn't touch memory.
+    let state = unsafe { ecall_send(&msg) }?;
+    HartState::from_reg(state)
+}
+
+/// Suspends the calling hart in the retentive state `suspend_type` until an interrupt or
+/// platform-specific event resumes it, returning once it has resumed. Returns
+/// `Error::InvalidParam` for non-retentive suspend types, which must use `hart_suspend()`.
+pub fn hart_retentive_suspend(suspend_type: SuspendType) -> Result<()> {
+    if !suspend_type.is_retentive() {
+        return Err(Error::InvalidParam);
+    }
+    // Safety: Retentive suspends resume after the call with the hart's state preserved, so no
+    // code is run at the resume address.
+    unsafe { hart_suspend(suspend_type, 0, 0) }
+}
+
+/// Suspends the calling hart in the state `suspend_type`. Harts resuming from non-retentive
+/// suspend types start executing at `resume_addr` with `opaque` in register a1, as with
+/// `hart_start()`; retentive ones return from this call.
+///
+/// Returns `Error::NotSupported` without making the call if `probe::require()` finds that the HSM
+/// extension isn't supported. Suspend was added to HSM after its other calls, and the probe covers
+/// the whole extension, so implementations of HSM without suspend still get the call and return
+/// `Error::NotSupported` from it.
+///
+/// # Safety
+///
+/// For non-retentive suspend types, `resume_addr` and `opaque` must satisfy the requirements of
+/// `hart_start()`.
+pub unsafe fn hart_suspend(suspend_type: SuspendType, resume_addr: u64, opaque: u64) -> Result<()> {
+    let msg = SbiMessage::HartState(HartSuspend {
+        suspend_type,
+        resume_addr,
+        opaque,
+    });
+    probe::require(SbiExtension::HartState)?;
+    // Safety: The caller guarantees that execution can resume at resume_addr.
+    ecall_send(&msg)?;
+    Ok(())
+}
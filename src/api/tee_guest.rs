@@ -2,14 +2,21 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::addr::region;
+use crate::api::probe;
 use crate::TeeGuestFunction::*;
-use crate::{ecall_send, Result, SbiMessage};
+use crate::{ecall_send, Result, SbiExtension, SbiMessage};
 
 /// Registers an emulated MMIO region in a previously-unused range of guest physical address space.
 /// Future accesses in the specified address range will trap to the host, allowing it to emulate
 /// the access.
+///
+/// Returns an error without making the call if `addr` or `len` isn't 4kB-aligned or the range runs
+/// past the end of the address space, as for the other calls here taking a range.
 pub fn add_emulated_mmio_region(addr: u64, len: u64) -> Result<()> {
+    let (addr, len) = region(addr, len)?;
     let msg = SbiMessage::TeeGuest(AddMmioRegion { addr, len });
+    probe::require(SbiExtension::TeeGuest)?;
     // Safety: AddMmioRegion does not directly access our memory. The specified range of
     // address space must have been previously inaccessible for the call to succeed, after which
     // accesses to that range have well-defined behavior.
@@ -20,7 +27,9 @@ pub fn add_emulated_mmio_region(addr: u64, len: u64) -> Result<()> {
 /// Unregister an emulated MMIO region, previously registered using `add_emulated_mmio_region`.
 /// Future accesses in the specified address range will result in a page fault.
 pub fn remove_emulated_mmio_region(addr: u64, len: u64) -> Result<()> {
+    let (addr, len) = region(addr, len)?;
     let msg = SbiMessage::TeeGuest(RemoveMmioRegion { addr, len });
+    probe::require(SbiExtension::TeeGuest)?;
     // Safety: RemoveMmioRegion does not directly access the memory. The specified range of
     // address space must have been previously registered for MMIO using `AddMmioRegion` for
     // this call to succeed.
@@ -37,7 +46,9 @@ pub fn remove_emulated_mmio_region(addr: u64, len: u64) -> Result<()> {
 /// returns, at which point accesses within the range are guaranteed to be to memory shared with
 /// the host.
 pub unsafe fn share_memory(addr: u64, len: u64) -> Result<()> {
+    let (addr, len) = region(addr, len)?;
     let msg = SbiMessage::TeeGuest(ShareMemory { addr, len });
+    probe::require(SbiExtension::TeeGuest)?;
     ecall_send(&msg)?;
     Ok(())
 }
@@ -51,7 +62,9 @@ pub unsafe fn share_memory(addr: u64, len: u64) -> Result<()> {
 /// returns, at which point accesses within the range are guaranteed to be to memory that is
 /// confidential to the calling VM.
 pub unsafe fn unshare_memory(addr: u64, len: u64) -> Result<()> {
+    let (addr, len) = region(addr, len)?;
     let msg = SbiMessage::TeeGuest(UnshareMemory { addr, len });
+    probe::require(SbiExtension::TeeGuest)?;
     ecall_send(&msg)?;
     Ok(())
 }
@@ -59,6 +72,7 @@ pub unsafe fn unshare_memory(addr: u64, len: u64) -> Result<()> {
 /// Allows injection of the specified external interrupt ID by the host to the calling CPU.
 pub fn allow_external_interrupt(id: u64) -> Result<()> {
     let msg = SbiMessage::TeeGuest(AllowExternalInterrupt { id: id as i64 });
+    probe::require(SbiExtension::TeeGuest)?;
     // Safety: AllowExternalInterrupt doesn't access our memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -67,6 +81,7 @@ pub fn allow_external_interrupt(id: u64) -> Result<()> {
 /// Allows injection of all external interrupts by the host to the calling CPU.
 pub fn allow_all_external_interrupts() -> Result<()> {
     let msg = SbiMessage::TeeGuest(AllowExternalInterrupt { id: -1 });
+    probe::require(SbiExtension::TeeGuest)?;
     // Safety: AllowExternalInterrupt doesn't access our memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -75,6 +90,7 @@ pub fn allow_all_external_interrupts() -> Result<()> {
 /// Denies injection of the specified external interrupt ID by the host to the calling CPU.
 pub fn deny_external_interrupt(id: u64) -> Result<()> {
     let msg = SbiMessage::TeeGuest(DenyExternalInterrupt { id: id as i64 });
+    probe::require(SbiExtension::TeeGuest)?;
     // Safety: DenyExternalInterrupt doesn't access our memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -83,6 +99,7 @@ pub fn deny_external_interrupt(id: u64) -> Result<()> {
 /// Denies injection of all external interrupts by the host to the calling CPU.
 pub fn deny_all_external_interrupts() -> Result<()> {
     let msg = SbiMessage::TeeGuest(DenyExternalInterrupt { id: -1 });
+    probe::require(SbiExtension::TeeGuest)?;
     // Safety: DenyExternalInterrupt doesn't access our memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
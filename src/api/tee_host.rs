@@ -2,13 +2,17 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use arrayvec::ArrayVec;
 use core::{marker::PhantomData, ptr};
 use static_assertions::const_assert;
 
+use crate::addr::{pages, region};
+use crate::api::probe;
 use crate::TeeHostFunction::*;
-use crate::{ecall_send, Error, Result, SbiMessage};
+use crate::{ecall_send, Error, Len, PageSize, PhysAddr, Result, SbiExtension, SbiMessage};
 use crate::{
-    NaclShmem, TsmInfo, TsmPageType, TsmShmemScratch, TvmCreateParams, NACL_SCRATCH_BYTES,
+    NaclShmem, TsmInfo, TsmPageType, TsmShmemScratch, TvmCreateParams, TvmMmioExit,
+    NACL_SCRATCH_BYTES,
 };
 
 /// Provides volatile accessors to a TEE `NaclShmem` area.
@@ -71,6 +75,15 @@ impl<'a> TsmShmemAreaRef<'a> {
             ptr::addr_of_mut!((*self.shmem_scratch_ptr()).guest_gprs[index]).write_volatile(val)
         };
     }
+
+    /// Decodes the emulated MMIO access that caused the most recent exit from `TvmCpuRun`, given
+    /// the `scause` and `stval` values reported for the exit. Returns `None` if the exit wasn't
+    /// caused by an access to an emulated MMIO region.
+    pub fn mmio_exit(&self, scause: u64, stval: u64) -> Option<TvmMmioExit> {
+        const CSR_HTVAL: u16 = 0x643;
+        const CSR_HTINST: u16 = 0x64a;
+        TvmMmioExit::from_exit(scause, stval, self.csr(CSR_HTVAL), self.csr(CSR_HTINST))
+    }
 }
 
 fn _assert_scratch_size() {
@@ -80,6 +93,7 @@ fn _assert_scratch_size() {
 /// Initiates a TSM fence on this CPU.
 pub fn tsm_initiate_fence() -> Result<()> {
     let msg = SbiMessage::TeeHost(TsmInitiateFence);
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: TsmInitiateFence doesn't read or write any memory we have access to.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -88,6 +102,7 @@ pub fn tsm_initiate_fence() -> Result<()> {
 /// Initiates a fence for the given TVM.
 pub fn tvm_initiate_fence(vmid: u64) -> Result<()> {
     let msg = SbiMessage::TeeHost(TvmInitiateFence { guest_id: vmid });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: TvmInitiateFence doesn't read or write any memory we have access to.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -98,9 +113,10 @@ pub fn get_info() -> Result<TsmInfo> {
     let mut tsm_info = TsmInfo::default();
     let tsm_info_size = core::mem::size_of::<TsmInfo>() as u64;
     let msg = SbiMessage::TeeHost(TsmGetInfo {
-        dest_addr: &mut tsm_info as *mut _ as u64,
-        len: tsm_info_size,
+        dest_addr: PhysAddr::new(&mut tsm_info as *mut _ as u64),
+        len: Len::new(tsm_info_size),
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: The passed info pointer is uniquely owned so it's safe to modify in SBI.
     let tsm_info_len = unsafe { ecall_send(&msg)? };
 
@@ -118,11 +134,15 @@ pub fn get_info() -> Result<TsmInfo> {
 ///
 /// The address provided must point to memory that won't be accessed again by the calling program
 /// until it is reclaimed from confidential memory.
+///
+/// Returns `Error::InvalidAddress` without making the call if `addr` isn't 4kB-aligned, or
+/// `Error::BadRange` if the pages run past the end of the address space.
 pub unsafe fn convert_pages(addr: u64, num_pages: u64) -> Result<()> {
     let msg = SbiMessage::TeeHost(TsmConvertPages {
-        page_addr: addr,
+        page_addr: pages(addr, PageSize::Size4k, num_pages)?,
         num_pages,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: The passed-in pages are unmapped and we do not access them again until they're
     // reclaimed.
     ecall_send(&msg)?;
@@ -130,11 +150,13 @@ pub unsafe fn convert_pages(addr: u64, num_pages: u64) -> Result<()> {
 }
 
 /// Reclaims pages that were previously converted to confidential memory with `convert_pages`.
+/// Returns an error without making the call for the same pages `convert_pages` rejects.
 pub fn reclaim_pages(addr: u64, num_pages: u64) -> Result<()> {
     let msg = SbiMessage::TeeHost(TsmReclaimPages {
-        page_addr: addr,
+        page_addr: pages(addr, PageSize::Size4k, num_pages)?,
         num_pages,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: The referenced pages are made accessible again, which is safe since we haven't
     // done anything with them since they were converted.
     unsafe { ecall_send(&msg) }?;
@@ -146,19 +168,24 @@ pub fn reclaim_pages(addr: u64, num_pages: u64) -> Result<()> {
 /// # Params:
 ///
 /// - tvm_page_directory_addr: The base physical address of the 16kB confidential memory region that
-/// should be used for the TVM's page directory. Must be 16kB-aligned.
+///   should be used for the TVM's page directory. Must be 16kB-aligned.
 ///
 /// - tvm_state_addr: The base physical address of the confidential memory region to be used to hold
-/// the TVM's global state. Must be page-aligned and `TsmInfo::tvm_state_pages` pages in length.
+///   the TVM's global state. Must be page-aligned and `TsmInfo::tvm_state_pages` pages in length.
+///
+/// Returns `Error::InvalidAddress` without making the call if either address is misaligned.
 pub fn tvm_create(tvm_page_directory_addr: u64, tvm_state_addr: u64) -> Result<u64> {
-    let tvm_create_params = TvmCreateParams {
-        tvm_page_directory_addr,
-        tvm_state_addr,
-    };
+    let tvm_create_params = TvmCreateParams::new(
+        PhysAddr::new(tvm_page_directory_addr),
+        PhysAddr::new(tvm_state_addr),
+    )?;
+    // The TSM reads the parameters as two 64-bit values, so they're passed in the `repr(C)`
+    // structure rather than as bytes, which have no alignment.
     let msg = SbiMessage::TeeHost(TvmCreate {
-        params_addr: (&tvm_create_params as *const TvmCreateParams) as u64,
-        len: core::mem::size_of::<TvmCreateParams>() as u64,
+        params_addr: PhysAddr::new(&tvm_create_params as *const TvmCreateParams as u64),
+        len: Len::new(TvmCreateParams::SIZE as u64),
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: creating a TVM will only touch pages that have already been converted to confidential
     // memory, so it can't affect memory safety as the host doesn't have access to those pages.
     let vmid = unsafe { ecall_send(&msg)? };
@@ -172,6 +199,7 @@ pub fn tvm_finalize(vmid: u64, entry_sepc: u64, entry_arg: u64) -> Result<()> {
         entry_sepc,
         entry_arg,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: `Finalize` doesn't touch memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -180,6 +208,7 @@ pub fn tvm_finalize(vmid: u64, entry_sepc: u64, entry_arg: u64) -> Result<()> {
 /// Destroys a TVM created with `tvm_create`.
 pub fn tvm_destroy(vmid: u64) -> Result<()> {
     let msg = SbiMessage::TeeHost(TvmDestroy { guest_id: vmid });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: destroying a VM doesn't write to memory that's accessible from the host.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -191,17 +220,20 @@ pub fn tvm_run(vmid: u64, vcpu_id: u64) -> Result<u64> {
         guest_id: vmid,
         vcpu_id,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: running a VM will only write to the shared-memory area registered in add_vcpu().
     unsafe { ecall_send(&msg) }
 }
 
-/// Adds pages to be used for page table entries of the given vmid.
+/// Adds pages to be used for page table entries of the given vmid. Returns an error without making
+/// the call if `page_addr` isn't 4kB-aligned or the pages run past the end of the address space.
 pub fn add_page_table_pages(vmid: u64, page_addr: u64, num_pages: u64) -> Result<()> {
     let msg = SbiMessage::TeeHost(AddPageTablePages {
         guest_id: vmid,
-        page_addr,
+        page_addr: pages(page_addr, PageSize::Size4k, num_pages)?,
         num_pages,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: `AddPageTablePages` only accesses pages that have been previously converted. Passing
     // non-converted memory will result in a failure and not touch the memory.
     unsafe { ecall_send(&msg) }?;
@@ -212,33 +244,64 @@ pub fn add_page_table_pages(vmid: u64, page_addr: u64, num_pages: u64) -> Result
 /// internal state.
 ///
 /// The address `state_page_addr` must reference confidential memory pages. The caller must provide
-/// `TsmInfo::tvm_vcpu_state_pages` pages.
+/// `TsmInfo::tvm_vcpu_state_pages` pages. Returns `Error::InvalidAddress` without making the call
+/// if it isn't 4kB-aligned.
 pub fn add_vcpu(vmid: u64, vcpu_id: u64, state_page_addr: u64) -> Result<()> {
     let msg = SbiMessage::TeeHost(TvmCpuCreate {
         guest_id: vmid,
         vcpu_id,
-        state_page_addr,
+        state_page_addr: PhysAddr::new_aligned(state_page_addr, PageSize::Size4k)?,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: TvmCpuCreate only accesses pages that have been converted and thus must already be
     // inaccessible to the calling program.
     unsafe { ecall_send(&msg) }?;
     Ok(())
 }
 
-/// Declares a memory region in the guest's physical address space.
+/// Declares a memory region in the guest's physical address space. Returns an error without making
+/// the call if `guest_addr` or `len` isn't 4kB-aligned or the region runs past the end of the
+/// address space.
+///
+/// ```
+/// use sbi_rs::{api::tee_host::add_memory_region, Error};
+///
+/// assert_eq!(add_memory_region(1, 0x8000_0800, 0x1000), Err(Error::InvalidAddress));
+/// assert_eq!(add_memory_region(1, 0x8000_0000, 0x800), Err(Error::InvalidParam));
+/// assert_eq!(add_memory_region(1, 0xffff_ffff_ffff_f000, 0x2000), Err(Error::BadRange));
+/// ```
 pub fn add_memory_region(vmid: u64, guest_addr: u64, len: u64) -> Result<()> {
+    let (guest_addr, len) = region(guest_addr, len)?;
     let msg = SbiMessage::TeeHost(TvmAddMemoryRegion {
         guest_id: vmid,
         guest_addr,
         len,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: `TvmAddMemoryRegion` doesn't access our memory at all.
     unsafe { ecall_send(&msg) }?;
     Ok(())
 }
 
+/// Declares an emulated MMIO region in the guest's physical address space. Accesses to the region
+/// by the guest cause `tvm_run` to return so that the host can emulate them. Returns an error
+/// without making the call for the same regions `add_memory_region` rejects.
+pub fn add_emulated_mmio_region(vmid: u64, guest_addr: u64, len: u64) -> Result<()> {
+    let (guest_addr, len) = region(guest_addr, len)?;
+    let msg = SbiMessage::TeeHost(TvmAddEmulatedMmioRegion {
+        guest_id: vmid,
+        guest_addr,
+        len,
+    });
+    probe::require(SbiExtension::TeeHost)?;
+    // Safety: `TvmAddEmulatedMmioRegion` doesn't access our memory at all.
+    unsafe { ecall_send(&msg) }?;
+    Ok(())
+}
+
 /// Copies the data from the pages backing `src_data` to the guest and records their measurement for
-/// attestation.  src_data must be aligned to the given page size.
+/// attestation.  src_data must be aligned to the given page size, as must `dest_addr` and
+/// `guest_addr`.
 pub fn add_measured_pages(
     vmid: u64,
     src_data: &[u8],
@@ -254,14 +317,17 @@ pub fn add_measured_pages(
         return Err(Error::InvalidParam);
     }
 
+    let page_size = page_type.page_size();
+    let num_pages = src_data.len() as u64 / page_type.size_bytes();
     let msg = SbiMessage::TeeHost(TvmAddMeasuredPages {
         guest_id: vmid,
-        src_addr: src_data.as_ptr() as u64,
-        dest_addr,
+        src_addr: PhysAddr::new(src_data.as_ptr() as u64),
+        dest_addr: pages(dest_addr, page_size, num_pages)?,
         page_type,
-        num_pages: src_data.len() as u64 / page_type.size_bytes(),
-        guest_addr,
+        num_pages,
+        guest_addr: pages(guest_addr, page_size, num_pages)?,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: `TvmAddMeasuredPages` only writes pages that have already been converted, and only
     // reads the pages pointed to by `src_addr`. This is safe because those pages are owned by the
     // borrowed slice and safe to read from.
@@ -270,7 +336,8 @@ pub fn add_measured_pages(
 }
 
 /// Adds previously converted pages to the guest at the given address. The page will be left cleared
-/// and read zeros to the guest.
+/// and read zeros to the guest. Returns an error without making the call if either address isn't
+/// aligned to `page_type` or the pages run past the end of the address space.
 pub fn add_zero_pages(
     vmid: u64,
     page_addr: u64,
@@ -278,19 +345,22 @@ pub fn add_zero_pages(
     num_pages: u64,
     guest_addr: u64,
 ) -> Result<()> {
+    let page_size = page_type.page_size();
     let msg = SbiMessage::TeeHost(TvmAddZeroPages {
         guest_id: vmid,
-        page_addr,
+        page_addr: pages(page_addr, page_size, num_pages)?,
         page_type,
         num_pages,
-        guest_addr,
+        guest_addr: pages(guest_addr, page_size, num_pages)?,
     });
+    probe::require(SbiExtension::TeeHost)?;
     // Safety: `TvmAddZeroPages` only touches pages that we've already converted.
     unsafe { ecall_send(&msg) }?;
     Ok(())
 }
 
-/// Adds pages shared between the host and the given TVM.
+/// Adds pages shared between the host and the given TVM. Returns an error without making the call
+/// for the same pages `add_zero_pages` rejects.
 ///
 /// # Safety
 ///
@@ -303,13 +373,137 @@ pub unsafe fn add_shared_pages(
     num_pages: u64,
     guest_addr: u64,
 ) -> Result<()> {
+    let page_size = page_type.page_size();
     let msg = SbiMessage::TeeHost(TvmAddSharedPages {
         guest_id: vmid,
-        page_addr,
+        page_addr: pages(page_addr, page_size, num_pages)?,
         page_type,
         num_pages,
-        guest_addr,
+        guest_addr: pages(guest_addr, page_size, num_pages)?,
     });
+    probe::require(SbiExtension::TeeHost)?;
     ecall_send(&msg)?;
     Ok(())
 }
+
+/// Removes pages previously shared with the given TVM using `add_shared_pages`. The pages may be
+/// reused by the host once a fence for the TVM has been completed with `tvm_initiate_fence`.
+/// Returns an error without making the call if `guest_addr` isn't aligned to `page_type` or the
+/// pages run past the end of the address space.
+pub fn remove_shared_pages(
+    vmid: u64,
+    guest_addr: u64,
+    page_type: TsmPageType,
+    num_pages: u64,
+) -> Result<()> {
+    let msg = SbiMessage::TeeHost(TvmRemoveSharedPages {
+        guest_id: vmid,
+        guest_addr: pages(guest_addr, page_type.page_size(), num_pages)?,
+        page_type,
+        num_pages,
+    });
+    probe::require(SbiExtension::TeeHost)?;
+    // Safety: `TvmRemoveSharedPages` only removes the TVM's access to the pages, it doesn't touch
+    // their contents.
+    unsafe { ecall_send(&msg) }?;
+    Ok(())
+}
+
+/// A range of host pages shared with a TVM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SharedPageRange {
+    /// The host physical address of the first page.
+    pub page_addr: u64,
+    /// The size of the pages.
+    pub page_type: TsmPageType,
+    /// The number of pages.
+    pub num_pages: u64,
+    /// The guest physical address the pages are mapped at.
+    pub guest_addr: u64,
+}
+
+/// Tracks the ranges of pages shared with a TVM, so that the host can make sure that all shared
+/// pages have been removed, e.g. when tearing down virtio queues, before destroying the TVM.
+/// Tracks up to `N` ranges.
+pub struct SharedPagesTracker<const N: usize> {
+    vmid: u64,
+    ranges: ArrayVec<SharedPageRange, N>,
+}
+
+impl<const N: usize> SharedPagesTracker<N> {
+    /// Creates a tracker for the shared pages of the TVM `vmid`.
+    pub fn new(vmid: u64) -> Self {
+        Self {
+            vmid,
+            ranges: ArrayVec::new(),
+        }
+    }
+
+    /// Returns the ranges of pages currently shared with the TVM.
+    pub fn ranges(&self) -> &[SharedPageRange] {
+        &self.ranges
+    }
+
+    /// Returns if no pages are currently shared with the TVM.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Shares the given range of pages with the TVM and records it. Returns an error without making
+    /// the call if the tracker is full.
+    ///
+    /// # Safety
+    ///
+    /// The pages to be shared must be owned by the caller and treated as volatile for the entire time
+    /// the page is shared.
+    pub unsafe fn add_shared_pages(&mut self, range: SharedPageRange) -> Result<()> {
+        if self.ranges.is_full() {
+            return Err(Error::InsufficientBufferCapacity);
+        }
+        add_shared_pages(
+            self.vmid,
+            range.page_addr,
+            range.page_type,
+            range.num_pages,
+            range.guest_addr,
+        )?;
+        self.ranges.push(range);
+        Ok(())
+    }
+
+    /// Removes the range of shared pages mapped at `guest_addr` from the TVM and returns it.
+    /// Returns an error if no range shared through this tracker is mapped at `guest_addr`.
+    pub fn remove_shared_pages(&mut self, guest_addr: u64) -> Result<SharedPageRange> {
+        let index = self
+            .ranges
+            .iter()
+            .position(|r| r.guest_addr == guest_addr)
+            .ok_or(Error::InvalidParam)?;
+        let range = self.ranges[index];
+        remove_shared_pages(
+            self.vmid,
+            range.guest_addr,
+            range.page_type,
+            range.num_pages,
+        )?;
+        self.ranges.remove(index);
+        Ok(range)
+    }
+
+    /// Removes all ranges of shared pages from the TVM.
+    pub fn remove_all(&mut self) -> Result<()> {
+        while let Some(range) = self.ranges.last() {
+            self.remove_shared_pages(range.guest_addr)?;
+        }
+        Ok(())
+    }
+
+    /// Destroys the TVM. Returns an error without destroying the TVM if any pages are still shared
+    /// with it.
+    pub fn tvm_destroy(&mut self) -> Result<()> {
+        if !self.is_empty() {
+            return Err(Error::Failed);
+        }
+        tvm_destroy(self.vmid)
+    }
+}
@@ -2,17 +2,19 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::api::probe;
 use crate::TeeInterruptFunction::*;
-use crate::TvmAiaParams;
-use crate::{ecall_send, Result, SbiMessage};
+use crate::{ecall_send, Len, PageSize, PhysAddr, Result, SbiExtension, SbiMessage};
+use crate::{ImsicFileMask, TvmAiaParams};
 
 /// Configures AIA virtualization for `tvm_id` with the settings in `tvm_aia_params`.
 pub fn tvm_aia_init(tvm_id: u64, tvm_aia_params: TvmAiaParams) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmAiaInit {
         tvm_id,
-        params_addr: (&tvm_aia_params as *const TvmAiaParams) as u64,
-        len: core::mem::size_of::<TvmAiaParams>() as u64,
+        params_addr: PhysAddr::new((&tvm_aia_params as *const TvmAiaParams) as u64),
+        len: Len::new(core::mem::size_of::<TvmAiaParams>() as u64),
     });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: `TvmConfigureAia` will only read up to `len` bytes of the `TvmAiaParams` structure
     // we passed in.
     unsafe { ecall_send(&msg) }?;
@@ -20,33 +22,41 @@ pub fn tvm_aia_init(tvm_id: u64, tvm_aia_params: TvmAiaParams) -> Result<()> {
 }
 
 /// Sets the guest physical address of the specified vCPU's virtualized IMSIC to `imsic_addr`.
+/// Returns `Error::InvalidAddress` without making the call if it isn't 4kB-aligned.
 pub fn set_vcpu_imsic_addr(tvm_id: u64, vcpu_id: u64, imsic_addr: u64) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmCpuSetImsicAddr {
         tvm_id,
         vcpu_id,
-        imsic_addr,
+        imsic_addr: PhysAddr::new_aligned(imsic_addr, PageSize::Size4k)?,
     });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: `TvmCpuSetImsicAddr` doesn't touch host memory in any way.
     unsafe { ecall_send(&msg) }?;
     Ok(())
 }
 
-/// Converts the guest interrupt file at `imsic_addr` for use with a TVM.
+/// Converts the guest interrupt file at `imsic_addr` for use with a TVM. Returns
+/// `Error::InvalidAddress` without making the call if `imsic_addr` isn't 4kB-aligned.
 ///
 /// # Safety
 ///
 /// The caller must not access the guest interrupt file again until it has been reclaimed.
 pub unsafe fn convert_imsic(imsic_addr: u64) -> Result<()> {
+    let imsic_addr = PhysAddr::new_aligned(imsic_addr, PageSize::Size4k)?;
     let msg = SbiMessage::TeeInterrupt(TsmConvertImsic { imsic_addr });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // The caller must guarantee that they won't access the page at `imsic_addr`.
     ecall_send(&msg)?;
     Ok(())
 }
 
 /// Reclaims the guest interrupt file at `imsic_addr` that was previously converted with
-/// `convert_imsic()`.
+/// `convert_imsic()`. Returns `Error::InvalidAddress` without making the call if `imsic_addr`
+/// isn't 4kB-aligned.
 pub fn reclaim_imsic(imsic_addr: u64) -> Result<()> {
+    let imsic_addr = PhysAddr::new_aligned(imsic_addr, PageSize::Size4k)?;
     let msg = SbiMessage::TeeInterrupt(TsmReclaimImsic { imsic_addr });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: The referenced page is made available again, which is safe since it hasn't been
     // accessible since conversion.
     unsafe { ecall_send(&msg) }?;
@@ -55,12 +65,13 @@ pub fn reclaim_imsic(imsic_addr: u64) -> Result<()> {
 
 /// Binds a vCPU to this physical CPU and the specified set of confidential guest interrupt
 /// files.
-pub fn bind_vcpu_imsic(tvm_id: u64, vcpu_id: u64, imsic_mask: u64) -> Result<()> {
+pub fn bind_vcpu_imsic(tvm_id: u64, vcpu_id: u64, imsic_mask: ImsicFileMask) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmCpuBindImsic {
         tvm_id,
         vcpu_id,
         imsic_mask,
     });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: The specified guest interrupt files must have already been inaccessible.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -71,6 +82,7 @@ pub fn bind_vcpu_imsic(tvm_id: u64, vcpu_id: u64, imsic_mask: u64) -> Result<()>
 /// completing the unbind with `unbind_vcpu_imsic_end()`.
 pub fn unbind_vcpu_imsic_begin(tvm_id: u64, vcpu_id: u64) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmCpuUnbindImsicBegin { tvm_id, vcpu_id });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: Does not access host memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -81,6 +93,7 @@ pub fn unbind_vcpu_imsic_begin(tvm_id: u64, vcpu_id: u64) -> Result<()> {
 /// and the vCPU can now be bound to another physical CPU.
 pub fn unbind_vcpu_imsic_end(tvm_id: u64, vcpu_id: u64) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmCpuUnbindImsicEnd { tvm_id, vcpu_id });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: Does not access host memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -94,6 +107,7 @@ pub fn inject_external_interrupt(tvm_id: u64, vcpu_id: u64, interrupt_id: u64) -
         vcpu_id,
         interrupt_id,
     });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: Does not access host memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -103,12 +117,13 @@ pub fn inject_external_interrupt(tvm_id: u64, vcpu_id: u64, interrupt_id: u64) -
 /// confidential guest interrupt file. The host must complete a TLB invalidation sequence
 /// for the TVM before cloning old interrupt file state using `rebind_vcpu_imsic_clone`. Once cloned
 /// the old file will be restored to new guest interrupt file on `rebind_vcpu_imsic_end` invocation.
-pub fn rebind_vcpu_imsic_begin(tvm_id: u64, vcpu_id: u64, imsic_mask: u64) -> Result<()> {
+pub fn rebind_vcpu_imsic_begin(tvm_id: u64, vcpu_id: u64, imsic_mask: ImsicFileMask) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmCpuRebindImsicBegin {
         tvm_id,
         vcpu_id,
         imsic_mask,
     });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: The specified guest interrupt files must have already been inaccessible.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -119,6 +134,7 @@ pub fn rebind_vcpu_imsic_begin(tvm_id: u64, vcpu_id: u64, imsic_mask: u64) -> Re
 /// vCPU.
 pub fn rebind_vcpu_imsic_clone(tvm_id: u64, vcpu_id: u64) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmCpuRebindImsicClone { tvm_id, vcpu_id });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: Does not access host memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -128,6 +144,7 @@ pub fn rebind_vcpu_imsic_clone(tvm_id: u64, vcpu_id: u64) -> Result<()> {
 /// interrupt files. Must be called from the same physical CPU as `rebind_vcpu_imsic_begin`.
 pub fn rebind_vcpu_imsic_end(tvm_id: u64, vcpu_id: u64) -> Result<()> {
     let msg = SbiMessage::TeeInterrupt(TvmCpuRebindImsicEnd { tvm_id, vcpu_id });
+    probe::require(SbiExtension::TeeInterrupt)?;
     // Safety: Does not access host memory.
     unsafe { ecall_send(&msg) }?;
     Ok(())
@@ -0,0 +1,238 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Mirrors a guest memory map, as described by a device tree or similar, into a TVM.
+//!
+//! The caller parses its memory map description and supplies the resulting regions and images as
+//! slices; no parser is needed here. `TvmLayout::new()` validates the layout, and
+//! `TvmLayout::populate()` issues the `TvmAddMemoryRegion`, `TvmAddEmulatedMmioRegion`,
+//! `TvmAddMeasuredPages` and `TvmAddZeroPages` calls to build it.
+
+use crate::api::tee_host::{
+    add_emulated_mmio_region, add_measured_pages, add_memory_region, add_zero_pages,
+};
+use crate::ValidationErrorKind::*;
+use crate::{Error, Result, TsmPageType, ValidationError};
+
+const PAGE_SIZE: u64 = 4096;
+
+/// How a region of guest physical address space is backed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuestRegionKind {
+    /// Confidential memory. Only the images within the region are populated; the host adds the
+    /// remaining pages later, for example on guest page faults.
+    Confidential,
+    /// Confidential memory whose pages not covered by an image are populated with zero pages.
+    ConfidentialZeroed,
+    /// An emulated MMIO region.
+    EmulatedMmio,
+}
+
+/// A region of a TVM's guest physical address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuestMemoryRegion {
+    /// The 4kB-aligned guest physical address of the start of the region.
+    pub guest_addr: u64,
+    /// The length of the region in bytes; must be a non-zero multiple of 4kB.
+    pub len: u64,
+    /// How the region is backed.
+    pub kind: GuestRegionKind,
+}
+
+impl GuestMemoryRegion {
+    fn end(&self) -> u64 {
+        self.guest_addr + self.len
+    }
+
+    fn contains(&self, guest_addr: u64, len: u64) -> bool {
+        guest_addr >= self.guest_addr && guest_addr + len <= self.end()
+    }
+}
+
+/// An image, such as a kernel or initramfs, to be copied into confidential guest memory and
+/// measured.
+#[derive(Clone, Copy, Debug)]
+pub struct GuestImage<'a> {
+    /// The contents of the image. Must be 4kB-aligned and a non-zero multiple of 4kB long.
+    pub data: &'a [u8],
+    /// The 4kB-aligned guest physical address at which to load the image.
+    pub guest_addr: u64,
+}
+
+impl<'a> GuestImage<'a> {
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+}
+
+/// A source of pages that have been converted with `convert_pages()` and may be donated to a TVM.
+pub trait ConvertedPageSource {
+    /// Returns the address of a run of between 1 and `max_pages` contiguous converted 4kB pages,
+    /// along with the number of pages in the run.
+    fn take_pages(&mut self, max_pages: u64) -> Result<(u64, u64)>;
+}
+
+/// A validated guest memory map for a TVM.
+#[derive(Clone, Copy, Debug)]
+pub struct TvmLayout<'a> {
+    regions: &'a [GuestMemoryRegion],
+    images: &'a [GuestImage<'a>],
+}
+
+// Checks the range of `len` bytes at `guest_addr` of a region or image, whose fields are named
+// `addr_field` and `len_field` in errors.
+fn check_range(
+    guest_addr: u64,
+    len: u64,
+    addr_field: &'static str,
+    len_field: &'static str,
+) -> core::result::Result<(), ValidationError> {
+    if !guest_addr.is_multiple_of(PAGE_SIZE) {
+        return Err(ValidationError::new(addr_field, Misaligned));
+    }
+    if !len.is_multiple_of(PAGE_SIZE) {
+        return Err(ValidationError::new(len_field, Misaligned));
+    }
+    if len == 0 {
+        return Err(ValidationError::new(len_field, Empty));
+    }
+    guest_addr
+        .checked_add(len)
+        .map(|_| ())
+        .ok_or(ValidationError::new(len_field, Overflow))
+}
+
+impl<'a> TvmLayout<'a> {
+    /// Validates the guest memory map made up of `regions` and the `images` to be loaded into it.
+    ///
+    /// Returns a `ValidationError` naming the offending field if a region or image is misaligned,
+    /// empty or wraps around the address space, if regions or images overlap each other, or if an
+    /// image doesn't fall entirely inside a single confidential region.
+    pub fn new(
+        regions: &'a [GuestMemoryRegion],
+        images: &'a [GuestImage<'a>],
+    ) -> core::result::Result<Self, ValidationError> {
+        for (i, r) in regions.iter().enumerate() {
+            check_range(r.guest_addr, r.len, "region.guest_addr", "region.len")?;
+            if regions[..i]
+                .iter()
+                .any(|other| r.guest_addr < other.end() && other.guest_addr < r.end())
+            {
+                return Err(ValidationError::new("region.guest_addr", Overlap));
+            }
+        }
+        for (i, image) in images.iter().enumerate() {
+            if image.data.as_ptr().align_offset(PAGE_SIZE as usize) != 0 {
+                return Err(ValidationError::new("image.data", Misaligned));
+            }
+            check_range(
+                image.guest_addr,
+                image.len(),
+                "image.guest_addr",
+                "image.data",
+            )?;
+            if !regions.iter().any(|r| {
+                r.kind != GuestRegionKind::EmulatedMmio && r.contains(image.guest_addr, image.len())
+            }) {
+                return Err(ValidationError::new("image.guest_addr", Uncovered));
+            }
+            if images[..i].iter().any(|other| {
+                image.guest_addr < other.guest_addr + other.len()
+                    && other.guest_addr < image.guest_addr + image.len()
+            }) {
+                return Err(ValidationError::new("image.guest_addr", Overlap));
+            }
+        }
+        Ok(Self { regions, images })
+    }
+
+    /// Returns the regions of the layout.
+    pub fn regions(&self) -> &'a [GuestMemoryRegion] {
+        self.regions
+    }
+
+    /// Returns the images of the layout.
+    pub fn images(&self) -> &'a [GuestImage<'a>] {
+        self.images
+    }
+
+    // Returns the lowest-addressed image at or above `guest_addr` that lies within `region`.
+    fn next_image(
+        &self,
+        region: &GuestMemoryRegion,
+        guest_addr: u64,
+    ) -> Option<&'a GuestImage<'a>> {
+        self.images
+            .iter()
+            .filter(|image| {
+                image.guest_addr >= guest_addr && region.contains(image.guest_addr, image.len())
+            })
+            .min_by_key(|image| image.guest_addr)
+    }
+
+    /// Builds the layout in the TVM `vmid`, taking the pages to donate from `pages`. Regions are
+    /// declared in the order they were supplied, and within each region images are loaded in
+    /// order of guest address so that the resulting measurement doesn't depend on the order of
+    /// `images`.
+    pub fn populate(&self, vmid: u64, pages: &mut dyn ConvertedPageSource) -> Result<()> {
+        for region in self.regions {
+            match region.kind {
+                GuestRegionKind::EmulatedMmio => {
+                    add_emulated_mmio_region(vmid, region.guest_addr, region.len)?;
+                    continue;
+                }
+                _ => add_memory_region(vmid, region.guest_addr, region.len)?,
+            }
+            let mut cursor = region.guest_addr;
+            while cursor < region.end() {
+                let image = self.next_image(region, cursor);
+                let gap_end = image.map_or(region.end(), |image| image.guest_addr);
+                if region.kind == GuestRegionKind::ConfidentialZeroed {
+                    add_zeroed_range(vmid, cursor, gap_end, pages)?;
+                }
+                let Some(image) = image else {
+                    break;
+                };
+                add_image(vmid, image, pages)?;
+                cursor = image.guest_addr + image.len();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn add_zeroed_range(
+    vmid: u64,
+    mut guest_addr: u64,
+    end: u64,
+    pages: &mut dyn ConvertedPageSource,
+) -> Result<()> {
+    while guest_addr < end {
+        let (page_addr, num_pages) = take_pages(pages, (end - guest_addr) / PAGE_SIZE)?;
+        add_zero_pages(vmid, page_addr, TsmPageType::Page4k, num_pages, guest_addr)?;
+        guest_addr += num_pages * PAGE_SIZE;
+    }
+    Ok(())
+}
+
+fn add_image(vmid: u64, image: &GuestImage, pages: &mut dyn ConvertedPageSource) -> Result<()> {
+    let mut data = image.data;
+    let mut guest_addr = image.guest_addr;
+    while !data.is_empty() {
+        let (dest_addr, num_pages) = take_pages(pages, data.len() as u64 / PAGE_SIZE)?;
+        let (chunk, rest) = data.split_at((num_pages * PAGE_SIZE) as usize);
+        add_measured_pages(vmid, chunk, dest_addr, TsmPageType::Page4k, guest_addr)?;
+        data = rest;
+        guest_addr += num_pages * PAGE_SIZE;
+    }
+    Ok(())
+}
+
+fn take_pages(pages: &mut dyn ConvertedPageSource, max_pages: u64) -> Result<(u64, u64)> {
+    let (page_addr, num_pages) = pages.take_pages(max_pages)?;
+    if num_pages == 0 || num_pages > max_pages || !page_addr.is_multiple_of(PAGE_SIZE) {
+        return Err(Error::InvalidParam);
+    }
+    Ok((page_addr, num_pages))
+}
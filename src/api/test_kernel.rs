@@ -0,0 +1,188 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A minimal runtime and assertion helpers for bare-metal test kernels that exercise SBI
+//! implementations, such as OpenSBI or KVM, through real `ecall`s.
+//!
+//! Each test's result is printed on the console, and the kernel exits with a System Reset call
+//! whose reason reports whether every test passed, so that QEMU's exit status can be checked by
+//! CI. `sbi_test_kernel!` provides the entry point, which sets up the stack from the linker
+//! symbol `_stack_top` before running the tests, along with the panic handler:
+//!
+//! ```ignore
+//! #![no_std]
+//! #![no_main]
+//!
+//! use sbi_rs::api::base;
+//! use sbi_rs::api::test_kernel::{check, Test, TestResult};
+//!
+//! fn spec_version(_hart_id: u64) -> TestResult {
+//!     let version = base::get_specification_version().map_err(|_| "call failed")?;
+//!     check(version >= 2, "version older than v0.2")
+//! }
+//!
+//! const TESTS: &[(&str, Test)] = &[("spec_version", spec_version)];
+//!
+//! sbi_rs::sbi_test_kernel!(TESTS);
+//! ```
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use crate::api::guest_runtime::GuestRuntime;
+use crate::api::reset;
+use crate::{ResetReason, ResetType};
+
+/// The outcome of a test: `Err` with a description of what went wrong if it failed.
+pub type TestResult = core::result::Result<(), &'static str>;
+
+/// A test, passed the ID of the hart it runs on.
+pub type Test = fn(u64) -> TestResult;
+
+/// Fails with `msg` unless `cond` holds.
+pub fn check(cond: bool, msg: &'static str) -> TestResult {
+    if cond {
+        Ok(())
+    } else {
+        Err(msg)
+    }
+}
+
+/// Fails with `msg` unless `left` and `right` are equal.
+pub fn check_eq<T: PartialEq>(left: T, right: T, msg: &'static str) -> TestResult {
+    check(left == right, msg)
+}
+
+/// Fails with `msg` if `result` is an error, returning its value otherwise.
+pub fn check_ok<T, E>(
+    result: core::result::Result<T, E>,
+    msg: &'static str,
+) -> core::result::Result<T, &'static str> {
+    result.map_err(|_| msg)
+}
+
+/// The number of tests that passed and failed in a run of `run_tests()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TestSummary {
+    /// The number of tests that passed.
+    pub passed: usize,
+    /// The number of tests that failed.
+    pub failed: usize,
+}
+
+impl TestSummary {
+    /// Returns if every test passed.
+    pub fn success(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// Shuts the system down, with `ResetReason::SystemFailure` if any test failed.
+    pub fn exit(&self) -> ! {
+        let reason = if self.success() {
+            ResetReason::NoReason
+        } else {
+            ResetReason::SystemFailure
+        };
+        let _ = reset::reset(ResetType::Shutdown, reason);
+        halt()
+    }
+}
+
+/// Runs `tests` on the hart with ID `hart_id`, printing each test's result and a summary on the
+/// console.
+///
+/// ```rust
+/// # #[cfg(all(feature = "std", feature = "handler"))] {
+/// use sbi_rs::api::test_kernel::{check, run_tests, Test, TestResult, TestSummary};
+/// use sbi_rs::api::time;
+/// use sbi_rs::mock::{MockFirmware, MockMachine};
+///
+/// fn set_timer(_hart_id: u64) -> TestResult {
+///     check(time::set_timer(0x1000).is_ok(), "set_timer failed")
+/// }
+///
+/// fn failing(_hart_id: u64) -> TestResult {
+///     Err("always fails")
+/// }
+///
+/// const TESTS: &[(&str, Test)] = &[("set_timer", set_timer), ("failing", failing)];
+///
+/// let firmware = MockFirmware::new(MockMachine::new(1));
+/// let summary = firmware.run(|| run_tests(0, TESTS));
+/// assert_eq!(summary, TestSummary { passed: 1, failed: 1 });
+/// let console = firmware.machine_mut().take_console();
+/// assert!(console.ends_with(b"test result: 1 passed; 1 failed\n"));
+/// # }
+/// ```
+pub fn run_tests(hart_id: u64, tests: &[(&str, Test)]) -> TestSummary {
+    let mut console = GuestRuntime::new().console();
+    let mut summary = TestSummary::default();
+    // Printing is best effort; the exit status reports the results either way.
+    let _ = writeln!(console, "sbi_rs tests on hart {hart_id}");
+    for (name, test) in tests {
+        match test(hart_id) {
+            Ok(()) => {
+                summary.passed += 1;
+                let _ = writeln!(console, "test {name} ... ok");
+            }
+            Err(msg) => {
+                summary.failed += 1;
+                let _ = writeln!(console, "test {name} ... FAILED: {msg}");
+            }
+        }
+    }
+    let _ = writeln!(
+        console,
+        "test result: {} passed; {} failed",
+        summary.passed, summary.failed
+    );
+    summary
+}
+
+/// Runs `tests` with `run_tests()` and shuts the system down, reporting whether they all passed.
+pub fn run_tests_and_exit(hart_id: u64, tests: &[(&str, Test)]) -> ! {
+    run_tests(hart_id, tests).exit()
+}
+
+/// Reports `info` on the console and shuts the system down as failed, for the test kernel's
+/// `#[panic_handler]`. A panic fails the whole run, including the tests that didn't run yet.
+pub fn handle_panic(info: &PanicInfo) -> ! {
+    GuestRuntime::new().handle_panic(info)
+}
+
+fn halt() -> ! {
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Defines the entry point and panic handler of a test kernel running the tests in `$tests`, a
+/// `&[(&str, Test)]`, with `run_tests_and_exit()`. The entry point `_start` must be placed at the
+/// address the SBI implementation jumps to, in the `.text.entry` section, and the stack must end
+/// at the linker symbol `_stack_top`.
+#[macro_export]
+macro_rules! sbi_test_kernel {
+    ($tests:expr) => {
+        core::arch::global_asm!(
+            ".section .text.entry",
+            ".global _start",
+            "_start:",
+            "la sp, _stack_top",
+            "call sbi_test_kernel_main",
+            "1:",
+            "wfi",
+            "j 1b",
+        );
+
+        #[no_mangle]
+        extern "C" fn sbi_test_kernel_main(hart_id: u64) -> ! {
+            $crate::api::test_kernel::run_tests_and_exit(hart_id, $tests)
+        }
+
+        #[panic_handler]
+        fn panic(info: &core::panic::PanicInfo) -> ! {
+            $crate::api::test_kernel::handle_panic(info)
+        }
+    };
+}
@@ -0,0 +1,89 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(all(
+    any(target_arch = "riscv64", target_arch = "riscv32"),
+    target_os = "none"
+))]
+use core::arch::asm;
+
+use crate::api::fallback::Fallback;
+#[cfg(not(all(
+    any(target_arch = "riscv64", target_arch = "riscv32"),
+    target_os = "none"
+)))]
+use crate::Error;
+use crate::TimeFunction::*;
+use crate::{ecall_send, Result, SbiMessage};
+
+/// A function programming the timer to fire at an absolute time.
+pub type SetTimerFn = fn(u64) -> Result<()>;
+
+/// Programs the timer to fire at the absolute time `stime_value` and clears any pending timer
+/// interrupt.
+pub fn set_timer(stime_value: u64) -> Result<()> {
+    let msg = SbiMessage::Time(SetTimer { stime_value });
+    // Safety: SetTimer doesn't touch memory.
+    unsafe { ecall_send(&msg) }?;
+    Ok(())
+}
+
+/// Cancels any previously programmed timer event and clears any pending timer interrupt.
+pub fn clear_timer() -> Result<()> {
+    set_timer(u64::MAX)
+}
+
+/// Programs the timer to fire at the absolute time `stime_value` by writing the `stimecmp` CSR
+/// of the Sstc extension directly, without trapping to the SBI implementation. The caller must
+/// have determined that Sstc is implemented and enabled for S mode. Fails with
+/// `Error::NotSupported` off-target.
+pub fn sstc_set_timer(stime_value: u64) -> Result<()> {
+    write_stimecmp(stime_value)
+}
+
+#[cfg(all(target_arch = "riscv64", target_os = "none"))]
+fn write_stimecmp(stime_value: u64) -> Result<()> {
+    // Safety: Writing stimecmp only affects when the supervisor timer interrupt is pending.
+    unsafe { asm!("csrw 0x14d, {}", in(reg) stime_value) };
+    Ok(())
+}
+
+#[cfg(all(target_arch = "riscv32", target_os = "none"))]
+fn write_stimecmp(stime_value: u64) -> Result<()> {
+    // Safety: As on RV64. The low half is first set to its maximum value so that no interrupt is
+    // raised for a time made of the new high half and the previous low half.
+    unsafe {
+        asm!(
+            "csrw 0x14d, {max}",
+            "csrw 0x15d, {hi}",
+            "csrw 0x14d, {lo}",
+            max = in(reg) u32::MAX,
+            hi = in(reg) (stime_value >> 32) as u32,
+            lo = in(reg) stime_value as u32,
+        )
+    };
+    Ok(())
+}
+
+#[cfg(not(all(
+    any(target_arch = "riscv64", target_arch = "riscv32"),
+    target_os = "none"
+)))]
+fn write_stimecmp(_stime_value: u64) -> Result<()> {
+    Err(Error::NotSupported)
+}
+
+/// Returns the timer, programmed with `sstc_set_timer()` if `has_sstc` is true and with the
+/// Timer extension's `set_timer()` otherwise. Whether Sstc is available is for the caller to
+/// detect, for example from the ISA string in the device tree.
+///
+/// ```no_run
+/// use sbi_rs::api::time;
+///
+/// let timer = time::timer(false);
+/// timer.call(0x1000).unwrap();
+/// ```
+pub fn timer(has_sstc: bool) -> Fallback<SetTimerFn> {
+    Fallback::new(has_sstc, sstc_set_timer, set_timer)
+}
@@ -0,0 +1,176 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use arrayvec::ArrayVec;
+use static_assertions::const_assert;
+
+use crate::api::pmu;
+use crate::{AsUsize, Error, PmuFunction, PmuSnapshot, Result, PMU_SNAPSHOT_MAX_COUNTERS, XLEN};
+
+fn _assert_snapshot_size() {
+    const_assert!(core::mem::size_of::<PmuSnapshot>() == 4096);
+}
+
+/// Exposes a subset of the host's PMU counters to a guest, for hypervisors implementing the PMU
+/// extension for their guests on top of the host's.
+///
+/// Guest counter `i` is backed by the `i`th host counter passed to `add_counter()`. Guest PMU
+/// calls passed to `handle()` are translated to calls on the backing host counters, which are
+/// forwarded to the host through `api::pmu`.
+pub struct VirtualPmu<const N: usize> {
+    host_counters: ArrayVec<u64, N>,
+}
+
+impl<const N: usize> VirtualPmu<N> {
+    /// Creates a virtual PMU with no counters.
+    pub fn new() -> Self {
+        Self {
+            host_counters: ArrayVec::new(),
+        }
+    }
+
+    /// Adds a guest counter backed by the host counter at `host_index`, returning the index of the
+    /// new guest counter. Returns an error if all `N` guest counters are in use or if the host
+    /// counter already backs another guest counter.
+    pub fn add_counter(&mut self, host_index: u64) -> Result<u64> {
+        if self.host_counters.contains(&host_index) {
+            return Err(Error::InvalidParam);
+        }
+        self.host_counters
+            .try_push(host_index)
+            .map_err(|_| Error::InsufficientBufferCapacity)?;
+        Ok(self.host_counters.len() as u64 - 1)
+    }
+
+    /// Returns the number of guest counters.
+    pub fn num_counters(&self) -> u64 {
+        self.host_counters.len() as u64
+    }
+
+    /// Returns the host counter backing the guest counter at `guest_index`.
+    pub fn host_index(&self, guest_index: u64) -> Result<u64> {
+        self.host_counters
+            .get(guest_index.as_usize()?)
+            .copied()
+            .ok_or(Error::InvalidParam)
+    }
+
+    /// Returns the guest counter backed by the host counter at `host_index`, if any.
+    pub fn guest_index(&self, host_index: u64) -> Option<u64> {
+        self.host_counters
+            .iter()
+            .position(|&h| h == host_index)
+            .map(|i| i as u64)
+    }
+
+    // Returns the host counters backing the guest counters selected by a guest's `counter_index`
+    // and `counter_mask` arguments, failing if any of the selected guest counters doesn't exist.
+    fn selected(&self, counter_index: u64, counter_mask: u64) -> Result<ArrayVec<u64, 64>> {
+        if counter_mask == 0 {
+            return Err(Error::InvalidParam);
+        }
+        (0..XLEN as u64)
+            .filter(|bit| counter_mask & (1 << bit) != 0)
+            .map(|bit| {
+                counter_index
+                    .checked_add(bit)
+                    .ok_or(Error::InvalidParam)
+                    .and_then(|guest_index| self.host_index(guest_index))
+            })
+            .collect()
+    }
+
+    /// Handles the PMU call `func` made by the guest, returning the value to be returned to the
+    /// guest.
+    ///
+    /// Calls that select several counters are forwarded to the host one counter at a time.
+    /// `ConfigureMatchingCounters` returns the first selected guest counter the host could
+    /// configure. `StartCounters` and `StopCounters` are applied to every selected counter; if any
+    /// of them fails, the first error is returned once all have been attempted.
+    pub fn handle(&self, func: &PmuFunction) -> Result<u64> {
+        use PmuFunction::*;
+        match *func {
+            GetNumCounters => Ok(self.num_counters()),
+            GetCounterInfo(guest_index) => {
+                pmu::get_counter_info(self.host_index(guest_index)?).map(|info| info.raw())
+            }
+            ConfigureMatchingCounters {
+                counter_index,
+                counter_mask,
+                config_flags,
+                event_type,
+                event_data,
+            } => {
+                let mut result = Err(Error::NotSupported);
+                for host in self.selected(counter_index, counter_mask)? {
+                    result = pmu::configure_matching_counters(
+                        host,
+                        1,
+                        config_flags,
+                        event_type,
+                        event_data,
+                    )
+                    .and_then(|host| self.guest_index(host).ok_or(Error::Failed));
+                    if result.is_ok() {
+                        break;
+                    }
+                }
+                result
+            }
+            StartCounters {
+                counter_index,
+                counter_mask,
+                start_flags,
+                initial_value,
+            } => self
+                .selected(counter_index, counter_mask)?
+                .into_iter()
+                .map(|host| pmu::start_counters(host, 1, start_flags, initial_value))
+                .fold(Ok(()), Result::and)
+                .map(|_| 0),
+            StopCounters {
+                counter_index,
+                counter_mask,
+                stop_flags,
+            } => self
+                .selected(counter_index, counter_mask)?
+                .into_iter()
+                .map(|host| pmu::stop_counters(host, 1, stop_flags))
+                .fold(Ok(()), Result::and)
+                .map(|_| 0),
+            ReadFirmwareCounter(guest_index) => {
+                pmu::read_firmware_counter(self.host_index(guest_index)?)
+            }
+            ReadFirmwareCounterHigh(guest_index) => {
+                // `read_firmware_counter()` already returns the full value on RV32.
+                let value = pmu::read_firmware_counter(self.host_index(guest_index)?)?;
+                Ok(if XLEN == 32 { value >> 32 } else { 0 })
+            }
+        }
+    }
+
+    /// Translates the host's PMU snapshot `host` into the guest's view of it in `guest`, moving the
+    /// value and overflow status of each backing host counter to the index of its guest counter.
+    pub fn translate_snapshot(&self, host: &PmuSnapshot, guest: &mut PmuSnapshot) {
+        guest.counter_overflow_bitmap = 0;
+        for (guest_index, &host_index) in self.host_counters.iter().enumerate() {
+            let Ok(host_index) = host_index.as_usize() else {
+                continue;
+            };
+            if guest_index >= PMU_SNAPSHOT_MAX_COUNTERS || host_index >= PMU_SNAPSHOT_MAX_COUNTERS {
+                continue;
+            }
+            guest.counter_values[guest_index] = host.counter_values[host_index];
+            if host.counter_overflow_bitmap & (1 << host_index) != 0 {
+                guest.counter_overflow_bitmap |= 1 << guest_index;
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for VirtualPmu<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
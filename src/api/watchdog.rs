@@ -0,0 +1,93 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::api::{reset, time};
+use crate::{ResetReason, ResetType, Result};
+
+/// The action a `Watchdog` takes when it expires.
+#[derive(Clone, Copy, Debug)]
+pub enum WatchdogAction {
+    /// Calls the given function. The watchdog is disarmed before the function is called.
+    Callback(fn()),
+    /// Resets the system with the given reset type, reporting a system failure as the reason.
+    Reset(ResetType),
+}
+
+/// A watchdog built on the SBI timer. Once armed, the watchdog must be refreshed before its
+/// timeout elapses or it takes its `WatchdogAction`.
+///
+/// The watchdog owns the supervisor timer while armed: the caller must invoke
+/// `handle_timer_interrupt()` from its timer interrupt handler and must not program the timer
+/// for other purposes. All times are in units of the `time` CSR and are supplied by the caller.
+pub struct Watchdog {
+    timeout: u64,
+    deadline: Option<u64>,
+    action: WatchdogAction,
+}
+
+impl Watchdog {
+    /// Creates a new, disarmed watchdog that takes `action` if it isn't refreshed within
+    /// `timeout` ticks of being armed.
+    pub fn new(timeout: u64, action: WatchdogAction) -> Self {
+        Self {
+            timeout,
+            deadline: None,
+            action,
+        }
+    }
+
+    /// Arms the watchdog, setting its deadline `timeout` ticks after `now`.
+    pub fn arm(&mut self, now: u64) -> Result<()> {
+        let deadline = now.saturating_add(self.timeout);
+        time::set_timer(deadline)?;
+        self.deadline = Some(deadline);
+        Ok(())
+    }
+
+    /// Pushes the deadline of the watchdog back to `timeout` ticks after `now`. Arms the watchdog
+    /// if it wasn't already armed.
+    pub fn refresh(&mut self, now: u64) -> Result<()> {
+        self.arm(now)
+    }
+
+    /// Disarms the watchdog and cancels its timer.
+    pub fn disarm(&mut self) -> Result<()> {
+        self.deadline = None;
+        time::clear_timer()
+    }
+
+    /// Returns the current deadline of the watchdog, or `None` if it isn't armed.
+    pub fn deadline(&self) -> Option<u64> {
+        self.deadline
+    }
+
+    /// Returns if the watchdog is armed and its deadline has passed at time `now`.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+
+    /// Handles a timer interrupt taken at time `now`. If the watchdog has expired it takes its
+    /// `WatchdogAction` and returns `true`, otherwise it re-programs the timer for its deadline
+    /// and returns `false`.
+    pub fn handle_timer_interrupt(&mut self, now: u64) -> Result<bool> {
+        let Some(deadline) = self.deadline else {
+            return Ok(false);
+        };
+        if now < deadline {
+            // Spurious or early interrupt; the timer interrupt is cleared by re-programming it.
+            time::set_timer(deadline)?;
+            return Ok(false);
+        }
+        match self.action {
+            WatchdogAction::Callback(f) => {
+                self.disarm()?;
+                f();
+            }
+            WatchdogAction::Reset(reset_type) => {
+                reset::reset(reset_type, ResetReason::SystemFailure)?;
+            }
+        }
+        Ok(true)
+    }
+}
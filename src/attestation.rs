@@ -114,6 +114,58 @@ impl AttestationCapabilities {
 
         Ok(self)
     }
+
+    /// Returns the descriptors of the static and runtime measurement registers.
+    pub fn measurement_registers(&self) -> &[MeasurementRegisterDescriptor] {
+        let num_registers = self.static_measurements as usize + self.runtime_measurements as usize;
+        &self.measurement_registers[..num_registers.min(MAX_MEASUREMENT_REGISTERS)]
+    }
+
+    /// Returns the descriptor of the runtime measurement register with the given TCG PCR index.
+    pub fn runtime_measurement_register(
+        &self,
+        tcg_pcr_index: u64,
+    ) -> Option<&MeasurementRegisterDescriptor> {
+        self.measurement_registers()
+            .iter()
+            .find(|r| r.is_runtime() && r.tcg_pcr_index() as u64 == tcg_pcr_index)
+    }
+
+    /// Checks that an `ExtendMeasurement` call with a measurement of `measurement_data_size`
+    /// bytes for the register at `measurement_index` is valid for these capabilities.
+    pub fn check_extend_measurement(
+        &self,
+        measurement_data_size: u64,
+        measurement_index: u64,
+    ) -> Result<()> {
+        if self.runtime_measurements == 0 {
+            return Err(Error::NotSupported);
+        }
+        if measurement_data_size != self.hash_algorithm.size() as u64 {
+            return Err(Error::InvalidParam);
+        }
+        self.runtime_measurement_register(measurement_index)
+            .ok_or(Error::InvalidParam)?;
+        Ok(())
+    }
+
+    /// Checks that a `ReadMeasurement` call with a buffer of `measurement_data_size` bytes for the
+    /// register at `measurement_index` is valid for these capabilities.
+    pub fn check_read_measurement(
+        &self,
+        measurement_data_size: u64,
+        measurement_index: u64,
+    ) -> Result<()> {
+        if self.runtime_measurements == 0 {
+            return Err(Error::NotSupported);
+        }
+        if measurement_data_size < self.hash_algorithm.size() as u64 {
+            return Err(Error::InsufficientBufferCapacity);
+        }
+        self.runtime_measurement_register(measurement_index)
+            .ok_or(Error::InvalidParam)?;
+        Ok(())
+    }
 }
 
 /// Measurement register descriptor.
@@ -132,7 +184,12 @@ pub struct MeasurementRegisterDescriptor {
 
 impl MeasurementRegisterDescriptor {
     /// Create a new measurement register descriptor.
-    pub fn new(tcb_layer_index: u8, fwid_index: u8, tcg_pcr_index: u8, runtime: bool) -> Self {
+    pub const fn new(
+        tcb_layer_index: u8,
+        fwid_index: u8,
+        tcg_pcr_index: u8,
+        runtime: bool,
+    ) -> Self {
         MeasurementRegisterDescriptor {
             tcb_layer_index,
             fwid_index,
@@ -140,311 +197,150 @@ impl MeasurementRegisterDescriptor {
             runtime,
         }
     }
-}
-
-/// Functions provided by the attestation extension.
-#[derive(Copy, Clone, Debug)]
-pub enum AttestationFunction {
-    /// Get the SBI implementation attestation capabilities.
-    /// The attestation capabilities let the SBI implementations expose which
-    /// hash algorithm is being used for measurements, which evidence formats
-    /// are supported. The attestation capabilities structure also contains a
-    /// map of all  measurement registers.
-    ///
-    /// a6 = 0
-    /// a0 = Attestation capabilities buffer
-    /// a1 = Attestation capabilities buffer size
-    GetCapabilities {
-        /// a0 = Capabilities structure address
-        caps_addr_out: u64,
-        /// a1 = Capabilities structure length
-        caps_size: u64,
-    },
-
-    /// Get an attestion evidence from a Certificate Signing Request (CSR)
-    /// <https://datatracker.ietf.org/doc/html/rfc2986>.
-    /// The caller passes the CSR and its length through the first 2 arguments.
-    /// The third argument is the address where the caller places a data blob
-    /// that will be included in the generated certificate. Typically, this is a
-    /// cryptographic nonce.
-    /// The fourth argument is the evidence format: DiceTcbInfo (0),
-    /// DiceMultiTcbInfo (1) or OpenDice (2).
-    /// The fifthh argument is the address where the generated certificate will be placed.
-    /// The evidence is formatted an x.509 DiceTcbInfo certificate extension
-    ///
-    /// a6 = 1
-    /// a0 = CSR address
-    /// a1 = CSR length
-    /// a2 = Data blob address
-    /// a3 = Attestation evidence format
-    /// a4 = Generated certificate address
-    /// a5 = Reserved length for the generated certificate address
-    GetEvidence {
-        /// a0 = CSR address
-        cert_request_addr: u64,
-        /// a1 = CSR length
-        cert_request_size: u64,
-        /// a2 = User data blob
-        request_data_addr: u64,
-        /// a3 = Attestation evidence format
-        evidence_format: u64,
-        /// a4 = Generated Certificate address
-        cert_addr_out: u64,
-        /// a5 = Reserved length for the generated certificate address
-        cert_size: u64,
-    },
 
-    /// Extend a measurement register with an additional measurement.
-    /// The first parameter is the address of the measurement buffer.
-    /// The second argument is the length of the measurement buffer, which must
-    /// be the same as the hash algorithm size reported by `GetCapabilities`.
-    /// The third parameter is the measurement register index, and it must be
-    /// one of the reported 'TCG_PCR_INDEX` from the runtime measurement
-    /// registers array.
-    ///
-    /// This function is not supported if the SBI implementation does not
-    /// support runtime measurements (i.e. `NUM_RMSMT_REGS` as reported by the
-    /// `GetCapabilities` function is set to 0).
-    ///
-    /// a6 = 2
-    /// a0 = Measurement data buffer address
-    /// a1 = Measurement data buffer length
-    /// a2 = Measurement register index.
-    ExtendMeasurement {
-        /// a0 = measurement data buffer address
-        measurement_data_addr: u64,
-        /// a1 = measurement data buffer length
-        measurement_data_size: u64,
-        /// a2 = measurement register index
-        measurement_index: u64,
-    },
-
-    /// Read a measurement register data back.
-    /// The first parameter is the address of the measurement buffer allocated
-    /// by the caller, for the SBI implementation to write the measurement data
-    /// into.
-    /// The second argument is the length of the measurement buffer, which must
-    /// be at least as large as the hash algorithm size reported by
-    /// `GetCapabilities`.
-    /// The third parameter is the measurement register index, and it must be
-    /// one of the reported 'TCG_PCR_INDEX` from the runtime measurement
-    /// registers array.
-    /// The returned value is the length of the measurement data.
-    ///
-    /// a6 = 3
-    /// a0 = Measurement data buffer address
-    /// a1 = Measurement data buffer length
-    /// a2 = Measurement register index.
-    ReadMeasurement {
-        /// a0 = measurement data buffer address
-        measurement_data_addr_out: u64,
-        /// a1 = measurement data buffer length
-        measurement_data_size: u64,
-        /// a2 = measurement register index
-        measurement_index: u64,
-    },
-}
-
-impl AttestationFunction {
-    /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
-        use AttestationFunction::*;
-        match args[6] {
-            0 => Ok(GetCapabilities {
-                caps_addr_out: args[0],
-                caps_size: args[1],
-            }),
-
-            1 => Ok(GetEvidence {
-                cert_request_addr: args[0],
-                cert_request_size: args[1],
-                request_data_addr: args[2],
-                evidence_format: args[3],
-                cert_addr_out: args[4],
-                cert_size: args[5],
-            }),
-
-            2 => Ok(ExtendMeasurement {
-                measurement_data_addr: args[0],
-                measurement_data_size: args[1],
-                measurement_index: args[2],
-            }),
-
-            3 => Ok(ReadMeasurement {
-                measurement_data_addr_out: args[0],
-                measurement_data_size: args[1],
-                measurement_index: args[2],
-            }),
-
-            _ => Err(Error::InvalidParam),
-        }
+    /// Returns the index of the TCB layer this register measures.
+    pub const fn tcb_layer_index(&self) -> u8 {
+        self.tcb_layer_index
     }
-}
-
-impl SbiFunction for AttestationFunction {
-    fn a6(&self) -> u64 {
-        use AttestationFunction::*;
-        match self {
-            GetCapabilities {
-                caps_addr_out: _,
-                caps_size: _,
-            } => 0,
 
-            GetEvidence {
-                cert_request_addr: _,
-                cert_request_size: _,
-                request_data_addr: _,
-                evidence_format: _,
-                cert_addr_out: _,
-                cert_size: _,
-            } => 1,
-
-            ExtendMeasurement {
-                measurement_data_addr: _,
-                measurement_data_size: _,
-                measurement_index: _,
-            } => 2,
-
-            ReadMeasurement {
-                measurement_data_addr_out: _,
-                measurement_data_size: _,
-                measurement_index: _,
-            } => 3,
-        }
-    }
-
-    fn a5(&self) -> u64 {
-        use AttestationFunction::*;
-        match self {
-            GetEvidence {
-                cert_request_addr: _,
-                cert_request_size: _,
-                request_data_addr: _,
-                evidence_format: _,
-                cert_addr_out: _,
-                cert_size,
-            } => *cert_size,
-            _ => 0,
-        }
+    /// Returns the index of the FWID this register maps to.
+    pub const fn fwid_index(&self) -> u8 {
+        self.fwid_index
     }
 
-    fn a4(&self) -> u64 {
-        use AttestationFunction::*;
-        match self {
-            GetEvidence {
-                cert_request_addr: _,
-                cert_request_size: _,
-                request_data_addr: _,
-                evidence_format: _,
-                cert_addr_out,
-                cert_size: _,
-            } => *cert_addr_out,
-            _ => 0,
-        }
+    /// Returns the TCG PCR index of this register.
+    pub const fn tcg_pcr_index(&self) -> u8 {
+        self.tcg_pcr_index
     }
 
-    fn a3(&self) -> u64 {
-        use AttestationFunction::*;
-        match self {
-            GetEvidence {
-                cert_request_addr: _,
-                cert_request_size: _,
-                request_data_addr: _,
-                evidence_format,
-                cert_addr_out: _,
-                cert_size: _,
-            } => *evidence_format,
-            _ => 0,
-        }
+    /// Returns if this is a runtime measurement register.
+    pub const fn is_runtime(&self) -> bool {
+        self.runtime
     }
+}
 
-    fn a2(&self) -> u64 {
-        use AttestationFunction::*;
-        match self {
-            GetEvidence {
-                cert_request_addr: _,
-                cert_request_size: _,
-                request_data_addr,
-                evidence_format: _,
-                cert_addr_out: _,
-                cert_size: _,
-            } => *request_data_addr,
-
-            ExtendMeasurement {
-                measurement_data_addr: _,
-                measurement_data_size: _,
-                measurement_index,
-            } => *measurement_index,
-
-            ReadMeasurement {
-                measurement_data_addr_out: _,
-                measurement_data_size: _,
-                measurement_index,
-            } => *measurement_index,
-
-            _ => 0,
-        }
+sbi_function! {
+    /// Functions provided by the attestation extension.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum AttestationFunction {
+        /// Get the SBI implementation attestation capabilities.
+        /// The attestation capabilities let the SBI implementations expose which
+        /// hash algorithm is being used for measurements, which evidence formats
+        /// are supported. The attestation capabilities structure also contains a
+        /// map of all  measurement registers.
+        ///
+        /// a6 = 0
+        /// a0 = Attestation capabilities buffer
+        /// a1 = Attestation capabilities buffer size
+        GetCapabilities(get_capabilities) = 0 => Empty {
+            /// a0 = Capabilities structure address
+            caps_addr_out => a0,
+            /// a1 = Capabilities structure length
+            caps_size => a1,
+        },
+
+        /// Get an attestion evidence from a Certificate Signing Request (CSR)
+        /// <https://datatracker.ietf.org/doc/html/rfc2986>.
+        /// The caller passes the CSR and its length through the first 2 arguments.
+        /// The third argument is the address where the caller places a data blob
+        /// that will be included in the generated certificate. Typically, this is a
+        /// cryptographic nonce.
+        /// The fourth argument is the evidence format: DiceTcbInfo (0),
+        /// DiceMultiTcbInfo (1) or OpenDice (2).
+        /// The fifthh argument is the address where the generated certificate will be placed.
+        /// The evidence is formatted an x.509 DiceTcbInfo certificate extension
+        ///
+        /// a6 = 1
+        /// a0 = CSR address
+        /// a1 = CSR length
+        /// a2 = Data blob address
+        /// a3 = Attestation evidence format
+        /// a4 = Generated certificate address
+        /// a5 = Reserved length for the generated certificate address
+        GetEvidence(get_evidence) = 1 {
+            /// a0 = CSR address
+            cert_request_addr => a0,
+            /// a1 = CSR length
+            cert_request_size => a1,
+            /// a2 = User data blob
+            request_data_addr => a2,
+            /// a3 = Attestation evidence format
+            evidence_format => a3,
+            /// a4 = Generated Certificate address
+            cert_addr_out => a4,
+            /// a5 = Reserved length for the generated certificate address
+            cert_size => a5,
+        },
+
+        /// Extend a measurement register with an additional measurement.
+        /// The first parameter is the address of the measurement buffer.
+        /// The second argument is the length of the measurement buffer, which must
+        /// be the same as the hash algorithm size reported by `GetCapabilities`.
+        /// The third parameter is the measurement register index, and it must be
+        /// one of the reported 'TCG_PCR_INDEX` from the runtime measurement
+        /// registers array.
+        ///
+        /// This function is not supported if the SBI implementation does not
+        /// support runtime measurements (i.e. `NUM_RMSMT_REGS` as reported by the
+        /// `GetCapabilities` function is set to 0).
+        ///
+        /// a6 = 2
+        /// a0 = Measurement data buffer address
+        /// a1 = Measurement data buffer length
+        /// a2 = Measurement register index.
+        ExtendMeasurement(extend_measurement) = 2 => Empty {
+            /// a0 = measurement data buffer address
+            measurement_data_addr => a0,
+            /// a1 = measurement data buffer length
+            measurement_data_size => a1,
+            /// a2 = measurement register index
+            measurement_index => a2,
+        },
+
+        /// Read a measurement register data back.
+        /// The first parameter is the address of the measurement buffer allocated
+        /// by the caller, for the SBI implementation to write the measurement data
+        /// into.
+        /// The second argument is the length of the measurement buffer, which must
+        /// be at least as large as the hash algorithm size reported by
+        /// `GetCapabilities`.
+        /// The third parameter is the measurement register index, and it must be
+        /// one of the reported 'TCG_PCR_INDEX` from the runtime measurement
+        /// registers array.
+        /// The returned value is the length of the measurement data.
+        ///
+        /// a6 = 3
+        /// a0 = Measurement data buffer address
+        /// a1 = Measurement data buffer length
+        /// a2 = Measurement register index.
+        ReadMeasurement(read_measurement) = 3 {
+            /// a0 = measurement data buffer address
+            measurement_data_addr_out => a0,
+            /// a1 = measurement data buffer length
+            measurement_data_size => a1,
+            /// a2 = measurement register index
+            measurement_index => a2,
+        },
     }
+}
 
-    fn a1(&self) -> u64 {
+impl AttestationFunction {
+    /// Checks the measurement register index and buffer size of an `ExtendMeasurement` or
+    /// `ReadMeasurement` call against the given capabilities. Other functions are always valid.
+    pub fn check_measurement(&self, caps: &AttestationCapabilities) -> Result<()> {
         use AttestationFunction::*;
-        match self {
-            GetCapabilities {
-                caps_addr_out: _,
-                caps_size,
-            } => *caps_size,
-
-            GetEvidence {
-                cert_request_addr: _,
-                cert_request_size,
-                request_data_addr: _,
-                evidence_format: _,
-                cert_addr_out: _,
-                cert_size: _,
-            } => *cert_request_size,
-
+        match *self {
             ExtendMeasurement {
                 measurement_data_addr: _,
                 measurement_data_size,
-                measurement_index: _,
-            } => *measurement_data_size,
-
+                measurement_index,
+            } => caps.check_extend_measurement(measurement_data_size, measurement_index),
             ReadMeasurement {
                 measurement_data_addr_out: _,
                 measurement_data_size,
-                measurement_index: _,
-            } => *measurement_data_size,
-        }
-    }
-
-    fn a0(&self) -> u64 {
-        use AttestationFunction::*;
-        match self {
-            GetCapabilities {
-                caps_addr_out,
-                caps_size: _,
-            } => *caps_addr_out,
-
-            GetEvidence {
-                cert_request_addr,
-                cert_request_size: _,
-                request_data_addr: _,
-                evidence_format: _,
-                cert_addr_out: _,
-                cert_size: _,
-            } => *cert_request_addr,
-
-            ExtendMeasurement {
-                measurement_data_addr,
-                measurement_data_size: _,
-                measurement_index: _,
-            } => *measurement_data_addr,
-
-            ReadMeasurement {
-                measurement_data_addr_out,
-                measurement_data_size: _,
-                measurement_index: _,
-            } => *measurement_data_addr_out,
+                measurement_index,
+            } => caps.check_read_measurement(measurement_data_size, measurement_index),
+            _ => Ok(()),
         }
     }
 }
@@ -2,11 +2,12 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "handler")]
 use crate::error::*;
 use crate::function::*;
 
 /// Functions defined for the Base extension
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BaseFunction {
     /// Returns the implemented version of the SBI standard.
     GetSpecificationVersion,
@@ -26,7 +27,8 @@ pub enum BaseFunction {
 
 impl BaseFunction {
     /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         use BaseFunction::*;
 
         match args[6] {
@@ -43,6 +45,21 @@ impl BaseFunction {
 }
 
 impl SbiFunction for BaseFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        use BaseFunction::*;
+        let (name, args): (_, &[_]) = match self {
+            GetSpecificationVersion => ("get_spec_version", &[]),
+            GetImplementationID => ("get_impl_id", &[]),
+            GetImplementationVersion => ("get_impl_version", &[]),
+            ProbeSbiExtension(_) => ("probe_extension", &["extension_id"]),
+            GetMachineVendorID => ("get_mvendorid", &[]),
+            GetMachineArchitectureID => ("get_marchid", &[]),
+            GetMachineImplementationID => ("get_mimpid", &[]),
+        };
+        Some(FunctionSignature::new(name, args))
+    }
+
+    #[inline(always)]
     fn a6(&self) -> u64 {
         use BaseFunction::*;
         match self {
@@ -56,6 +73,7 @@ impl SbiFunction for BaseFunction {
         }
     }
 
+    #[inline(always)]
     fn a0(&self) -> u64 {
         use BaseFunction::*;
         match self {
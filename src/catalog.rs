@@ -0,0 +1,145 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Metadata for every SBI function this build can decode, for tools that need to enumerate
+//! them, such as fuzzers building structured dictionaries and coverage reports over traces.
+//!
+//! ```rust
+//! # use sbi_rs::{catalog::catalog, EXT_HART_STATE};
+//! let hart_start = catalog()
+//!     .into_iter()
+//!     .find(|f| f.eid == EXT_HART_STATE && f.fid == 0)
+//!     .unwrap();
+//! assert_eq!(hart_start.name, "hart_start");
+//! assert_eq!(hart_start.args[1].name, "start_addr");
+//! ```
+
+use std::vec::Vec;
+
+use crate::{SbiExtension, SbiMessage};
+
+/// The highest function ID looked for in each extension.
+const MAX_FID: u64 = 63;
+
+/// What an argument of an SBI function holds, as far as tools generating or checking calls are
+/// concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgRole {
+    /// A physical or guest physical address, or half of one.
+    Address,
+    /// A length or size in bytes.
+    Length,
+    /// A number of pages or other units.
+    Count,
+    /// The ID or index of a hart, guest, counter, register or extension.
+    Id,
+    /// A bitmask selecting harts, counters or interrupt files.
+    Mask,
+    /// A set of flags.
+    Flags,
+    /// A value selecting one of a fixed set of options, such as a reset or page type.
+    Selector,
+    /// Any other value.
+    Value,
+}
+
+impl ArgRole {
+    /// Returns the role of the argument called `name` in a `FunctionSignature`.
+    pub fn from_name(name: &str) -> Self {
+        use ArgRole::*;
+        let name = name
+            .trim_end_matches("_lo")
+            .trim_end_matches("_hi")
+            .trim_end_matches("_out");
+        if name.ends_with("addr") || name.ends_with("_pfn") || name.ends_with("sepc") {
+            Address
+        } else if name == "len" || name.ends_with("size") {
+            Length
+        } else if name.starts_with("num_") {
+            Count
+        } else if name.ends_with("mask") {
+            Mask
+        } else if name.ends_with("flags") {
+            Flags
+        } else if name.ends_with("id")
+            || name.ends_with("index")
+            || name == "csr_num"
+            || name == "hart_mask_base"
+        {
+            Id
+        } else if name.ends_with("type") || name.ends_with("format") || name == "reason" {
+            Selector
+        } else {
+            Value
+        }
+    }
+}
+
+/// An argument of an SBI function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArgInfo {
+    /// The name of the argument.
+    pub name: &'static str,
+    /// What the argument holds.
+    pub role: ArgRole,
+}
+
+/// An SBI function known to this build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionInfo {
+    /// The extension the function belongs to.
+    pub extension: SbiExtension,
+    /// The extension ID, passed in a7.
+    pub eid: u64,
+    /// The function ID, passed in a6.
+    pub fid: u64,
+    /// The name of the function.
+    pub name: &'static str,
+    /// The arguments of the function, passed in a0 onwards.
+    pub args: Vec<ArgInfo>,
+}
+
+/// Returns every function this build decodes, in canonical extension order and then by function
+/// ID. Extensions disabled by Cargo features are left out.
+pub fn catalog() -> Vec<FunctionInfo> {
+    let mut functions = Vec::new();
+    for extension in SbiExtension::ALL {
+        let fids = if extension.is_legacy() {
+            0..=0
+        } else {
+            0..=MAX_FID
+        };
+        for fid in fids {
+            if let Some(sig) = signature(extension.eid(), fid) {
+                functions.push(FunctionInfo {
+                    extension,
+                    eid: extension.eid(),
+                    fid,
+                    name: sig.name,
+                    args: sig
+                        .args
+                        .iter()
+                        .map(|&name| ArgInfo {
+                            name,
+                            role: ArgRole::from_name(name),
+                        })
+                        .collect(),
+                });
+            }
+        }
+    }
+    functions
+}
+
+// Returns the signature of function `fid` of extension `eid`, if it can be decoded. Functions
+// are decoded with a few different argument values, so that functions rejecting zero-length or
+// misaligned arguments are found too.
+fn signature(eid: u64, fid: u64) -> Option<crate::FunctionSignature> {
+    [0, 0x1000, 1].into_iter().find_map(|arg| {
+        let mut regs = [arg; 8];
+        regs[6] = fid;
+        regs[7] = eid;
+        SbiMessage::from_regs(&regs).ok()?.signature()
+    })
+}
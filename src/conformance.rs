@@ -0,0 +1,278 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checking of an SBI implementation built on `SbiDispatcher` against the requirements of the
+//! SBI specification, for hypervisor authors validating their emulation.
+//!
+//! `check_conformance()` makes a scripted set of calls through the dispatcher's `handle_call()`,
+//! as a guest would, and reports each result that deviates from the specification:
+//!
+//! - Probing: the Base extension is implemented and reports a specification version of at least
+//!   v0.2, unused extension IDs probe as absent, and calls to extensions that probe as absent
+//!   fail with `Error::NotSupported`.
+//! - Error codes: the calls of `error_cases::error_cases()` to the extensions that probe as
+//!   present fail with the errors the specification allows.
+//! - HSM state transitions: the calling hart is started, starting it again fails, harts past
+//!   the last one are rejected, and, if given an address to start harts at, stopped harts move
+//!   to `StartPending` or `Started` when started.
+//!
+//! ```rust
+//! use sbi_rs::conformance::{check_conformance, ConformanceConfig};
+//! use sbi_rs::handler::{BaseHandler, HsmHandler, SbiDispatcher};
+//! use sbi_rs::*;
+//!
+//! struct Base;
+//!
+//! impl BaseHandler for Base {
+//!     fn get_spec_version(&mut self) -> Result<u64> {
+//!         Ok(2 << 24)
+//!     }
+//! }
+//!
+//! // Reports every hart as started, including those that don't exist.
+//! struct Harts;
+//!
+//! impl HsmHandler for Harts {
+//!     fn hart_get_status(&mut self, _hart_id: u64) -> Result<u64> {
+//!         Ok(HartState::Started as u64)
+//!     }
+//! }
+//!
+//! let (mut base, mut harts) = (Base, Harts);
+//! let mut dispatcher = SbiDispatcher::new().with_base(&mut base).with_hsm(&mut harts);
+//! let deviations = check_conformance(&mut dispatcher, &ConformanceConfig::new(0, 1));
+//! assert!(deviations
+//!     .iter()
+//!     .any(|deviation| deviation.check == "hart_get_status fails for missing harts"));
+//! ```
+
+use std::vec::Vec;
+
+use crate::error_cases::error_cases;
+use crate::handler::SbiDispatcher;
+use crate::{consts::*, Error, HartState, SbiExtension, SbiMessage, SbiReturn};
+
+/// The configuration of the system under test.
+#[derive(Clone, Copy, Debug)]
+pub struct ConformanceConfig {
+    /// The ID of the hart making the calls, which must be started.
+    pub hart_id: u64,
+    /// The number of harts in the system, with IDs `0..num_harts`.
+    pub num_harts: u64,
+    /// The address stopped harts are started at, or `None` to leave them stopped.
+    pub start_addr: Option<u64>,
+}
+
+impl ConformanceConfig {
+    /// Creates the configuration for calls made from `hart_id` on a system with `num_harts`
+    /// harts, leaving the other harts stopped.
+    pub const fn new(hart_id: u64, num_harts: u64) -> Self {
+        Self {
+            hart_id,
+            num_harts,
+            start_addr: None,
+        }
+    }
+
+    /// Starts stopped harts at `start_addr` to check their state transitions.
+    pub const fn with_start_addr(self, start_addr: u64) -> Self {
+        Self {
+            start_addr: Some(start_addr),
+            ..self
+        }
+    }
+}
+
+/// A call for which the implementation's result deviates from the specification.
+#[derive(Clone, Copy, Debug)]
+pub struct Deviation {
+    /// The requirement of the specification that isn't met.
+    pub check: &'static str,
+    /// The `a0-a7` registers of the call.
+    pub regs: [u64; 8],
+    /// The result returned by the implementation.
+    pub actual: SbiReturn,
+}
+
+// An extension ID outside every range the specification assigns.
+const UNUSED_EID: u64 = 0x7fff_ffff;
+
+fn regs(eid: u64, fid: u64, args: &[u64]) -> [u64; 8] {
+    let mut regs = [0; 8];
+    regs[..args.len()].copy_from_slice(args);
+    regs[6] = fid;
+    regs[7] = eid;
+    regs
+}
+
+// Makes calls through a dispatcher, recording the deviations found.
+struct Checker<'d, 'a> {
+    dispatcher: &'d mut SbiDispatcher<'a>,
+    deviations: Vec<Deviation>,
+}
+
+impl Checker<'_, '_> {
+    fn call(&mut self, regs: [u64; 8]) -> SbiReturn {
+        let mut out = regs;
+        self.dispatcher.handle_call(&mut out);
+        SbiReturn {
+            error_code: out[0] as i64,
+            return_value: out[1],
+        }
+    }
+
+    // Makes the call in `regs`, recording a deviation from `check` unless `ok` accepts the
+    // result. Returns the result.
+    fn expect(
+        &mut self,
+        check: &'static str,
+        regs: [u64; 8],
+        ok: impl FnOnce(&SbiReturn) -> bool,
+    ) -> SbiReturn {
+        let actual = self.call(regs);
+        if !ok(&actual) {
+            self.deviations.push(Deviation {
+                check,
+                regs,
+                actual,
+            });
+        }
+        actual
+    }
+
+    fn expect_error(&mut self, check: &'static str, regs: [u64; 8], errors: &[Error]) {
+        self.expect(check, regs, |ret| {
+            errors.iter().any(|e| e.code() == ret.error_code)
+        });
+    }
+
+    fn supports(&mut self, eid: u64) -> bool {
+        let ret = self.call(regs(EXT_BASE, 3, &[eid]));
+        ret.error_code == 0 && ret.return_value != 0
+    }
+
+    fn check_probing(&mut self) {
+        self.expect(
+            "the Base extension reports a specification version of at least v0.2",
+            regs(EXT_BASE, 0, &[]),
+            // The version has the top bit clear and is at least major 0, minor 2.
+            |ret| ret.error_code == 0 && ret.return_value >> 31 == 0 && ret.return_value >= 2,
+        );
+        self.expect(
+            "the Base extension probes as present",
+            regs(EXT_BASE, 3, &[EXT_BASE]),
+            |ret| ret.error_code == 0 && ret.return_value != 0,
+        );
+        self.expect(
+            "unused extension IDs probe as absent",
+            regs(EXT_BASE, 3, &[UNUSED_EID]),
+            |ret| *ret == SbiReturn::success(0),
+        );
+        for ext in SbiExtension::ALL {
+            if self.supports(ext.eid()) {
+                continue;
+            }
+            let regs = regs(ext.eid(), 0, &[]);
+            // Frames this crate can't decode never reach the implementation, and the dispatcher
+            // completes no-ops without it.
+            if SbiMessage::from_regs(&regs).map_or(true, |msg| msg.is_noop()) {
+                continue;
+            }
+            self.expect_error(
+                "calls to extensions that probe as absent fail with NotSupported",
+                regs,
+                &[Error::NotSupported],
+            );
+        }
+    }
+
+    fn check_error_codes(&mut self) {
+        for case in error_cases() {
+            if case.extension.is_some_and(|ext| !self.supports(ext.eid())) {
+                continue;
+            }
+            self.expect(
+                "calls with bad arguments fail with the errors the specification allows",
+                case.regs,
+                |ret| case.accepts(ret),
+            );
+        }
+    }
+
+    fn check_hsm(&mut self, config: &ConformanceConfig) {
+        if !self.supports(EXT_HART_STATE) {
+            return;
+        }
+        let status = |hart_id| regs(EXT_HART_STATE, 2, &[hart_id]);
+        let start = |hart_id, start_addr| regs(EXT_HART_STATE, 0, &[hart_id, start_addr, 0]);
+        let is_state = |ret: &SbiReturn, states: &[HartState]| {
+            ret.error_code == 0 && states.iter().any(|&s| s as u64 == ret.return_value)
+        };
+
+        self.expect(
+            "hart_get_status reports the calling hart as started",
+            status(config.hart_id),
+            |ret| is_state(ret, &[HartState::Started]),
+        );
+        self.expect_error(
+            "hart_get_status fails for missing harts",
+            status(config.num_harts),
+            &[Error::InvalidParam],
+        );
+        self.expect_error(
+            "hart_start fails for the calling hart",
+            start(config.hart_id, config.start_addr.unwrap_or(0)),
+            &[Error::AlreadyAvailable],
+        );
+        self.expect_error(
+            "hart_start fails for missing harts",
+            start(config.num_harts, config.start_addr.unwrap_or(0)),
+            &[Error::InvalidParam],
+        );
+
+        let Some(start_addr) = config.start_addr else {
+            return;
+        };
+        for hart_id in (0..config.num_harts).filter(|&id| id != config.hart_id) {
+            if !is_state(&self.call(status(hart_id)), &[HartState::Stopped]) {
+                continue;
+            }
+            let ret = self.expect(
+                "hart_start succeeds for stopped harts",
+                start(hart_id, start_addr),
+                |ret| ret.error_code == 0,
+            );
+            if ret.error_code != 0 {
+                continue;
+            }
+            self.expect(
+                "started harts are reported as start pending or started",
+                status(hart_id),
+                |ret| is_state(ret, &[HartState::StartPending, HartState::Started]),
+            );
+            self.expect_error(
+                "hart_start fails for harts already starting or started",
+                start(hart_id, start_addr),
+                &[Error::AlreadyStarted, Error::AlreadyAvailable],
+            );
+        }
+    }
+}
+
+/// Runs the scripted calls through `dispatcher` on the system described by `config`, returning
+/// every deviation from the specification found. Harts are only started if `config` has a start
+/// address, and no other call changes the state of the system.
+pub fn check_conformance(
+    dispatcher: &mut SbiDispatcher,
+    config: &ConformanceConfig,
+) -> Vec<Deviation> {
+    let mut checker = Checker {
+        dispatcher,
+        deviations: Vec::new(),
+    };
+    checker.check_probing();
+    checker.check_error_codes();
+    checker.check_hsm(config);
+    checker.deviations
+}
@@ -7,6 +7,9 @@
 // Extension constants
 pub const EXT_PUT_CHAR: u64 = 0x01;
 pub const EXT_BASE: u64 = 0x10;
+pub const EXT_TIME: u64 = 0x54494D45; // TIME
+pub const EXT_IPI: u64 = 0x735049; // sPI
+pub const EXT_RFENCE: u64 = 0x52464E43; // RFNC
 pub const EXT_HART_STATE: u64 = 0x48534D;
 pub const EXT_PMU: u64 = 0x504D55;
 pub const EXT_RESET: u64 = 0x53525354;
@@ -17,5 +20,13 @@ pub const EXT_TEE_HOST: u64 = 0x54454548; // TEEH
 pub const EXT_TEE_INTERRUPT: u64 = 0x54454549; // TEEI
 pub const EXT_TEE_GUEST: u64 = 0x54454547; // TEEG
 
+// Extension ID ranges reserved by the SBI specification
+pub const EXT_LEGACY_START: u64 = 0x00;
+pub const EXT_LEGACY_END: u64 = 0x0F;
+pub const EXT_VENDOR_START: u64 = 0x0900_0000;
+pub const EXT_VENDOR_END: u64 = 0x09FF_FFFF;
+pub const EXT_FIRMWARE_START: u64 = 0x0A00_0000;
+pub const EXT_FIRMWARE_END: u64 = 0x0AFF_FFFF;
+
 pub const SBI_SUCCESS: i64 = 0;
 pub const SBI_ERR_INVALID_ADDRESS: i64 = -5;
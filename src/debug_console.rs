@@ -2,44 +2,107 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::addr::*;
+#[cfg(feature = "handler")]
+use crate::decode::Validator;
 use crate::error::*;
 use crate::function::*;
+use crate::xlen::*;
 
 /// Functions for the Debug Console extension
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DebugConsoleFunction {
     /// Prints the given string to the system console.
     PutString {
         /// The length of the string to print.
-        len: u64,
-        /// The address of the string.
-        addr: u64,
+        len: Len,
+        /// The address of the string. Passed as a pair of registers holding its low and high
+        /// halves; the high half is 0 on RV64.
+        addr: PhysAddr,
     },
 }
 
 impl DebugConsoleFunction {
     /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         Ok(match args[6] {
             0 => DebugConsoleFunction::PutString {
-                len: args[0],
-                addr: args[1],
+                len: Len::new(args[0]),
+                addr: PhysAddr::new(join_reg_pair(args[1], args[2])?),
             },
             _ => return Err(Error::NotSupported),
         })
     }
+
+    /// Validates the arguments of the call within the budget tracked by `v`.
+    #[cfg(feature = "handler")]
+    pub(crate) fn validate(&self, v: &mut Validator) -> Result<()> {
+        match *self {
+            DebugConsoleFunction::PutString { len, addr } => v.range(addr.bits(), len.bytes()),
+        }
+    }
+
+    /// Returns if the call has no effect: a write of zero bytes.
+    pub const fn is_noop(&self) -> bool {
+        match *self {
+            DebugConsoleFunction::PutString { len, addr: _ } => len.is_zero(),
+        }
+    }
+
+    /// Returns the length of the string as a `usize`, or `Error::InvalidParam` if it doesn't fit.
+    ///
+    /// ```
+    /// use sbi_rs::{DebugConsoleFunction, Len, PhysAddr};
+    ///
+    /// let f = DebugConsoleFunction::PutString {
+    ///     len: Len::new(5),
+    ///     addr: PhysAddr::new(0x8000_1000),
+    /// };
+    /// assert_eq!(f.len_usize(), Ok(5));
+    /// assert_eq!(f.addr_usize(), Ok(0x8000_1000));
+    /// ```
+    pub fn len_usize(&self) -> Result<usize> {
+        match self {
+            DebugConsoleFunction::PutString { len, addr: _ } => len.as_usize(),
+        }
+    }
+
+    /// Returns the address of the string as a `usize`, or `Error::InvalidParam` if it doesn't
+    /// fit.
+    pub fn addr_usize(&self) -> Result<usize> {
+        match self {
+            DebugConsoleFunction::PutString { len: _, addr } => addr.as_usize(),
+        }
+    }
 }
 
 impl SbiFunction for DebugConsoleFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        Some(FunctionSignature::new(
+            "console_write",
+            &["len", "addr_lo", "addr_hi"],
+        ))
+    }
+
+    #[inline(always)]
     fn a0(&self) -> u64 {
         match self {
-            DebugConsoleFunction::PutString { len, addr: _ } => *len,
+            DebugConsoleFunction::PutString { len, addr: _ } => len.bytes(),
         }
     }
 
+    #[inline(always)]
     fn a1(&self) -> u64 {
         match self {
-            DebugConsoleFunction::PutString { len: _, addr } => *addr,
+            DebugConsoleFunction::PutString { len: _, addr } => split_reg_pair(addr.bits()).0,
+        }
+    }
+
+    #[inline(always)]
+    fn a2(&self) -> u64 {
+        match self {
+            DebugConsoleFunction::PutString { len: _, addr } => split_reg_pair(addr.bits()).1,
         }
     }
 }
@@ -0,0 +1,140 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Budgets for the argument validation performed when decoding SBI calls from untrusted
+//! callers.
+//!
+//! `SbiMessage::from_regs()` only checks that the registers can be represented by the typed
+//! fields of a message. `SbiMessage::from_regs_with_budget()` runs the same decoders and then
+//! validates the arguments of the call (alignment of addresses and lengths, and that address
+//! ranges don't wrap) within the limits set by a `DecodeBudget`. Hot paths can pick a cheap
+//! budget that skips the expensive range checks, while control-plane paths use the strict one.
+
+use crate::error::*;
+#[cfg(feature = "tee")]
+use crate::TsmPageType;
+
+/// The default maximum number of checks performed by a budgeted decode. This is enough to fully
+/// validate any SBI call known to this crate.
+pub const DEFAULT_MAX_CHECKS: u32 = 8;
+
+#[cfg(feature = "tee")]
+const PAGE_SIZE_4K: u64 = 4096;
+
+/// Limits on the argument validation performed when decoding an SBI call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeBudget {
+    max_checks: u32,
+    skip_expensive: bool,
+}
+
+impl DecodeBudget {
+    /// Performs only the cheap checks, such as address alignment.
+    pub const CHEAP: DecodeBudget = DecodeBudget::new(DEFAULT_MAX_CHECKS, true);
+
+    /// Performs all checks.
+    pub const STRICT: DecodeBudget = DecodeBudget::new(DEFAULT_MAX_CHECKS, false);
+
+    /// Creates a budget that allows up to `max_checks` checks per decode, skipping the expensive
+    /// ones if `skip_expensive` is set. Decoding fails with `Error::Failed` if the call needs
+    /// more than `max_checks` checks.
+    pub const fn new(max_checks: u32, skip_expensive: bool) -> Self {
+        Self {
+            max_checks,
+            skip_expensive,
+        }
+    }
+
+    /// Returns the maximum number of checks performed per decode.
+    pub const fn max_checks(&self) -> u32 {
+        self.max_checks
+    }
+
+    /// Returns if the expensive checks are skipped.
+    pub const fn skip_expensive(&self) -> bool {
+        self.skip_expensive
+    }
+}
+
+impl Default for DecodeBudget {
+    fn default() -> Self {
+        Self::STRICT
+    }
+}
+
+/// Tracks the remaining checks of a `DecodeBudget` while validating a single call.
+pub(crate) struct Validator {
+    remaining: u32,
+    skip_expensive: bool,
+}
+
+impl Validator {
+    pub(crate) fn new(budget: DecodeBudget) -> Self {
+        Self {
+            remaining: budget.max_checks,
+            skip_expensive: budget.skip_expensive,
+        }
+    }
+
+    fn take(&mut self) -> Result<()> {
+        self.remaining = self.remaining.checked_sub(1).ok_or(Error::Failed)?;
+        Ok(())
+    }
+
+    /// Checks that `addr` is aligned to `align` bytes, which must be a power of two.
+    #[cfg(feature = "tee")]
+    pub(crate) fn aligned(&mut self, addr: u64, align: u64) -> Result<()> {
+        self.take()?;
+        if addr & (align - 1) == 0 {
+            Ok(())
+        } else {
+            Err(Error::InvalidParam)
+        }
+    }
+
+    /// Checks that `addr` is 4kB-aligned.
+    #[cfg(feature = "tee")]
+    pub(crate) fn page_aligned(&mut self, addr: u64) -> Result<()> {
+        self.aligned(addr, PAGE_SIZE_4K)
+    }
+
+    /// Checks that `addr` and `len` are both 4kB-aligned.
+    #[cfg(feature = "tee")]
+    pub(crate) fn page_aligned_range(&mut self, addr: u64, len: u64) -> Result<()> {
+        self.page_aligned(addr)?;
+        self.page_aligned(len)
+    }
+
+    /// Checks that the range of `len` bytes starting at `addr` doesn't wrap. This is an expensive
+    /// check.
+    pub(crate) fn range(&mut self, addr: u64, len: u64) -> Result<()> {
+        if self.skip_expensive {
+            return Ok(());
+        }
+        self.take()?;
+        addr.checked_add(len)
+            .map(|_| ())
+            .ok_or(Error::InvalidAddress)
+    }
+
+    /// Checks that the range of `num_pages` pages of `page_type` starting at `addr` doesn't wrap.
+    /// This is an expensive check.
+    #[cfg(feature = "tee")]
+    pub(crate) fn pages(
+        &mut self,
+        addr: u64,
+        page_type: TsmPageType,
+        num_pages: u64,
+    ) -> Result<()> {
+        if self.skip_expensive {
+            return Ok(());
+        }
+        self.take()?;
+        num_pages
+            .checked_mul(page_type.size_bytes())
+            .and_then(|len| addr.checked_add(len))
+            .map(|_| ())
+            .ok_or(Error::InvalidAddress)
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Differential testing of two SBI handler implementations against a recorded trace.
+//!
+//! Each message of the trace is run through both handlers, which are passed the `a0-a7`
+//! registers of the call and return its result, until the handlers return different results.
+//! This is meant for de-risking handler refactors: run a trace captured from a real workload
+//! through the old and the new handler and check that they agree.
+//!
+//! ```rust
+//! # use sbi_rs::{differential::first_divergence, *};
+//! let trace = [
+//!     TaggedMessage::new(SbiMessage::Base(BaseFunction::GetSpecificationVersion)),
+//!     TaggedMessage::new(SbiMessage::Time(TimeFunction::SetTimer { stime_value: 0 })),
+//! ];
+//! let old = |_: &[u64; 8]| SbiReturn::success(0);
+//! let new = |regs: &[u64; 8]| match SbiMessage::from_regs(regs) {
+//!     Ok(SbiMessage::Time(_)) => Error::NotSupported.into(),
+//!     _ => SbiReturn::success(0),
+//! };
+//! let divergence = first_divergence(trace, old, new).unwrap();
+//! assert_eq!(divergence.index, 1);
+//! ```
+
+use crate::{SbiMessage, SbiReturn, SequenceTag, TaggedMessage};
+
+/// The first message of a trace for which two handlers returned different results.
+#[derive(Clone, Copy, Debug)]
+pub struct Divergence {
+    /// The position of the message in the trace.
+    pub index: usize,
+    /// The message on which the handlers diverged.
+    pub msg: SbiMessage,
+    /// The sequence tag of the message, if the trace is tagged.
+    pub tag: Option<SequenceTag>,
+    /// The result returned by the first handler.
+    pub left: SbiReturn,
+    /// The result returned by the second handler.
+    pub right: SbiReturn,
+}
+
+/// Runs every message of `trace` through `left` and `right` in order, returning the first
+/// message for which they return different results, or `None` if they agree on the whole trace.
+/// Neither handler is called again once they have diverged, as their states may no longer match.
+pub fn first_divergence<I, L, R>(trace: I, mut left: L, mut right: R) -> Option<Divergence>
+where
+    I: IntoIterator<Item = TaggedMessage>,
+    L: FnMut(&[u64; 8]) -> SbiReturn,
+    R: FnMut(&[u64; 8]) -> SbiReturn,
+{
+    trace.into_iter().enumerate().find_map(|(index, tagged)| {
+        let regs = tagged.msg.to_regs();
+        let (l, r) = (left(&regs), right(&regs));
+        (l != r).then_some(Divergence {
+            index,
+            msg: tagged.msg,
+            tag: tagged.tag,
+            left: l,
+            right: r,
+        })
+    })
+}
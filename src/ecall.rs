@@ -0,0 +1,355 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(all(
+    any(target_arch = "riscv64", target_arch = "riscv32"),
+    target_os = "none"
+))]
+use core::arch::asm;
+
+use crate::error::*;
+use crate::{SbiMessage, SbiReturn};
+
+/// A backend that issues SBI calls on behalf of `ecall_send`.
+///
+/// On riscv targets without an OS, `AsmEcallSender` issues the call with an `ecall` instruction.
+/// Elsewhere, with the `std` feature enabled, a software backend such as a simulator or a mock
+/// firmware can be installed with `with_ecall_sender()` so that the caller-side API in `api` can
+/// be exercised off-target.
+pub trait EcallSender {
+    /// Issues the SBI call held in the `a0-a7` registers in `regs`, returning the values left in
+    /// `a0` and `a1` by the call.
+    ///
+    /// # Safety
+    ///
+    /// See `ecall_send`.
+    unsafe fn send(&self, regs: &[u64; 8]) -> SbiReturn;
+}
+
+/// The `EcallSender` that traps to the firmware or hypervisor with an `ecall` instruction.
+#[cfg(all(
+    any(target_arch = "riscv64", target_arch = "riscv32"),
+    target_os = "none"
+))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsmEcallSender;
+
+#[cfg(all(
+    any(target_arch = "riscv64", target_arch = "riscv32"),
+    target_os = "none"
+))]
+impl EcallSender for AsmEcallSender {
+    #[inline(always)]
+    unsafe fn send(&self, regs: &[u64; 8]) -> SbiReturn {
+        ecall_send_raw(*regs).into()
+    }
+}
+
+#[cfg(all(
+    feature = "std",
+    not(all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    ))
+))]
+mod software {
+    use core::cell::Cell;
+
+    use super::EcallSender;
+    use crate::SbiReturn;
+
+    type SenderPtr = *const (dyn EcallSender + 'static);
+
+    std::thread_local! {
+        static SENDER: Cell<Option<SenderPtr>> = const { Cell::new(None) };
+    }
+
+    // Restores the previously installed sender when dropped, including on unwind.
+    struct Restore(Option<SenderPtr>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SENDER.with(|s| s.set(self.0));
+        }
+    }
+
+    /// Runs `f` with `sender` installed as the backend for `ecall_send` on the current thread.
+    /// Calls can be nested; the previously installed backend is restored when `f` returns.
+    pub fn with_ecall_sender<R>(sender: &dyn EcallSender, f: impl FnOnce() -> R) -> R {
+        // Safety: Only the lifetime is changed. The pointer is removed from `SENDER` by `Restore`
+        // before `sender` goes out of scope, so it is never dereferenced after `sender` is gone.
+        let ptr: SenderPtr = unsafe { core::mem::transmute(sender as *const dyn EcallSender) };
+        let _restore = Restore(SENDER.with(|s| s.replace(Some(ptr))));
+        f()
+    }
+
+    pub(super) unsafe fn send(regs: &[u64; 8]) -> SbiReturn {
+        let ptr = SENDER
+            .with(|s| s.get())
+            .expect("ecall_send called without an EcallSender");
+        // Safety: `ptr` was installed by `with_ecall_sender()`, which is still running on this
+        // thread and keeps the sender borrowed.
+        (*ptr).send(regs)
+    }
+}
+
+#[cfg(all(
+    feature = "std",
+    not(all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    ))
+))]
+pub use software::with_ecall_sender;
+
+/// Issues the SBI call held in the `a0-a7` registers in `regs`, returning the values left in
+/// `a0` and `a1` by the call, with `a0` sign-extended from XLEN bits.
+///
+/// This is the non-generic core every call made through this crate funnels into, so the `ecall`
+/// block and its register constraints are written once for typed messages, `sbi_call!` and
+/// `AsmEcallSender` alike. It is always inlined along with the rest of the ecall path (see
+/// `ecall_send`), so each call site issues its own `ecall` from the registers its arguments were
+/// computed in rather than calling shared code. On riscv32,
+/// every register value must fit in 32 bits; wider arguments must be passed as register pairs
+/// (see `split_reg_pair`). With the `std` feature off-target, the call is passed to the sender
+/// installed with `with_ecall_sender()`.
+///
+/// # Safety
+///
+/// See `ecall_send`.
+#[cfg(all(target_arch = "riscv64", target_os = "none"))]
+#[inline(always)]
+pub unsafe fn ecall_send_raw(regs: [u64; 8]) -> (u64, u64) {
+    // normally error code
+    let mut a0: u64;
+    // normally return value
+    let mut a1;
+    asm!("ecall", inlateout("a0") regs[0]=>a0, inlateout("a1")regs[1]=>a1,
+                in("a2")regs[2], in("a3") regs[3],
+                in("a4")regs[4], in("a5") regs[5],
+                in("a6")regs[6], in("a7") regs[7], options(nostack));
+    (a0, a1)
+}
+
+/// Issues the SBI call held in the `a0-a7` registers in `regs`, returning the values left in
+/// `a0` and `a1` by the call, with `a0` sign-extended from XLEN bits.
+///
+/// # Safety
+///
+/// See `ecall_send`.
+#[cfg(all(target_arch = "riscv32", target_os = "none"))]
+#[inline(always)]
+pub unsafe fn ecall_send_raw(regs: [u64; 8]) -> (u64, u64) {
+    // normally error code
+    let mut a0: usize;
+    // normally return value
+    let mut a1: usize;
+    let regs = regs.map(|reg| reg as usize);
+    asm!("ecall", inlateout("a0") regs[0]=>a0, inlateout("a1")regs[1]=>a1,
+                in("a2")regs[2], in("a3") regs[3],
+                in("a4")regs[4], in("a5") regs[5],
+                in("a6")regs[6], in("a7") regs[7], options(nostack));
+    // Sign-extend the error code so negative errors are preserved.
+    (a0 as isize as i64 as u64, a1 as u64)
+}
+
+/// Issues the SBI call held in the `a0-a7` registers in `regs` through the `EcallSender`
+/// installed on the current thread with `with_ecall_sender()`, returning the values left in `a0`
+/// and `a1` by the call. Panics if no sender is installed.
+///
+/// # Safety
+///
+/// See `ecall_send`.
+#[cfg(all(
+    feature = "std",
+    not(all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    ))
+))]
+#[inline(always)]
+pub unsafe fn ecall_send_raw(regs: [u64; 8]) -> (u64, u64) {
+    let ret = software::send(&regs);
+    (ret.error_code as u64, ret.return_value)
+}
+
+/// Send an ecall to the firmware or hypervisor.
+///
+/// # Safety
+///
+/// The caller must verify that any memory references contained in `msg` obey Rust's memory
+/// safety rules. For example, any pointers to memory that will be modified in the handling of
+/// the ecall must be uniquely owned. Similarly any pointers read by the ecall must not be
+/// mutably borrowed.
+///
+/// In addition the caller is placing trust in the firmware or hypervisor to maintain the promises
+/// of the interface w.r.t. reading and writing only within the provided bounds.
+///
+/// Calls that are no-ops or that are invalid because of a zero length or count are completed
+/// without being issued; see `SbiMessage::is_noop()`.
+///
+/// `ecall_send` and the message encoders are always inlined, so that a call whose message is
+/// built at the call site compiles to moving its arguments into `a0-a7`, without matching on the
+/// message, before funnelling into `ecall_send_raw()`. The no-op and zero-length checks are
+/// inlined with them: they fold away for calls without a length or count, and are left as a
+/// single test of that argument for calls with one.
+#[cfg(any(
+    feature = "std",
+    all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    )
+))]
+#[inline(always)]
+pub unsafe fn ecall_send(msg: &SbiMessage) -> Result<u64> {
+    let ret = match precheck(msg) {
+        Some(ret) => ret,
+        None => send_regs(&msg.to_regs()),
+    };
+    msg.result(ret.error_code as u64, ret.return_value)
+}
+
+/// Send an ecall to the firmware or hypervisor, returning the raw `a0` and `a1` values set by the
+/// call. Unlike `ecall_send`, the value returned in `a1` is available even if the call fails, and
+/// the call is always issued, including no-ops and calls of zero length or count that
+/// `ecall_send` completes itself.
+///
+/// # Safety
+///
+/// See `ecall_send`.
+#[cfg(all(
+    any(target_arch = "riscv64", target_arch = "riscv32"),
+    target_os = "none"
+))]
+#[inline(always)]
+pub unsafe fn ecall_send_with_return(msg: &SbiMessage) -> SbiReturn {
+    send_regs(&msg.to_regs())
+}
+
+/// Send an ecall to the `EcallSender` installed on the current thread with `with_ecall_sender()`,
+/// returning the raw `a0` and `a1` values set by the call. As on target, the call is issued even
+/// if `ecall_send` would complete it itself. Panics if no sender is installed.
+///
+/// ```
+/// use sbi_rs::*;
+/// use std::cell::Cell;
+///
+/// struct Counter(Cell<u32>);
+///
+/// impl EcallSender for Counter {
+///     unsafe fn send(&self, _regs: &[u64; 8]) -> SbiReturn {
+///         self.0.set(self.0.get() + 1);
+///         SbiReturn::success(0)
+///     }
+/// }
+///
+/// let sender = Counter(Cell::new(0));
+/// let empty = SbiMessage::DebugConsole(DebugConsoleFunction::PutString {
+///     len: Len::new(0),
+///     addr: PhysAddr::new(0x8000_0000),
+/// });
+/// with_ecall_sender(&sender, || unsafe {
+///     ecall_send(&empty).unwrap();
+///     assert_eq!(sender.0.get(), 0);
+///     ecall_send_with_return(&empty);
+///     assert_eq!(sender.0.get(), 1);
+/// });
+/// ```
+///
+/// # Safety
+///
+/// See `ecall_send`.
+#[cfg(all(
+    feature = "std",
+    not(all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    ))
+))]
+#[inline(always)]
+pub unsafe fn ecall_send_with_return(msg: &SbiMessage) -> SbiReturn {
+    send_regs(&msg.to_regs())
+}
+
+#[cfg(any(
+    feature = "std",
+    all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    )
+))]
+#[inline(always)]
+unsafe fn send_regs(regs: &[u64; 8]) -> SbiReturn {
+    ecall_send_raw(*regs).into()
+}
+
+/// Issues the SBI call with extension ID `eid`, function ID `fid` and `args` in `a0` onwards on
+/// behalf of `sbi_call!`.
+///
+/// # Safety
+///
+/// See `ecall_send`.
+#[doc(hidden)]
+#[cfg(any(
+    feature = "std",
+    all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    )
+))]
+pub unsafe fn __sbi_call<const N: usize>(eid: u64, fid: u64, args: [u64; N]) -> Result<u64> {
+    const { assert!(N <= 6, "SBI calls take at most six arguments") };
+    let mut regs = [0; 8];
+    regs[..N].copy_from_slice(&args);
+    regs[6] = fid;
+    regs[7] = eid;
+    send_regs(&regs).into()
+}
+
+/// Issues an ad-hoc SBI call with the given extension ID, function ID and up to six arguments,
+/// returning `Result<u64>` as `ecall_send` does. Meant for experimenting with extensions this
+/// crate doesn't model yet; the arguments are cast to `u64` and passed in `a0` onwards without
+/// any validation.
+///
+/// # Safety
+///
+/// Must be used in an `unsafe` block; see `ecall_send` for the requirements on the call.
+///
+/// ```ignore
+/// # use sbi_rs::sbi_call;
+/// // sbi_get_spec_version()
+/// let version = unsafe { sbi_call!(0x10, 0) };
+/// // sbi_debug_console_write_byte('x')
+/// unsafe { sbi_call!(0x4442434E, 2, b'x') }.unwrap();
+/// ```
+#[macro_export]
+macro_rules! sbi_call {
+    ($eid:expr, $fid:expr $(, $arg:expr)* $(,)?) => {
+        $crate::__sbi_call($eid as u64, $fid as u64, [$($arg as u64),*])
+    };
+}
+
+#[cfg(not(any(
+    feature = "std",
+    all(
+        any(target_arch = "riscv64", target_arch = "riscv32"),
+        target_os = "none"
+    )
+)))]
+pub(crate) unsafe fn ecall_send(msg: &SbiMessage) -> Result<u64> {
+    match precheck(msg) {
+        Some(ret) => ret.into(),
+        None => panic!("ecall_send called"),
+    }
+}
+
+// Returns the result of `msg` if it can be completed without being issued, because it is a no-op
+// or is invalid for having a zero length or count.
+#[inline(always)]
+fn precheck(msg: &SbiMessage) -> Option<SbiReturn> {
+    if let Err(e) = msg.check_zero_length() {
+        return Some(e.into());
+    }
+    msg.is_noop().then_some(SbiReturn::success(0))
+}
@@ -2,35 +2,60 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use core::fmt;
+
 /// Errors passed over the SBI protocol.
 ///
 /// Constants from the SBI [spec](https://github.com/riscv-non-isa/riscv-sbi-doc/releases).
-#[repr(i64)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// With the `tiny` feature, errors are displayed and debug-formatted as their error code.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
 pub enum Error {
     /// Generic failure in execution of the SBI call.
-    Failed = -1,
+    Failed,
     /// Extension or function is not supported.
-    NotSupported = -2,
+    NotSupported,
     /// Parameter passed isn't valid.
-    InvalidParam = -3,
+    InvalidParam,
     /// Permission denied.
-    Denied = -4,
+    Denied,
     /// Address passed is invalid.
-    InvalidAddress = -5,
+    InvalidAddress,
     /// The given hart has already been started.
-    AlreadyAvailable = -6,
+    AlreadyAvailable,
     /// Some of the given counters have already been started.
-    AlreadyStarted = -7,
+    AlreadyStarted,
     /// Some of the given counters have already been stopped.
-    AlreadyStopped = -8,
-    /// The buffer passed as a parameter is not large enough.
-    InsufficientBufferCapacity = -9,
+    AlreadyStopped,
+    /// The buffer passed as a parameter is not large enough. Not defined by the SBI
+    /// specification, so it is passed as `INSUFFICIENT_BUFFER_CAPACITY_CODE`, outside the range
+    /// of codes the specification assigns.
+    InsufficientBufferCapacity,
+    /// The shared memory required by the call isn't available.
+    NoShmem,
+    /// The call is invalid in the current state of the target.
+    InvalidState,
+    /// The range passed is invalid, for example because it wraps or isn't fully covered.
+    BadRange,
+    /// The call timed out.
+    Timeout,
+    /// An input or output error occurred.
+    Io,
+    /// Permission denied because the target is locked.
+    DeniedLocked,
+    /// An error code not defined by the SBI specification, preserved as is. Non-negative values
+    /// aren't error codes and are passed as `Error::Failed` rather than as success.
+    Unknown(i64),
 }
 
+/// The error code passed for `Error::InsufficientBufferCapacity`.
+pub const INSUFFICIENT_BUFFER_CAPACITY_CODE: i64 = -1000;
+
 impl Error {
-    /// Parse the given error code to an `Error` enum.
-    pub fn from_code(e: i64) -> Self {
+    /// Parse the given error code to an `Error` enum. Codes not defined by the SBI specification
+    /// are preserved in `Error::Unknown`.
+    pub const fn from_code(e: i64) -> Self {
         use Error::*;
         match e {
             -1 => Failed,
@@ -41,11 +66,174 @@ impl Error {
             -6 => AlreadyAvailable,
             -7 => AlreadyStarted,
             -8 => AlreadyStopped,
-            -9 => InsufficientBufferCapacity,
-            _ => Failed,
+            -9 => NoShmem,
+            -10 => InvalidState,
+            -11 => BadRange,
+            -12 => Timeout,
+            -13 => Io,
+            -14 => DeniedLocked,
+            INSUFFICIENT_BUFFER_CAPACITY_CODE => InsufficientBufferCapacity,
+            e => Unknown(e),
         }
     }
+
+    /// Returns the SBI error code for this error, which is always negative.
+    pub const fn code(&self) -> i64 {
+        use Error::*;
+        match self {
+            Failed => -1,
+            NotSupported => -2,
+            InvalidParam => -3,
+            Denied => -4,
+            InvalidAddress => -5,
+            AlreadyAvailable => -6,
+            AlreadyStarted => -7,
+            AlreadyStopped => -8,
+            NoShmem => -9,
+            InvalidState => -10,
+            BadRange => -11,
+            Timeout => -12,
+            Io => -13,
+            DeniedLocked => -14,
+            InsufficientBufferCapacity => INSUFFICIENT_BUFFER_CAPACITY_CODE,
+            // An `SbiReturn` with a non-negative code would read as success.
+            Unknown(e) if *e >= 0 => -1,
+            Unknown(e) => *e,
+        }
+    }
+}
+
+impl From<i64> for Error {
+    fn from(e: i64) -> Self {
+        Self::from_code(e)
+    }
 }
 
+impl From<Error> for i64 {
+    fn from(e: Error) -> Self {
+        e.code()
+    }
+}
+
+#[cfg(feature = "tiny")]
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error({})", self.code())
+    }
+}
+
+impl fmt::Display for Error {
+    #[cfg(feature = "tiny")]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SBI error {}", self.code())
+    }
+
+    #[cfg(not(feature = "tiny"))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        let msg = match self {
+            Failed => "failed",
+            NotSupported => "not supported",
+            InvalidParam => "invalid parameter",
+            Denied => "denied",
+            InvalidAddress => "invalid address",
+            AlreadyAvailable => "already available",
+            AlreadyStarted => "already started",
+            AlreadyStopped => "already stopped",
+            InsufficientBufferCapacity => "insufficient buffer capacity",
+            NoShmem => "shared memory not available",
+            InvalidState => "invalid state",
+            BadRange => "bad range",
+            Timeout => "timed out",
+            Io => "input/output error",
+            DeniedLocked => "denied (locked)",
+            Unknown(e) => return write!(f, "unknown SBI error {e}"),
+        };
+        f.write_str(msg)
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// How an argument rejected by a `ValidationError` is invalid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// The address or length isn't suitably aligned.
+    Misaligned,
+    /// The length or count is zero.
+    Empty,
+    /// The range overlaps another range that was passed along with it.
+    Overlap,
+    /// The range wraps around the end of the address space.
+    Overflow,
+    /// The range isn't covered by the ranges it must fall within.
+    Uncovered,
+    /// The value is outside of the range of values that can be used.
+    OutOfRange,
+}
+
+impl ValidationErrorKind {
+    /// Returns the `Error` reported for arguments rejected for this reason.
+    pub const fn error(&self) -> Error {
+        use ValidationErrorKind::*;
+        match self {
+            Misaligned | Empty | Overlap | OutOfRange => Error::InvalidParam,
+            Overflow | Uncovered => Error::InvalidAddress,
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ValidationErrorKind::*;
+        let msg = match self {
+            Misaligned => "misaligned",
+            Empty => "empty",
+            Overlap => "overlapping",
+            Overflow => "wraps around the address space",
+            Uncovered => "not covered",
+            OutOfRange => "out of range",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// An argument rejected by a builder or wrapper before any SBI call is made, identifying the
+/// offending field. Converts into the `Error` that describes it, so `?` works in functions
+/// returning `Result`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The name of the rejected field, e.g. `image.guest_addr`.
+    pub field: &'static str,
+    /// Why the field was rejected.
+    pub kind: ValidationErrorKind,
+}
+
+impl ValidationError {
+    /// Creates an error rejecting `field` for the reason `kind`.
+    pub const fn new(field: &'static str, kind: ValidationErrorKind) -> Self {
+        Self { field, kind }
+    }
+
+    /// Returns the `Error` that describes this error.
+    pub const fn error(&self) -> Error {
+        self.kind.error()
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        e.error()
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.field, self.kind)
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
 /// Holds the result of a TEE operation.
 pub type Result<T> = core::result::Result<T, Error>;
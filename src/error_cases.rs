@@ -0,0 +1,338 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A table of register frames, one per documented error condition of each SBI function, for
+//! table-driven testing of SBI handlers.
+//!
+//! Each `ErrorCase` holds the `a0-a7` registers of a call that a conforming handler must fail,
+//! along with the set of errors the specification allows for it. Cases only depend on the
+//! arguments of the call, not on the state of the handler, so they can be run against a freshly
+//! constructed handler in any order.
+//!
+//! ```rust
+//! # use sbi_rs::{error_cases::check_handler, SbiMessage, SbiReturn};
+//! let failures = check_handler(|regs| match SbiMessage::from_regs(regs) {
+//!     Ok(_) => SbiReturn::success(0),
+//!     Err(e) => e.into(),
+//! });
+//! // Decoding alone catches malformed arguments, but not alignment or ID range errors.
+//! assert!(!failures.is_empty());
+//! ```
+
+use std::vec::Vec;
+
+use crate::{consts::*, Error, SbiExtension, SbiReturn, HART_MASK_BASE_ALL};
+
+/// The kind of error condition exercised by an `ErrorCase`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCondition {
+    /// The extension ID isn't implemented.
+    UnknownExtension,
+    /// The function ID isn't implemented by the extension.
+    UnknownFunction,
+    /// An argument holds a value the specification marks as reserved.
+    ReservedValue,
+    /// An address or length argument isn't suitably aligned.
+    Misaligned,
+    /// An ID argument (hart, counter, guest or register) is out of range.
+    OutOfRangeId,
+}
+
+/// A register frame that a conforming SBI handler must fail.
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorCase {
+    /// The extension being called, or `None` for calls to unknown extensions.
+    pub extension: Option<SbiExtension>,
+    /// The name of the function being called.
+    pub function: &'static str,
+    /// The error condition exercised by the call.
+    pub condition: ErrorCondition,
+    /// The `a0-a7` registers of the call.
+    pub regs: [u64; 8],
+    /// The errors a conforming handler may return for the call.
+    pub expected: &'static [Error],
+}
+
+impl ErrorCase {
+    /// Returns if `ret` is an acceptable result for this case.
+    pub fn accepts(&self, ret: &SbiReturn) -> bool {
+        self.expected.iter().any(|&e| e.code() == ret.error_code)
+    }
+}
+
+/// An `ErrorCase` for which a handler returned an unexpected result.
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorCaseFailure {
+    /// The case that failed.
+    pub case: ErrorCase,
+    /// The result returned by the handler.
+    pub actual: SbiReturn,
+}
+
+const NOT_SUPPORTED: &[Error] = &[Error::NotSupported];
+const INVALID_PARAM: &[Error] = &[Error::InvalidParam];
+const BAD_ADDRESS: &[Error] = &[Error::InvalidParam, Error::InvalidAddress];
+
+// An address that is not aligned to a 4kB page.
+const MISALIGNED_ADDR: u64 = 0x8000_1001;
+// A length of one page.
+const PAGE_LEN: u64 = 0x1000;
+
+fn regs(eid: u64, fid: u64, args: &[u64]) -> [u64; 8] {
+    let mut regs = [0; 8];
+    regs[..args.len()].copy_from_slice(args);
+    regs[6] = fid;
+    regs[7] = eid;
+    regs
+}
+
+/// Returns the error cases for every extension and function known to this crate.
+pub fn error_cases() -> Vec<ErrorCase> {
+    use ErrorCondition::*;
+    use SbiExtension::*;
+
+    let case = |extension, function, condition, regs, expected| ErrorCase {
+        extension: Some(extension),
+        function,
+        condition,
+        regs,
+        expected,
+    };
+
+    let mut cases = Vec::new();
+    cases.push(ErrorCase {
+        extension: None,
+        function: "unknown",
+        condition: UnknownExtension,
+        regs: regs(0x7fff_ffff, 0, &[]),
+        expected: NOT_SUPPORTED,
+    });
+    for ext in SbiExtension::ALL {
+        // Legacy extensions don't have function IDs.
+        if ext == PutChar {
+            continue;
+        }
+        cases.push(case(
+            ext,
+            "unknown",
+            UnknownFunction,
+            regs(ext.eid(), 0xffff, &[]),
+            NOT_SUPPORTED,
+        ));
+    }
+
+    cases.extend([
+        // IPI
+        case(
+            Ipi,
+            "send_ipi",
+            OutOfRangeId,
+            regs(EXT_IPI, 0, &[1, HART_MASK_BASE_ALL - 1]),
+            INVALID_PARAM,
+        ),
+        // RFENCE
+        case(
+            Rfence,
+            "remote_fence_i",
+            OutOfRangeId,
+            regs(EXT_RFENCE, 0, &[1, HART_MASK_BASE_ALL - 1]),
+            INVALID_PARAM,
+        ),
+        // HSM
+        case(
+            HartState,
+            "hart_start",
+            OutOfRangeId,
+            regs(EXT_HART_STATE, 0, &[u64::MAX, 0x8020_0000, 0]),
+            INVALID_PARAM,
+        ),
+        case(
+            HartState,
+            "hart_get_status",
+            OutOfRangeId,
+            regs(EXT_HART_STATE, 2, &[u64::MAX]),
+            INVALID_PARAM,
+        ),
+        case(
+            HartState,
+            "hart_suspend",
+            ReservedValue,
+            regs(EXT_HART_STATE, 3, &[0x1, 0, 0]),
+            INVALID_PARAM,
+        ),
+        // SRST
+        case(
+            Reset,
+            "system_reset",
+            ReservedValue,
+            regs(EXT_RESET, 0, &[0x3, 0]),
+            INVALID_PARAM,
+        ),
+        case(
+            Reset,
+            "system_reset",
+            ReservedValue,
+            regs(EXT_RESET, 0, &[0, 0x2]),
+            INVALID_PARAM,
+        ),
+        // PMU
+        case(
+            Pmu,
+            "counter_get_info",
+            OutOfRangeId,
+            regs(EXT_PMU, 1, &[u64::MAX]),
+            INVALID_PARAM,
+        ),
+        case(
+            Pmu,
+            "counter_config_matching",
+            ReservedValue,
+            regs(EXT_PMU, 2, &[0, 1, 1 << 8, 1, 0]),
+            INVALID_PARAM,
+        ),
+        case(
+            Pmu,
+            "counter_start",
+            ReservedValue,
+            regs(EXT_PMU, 3, &[0, 1, 1 << 8, 0]),
+            INVALID_PARAM,
+        ),
+        case(
+            Pmu,
+            "counter_stop",
+            ReservedValue,
+            regs(EXT_PMU, 4, &[0, 1, 1 << 8]),
+            INVALID_PARAM,
+        ),
+        case(
+            Pmu,
+            "counter_fw_read",
+            OutOfRangeId,
+            regs(EXT_PMU, 5, &[u64::MAX]),
+            INVALID_PARAM,
+        ),
+        // TEE Host
+        case(
+            TeeHost,
+            "tsm_convert_pages",
+            Misaligned,
+            regs(EXT_TEE_HOST, 1, &[MISALIGNED_ADDR, 1]),
+            BAD_ADDRESS,
+        ),
+        case(
+            TeeHost,
+            "tsm_reclaim_pages",
+            Misaligned,
+            regs(EXT_TEE_HOST, 2, &[MISALIGNED_ADDR, 1]),
+            BAD_ADDRESS,
+        ),
+        case(
+            TeeHost,
+            "tvm_destroy",
+            OutOfRangeId,
+            regs(EXT_TEE_HOST, 7, &[u64::MAX]),
+            INVALID_PARAM,
+        ),
+        case(
+            TeeHost,
+            "tvm_add_measured_pages",
+            ReservedValue,
+            regs(EXT_TEE_HOST, 10, &[0, 0, 0, 4, 1, 0]),
+            INVALID_PARAM,
+        ),
+        case(
+            TeeHost,
+            "tvm_add_zero_pages",
+            ReservedValue,
+            regs(EXT_TEE_HOST, 11, &[0, 0, 4, 1, 0]),
+            INVALID_PARAM,
+        ),
+        case(
+            TeeHost,
+            "tvm_add_shared_pages",
+            ReservedValue,
+            regs(EXT_TEE_HOST, 12, &[0, 0, 4, 1, 0]),
+            INVALID_PARAM,
+        ),
+        case(
+            TeeHost,
+            "tvm_cpu_run",
+            OutOfRangeId,
+            regs(EXT_TEE_HOST, 16, &[u64::MAX, 0]),
+            INVALID_PARAM,
+        ),
+        // TEE Interrupt
+        case(
+            TeeInterrupt,
+            "tsm_convert_imsic",
+            Misaligned,
+            regs(EXT_TEE_INTERRUPT, 2, &[MISALIGNED_ADDR]),
+            BAD_ADDRESS,
+        ),
+        case(
+            TeeInterrupt,
+            "tvm_cpu_bind_imsic",
+            ReservedValue,
+            regs(EXT_TEE_INTERRUPT, 4, &[0, 0, 0x1]),
+            INVALID_PARAM,
+        ),
+        case(
+            TeeInterrupt,
+            "tvm_cpu_rebind_imsic_begin",
+            ReservedValue,
+            regs(EXT_TEE_INTERRUPT, 8, &[0, 0, 0x1]),
+            INVALID_PARAM,
+        ),
+        // TEE Guest
+        case(
+            TeeGuest,
+            "add_mmio_region",
+            Misaligned,
+            regs(EXT_TEE_GUEST, 0, &[MISALIGNED_ADDR, PAGE_LEN]),
+            BAD_ADDRESS,
+        ),
+        case(
+            TeeGuest,
+            "share_memory",
+            Misaligned,
+            regs(EXT_TEE_GUEST, 2, &[MISALIGNED_ADDR, PAGE_LEN]),
+            BAD_ADDRESS,
+        ),
+        case(
+            TeeGuest,
+            "unshare_memory",
+            Misaligned,
+            regs(EXT_TEE_GUEST, 3, &[MISALIGNED_ADDR, PAGE_LEN]),
+            BAD_ADDRESS,
+        ),
+        // Attestation
+        case(
+            Attestation,
+            "extend_measurement",
+            OutOfRangeId,
+            regs(EXT_ATTESTATION, 2, &[0, 0, u64::MAX]),
+            INVALID_PARAM,
+        ),
+        case(
+            Attestation,
+            "read_measurement",
+            OutOfRangeId,
+            regs(EXT_ATTESTATION, 3, &[0, 0, u64::MAX]),
+            INVALID_PARAM,
+        ),
+    ]);
+    cases
+}
+
+/// Runs every error case through `handler`, which is passed the `a0-a7` registers of a call and
+/// returns its result. Returns the cases for which the handler returned an unexpected result.
+pub fn check_handler<F: FnMut(&[u64; 8]) -> SbiReturn>(mut handler: F) -> Vec<ErrorCaseFailure> {
+    error_cases()
+        .into_iter()
+        .filter_map(|case| {
+            let actual = handler(&case.regs);
+            (!case.accepts(&actual)).then_some(ErrorCaseFailure { case, actual })
+        })
+        .collect()
+}
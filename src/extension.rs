@@ -0,0 +1,206 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use core::fmt;
+
+use crate::consts::*;
+
+/// The SBI extensions known to this crate, in canonical order: the extensions defined by the SBI
+/// specification in the order they appear there, followed by the extensions under development.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SbiExtension {
+    /// The Base extension.
+    Base,
+    /// The legacy PutChar extension.
+    PutChar,
+    /// The Timer extension.
+    Time,
+    /// The IPI extension.
+    Ipi,
+    /// The RFENCE extension.
+    Rfence,
+    /// The Hart State Management extension.
+    HartState,
+    /// The System Reset extension.
+    Reset,
+    /// The Performance Monitoring Unit extension.
+    Pmu,
+    /// The Debug Console extension.
+    DebugConsole,
+    /// The Nested Virtualization Acceleration extension.
+    Nacl,
+    /// The TEE Host extension.
+    TeeHost,
+    /// The TEE Interrupt extension.
+    TeeInterrupt,
+    /// The TEE Guest extension.
+    TeeGuest,
+    /// The Attestation extension.
+    Attestation,
+}
+
+impl SbiExtension {
+    /// All known extensions, in canonical order.
+    pub const ALL: [SbiExtension; 14] = [
+        SbiExtension::Base,
+        SbiExtension::PutChar,
+        SbiExtension::Time,
+        SbiExtension::Ipi,
+        SbiExtension::Rfence,
+        SbiExtension::HartState,
+        SbiExtension::Reset,
+        SbiExtension::Pmu,
+        SbiExtension::DebugConsole,
+        SbiExtension::Nacl,
+        SbiExtension::TeeHost,
+        SbiExtension::TeeInterrupt,
+        SbiExtension::TeeGuest,
+        SbiExtension::Attestation,
+    ];
+
+    /// Returns the extension ID (the value of a7) of this extension.
+    pub const fn eid(&self) -> u64 {
+        use SbiExtension::*;
+        match self {
+            Base => EXT_BASE,
+            PutChar => EXT_PUT_CHAR,
+            Time => EXT_TIME,
+            Ipi => EXT_IPI,
+            Rfence => EXT_RFENCE,
+            HartState => EXT_HART_STATE,
+            Reset => EXT_RESET,
+            Pmu => EXT_PMU,
+            DebugConsole => EXT_DBCN,
+            Nacl => EXT_NACL,
+            TeeHost => EXT_TEE_HOST,
+            TeeInterrupt => EXT_TEE_INTERRUPT,
+            TeeGuest => EXT_TEE_GUEST,
+            Attestation => EXT_ATTESTATION,
+        }
+    }
+
+    /// Returns the extension with the given extension ID, if it is known to this crate.
+    pub fn from_eid(eid: u64) -> Option<Self> {
+        Self::ALL.into_iter().find(|ext| ext.eid() == eid)
+    }
+
+    /// Returns if this is one of the deprecated legacy (v0.1) extensions, whose extension IDs are
+    /// in `EXT_LEGACY_START..=EXT_LEGACY_END`.
+    pub const fn is_legacy(&self) -> bool {
+        matches!(self.eid(), EXT_LEGACY_START..=EXT_LEGACY_END)
+    }
+
+    /// Returns the short name of this extension.
+    pub const fn name(&self) -> &'static str {
+        use SbiExtension::*;
+        match self {
+            Base => "BASE",
+            PutChar => "PUTCHAR",
+            Time => "TIME",
+            Ipi => "IPI",
+            Rfence => "RFNC",
+            HartState => "HSM",
+            Reset => "SRST",
+            Pmu => "PMU",
+            DebugConsole => "DBCN",
+            Nacl => "NACL",
+            TeeHost => "TEEH",
+            TeeInterrupt => "TEEI",
+            TeeGuest => "TEEG",
+            Attestation => "ATST",
+        }
+    }
+}
+
+/// Displays the name of the extension, or its extension ID with the `tiny` feature.
+impl fmt::Display for SbiExtension {
+    #[cfg(feature = "tiny")]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.eid())
+    }
+
+    #[cfg(not(feature = "tiny"))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A set of SBI extensions. Iterates, and is displayed, in canonical extension order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ExtensionSet(u32);
+
+impl ExtensionSet {
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Creates a set from its raw bitmap, as returned by `raw()`. Bits not assigned to an extension
+    /// are ignored.
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw & ((1 << SbiExtension::ALL.len()) - 1))
+    }
+
+    /// Returns the raw bitmap of the set, in which bit `ext as u32` is set for each extension
+    /// `ext` in the set.
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Adds `ext` to the set.
+    pub const fn insert(&mut self, ext: SbiExtension) {
+        self.0 |= 1 << ext as u32;
+    }
+
+    /// Removes `ext` from the set.
+    pub const fn remove(&mut self, ext: SbiExtension) {
+        self.0 &= !(1 << ext as u32);
+    }
+
+    /// Returns if `ext` is in the set.
+    pub const fn contains(&self, ext: SbiExtension) -> bool {
+        self.0 & (1 << ext as u32) != 0
+    }
+
+    /// Returns if the set is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the number of extensions in the set.
+    pub const fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns an iterator over the extensions in the set, in canonical order.
+    pub fn iter(&self) -> impl Iterator<Item = SbiExtension> {
+        let set = *self;
+        SbiExtension::ALL
+            .into_iter()
+            .filter(move |&ext| set.contains(ext))
+    }
+}
+
+impl FromIterator<SbiExtension> for ExtensionSet {
+    fn from_iter<I: IntoIterator<Item = SbiExtension>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for ext in iter {
+            set.insert(ext);
+        }
+        set
+    }
+}
+
+impl fmt::Display for ExtensionSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, ext) in self.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{ext}")?;
+        }
+        Ok(())
+    }
+}
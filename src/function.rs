@@ -2,51 +2,382 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use core::fmt;
+
+use crate::consts::SBI_SUCCESS;
 use crate::error::*;
 
+/// How an SBI function returns its result in `a0` and `a1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReturnKind {
+    /// `a0` holds an SBI error code and `a1` holds the value returned on success.
+    Value,
+    /// `a0` holds an SBI error code. Nothing is returned in `a1`, so success yields 0.
+    Empty,
+    /// The legacy (v0.1) convention: `a0` is 0 on success and an implementation-defined error
+    /// value on failure. Nothing is returned in `a1`, so success yields 0 and failure
+    /// `Error::Failed`.
+    Legacy,
+}
+
+impl ReturnKind {
+    /// Returns the result of a call using this convention from the values left in `a0` and `a1`.
+    pub fn decode(self, a0: u64, a1: u64) -> Result<u64> {
+        match (self, a0 as i64) {
+            (ReturnKind::Legacy, SBI_SUCCESS) => Ok(0),
+            (ReturnKind::Legacy, _) => Err(Error::Failed),
+            (ReturnKind::Value, SBI_SUCCESS) => Ok(a1),
+            (ReturnKind::Empty, SBI_SUCCESS) => Ok(0),
+            (_, e) => Err(Error::from_code(e)),
+        }
+    }
+}
+
+/// The name of an SBI function and of the arguments it passes in `a0` onwards, used to display
+/// calls in logs and traces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FunctionSignature {
+    /// The name of the function, e.g. `hart_start`.
+    pub name: &'static str,
+    /// The names of the arguments passed in `a0` onwards.
+    pub args: &'static [&'static str],
+}
+
+impl FunctionSignature {
+    /// Creates a signature for the function `name` taking the arguments `args`.
+    pub const fn new(name: &'static str, args: &'static [&'static str]) -> Self {
+        Self { name, args }
+    }
+}
+
+/// An argument register value, displayed in decimal if it is small and in hex, in groups of four
+/// digits, otherwise.
+#[derive(Clone, Copy, Debug)]
+pub struct ArgValue(pub u64);
+
+impl fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 < 1024 {
+            return write!(f, "{}", self.0);
+        }
+        f.write_str("0x")?;
+        let digits = (u64::BITS - self.0.leading_zeros()).div_ceil(4);
+        for i in (0..digits).rev() {
+            write!(f, "{:x}", (self.0 >> (i * 4)) & 0xf)?;
+            if i != 0 && i % 4 == 0 {
+                f.write_str("_")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Formats the call `func` to the extension `ext` as `EXT::name(arg=value, ...)`. Functions
+// without a signature, and every function with the `tiny` feature, are displayed with their
+// function ID and raw argument registers.
+pub(crate) fn fmt_call(
+    f: &mut fmt::Formatter<'_>,
+    ext: &dyn fmt::Display,
+    func: &dyn SbiFunction,
+) -> fmt::Result {
+    let regs = [
+        func.a0(),
+        func.a1(),
+        func.a2(),
+        func.a3(),
+        func.a4(),
+        func.a5(),
+    ];
+    #[cfg(feature = "tiny")]
+    let signature = None::<FunctionSignature>;
+    #[cfg(not(feature = "tiny"))]
+    let signature = func.signature();
+    match signature {
+        Some(sig) => {
+            write!(f, "{}::{}(", ext, sig.name)?;
+            for (i, (name, val)) in sig.args.iter().zip(regs).enumerate() {
+                if i != 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}={}", name, ArgValue(val))?;
+            }
+        }
+        None => return fmt_raw_call(f, ext, func.a6(), regs),
+    }
+    f.write_str(")")
+}
+
+// Formats a call to function `fid` of `ext` with its raw argument registers `regs`, for calls
+// without a signature.
+pub(crate) fn fmt_raw_call(
+    f: &mut fmt::Formatter<'_>,
+    ext: &dyn fmt::Display,
+    fid: u64,
+    regs: [u64; 6],
+) -> fmt::Result {
+    write!(f, "{}::function_{}(", ext, fid)?;
+    for (i, val) in regs.into_iter().enumerate() {
+        if i != 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "a{}={}", i, ArgValue(val))?;
+    }
+    f.write_str(")")
+}
+
 /// A Trait for an SbiFunction. Implementers use this trait to specify how to parse from and
 /// serialize into the a0-a7 registers used to make SBI calls.
 pub trait SbiFunction {
     /// Returns the `u64` value that should be stored in register a6 before making the ecall for
     /// this function.
+    #[inline(always)]
     fn a6(&self) -> u64 {
         0
     }
     /// Returns the `u64` value that should be stored in register a5 before making the ecall for
     /// this function.
+    #[inline(always)]
     fn a5(&self) -> u64 {
         0
     }
     /// Returns the `u64` value that should be stored in register a4 before making the ecall for
     /// this function.
+    #[inline(always)]
     fn a4(&self) -> u64 {
         0
     }
     /// Returns the `u64` value that should be stored in register a3 before making the ecall for
     /// this function.
+    #[inline(always)]
     fn a3(&self) -> u64 {
         0
     }
     /// Returns the `u64` value that should be stored in register a2 before making the ecall for
     /// this function.
+    #[inline(always)]
     fn a2(&self) -> u64 {
         0
     }
     /// Returns the `u64` value that should be stored in register a1 before making the ecall for
     /// this function.
+    #[inline(always)]
     fn a1(&self) -> u64 {
         0
     }
     /// Returns the `u64` value that should be stored in register a0 before making the ecall for
     /// this function.
+    #[inline(always)]
     fn a0(&self) -> u64 {
         0
     }
-    /// Returns a result parsed from the a0 and a1 return value registers.
+    /// Returns the values of registers a0-a6, in order, for this function. Defaults to
+    /// collecting `a0()` through `a6()`; encoders that compute every register at once override it
+    /// so that encoding takes a single match.
+    #[inline(always)]
+    fn regs(&self) -> [u64; 7] {
+        [
+            self.a0(),
+            self.a1(),
+            self.a2(),
+            self.a3(),
+            self.a4(),
+            self.a5(),
+            self.a6(),
+        ]
+    }
+    /// Returns the name and arguments of this function, for display. Functions without a
+    /// signature are displayed with their raw function ID and argument registers.
+    fn signature(&self) -> Option<FunctionSignature> {
+        None
+    }
+    /// Returns how this function returns its result. Defaults to `ReturnKind::Value`.
+    fn return_kind(&self) -> ReturnKind {
+        ReturnKind::Value
+    }
+    /// Returns a result parsed from the a0 and a1 return value registers according to
+    /// `return_kind()`.
     fn result(&self, a0: u64, a1: u64) -> Result<u64> {
-        match a0 {
-            0 => Ok(a1),
-            e => Err(Error::from_code(e as i64)),
-        }
+        self.return_kind().decode(a0, a1)
     }
 }
+
+// Returns the index of the argument register `$reg` in the array built by `sbi_function!` and
+// `define_vendor_extension!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sbi_reg_index {
+    (a0) => {
+        0
+    };
+    (a1) => {
+        1
+    };
+    (a2) => {
+        2
+    };
+    (a3) => {
+        3
+    };
+    (a4) => {
+        4
+    };
+    (a5) => {
+        5
+    };
+}
+
+// Returns the `ReturnKind` named in an `sbi_function!` or `define_vendor_extension!` variant,
+// defaulting to `Value`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sbi_return_kind {
+    () => {
+        $crate::ReturnKind::Value
+    };
+    ($kind:ident) => {
+        $crate::ReturnKind::$kind
+    };
+}
+
+// Declares the function enum of an extension whose arguments are all plain `u64`s, along with
+// its `from_regs()` decoder and `SbiFunction` encoder. Each variant is followed by the name of the
+// function for display, its function ID and, for functions that don't return
+// `ReturnKind::Value`, by its `ReturnKind`. Each field is followed by the argument register it is
+// passed in, and fields are listed in register order:
+//
+//     sbi_function! {
+//         /// Functions for the Foo extension.
+//         #[derive(Copy, Clone, Debug)]
+//         pub enum FooFunction {
+//             /// a6 = 0
+//             Bar(bar) = 0 {
+//                 /// a0 = the baz
+//                 baz => a0,
+//             },
+//             /// a6 = 1
+//             Reset(reset) = 1 => Empty,
+//         }
+//     }
+//
+// Calls with an unknown function ID fail to decode with `Error::NotSupported`. Extensions with
+// typed or validated arguments implement these by hand.
+macro_rules! sbi_function {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$vmeta:meta])*
+                $variant:ident($fname:ident) = $fid:literal $(=> $kind:ident)? $({
+                    $(
+                        $(#[$fmeta:meta])*
+                        $field:ident => $reg:ident
+                    ),* $(,)?
+                })?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $(
+                $(#[$vmeta])*
+                $variant $({
+                    $(
+                        $(#[$fmeta])*
+                        $field: u64,
+                    )*
+                })?,
+            )*
+        }
+
+        impl $name {
+            /// Attempts to parse `Self` from the passed in `a0-a7`.
+            #[cfg(feature = "handler")]
+            pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
+                match args[6] {
+                    $(
+                        $fid => Ok($name::$variant $({
+                            $($field: args[$crate::__sbi_reg_index!($reg)],)*
+                        })?),
+                    )*
+                    _ => Err(Error::NotSupported),
+                }
+            }
+
+            // Returns the values of a0-a6 for the call.
+            #[inline(always)]
+            fn sbi_regs(&self) -> [u64; 7] {
+                #[allow(unused_mut)]
+                let mut regs = [0; 7];
+                match self {
+                    $(
+                        $name::$variant $({ $($field,)* })? => {
+                            regs[6] = $fid;
+                            $($(regs[$crate::__sbi_reg_index!($reg)] = *$field;)*)?
+                        }
+                    )*
+                }
+                regs
+            }
+        }
+
+        impl SbiFunction for $name {
+            fn signature(&self) -> Option<FunctionSignature> {
+                match self {
+                    $(
+                        $name::$variant { .. } => Some(FunctionSignature::new(
+                            stringify!($fname),
+                            &[$($(stringify!($field)),*)?],
+                        )),
+                    )*
+                }
+            }
+
+            fn return_kind(&self) -> ReturnKind {
+                match self {
+                    $($name::$variant { .. } => $crate::__sbi_return_kind!($($kind)?),)*
+                }
+            }
+
+            #[inline(always)]
+            fn regs(&self) -> [u64; 7] {
+                self.sbi_regs()
+            }
+
+            #[inline(always)]
+            fn a6(&self) -> u64 {
+                self.sbi_regs()[6]
+            }
+
+            #[inline(always)]
+            fn a5(&self) -> u64 {
+                self.sbi_regs()[5]
+            }
+
+            #[inline(always)]
+            fn a4(&self) -> u64 {
+                self.sbi_regs()[4]
+            }
+
+            #[inline(always)]
+            fn a3(&self) -> u64 {
+                self.sbi_regs()[3]
+            }
+
+            #[inline(always)]
+            fn a2(&self) -> u64 {
+                self.sbi_regs()[2]
+            }
+
+            #[inline(always)]
+            fn a1(&self) -> u64 {
+                self.sbi_regs()[1]
+            }
+
+            #[inline(always)]
+            fn a0(&self) -> u64 {
+                self.sbi_regs()[0]
+            }
+        }
+    };
+}
+pub(crate) use sbi_function;
@@ -0,0 +1,81 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+/// The argument registers used by SBI calls.
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GprIndex {
+    /// `a0` (`x10`).
+    A0 = 10,
+    /// `a1` (`x11`).
+    A1,
+    /// `a2` (`x12`).
+    A2,
+    /// `a3` (`x13`).
+    A3,
+    /// `a4` (`x14`).
+    A4,
+    /// `a5` (`x15`).
+    A5,
+    /// `a6` (`x16`).
+    A6,
+    /// `a7` (`x17`).
+    A7,
+}
+
+impl GprIndex {
+    /// The argument registers, in order.
+    pub const ARGS: [GprIndex; 8] = [
+        GprIndex::A0,
+        GprIndex::A1,
+        GprIndex::A2,
+        GprIndex::A3,
+        GprIndex::A4,
+        GprIndex::A5,
+        GprIndex::A6,
+        GprIndex::A7,
+    ];
+
+    /// Returns the number of the register in the GPR file, e.g. 10 for `a0`.
+    pub const fn gpr_number(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the position of the register among the argument registers, e.g. 0 for `a0`.
+    pub const fn arg_index(self) -> usize {
+        self as usize - GprIndex::A0 as usize
+    }
+}
+
+/// Access to the argument registers of a saved register file, such as a hypervisor's trap frame,
+/// so that SBI calls can be decoded from it and their results written back to it directly.
+pub trait GprAccess {
+    /// Returns the value of the register `index`.
+    fn gpr(&self, index: GprIndex) -> u64;
+
+    /// Sets the value of the register `index` to `val`.
+    fn set_gpr(&mut self, index: GprIndex, val: u64);
+}
+
+/// The `a0-a7` registers, indexed from 0.
+impl GprAccess for [u64; 8] {
+    fn gpr(&self, index: GprIndex) -> u64 {
+        self[index.arg_index()]
+    }
+
+    fn set_gpr(&mut self, index: GprIndex, val: u64) {
+        self[index.arg_index()] = val;
+    }
+}
+
+/// A full GPR file, indexed by register number.
+impl GprAccess for [u64; 32] {
+    fn gpr(&self, index: GprIndex) -> u64 {
+        self[index.gpr_number()]
+    }
+
+    fn set_gpr(&mut self, index: GprIndex, val: u64) {
+        self[index.gpr_number()] = val;
+    }
+}
@@ -0,0 +1,1248 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Routing of decoded SBI calls to the components of an SBI implementation.
+//!
+//! An implementation, such as a hypervisor emulating SBI for its guests, implements the handler
+//! trait of each extension it supports and registers the handlers with an `SbiDispatcher`. The
+//! dispatcher decodes each call, passes it to the handler registered for its extension and
+//! writes the result back to the caller's registers.
+//!
+//! Each handler trait has a method per function of the extension, named after the function in
+//! the SBI specification, that fails with `Error::NotSupported` unless implemented. Handlers only
+//! implement the functions they support, and calls to the others, or to extensions without a
+//! registered handler, fail with the error code the specification requires.
+//!
+//! ```rust
+//! use sbi_rs::handler::{SbiDispatcher, TimeHandler};
+//! use sbi_rs::*;
+//!
+//! struct Timer {
+//!     next_event: u64,
+//! }
+//!
+//! impl TimeHandler for Timer {
+//!     fn set_timer(&mut self, stime_value: u64) -> Result<u64> {
+//!         self.next_event = stime_value;
+//!         Ok(0)
+//!     }
+//! }
+//!
+//! let mut timer = Timer { next_event: 0 };
+//! let mut dispatcher = SbiDispatcher::new().with_time(&mut timer);
+//! let mut regs = [0x1000, 0, 0, 0, 0, 0, 0, EXT_TIME];
+//! dispatcher.handle_call(&mut regs);
+//! assert_eq!(regs[0], 0);
+//!
+//! // Probing reports the extensions with a registered handler.
+//! let probe = |eid| SbiMessage::Base(BaseFunction::ProbeSbiExtension(eid));
+//! assert_eq!(dispatcher.dispatch(probe(EXT_TIME)), SbiReturn::success(1));
+//! assert_eq!(dispatcher.dispatch(probe(EXT_HART_STATE)), SbiReturn::success(0));
+//! assert_eq!(timer.next_event, 0x1000);
+//! ```
+
+use arrayvec::ArrayVec;
+use core::fmt;
+
+use crate::error::*;
+use crate::*;
+
+// Emits a handler method, or the call of one, as an `async fn`, or awaited, if given `[async]`.
+macro_rules! sbi_maybe_async {
+    ([] $(#[$attr:meta])* fn $($item:tt)*) => {
+        $(#[$attr])* fn $($item)*
+    };
+    ([async] $(#[$attr:meta])* fn $($item:tt)*) => {
+        $(#[$attr])* async fn $($item)*
+    };
+    ([] $call:expr) => {
+        $call
+    };
+    ([async] $call:expr) => {
+        $call.await
+    };
+}
+
+// Declares the methods of a handler trait: one per function, failing with `Error::NotSupported`
+// unless implemented, and `handle()` passing each call to the method for its function. The
+// methods of asynchronous handler traits are declared by passing `[async]` rather than `[]`.
+macro_rules! sbi_handler_methods {
+    ($mode:tt u64; fn $method:ident($arg:ident)) => {
+        sbi_maybe_async! { $mode
+            /// Handles the `console_putchar` function.
+            #[allow(unused_variables)]
+            fn $method(&mut self, $arg: u64) -> Result<u64> {
+                Err(Error::NotSupported)
+            }
+        }
+
+        sbi_maybe_async! { $mode
+            /// Handles the call, passing it to the method for its function.
+            fn handle(&mut self, func: u64) -> Result<u64> {
+                sbi_maybe_async!($mode self.$method(func))
+            }
+        }
+    };
+    ($mode:tt $enum:ident; $(
+        fn $method:ident$(($targ:ident: $tty:ty))?$({ $($field:ident: $fty:ty),* $(,)? })?
+            = $variant:ident;
+    )*) => {
+        $(
+            sbi_maybe_async! { $mode
+                #[doc = concat!("Handles the `", stringify!($method), "` function.")]
+                #[allow(unused_variables)]
+                fn $method(&mut self $(, $targ: $tty)? $($(, $field: $fty)*)?) -> Result<u64> {
+                    Err(Error::NotSupported)
+                }
+            }
+        )*
+
+        sbi_maybe_async! { $mode
+            /// Handles the call, passing it to the method for its function.
+            fn handle(&mut self, func: $enum) -> Result<u64> {
+                match func {
+                    $(
+                        $enum::$variant$(($targ))?$({ $($field),* })? => {
+                            sbi_maybe_async!($mode self.$method($($targ)? $($($field),*)?))
+                        }
+                    )*
+                }
+            }
+        }
+    };
+}
+
+// Declares the handler traits of each extension, along with the `SbiDispatcher` fields, builder
+// methods and dispatch arms that route calls to them.
+macro_rules! sbi_handlers {
+    ($(
+        #[doc = $doc:literal]
+        $(#[cfg($cfg:meta)])?
+        $trait:ident, $async_trait:ident($arg:ident) => $variant:ident($eid:ident), $field:ident,
+            $with:ident {
+            $($methods:tt)*
+        }
+    )*) => {
+        $(
+            #[doc = $doc]
+            $(#[cfg($cfg)])?
+            pub trait $trait {
+                sbi_handler_methods!([] $arg; $($methods)*);
+            }
+
+            #[doc = concat!("The asynchronous form of `", stringify!($trait), "`.")]
+            #[cfg(feature = "async")]
+            $(#[cfg($cfg)])?
+            #[allow(async_fn_in_trait)]
+            pub trait $async_trait {
+                sbi_handler_methods!([async] $arg; $($methods)*);
+            }
+        )*
+
+        /// Handles calls asynchronously for an `AsyncSbiDispatcher`, with a method per extension
+        /// that fails with `Error::NotSupported` unless implemented. Implementations typically
+        /// pass the calls to the extensions they support on to the extensions' asynchronous
+        /// handler traits.
+        #[cfg(feature = "async")]
+        #[allow(async_fn_in_trait)]
+        pub trait AsyncSbiHandler {
+            /// Returns if the extension with ID `eid` is implemented, as reported to callers
+            /// probing for it.
+            fn implements(&self, eid: u64) -> bool;
+
+            $(
+                #[doc = concat!("Handles ", stringify!($variant), " calls.")]
+                $(#[cfg($cfg)])?
+                #[allow(unused_variables)]
+                async fn $field(&mut self, func: $arg) -> Result<u64> {
+                    Err(Error::NotSupported)
+                }
+            )*
+
+            /// Handles calls to extensions this crate doesn't model.
+            #[cfg(feature = "unknown")]
+            #[allow(unused_variables)]
+            async fn unknown(&mut self, eid: u64, fid: u64, args: [u64; 6]) -> Result<u64> {
+                Err(Error::NotSupported)
+            }
+        }
+
+        #[cfg(feature = "async")]
+        impl<'a, H: AsyncSbiHandler> AsyncSbiDispatcher<'a, H> {
+            // Passes `msg` to the handler method for its extension.
+            async fn route(&mut self, msg: SbiMessage) -> Result<u64> {
+                match msg {
+                    $(
+                        $(#[cfg($cfg)])?
+                        SbiMessage::$variant(func) => self.handler.$field(func).await,
+                    )*
+                    #[cfg(feature = "unknown")]
+                    SbiMessage::Unknown { eid, fid, args } => {
+                        self.handler.unknown(eid, fid, args).await
+                    }
+                }
+            }
+        }
+
+        /// Routes decoded SBI calls to the handler registered for their extension.
+        #[derive(Default)]
+        pub struct SbiDispatcher<'a> {
+            $(
+                $(#[cfg($cfg)])?
+                $field: Option<&'a mut dyn $trait>,
+            )*
+            #[cfg(feature = "unknown")]
+            unknown: Option<&'a mut dyn UnknownHandler>,
+            hooks: DispatchHooks<'a>,
+        }
+
+        impl<'a> SbiDispatcher<'a> {
+            $(
+                #[doc = concat!("Registers the handler for ", stringify!($variant), " calls.")]
+                $(#[cfg($cfg)])?
+                pub fn $with(mut self, handler: &'a mut dyn $trait) -> Self {
+                    self.$field = Some(handler);
+                    self
+                }
+            )*
+
+            /// Registers the handler for calls to extensions this crate doesn't model.
+            #[cfg(feature = "unknown")]
+            pub fn with_unknown(mut self, handler: &'a mut dyn UnknownHandler) -> Self {
+                self.unknown = Some(handler);
+                self
+            }
+
+            /// Returns if a handler is registered for the extension with ID `eid` and the filter
+            /// doesn't deny every call to it, as reported to callers probing for the extension.
+            pub fn has_handler(&self, eid: u64) -> bool {
+                self.hooks.allows_extension(eid) && match eid {
+                    $(
+                        $(#[cfg($cfg)])?
+                        $eid => self.$field.is_some(),
+                    )*
+                    #[cfg(feature = "unknown")]
+                    _ => self.unknown.as_ref().is_some_and(|handler| handler.implements(eid)),
+                    #[cfg(not(feature = "unknown"))]
+                    _ => false,
+                }
+            }
+
+            // Passes `msg` to the handler registered for its extension.
+            fn route(&mut self, msg: SbiMessage) -> Result<u64> {
+                match msg {
+                    $(
+                        $(#[cfg($cfg)])?
+                        SbiMessage::$variant(func) => match self.$field.as_mut() {
+                            Some(handler) => handler.handle(func),
+                            None => Err(Error::NotSupported),
+                        },
+                    )*
+                    #[cfg(feature = "unknown")]
+                    SbiMessage::Unknown { eid, fid, args } => match self.unknown.as_mut() {
+                        Some(handler) => handler.handle(eid, fid, args),
+                        None => Err(Error::NotSupported),
+                    },
+                }
+            }
+        }
+    };
+}
+
+sbi_handlers! {
+    /// Handles calls to the Base extension.
+    BaseHandler, AsyncBaseHandler(BaseFunction) => Base(EXT_BASE), base, with_base {
+        fn get_spec_version = GetSpecificationVersion;
+        fn get_impl_id = GetImplementationID;
+        fn get_impl_version = GetImplementationVersion;
+        fn probe_extension(extension_id: u64) = ProbeSbiExtension;
+        fn get_mvendorid = GetMachineVendorID;
+        fn get_marchid = GetMachineArchitectureID;
+        fn get_mimpid = GetMachineImplementationID;
+    }
+    /// Handles calls to the legacy PutChar extension.
+    PutCharHandler, AsyncPutCharHandler(u64) => PutChar(EXT_PUT_CHAR), put_char, with_put_char {
+        fn console_putchar(ch)
+    }
+    /// Handles calls to the Timer extension.
+    TimeHandler, AsyncTimeHandler(TimeFunction) => Time(EXT_TIME), time, with_time {
+        fn set_timer { stime_value: u64 } = SetTimer;
+    }
+    /// Handles calls to the IPI extension.
+    IpiHandler, AsyncIpiHandler(IpiFunction) => Ipi(EXT_IPI), ipi, with_ipi {
+        fn send_ipi { hart_mask: HartMask } = SendIpi;
+    }
+    /// Handles calls to the RFENCE extension.
+    RfenceHandler, AsyncRfenceHandler(RfenceFunction) => Rfence(EXT_RFENCE), rfence, with_rfence {
+        fn remote_fence_i { hart_mask: HartMask } = RemoteFenceI;
+        fn remote_sfence_vma { hart_mask: HartMask, start_addr: u64, size: u64 } = RemoteSfenceVma;
+        fn remote_sfence_vma_asid {
+            hart_mask: HartMask,
+            start_addr: u64,
+            size: u64,
+            asid: u64,
+        } = RemoteSfenceVmaAsid;
+        fn remote_hfence_gvma_vmid {
+            hart_mask: HartMask,
+            start_addr: u64,
+            size: u64,
+            vmid: u64,
+        } = RemoteHfenceGvmaVmid;
+        fn remote_hfence_gvma {
+            hart_mask: HartMask,
+            start_addr: u64,
+            size: u64,
+        } = RemoteHfenceGvma;
+        fn remote_hfence_vvma_asid {
+            hart_mask: HartMask,
+            start_addr: u64,
+            size: u64,
+            asid: u64,
+        } = RemoteHfenceVvmaAsid;
+        fn remote_hfence_vvma {
+            hart_mask: HartMask,
+            start_addr: u64,
+            size: u64,
+        } = RemoteHfenceVvma;
+    }
+    /// Handles calls to the Hart State Management extension.
+    HsmHandler, AsyncHsmHandler(StateFunction) => HartState(EXT_HART_STATE), hsm, with_hsm {
+        fn hart_start { hart_id: u64, start_addr: u64, opaque: u64 } = HartStart;
+        fn hart_stop = HartStop;
+        fn hart_get_status { hart_id: u64 } = HartStatus;
+        fn hart_suspend {
+            suspend_type: SuspendType,
+            resume_addr: u64,
+            opaque: u64,
+        } = HartSuspend;
+    }
+    /// Handles calls to the System Reset extension.
+    ResetHandler, AsyncResetHandler(ResetFunction) => Reset(EXT_RESET), reset, with_reset {
+        fn system_reset { reset_type: ResetType, reason: ResetReason } = Reset;
+    }
+    /// Handles calls to the Performance Monitoring Unit extension.
+    #[cfg(feature = "pmu")]
+    PmuHandler, AsyncPmuHandler(PmuFunction) => Pmu(EXT_PMU), pmu, with_pmu {
+        fn num_counters = GetNumCounters;
+        fn counter_get_info(counter_index: u64) = GetCounterInfo;
+        fn counter_config_matching {
+            counter_index: u64,
+            counter_mask: u64,
+            config_flags: PmuCounterConfigFlags,
+            event_type: PmuEventType,
+            event_data: u64,
+        } = ConfigureMatchingCounters;
+        fn counter_start {
+            counter_index: u64,
+            counter_mask: u64,
+            start_flags: PmuCounterStartFlags,
+            initial_value: u64,
+        } = StartCounters;
+        fn counter_stop {
+            counter_index: u64,
+            counter_mask: u64,
+            stop_flags: PmuCounterStopFlags,
+        } = StopCounters;
+        fn counter_fw_read(counter_index: u64) = ReadFirmwareCounter;
+        fn counter_fw_read_hi(counter_index: u64) = ReadFirmwareCounterHigh;
+    }
+    /// Handles calls to the Debug Console extension.
+    DebugConsoleHandler, AsyncDebugConsoleHandler(DebugConsoleFunction) => DebugConsole(EXT_DBCN), debug_console, with_debug_console {
+        fn console_write { len: Len, addr: PhysAddr } = PutString;
+    }
+    /// Handles calls to the Nested Virtualization Acceleration extension.
+    #[cfg(feature = "nacl")]
+    NaclHandler, AsyncNaclHandler(NaclFunction) => Nacl(EXT_NACL), nacl, with_nacl {
+        fn set_shmem { shmem_pfn: u64 } = SetShmem;
+        fn sync_csr { csr_num: u64 } = SyncCsr;
+    }
+    /// Handles calls to the TEE Host extension.
+    #[cfg(feature = "tee")]
+    TeeHostHandler, AsyncTeeHostHandler(TeeHostFunction) => TeeHost(EXT_TEE_HOST), tee_host, with_tee_host {
+        fn tsm_get_info { dest_addr: PhysAddr, len: Len } = TsmGetInfo;
+        fn tsm_convert_pages { page_addr: PhysAddr, num_pages: u64 } = TsmConvertPages;
+        fn tsm_reclaim_pages { page_addr: PhysAddr, num_pages: u64 } = TsmReclaimPages;
+        fn tsm_initiate_fence = TsmInitiateFence;
+        fn tsm_local_fence = TsmLocalFence;
+        fn tvm_create { params_addr: PhysAddr, len: Len } = TvmCreate;
+        fn tvm_finalize { guest_id: u64, entry_sepc: u64, entry_arg: u64 } = Finalize;
+        fn tvm_destroy { guest_id: u64 } = TvmDestroy;
+        fn tvm_add_memory_region {
+            guest_id: u64,
+            guest_addr: PhysAddr,
+            len: Len,
+        } = TvmAddMemoryRegion;
+        fn tvm_add_page_table_pages {
+            guest_id: u64,
+            page_addr: PhysAddr,
+            num_pages: u64,
+        } = AddPageTablePages;
+        fn tvm_add_measured_pages {
+            guest_id: u64,
+            src_addr: PhysAddr,
+            dest_addr: PhysAddr,
+            page_type: TsmPageType,
+            num_pages: u64,
+            guest_addr: PhysAddr,
+        } = TvmAddMeasuredPages;
+        fn tvm_add_zero_pages {
+            guest_id: u64,
+            page_addr: PhysAddr,
+            page_type: TsmPageType,
+            num_pages: u64,
+            guest_addr: PhysAddr,
+        } = TvmAddZeroPages;
+        fn tvm_add_shared_pages {
+            guest_id: u64,
+            page_addr: PhysAddr,
+            page_type: TsmPageType,
+            num_pages: u64,
+            guest_addr: PhysAddr,
+        } = TvmAddSharedPages;
+        fn tvm_add_emulated_mmio_region {
+            guest_id: u64,
+            guest_addr: PhysAddr,
+            len: Len,
+        } = TvmAddEmulatedMmioRegion;
+        fn tvm_remove_shared_pages {
+            guest_id: u64,
+            guest_addr: PhysAddr,
+            page_type: TsmPageType,
+            num_pages: u64,
+        } = TvmRemoveSharedPages;
+        fn tvm_cpu_create { guest_id: u64, vcpu_id: u64, state_page_addr: PhysAddr } = TvmCpuCreate;
+        fn tvm_cpu_run { guest_id: u64, vcpu_id: u64 } = TvmCpuRun;
+        fn tvm_initiate_fence { guest_id: u64 } = TvmInitiateFence;
+    }
+    /// Handles calls to the TEE Interrupt extension.
+    #[cfg(feature = "tee")]
+    TeeInterruptHandler, AsyncTeeInterruptHandler(TeeInterruptFunction) => TeeInterrupt(EXT_TEE_INTERRUPT), tee_interrupt, with_tee_interrupt {
+        fn tvm_aia_init { tvm_id: u64, params_addr: PhysAddr, len: Len } = TvmAiaInit;
+        fn tvm_cpu_set_imsic_addr {
+            tvm_id: u64,
+            vcpu_id: u64,
+            imsic_addr: PhysAddr,
+        } = TvmCpuSetImsicAddr;
+        fn tsm_convert_imsic { imsic_addr: PhysAddr } = TsmConvertImsic;
+        fn tsm_reclaim_imsic { imsic_addr: PhysAddr } = TsmReclaimImsic;
+        fn tvm_cpu_bind_imsic {
+            tvm_id: u64,
+            vcpu_id: u64,
+            imsic_mask: ImsicFileMask,
+        } = TvmCpuBindImsic;
+        fn tvm_cpu_unbind_imsic_begin { tvm_id: u64, vcpu_id: u64 } = TvmCpuUnbindImsicBegin;
+        fn tvm_cpu_unbind_imsic_end { tvm_id: u64, vcpu_id: u64 } = TvmCpuUnbindImsicEnd;
+        fn tvm_cpu_inject_external_interrupt {
+            tvm_id: u64,
+            vcpu_id: u64,
+            interrupt_id: u64,
+        } = TvmCpuInjectExternalInterrupt;
+        fn tvm_cpu_rebind_imsic_begin {
+            tvm_id: u64,
+            vcpu_id: u64,
+            imsic_mask: ImsicFileMask,
+        } = TvmCpuRebindImsicBegin;
+        fn tvm_cpu_rebind_imsic_clone { tvm_id: u64, vcpu_id: u64 } = TvmCpuRebindImsicClone;
+        fn tvm_cpu_rebind_imsic_end { tvm_id: u64, vcpu_id: u64 } = TvmCpuRebindImsicEnd;
+    }
+    /// Handles calls to the TEE Guest extension.
+    #[cfg(feature = "tee")]
+    TeeGuestHandler, AsyncTeeGuestHandler(TeeGuestFunction) => TeeGuest(EXT_TEE_GUEST), tee_guest, with_tee_guest {
+        fn add_mmio_region { addr: PhysAddr, len: Len } = AddMmioRegion;
+        fn remove_mmio_region { addr: PhysAddr, len: Len } = RemoveMmioRegion;
+        fn share_memory { addr: PhysAddr, len: Len } = ShareMemory;
+        fn unshare_memory { addr: PhysAddr, len: Len } = UnshareMemory;
+        fn allow_external_interrupt { id: i64 } = AllowExternalInterrupt;
+        fn deny_external_interrupt { id: i64 } = DenyExternalInterrupt;
+    }
+    /// Handles calls to the Attestation extension.
+    #[cfg(feature = "attestation")]
+    AttestationHandler, AsyncAttestationHandler(AttestationFunction) => Attestation(EXT_ATTESTATION), attestation, with_attestation {
+        fn get_capabilities { caps_addr_out: u64, caps_size: u64 } = GetCapabilities;
+        fn get_evidence {
+            cert_request_addr: u64,
+            cert_request_size: u64,
+            request_data_addr: u64,
+            evidence_format: u64,
+            cert_addr_out: u64,
+            cert_size: u64,
+        } = GetEvidence;
+        fn extend_measurement {
+            measurement_data_addr: u64,
+            measurement_data_size: u64,
+            measurement_index: u64,
+        } = ExtendMeasurement;
+        fn read_measurement {
+            measurement_data_addr_out: u64,
+            measurement_data_size: u64,
+            measurement_index: u64,
+        } = ReadMeasurement;
+    }
+}
+
+impl<'a> SbiDispatcher<'a> {
+    /// Creates a dispatcher with no handlers registered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the filter checking calls before they're handled.
+    pub fn with_filter(mut self, filter: &'a mut dyn SbiFilter) -> Self {
+        self.hooks.filter = Some(filter);
+        self
+    }
+
+    /// Sets the observer notified of the calls handled and of the dispatcher's decisions.
+    pub fn with_observer(mut self, observer: &'a mut dyn DispatchObserver) -> Self {
+        self.hooks.observer = Some(observer);
+        self
+    }
+
+    /// Sets the metrics recording every call handled.
+    pub fn with_metrics(mut self, metrics: &'a mut dyn DispatchMetrics) -> Self {
+        self.hooks.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets the time source used to measure how long calls take to handle, for the metrics.
+    pub fn with_time_source(mut self, now: TimeSource) -> Self {
+        self.hooks.now = Some(now);
+        self
+    }
+
+    /// Handles the decoded call `msg`. Calls rejected by the filter fail with `Error::Denied`,
+    /// and the reason is passed to the observer; others are handled as rewritten by the filter.
+    /// Calls that are no-ops, as described for `SbiMessage::is_noop()`, succeed without being
+    /// passed to a handler.
+    ///
+    /// Calls to the Base extension's `probe_extension` function are answered by the dispatcher
+    /// with `has_handler()`, reporting the extensions that have a handler registered and that the
+    /// filter allows, so that the extensions reported to the caller are always those it can use.
+    pub fn dispatch(&mut self, msg: SbiMessage) -> SbiReturn {
+        let start = self.hooks.enter(&msg);
+        let ret = match self.hooks.prepare(msg) {
+            Ok(SbiMessage::Base(BaseFunction::ProbeSbiExtension(eid))) => {
+                SbiReturn::success(self.has_handler(eid) as u64)
+            }
+            Ok(msg) => self.route(msg).into(),
+            Err(ret) => ret,
+        };
+        self.hooks.exit(&msg, &ret, start);
+        ret
+    }
+
+    /// Decodes the call in the caller's registers `regs`, handles it and writes the result back
+    /// to `regs` as `SbiMessage::write_result()` does. Calls that fail to decode are failed with
+    /// the decoding error.
+    pub fn handle_call<R: GprAccess + ?Sized>(&mut self, regs: &mut R) {
+        match SbiMessage::from_gprs(regs) {
+            Ok(msg) => {
+                let ret = self.dispatch(msg);
+                msg.write_result(ret, regs);
+            }
+            Err(e) => {
+                regs.set_gpr(GprIndex::A0, e.code() as u64);
+                regs.set_gpr(GprIndex::A1, 0);
+            }
+        }
+    }
+}
+
+/// Handles calls to extensions this crate doesn't model, decoded as `SbiMessage::Unknown`, for
+/// example to log them or forward them to the SBI implementation below.
+#[cfg(feature = "unknown")]
+pub trait UnknownHandler {
+    /// Returns if the extension with ID `eid` is implemented, as reported to callers probing for
+    /// it. Defaults to reporting no extension.
+    #[allow(unused_variables)]
+    fn implements(&self, eid: u64) -> bool {
+        false
+    }
+
+    /// Handles a call to function `fid` of extension `eid`, passed `args` in a0-a5.
+    fn handle(&mut self, eid: u64, fid: u64, args: [u64; 6]) -> Result<u64>;
+}
+
+// The filter, observer and metrics of a dispatcher.
+#[derive(Default)]
+struct DispatchHooks<'a> {
+    filter: Option<&'a mut dyn SbiFilter>,
+    observer: Option<&'a mut dyn DispatchObserver>,
+    metrics: Option<&'a mut dyn DispatchMetrics>,
+    now: Option<TimeSource>,
+}
+
+impl DispatchHooks<'_> {
+    // Notifies the observer of `msg`, returning the time at which handling it started.
+    fn enter(&mut self, msg: &SbiMessage) -> Option<u64> {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.entered(msg);
+        }
+        self.now.map(|now| now())
+    }
+
+    // Returns if the filter may allow calls to the extension with ID `eid`.
+    fn allows_extension(&self, eid: u64) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.allows_extension(eid))
+    }
+
+    // Applies the filter to `msg`, returning the call to pass on to a handler or the result of
+    // calls completed without one.
+    fn prepare(&mut self, mut msg: SbiMessage) -> core::result::Result<SbiMessage, SbiReturn> {
+        if let Some(filter) = self.filter.as_mut() {
+            if let Err(reason) = filter.check(&msg) {
+                if let Some(observer) = self.observer.as_mut() {
+                    observer.denied(&msg, reason);
+                }
+                return Err(Error::Denied.into());
+            }
+            msg = filter.rewrite(msg);
+        }
+        if msg.is_noop() {
+            return Err(SbiReturn::success(0));
+        }
+        Ok(msg)
+    }
+
+    // Records the result `ret` of `msg`, handling of which started at `start`.
+    fn exit(&mut self, msg: &SbiMessage, ret: &SbiReturn, start: Option<u64>) {
+        if let Some(metrics) = self.metrics.as_mut() {
+            let ticks = self
+                .now
+                .zip(start)
+                .map(|(now, start)| now().wrapping_sub(start));
+            metrics.record(msg.extension_id(), msg.function_id(), ret, ticks);
+        }
+        if let Some(observer) = self.observer.as_mut() {
+            observer.exited(msg, ret);
+        }
+    }
+}
+
+/// The asynchronous form of `SbiDispatcher`, for VMMs running vCPUs as tasks that may await
+/// long-running emulations such as console reads. Calls are handled by a single
+/// `AsyncSbiHandler` and go through the same filter, observer and metrics as with
+/// `SbiDispatcher`.
+///
+/// ```rust
+/// use core::future::Future;
+/// use core::pin::pin;
+/// use core::task::{Context, Poll, Waker};
+/// use sbi_rs::handler::{AsyncSbiDispatcher, AsyncSbiHandler, AsyncTimeHandler};
+/// use sbi_rs::*;
+///
+/// struct Vcpu {
+///     next_event: u64,
+/// }
+///
+/// impl AsyncTimeHandler for Vcpu {
+///     async fn set_timer(&mut self, stime_value: u64) -> Result<u64> {
+///         self.next_event = stime_value;
+///         Ok(0)
+///     }
+/// }
+///
+/// impl AsyncSbiHandler for Vcpu {
+///     fn implements(&self, eid: u64) -> bool {
+///         eid == EXT_TIME
+///     }
+///
+///     async fn time(&mut self, func: TimeFunction) -> Result<u64> {
+///         AsyncTimeHandler::handle(self, func).await
+///     }
+/// }
+///
+/// async fn run(vcpu: &mut Vcpu, msg: SbiMessage) -> SbiReturn {
+///     AsyncSbiDispatcher::new(vcpu).dispatch(msg).await
+/// }
+///
+/// let mut vcpu = Vcpu { next_event: 0 };
+/// let msg = SbiMessage::Time(TimeFunction::SetTimer { stime_value: 0x1000 });
+/// let mut cx = Context::from_waker(Waker::noop());
+/// let ret = pin!(run(&mut vcpu, msg)).poll(&mut cx);
+/// assert_eq!(ret, Poll::Ready(SbiReturn::success(0)));
+/// assert_eq!(vcpu.next_event, 0x1000);
+/// ```
+#[cfg(feature = "async")]
+pub struct AsyncSbiDispatcher<'a, H> {
+    handler: &'a mut H,
+    hooks: DispatchHooks<'a>,
+}
+
+#[cfg(feature = "async")]
+impl<'a, H: AsyncSbiHandler> AsyncSbiDispatcher<'a, H> {
+    /// Creates a dispatcher passing calls to `handler`.
+    pub fn new(handler: &'a mut H) -> Self {
+        Self {
+            handler,
+            hooks: DispatchHooks::default(),
+        }
+    }
+
+    /// Sets the filter checking calls before they're handled.
+    pub fn with_filter(mut self, filter: &'a mut dyn SbiFilter) -> Self {
+        self.hooks.filter = Some(filter);
+        self
+    }
+
+    /// Sets the observer notified of the calls handled and of the dispatcher's decisions.
+    pub fn with_observer(mut self, observer: &'a mut dyn DispatchObserver) -> Self {
+        self.hooks.observer = Some(observer);
+        self
+    }
+
+    /// Sets the metrics recording every call handled.
+    pub fn with_metrics(mut self, metrics: &'a mut dyn DispatchMetrics) -> Self {
+        self.hooks.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets the time source used to measure how long calls take to handle, for the metrics.
+    pub fn with_time_source(mut self, now: TimeSource) -> Self {
+        self.hooks.now = Some(now);
+        self
+    }
+
+    /// Handles the decoded call `msg` as `SbiDispatcher::dispatch()` does, answering probes from
+    /// `AsyncSbiHandler::implements()`.
+    pub async fn dispatch(&mut self, msg: SbiMessage) -> SbiReturn {
+        let start = self.hooks.enter(&msg);
+        let ret = match self.hooks.prepare(msg) {
+            Ok(SbiMessage::Base(BaseFunction::ProbeSbiExtension(eid))) => {
+                let available = self.hooks.allows_extension(eid) && self.handler.implements(eid);
+                SbiReturn::success(available as u64)
+            }
+            Ok(msg) => self.route(msg).await.into(),
+            Err(ret) => ret,
+        };
+        self.hooks.exit(&msg, &ret, start);
+        ret
+    }
+
+    /// Decodes the call in the caller's registers `regs`, handles it and writes the result back
+    /// to `regs`, as `SbiDispatcher::handle_call()` does.
+    pub async fn handle_call<R: GprAccess + ?Sized>(&mut self, regs: &mut R) {
+        match SbiMessage::from_gprs(regs) {
+            Ok(msg) => {
+                let ret = self.dispatch(msg).await;
+                msg.write_result(ret, regs);
+            }
+            Err(e) => {
+                regs.set_gpr(GprIndex::A0, e.code() as u64);
+                regs.set_gpr(GprIndex::A1, 0);
+            }
+        }
+    }
+}
+
+/// Passes the decoded call `msg` on to the SBI implementation below, for hypervisors that handle
+/// calls such as `set_timer` by proxying them to the firmware. The call is re-encoded from `msg`,
+/// so only the arguments of the function are passed on.
+///
+/// # Safety
+///
+/// See `ecall_send`. Addresses in `msg` are passed on as is, so calls such as console writes must
+/// have their guest addresses translated by the caller before being forwarded.
+#[cfg(all(feature = "caller", target_arch = "riscv64", target_os = "none"))]
+pub unsafe fn forward(msg: &SbiMessage) -> Result<u64> {
+    ecall_send(msg)
+}
+
+/// Identifies the rule of an `SbiFilter` that rejected a call, as reported to the
+/// `DispatchObserver` so that failing calls can be explained from logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DenialReason {
+    /// The ID of the rejecting rule, chosen by the filter.
+    pub rule_id: u32,
+}
+
+impl DenialReason {
+    /// Creates a reason identifying the rule with ID `rule_id`.
+    pub const fn new(rule_id: u32) -> Self {
+        Self { rule_id }
+    }
+}
+
+impl fmt::Display for DenialReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "denied by rule {}", self.rule_id)
+    }
+}
+
+/// Decides which calls an `SbiDispatcher` passes on to its handlers.
+///
+/// ```rust
+/// use sbi_rs::handler::{DenialReason, DispatchObserver, SbiDispatcher};
+/// use sbi_rs::*;
+///
+/// const NO_RESET: DenialReason = DenialReason::new(7);
+///
+/// // Keeps the guest from resetting the system.
+/// let mut filter = |msg: &SbiMessage| match msg {
+///     SbiMessage::Reset(_) => Err(NO_RESET),
+///     _ => Ok(()),
+/// };
+///
+/// #[derive(Default)]
+/// struct Log(Option<DenialReason>);
+///
+/// impl DispatchObserver for Log {
+///     fn denied(&mut self, _msg: &SbiMessage, reason: DenialReason) {
+///         self.0 = Some(reason);
+///     }
+/// }
+///
+/// let mut log = Log::default();
+/// let mut dispatcher = SbiDispatcher::new()
+///     .with_filter(&mut filter)
+///     .with_observer(&mut log);
+/// let msg = SbiMessage::Reset(ResetFunction::shutdown());
+/// assert_eq!(dispatcher.dispatch(msg), Error::Denied.into());
+/// assert_eq!(log.0, Some(NO_RESET));
+/// ```
+pub trait SbiFilter {
+    /// Checks if `msg` may be handled, returning the reason it may not otherwise.
+    fn check(&mut self, msg: &SbiMessage) -> core::result::Result<(), DenialReason>;
+
+    /// Returns the call to handle in place of `msg`, which passed `check()`. The result is
+    /// written back to the caller as the result of `msg`. Defaults to handling `msg` as is.
+    fn rewrite(&mut self, msg: SbiMessage) -> SbiMessage {
+        msg
+    }
+
+    /// Returns if some calls to the extension with ID `eid` may pass `check()`. Extensions for
+    /// which it returns false probe as unavailable. Defaults to true, as calls made to closures
+    /// can't be predicted.
+    #[allow(unused_variables)]
+    fn allows_extension(&self, eid: u64) -> bool {
+        true
+    }
+}
+
+impl<F> SbiFilter for F
+where
+    F: FnMut(&SbiMessage) -> core::result::Result<(), DenialReason>,
+{
+    fn check(&mut self, msg: &SbiMessage) -> core::result::Result<(), DenialReason> {
+        self(msg)
+    }
+}
+
+/// What an `SbiPolicy` does with the calls matching one of its rules.
+#[derive(Clone, Copy, Debug)]
+pub enum PolicyAction {
+    /// Handle the call.
+    Allow,
+    /// Fail the call with `Error::Denied`, for the given reason.
+    Deny(DenialReason),
+    /// Handle the call returned by the function in place of the call.
+    Rewrite(fn(SbiMessage) -> SbiMessage),
+}
+
+/// A rule of an `SbiPolicy`, applying to the calls to an extension or to one of its functions.
+#[derive(Clone, Copy, Debug)]
+pub struct PolicyRule {
+    eid: u64,
+    fid: Option<u64>,
+    action: PolicyAction,
+}
+
+impl PolicyRule {
+    /// Creates a rule applying `action` to every call to the extension with ID `eid`.
+    pub const fn extension(eid: u64, action: PolicyAction) -> Self {
+        Self {
+            eid,
+            fid: None,
+            action,
+        }
+    }
+
+    /// Creates a rule applying `action` to calls to the function `fid` of the extension with ID
+    /// `eid`.
+    pub const fn function(eid: u64, fid: u64, action: PolicyAction) -> Self {
+        Self {
+            eid,
+            fid: Some(fid),
+            action,
+        }
+    }
+
+    /// Returns if the rule applies to `msg`.
+    pub fn matches(&self, msg: &SbiMessage) -> bool {
+        msg.extension_id() == self.eid && self.fid.is_none_or(|fid| msg.function_id() == fid)
+    }
+}
+
+/// A configurable `SbiFilter` allowing, denying or rewriting calls per extension or function, for
+/// hypervisors restricting what untrusted guests may do. Calls are acted on by the first of up to
+/// `N` rules that matches them, in the order the rules were added, or by the default action if
+/// none does.
+///
+/// ```rust
+/// use sbi_rs::handler::*;
+/// use sbi_rs::*;
+///
+/// let mut policy = SbiPolicy::<4>::new();
+/// policy
+///     .push(PolicyRule::extension(EXT_RESET, PolicyAction::Deny(DenialReason::new(1))))
+///     .unwrap();
+/// let mut dispatcher = SbiDispatcher::new().with_filter(&mut policy);
+/// let msg = SbiMessage::Reset(ResetFunction::shutdown());
+/// assert_eq!(dispatcher.dispatch(msg), Error::Denied.into());
+///
+/// // The denied extension probes as unavailable, even with a handler registered for it.
+/// struct Reset;
+///
+/// impl ResetHandler for Reset {
+///     fn system_reset(&mut self, _: ResetType, _: ResetReason) -> Result<u64> {
+///         Ok(0)
+///     }
+/// }
+///
+/// let mut reset = Reset;
+/// let mut dispatcher = SbiDispatcher::new()
+///     .with_reset(&mut reset)
+///     .with_filter(&mut policy);
+/// let probe = SbiMessage::Base(BaseFunction::ProbeSbiExtension(EXT_RESET));
+/// assert_eq!(dispatcher.dispatch(probe), SbiReturn::success(0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SbiPolicy<const N: usize> {
+    rules: ArrayVec<PolicyRule, N>,
+    default: PolicyAction,
+}
+
+impl<const N: usize> SbiPolicy<N> {
+    /// Creates a policy with no rules, allowing every call.
+    pub const fn new() -> Self {
+        Self {
+            rules: ArrayVec::new_const(),
+            default: PolicyAction::Allow,
+        }
+    }
+
+    /// Sets the action for calls matching none of the rules.
+    pub fn set_default(&mut self, action: PolicyAction) {
+        self.default = action;
+    }
+
+    /// Adds `rule` after the existing rules. Fails with `Error::InsufficientBufferCapacity` if the
+    /// policy already has `N` rules.
+    pub fn push(&mut self, rule: PolicyRule) -> Result<()> {
+        self.rules
+            .try_push(rule)
+            .map_err(|_| Error::InsufficientBufferCapacity)
+    }
+
+    /// Returns the action applied to `msg`.
+    pub fn action(&self, msg: &SbiMessage) -> PolicyAction {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(msg))
+            .map_or(self.default, |rule| rule.action)
+    }
+}
+
+impl<const N: usize> Default for SbiPolicy<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An extension is reported as allowed unless the first rule for the whole extension, or the
+/// default action if there is none, denies it and no earlier rule for one of its functions lets
+/// calls to that function through.
+impl<const N: usize> SbiFilter for SbiPolicy<N> {
+    fn allows_extension(&self, eid: u64) -> bool {
+        for rule in self.rules.iter().filter(|rule| rule.eid == eid) {
+            let denies = matches!(rule.action, PolicyAction::Deny(_));
+            if rule.fid.is_none() || !denies {
+                return !denies;
+            }
+        }
+        !matches!(self.default, PolicyAction::Deny(_))
+    }
+
+    fn check(&mut self, msg: &SbiMessage) -> core::result::Result<(), DenialReason> {
+        match self.action(msg) {
+            PolicyAction::Deny(reason) => Err(reason),
+            _ => Ok(()),
+        }
+    }
+
+    fn rewrite(&mut self, msg: SbiMessage) -> SbiMessage {
+        match self.action(&msg) {
+            PolicyAction::Rewrite(f) => f(msg),
+            _ => msg,
+        }
+    }
+}
+
+/// Records the calls handled by an `SbiDispatcher`.
+pub trait DispatchMetrics {
+    /// Records a call to function `fid` of the extension with ID `eid` that returned `ret`,
+    /// taking `ticks` to handle if the dispatcher has a time source.
+    fn record(&mut self, eid: u64, fid: u64, ret: &SbiReturn, ticks: Option<u64>);
+}
+
+/// The calls made to a function, as counted by `CallCounters`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CallStats {
+    /// The extension ID of the function.
+    pub eid: u64,
+    /// The function ID of the function.
+    pub fid: u64,
+    /// The number of calls.
+    pub calls: u64,
+    /// The number of calls that failed.
+    pub errors: u64,
+    /// The total time taken by the timed calls, in ticks of the dispatcher's time source.
+    pub total_ticks: u64,
+    /// The longest time taken by a call, in ticks of the dispatcher's time source.
+    pub max_ticks: u64,
+}
+
+/// `DispatchMetrics` counting the calls, errors and time taken per function, for up to `N`
+/// functions. Calls to further functions are only counted by `dropped()`.
+///
+/// ```rust
+/// use sbi_rs::handler::{CallCounters, SbiDispatcher};
+/// use sbi_rs::*;
+///
+/// let mut counters = CallCounters::<8>::new();
+/// let mut dispatcher = SbiDispatcher::new().with_metrics(&mut counters);
+/// let msg = SbiMessage::Time(TimeFunction::SetTimer { stime_value: 0 });
+/// dispatcher.dispatch(msg);
+/// dispatcher.dispatch(msg);
+/// let stats = counters.stats(EXT_TIME, 0).unwrap();
+/// assert_eq!((stats.calls, stats.errors), (2, 2));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CallCounters<const N: usize> {
+    stats: ArrayVec<CallStats, N>,
+    dropped: u64,
+}
+
+impl<const N: usize> CallCounters<N> {
+    /// Creates a table with no calls counted.
+    pub const fn new() -> Self {
+        Self {
+            stats: ArrayVec::new_const(),
+            dropped: 0,
+        }
+    }
+
+    /// Returns the calls made to function `fid` of the extension with ID `eid`, if any were
+    /// counted.
+    pub fn stats(&self, eid: u64, fid: u64) -> Option<&CallStats> {
+        self.stats.iter().find(|s| s.eid == eid && s.fid == fid)
+    }
+
+    /// Returns an iterator over the functions called, in the order of their first call.
+    pub fn iter(&self) -> impl Iterator<Item = &CallStats> {
+        self.stats.iter()
+    }
+
+    /// Returns the number of calls not counted because the table was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Clears every count.
+    pub fn reset(&mut self) {
+        self.stats.clear();
+        self.dropped = 0;
+    }
+}
+
+impl<const N: usize> DispatchMetrics for CallCounters<N> {
+    fn record(&mut self, eid: u64, fid: u64, ret: &SbiReturn, ticks: Option<u64>) {
+        let index = match self.stats.iter().position(|s| s.eid == eid && s.fid == fid) {
+            Some(index) => index,
+            None => {
+                let stats = CallStats {
+                    eid,
+                    fid,
+                    ..Default::default()
+                };
+                if self.stats.try_push(stats).is_err() {
+                    self.dropped += 1;
+                    return;
+                }
+                self.stats.len() - 1
+            }
+        };
+        let stats = &mut self.stats[index];
+        stats.calls += 1;
+        if ret.error_code != SBI_SUCCESS {
+            stats.errors += 1;
+        }
+        if let Some(ticks) = ticks {
+            stats.total_ticks = stats.total_ticks.wrapping_add(ticks);
+            stats.max_ticks = stats.max_ticks.max(ticks);
+        }
+    }
+}
+
+/// Notified by an `SbiDispatcher` of the calls it handles and of its decisions, for logging and
+/// tracing. Every method does nothing unless implemented.
+///
+/// ```rust
+/// use sbi_rs::handler::{DispatchObserver, SbiDispatcher};
+/// use sbi_rs::*;
+///
+/// #[derive(Default)]
+/// struct Log {
+///     calls: usize,
+///     last: Option<SbiReturn>,
+/// }
+///
+/// impl DispatchObserver for Log {
+///     fn entered(&mut self, _msg: &SbiMessage) {
+///         self.calls += 1;
+///     }
+///
+///     fn exited(&mut self, _msg: &SbiMessage, ret: &SbiReturn) {
+///         self.last = Some(*ret);
+///     }
+/// }
+///
+/// let mut log = Log::default();
+/// let mut dispatcher = SbiDispatcher::new().with_observer(&mut log);
+/// dispatcher.dispatch(SbiMessage::Base(BaseFunction::GetSpecificationVersion));
+/// assert_eq!(log.calls, 1);
+/// assert_eq!(log.last, Some(Error::NotSupported.into()));
+/// ```
+pub trait DispatchObserver {
+    /// Called with each call `msg` before it's handled.
+    #[allow(unused_variables)]
+    fn entered(&mut self, msg: &SbiMessage) {}
+
+    /// Called when the filter rejects `msg` for `reason`.
+    #[allow(unused_variables)]
+    fn denied(&mut self, msg: &SbiMessage, reason: DenialReason) {}
+
+    /// Called with each call `msg`, as passed to `entered()`, and its result once it's handled.
+    #[allow(unused_variables)]
+    fn exited(&mut self, msg: &SbiMessage, ret: &SbiReturn) {}
+}
+
+/// Identifies a long-running operation split across several calls: the hart making the calls
+/// and the function being called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ContinuationKey {
+    /// The ID of the hart making the call.
+    pub hart_id: u64,
+    /// The extension ID of the call.
+    pub eid: u64,
+    /// The function ID of the call.
+    pub fid: u64,
+}
+
+impl ContinuationKey {
+    /// Creates the key for the call `msg` made by the hart with ID `hart_id`.
+    pub fn new(hart_id: u64, msg: &SbiMessage) -> Self {
+        Self {
+            hart_id,
+            eid: msg.extension_id(),
+            fid: msg.function_id(),
+        }
+    }
+}
+
+/// The outcome of a step of a long-running operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// The operation is complete, with the result to pass back to the caller.
+    Done(Result<u64>),
+    /// The operation is incomplete. The caller is asked to retry the call, and the state passed
+    /// along is handed back to the handler when it does.
+    Retry(u64),
+}
+
+/// The error passed back to callers asked to retry an incomplete operation, `SBI_ERR_TIMEOUT`
+/// (-12) on the wire.
+pub const RETRY_ERROR: Error = Error::Timeout;
+
+/// Tracks the state of long-running operations, such as converting a large range of pages, that
+/// a handler completes over several calls rather than stalling the caller for the whole
+/// operation. Up to `N` operations can be in progress at once.
+///
+/// The caller sees an incomplete operation fail with `RETRY_ERROR` and repeats the call with the
+/// same arguments until it completes. As the arguments aren't recorded, handlers resuming an
+/// operation should check that its state matches the arguments of the repeated call.
+///
+/// ```rust
+/// use sbi_rs::handler::{ContinuationKey, ContinuationTable, Progress, RETRY_ERROR};
+/// use sbi_rs::*;
+///
+/// let msg = SbiMessage::from_regs(&[0, 0, 0, 0, 0, 0, 0, EXT_BASE]).unwrap();
+/// let key = ContinuationKey::new(0, &msg);
+/// let mut table = ContinuationTable::<4>::new();
+/// // Handle two pages per call out of five.
+/// let mut step = |done: Option<u64>| {
+///     let done = done.unwrap_or(0) + 2;
+///     if done < 5 {
+///         Progress::Retry(done)
+///     } else {
+///         Progress::Done(Ok(5))
+///     }
+/// };
+/// assert_eq!(table.run(key, &mut step), Err(RETRY_ERROR));
+/// assert_eq!(table.run(key, &mut step), Err(RETRY_ERROR));
+/// assert_eq!(table.run(key, &mut step), Ok(5));
+/// assert!(!table.in_progress(key));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ContinuationTable<const N: usize> {
+    entries: ArrayVec<(ContinuationKey, u64), N>,
+}
+
+impl<const N: usize> ContinuationTable<N> {
+    /// Creates a table with no operations in progress.
+    pub const fn new() -> Self {
+        Self {
+            entries: ArrayVec::new_const(),
+        }
+    }
+
+    /// Runs a step of the operation identified by `key`. `step` is passed the state recorded
+    /// when the operation was last retried, or `None` if it's starting. Returns the result of
+    /// the operation once done, or `RETRY_ERROR` if it must be retried. Fails with
+    /// `Error::Failed` if the operation must be retried but the table is full.
+    pub fn run<F>(&mut self, key: ContinuationKey, step: F) -> Result<u64>
+    where
+        F: FnOnce(Option<u64>) -> Progress,
+    {
+        let state = self.take(key);
+        match step(state) {
+            Progress::Done(result) => result,
+            Progress::Retry(state) => {
+                self.entries
+                    .try_push((key, state))
+                    .map_err(|_| Error::Failed)?;
+                Err(RETRY_ERROR)
+            }
+        }
+    }
+
+    /// Returns if the operation identified by `key` is in progress.
+    pub fn in_progress(&self, key: ContinuationKey) -> bool {
+        self.entries.iter().any(|(k, _)| *k == key)
+    }
+
+    /// Abandons the operation identified by `key`, returning its state if it was in progress.
+    pub fn take(&mut self, key: ContinuationKey) -> Option<u64> {
+        let index = self.entries.iter().position(|(k, _)| *k == key)?;
+        Some(self.entries.swap_remove(index).1)
+    }
+
+    /// Abandons every operation in progress on the hart with ID `hart_id`, as when the hart is
+    /// stopped or reset.
+    pub fn cancel_hart(&mut self, hart_id: u64) {
+        self.entries.retain(|(k, _)| k.hart_id != hart_id);
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error::*;
+use crate::xlen::*;
+
+/// The `hart_mask_base` value that selects every hart in the system, -1 in XLEN bits.
+pub const HART_MASK_BASE_ALL: u64 = usize::MAX as u64;
+
+/// A set of harts, as passed to IPI and RFENCE calls in a `hart_mask` and `hart_mask_base` pair
+/// of registers.
+///
+/// Bit `i` of the mask selects the hart with ID `base + i`, so a single mask can select harts
+/// within a window of XLEN consecutive hart IDs. A base of `HART_MASK_BASE_ALL` selects every
+/// hart regardless of the mask. A mask selecting no harts is valid and makes the call a no-op.
+///
+/// The same set of harts can be encoded with different bases, so masks compare equal if they
+/// select the same harts, whatever their raw register values.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HartMask {
+    mask: u64,
+    base: u64,
+}
+
+impl HartMask {
+    /// Creates a hart mask from the raw `hart_mask` and `hart_mask_base` register values.
+    pub const fn from_raw(mask: u64, base: u64) -> Self {
+        Self { mask, base }
+    }
+
+    /// Creates an empty hart mask.
+    pub const fn new() -> Self {
+        Self { mask: 0, base: 0 }
+    }
+
+    /// Creates a hart mask that selects every hart in the system.
+    pub const fn all() -> Self {
+        Self {
+            mask: 0,
+            base: HART_MASK_BASE_ALL,
+        }
+    }
+
+    /// Creates a hart mask selecting the harts in `hart_ids`. Fails with a `ValidationError` for
+    /// `hart_id` if the harts don't fit in a window of XLEN consecutive hart IDs.
+    pub fn from_hart_ids<I: IntoIterator<Item = u64>>(
+        hart_ids: I,
+    ) -> core::result::Result<Self, ValidationError> {
+        let mut mask = Self::new();
+        for hart_id in hart_ids {
+            mask.set(hart_id)?;
+        }
+        Ok(mask)
+    }
+
+    /// Returns the raw `hart_mask` register value.
+    pub const fn mask(&self) -> u64 {
+        self.mask
+    }
+
+    /// Returns the raw `hart_mask_base` register value.
+    pub const fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Returns the canonical encoding of the harts selected by the mask: based at the lowest
+    /// selected hart, or `HartMask::new()` or `HartMask::all()` for masks selecting no harts or
+    /// every hart.
+    pub const fn canonicalize(&self) -> Self {
+        if self.is_all() {
+            return Self::all();
+        }
+        if self.mask == 0 {
+            return Self::new();
+        }
+        let shift = self.mask.trailing_zeros();
+        match self.base.checked_add(shift as u64) {
+            // Harts past the last valid hart ID can't be rebased onto; leave such masks as is.
+            Some(base) if base != HART_MASK_BASE_ALL => Self {
+                mask: self.mask >> shift,
+                base,
+            },
+            _ => *self,
+        }
+    }
+
+    /// Returns if the mask selects every hart in the system.
+    pub const fn is_all(&self) -> bool {
+        self.base == HART_MASK_BASE_ALL
+    }
+
+    /// Returns if the mask selects no harts.
+    pub const fn is_empty(&self) -> bool {
+        !self.is_all() && self.mask == 0
+    }
+
+    /// Returns if the hart with ID `hart_id` is selected by the mask.
+    pub const fn contains(&self, hart_id: u64) -> bool {
+        if self.is_all() {
+            return true;
+        }
+        hart_id >= self.base
+            && hart_id - self.base < XLEN as u64
+            && self.mask & (1 << (hart_id - self.base)) != 0
+    }
+
+    /// Adds the hart with ID `hart_id` to the mask, moving the base of an empty mask or lowering
+    /// it to make room if necessary. Fails with a `ValidationError` if the hart can't be selected
+    /// along with the harts already in the mask, identifying `hart_id` as the offending field.
+    pub fn set(&mut self, hart_id: u64) -> core::result::Result<(), ValidationError> {
+        const OUT_OF_RANGE: ValidationError =
+            ValidationError::new("hart_id", ValidationErrorKind::OutOfRange);
+        if self.is_all() {
+            return Ok(());
+        }
+        if hart_id == HART_MASK_BASE_ALL {
+            return Err(OUT_OF_RANGE);
+        }
+        if self.mask == 0 {
+            self.base = hart_id;
+        } else if hart_id < self.base {
+            let shift = self.base - hart_id;
+            let top = u64::BITS - 1 - self.mask.leading_zeros();
+            if shift + top as u64 >= XLEN as u64 {
+                return Err(OUT_OF_RANGE);
+            }
+            self.mask <<= shift;
+            self.base = hart_id;
+        } else if hart_id - self.base >= XLEN as u64 {
+            return Err(OUT_OF_RANGE);
+        }
+        self.mask |= 1 << (hart_id - self.base);
+        Ok(())
+    }
+
+    /// Removes the hart with ID `hart_id` from the mask. Returns `Error::InvalidParam` if the mask
+    /// selects every hart, as the remaining harts can't be represented.
+    pub fn clear(&mut self, hart_id: u64) -> Result<()> {
+        if self.is_all() {
+            return Err(Error::InvalidParam);
+        }
+        if self.contains(hart_id) {
+            self.mask &= !(1 << (hart_id - self.base));
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the IDs of the selected harts among harts `0..num_harts`, in
+    /// increasing order.
+    pub fn iter(&self, num_harts: u64) -> impl Iterator<Item = u64> {
+        let mask = *self;
+        let (start, end) = if mask.is_all() {
+            (0, num_harts)
+        } else {
+            let end = mask.base.saturating_add(XLEN as u64);
+            (mask.base, end.min(num_harts))
+        };
+        (start..end).filter(move |&hart_id| mask.contains(hart_id))
+    }
+
+    /// Checks that every selected hart is among harts `0..num_harts`, as a handler must before
+    /// acting on the mask.
+    pub fn check(&self, num_harts: u64) -> Result<()> {
+        if self.is_all() || self.mask == 0 {
+            return Ok(());
+        }
+        let top = u64::BITS - 1 - self.mask.leading_zeros();
+        if top >= XLEN || self.base.saturating_add(top as u64) >= num_harts {
+            return Err(Error::InvalidParam);
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for HartMask {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.canonicalize(), other.canonicalize());
+        a.mask == b.mask && a.base == b.base
+    }
+}
+
+impl Eq for HartMask {}
@@ -0,0 +1,79 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(feature = "handler")]
+use crate::error::*;
+use crate::function::*;
+use crate::hart_mask::*;
+
+/// Functions for the IPI extension
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IpiFunction {
+    /// Sends an inter-processor interrupt to the harts selected by `hart_mask`. The interrupt is
+    /// received as a supervisor software interrupt.
+    ///
+    /// a6 = 0
+    SendIpi {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+    },
+}
+
+impl IpiFunction {
+    /// Attempts to parse `Self` from the passed in `a0-a7`.
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
+        use IpiFunction::*;
+        match args[6] {
+            0 => Ok(SendIpi {
+                hart_mask: HartMask::from_raw(args[0], args[1]),
+            }),
+            _ => Err(Error::NotSupported),
+        }
+    }
+
+    /// Returns if the call has no effect: it selects no harts.
+    pub const fn is_noop(&self) -> bool {
+        match self {
+            IpiFunction::SendIpi { hart_mask } => hart_mask.is_empty(),
+        }
+    }
+}
+
+impl SbiFunction for IpiFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        Some(FunctionSignature::new(
+            "send_ipi",
+            &["hart_mask", "hart_mask_base"],
+        ))
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        ReturnKind::Empty
+    }
+
+    #[inline(always)]
+    fn a6(&self) -> u64 {
+        use IpiFunction::*;
+        match self {
+            SendIpi { .. } => 0,
+        }
+    }
+
+    #[inline(always)]
+    fn a0(&self) -> u64 {
+        use IpiFunction::*;
+        match self {
+            SendIpi { hart_mask } => hart_mask.mask(),
+        }
+    }
+
+    #[inline(always)]
+    fn a1(&self) -> u64 {
+        use IpiFunction::*;
+        match self {
+            SendIpi { hart_mask } => hart_mask.base(),
+        }
+    }
+}
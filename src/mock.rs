@@ -0,0 +1,532 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A simulated SBI implementation for testing guest code on the host, without QEMU.
+//!
+//! `MockMachine` models a set of harts with their HSM states, timers, pending IPIs and remote
+//! fences, along with a console buffer and the last reset request, and implements the handler
+//! traits of the Base, legacy PutChar, Timer, IPI, RFENCE, HSM and System Reset extensions over
+//! it. `MockFirmware` installs a machine as the backend of `ecall_send`, so that code written
+//! against `api` runs unmodified and its effects can be checked on the machine afterwards.
+//!
+//! Calls are made from the machine's current hart, hart 0 unless changed with
+//! `set_current_hart()`. Calls that wouldn't return on real hardware, such as `HartStop` and
+//! `Reset`, update the machine and return success.
+//!
+//! To exercise the error handling of guest code deterministically, a machine can be set up to
+//! fail chosen calls with `inject_error()`, to write at most a few bytes per Debug Console write
+//! with `set_console_write_limit()`, and to leave harts in the `StartPending` and `StopPending`
+//! states for a number of status queries with `set_hsm_transition_delay()`:
+//!
+//! ```rust
+//! use sbi_rs::api::{debug_console, hsm, time};
+//! use sbi_rs::mock::{MockFirmware, MockMachine};
+//! use sbi_rs::*;
+//!
+//! let mut machine = MockMachine::new(2);
+//! machine.inject_error(EXT_TIME, 0, Error::Failed, 1);
+//! machine.set_hsm_transition_delay(1);
+//! machine.set_console_write_limit(Some(2));
+//! let firmware = MockFirmware::new(machine);
+//! firmware.run(|| {
+//!     assert_eq!(time::set_timer(0x1000), Err(Error::Failed));
+//!     assert_eq!(time::set_timer(0x1000), Ok(()));
+//!     // Safety: The mock never runs code at the start address.
+//!     unsafe { hsm::hart_start(1, 0x8020_0000, 0) }.unwrap();
+//!     assert_eq!(hsm::hart_status(1), Ok(HartState::StartPending));
+//!     assert_eq!(hsm::hart_status(1), Ok(HartState::Started));
+//!     // Partial writes are retried until the whole string is printed.
+//!     debug_console::console_puts(b"hello").unwrap();
+//! });
+//! assert_eq!(firmware.machine_mut().console(), b"hello");
+//! ```
+//!
+//! ```rust
+//! use core::fmt::Write;
+//! use sbi_rs::api::debug_console::DebugConsoleWriter;
+//! use sbi_rs::api::{hsm, ipi, time};
+//! use sbi_rs::mock::{MockFirmware, MockMachine};
+//! use sbi_rs::*;
+//!
+//! let firmware = MockFirmware::new(MockMachine::new(2));
+//! firmware.run(|| {
+//!     time::set_timer(0x1000).unwrap();
+//!     // Safety: The mock never runs code at the start address.
+//!     unsafe { hsm::hart_start(1, 0x8020_0000, 0) }.unwrap();
+//!     ipi::send_ipi(HartMask::from_hart_ids([1]).unwrap()).unwrap();
+//!     write!(DebugConsoleWriter::new(), "hello").unwrap();
+//! });
+//!
+//! let mut machine = firmware.machine_mut();
+//! assert_eq!(machine.hart(0).unwrap().timer_deadline(), Some(0x1000));
+//! assert_eq!(machine.hart(1).unwrap().state(), HartState::Started);
+//! assert!(machine.hart(1).unwrap().ipi_pending());
+//! assert_eq!(machine.console(), b"hello");
+//!
+//! machine.advance_time(0x1000);
+//! assert!(machine.timer_pending(0));
+//! ```
+
+use core::cell::{RefCell, RefMut};
+use std::vec::Vec;
+
+use crate::handler::*;
+use crate::*;
+
+/// The implementation ID reported by `MockMachine`: "mock" in ASCII.
+pub const MOCK_IMPL_ID: u64 = 0x6d6f_636b;
+
+/// The state of a hart of a `MockMachine`.
+#[derive(Clone, Debug)]
+pub struct MockHart {
+    state: HartState,
+    start_addr: u64,
+    opaque: u64,
+    timer_deadline: Option<u64>,
+    ipi_pending: bool,
+    fences: u64,
+    last_suspend: Option<SuspendType>,
+    // The number of status queries left before a pending start or stop completes.
+    pending_polls: u32,
+}
+
+impl MockHart {
+    fn new(state: HartState) -> Self {
+        Self {
+            state,
+            start_addr: 0,
+            opaque: 0,
+            timer_deadline: None,
+            ipi_pending: false,
+            fences: 0,
+            last_suspend: None,
+            pending_polls: 0,
+        }
+    }
+
+    /// Returns the HSM state of the hart.
+    pub fn state(&self) -> HartState {
+        self.state
+    }
+
+    /// Returns the start address and opaque value passed when the hart was last started.
+    pub fn start_args(&self) -> (u64, u64) {
+        (self.start_addr, self.opaque)
+    }
+
+    /// Returns the time programmed for the hart's next timer event, if any.
+    pub fn timer_deadline(&self) -> Option<u64> {
+        self.timer_deadline
+    }
+
+    /// Returns if an IPI is pending for the hart.
+    pub fn ipi_pending(&self) -> bool {
+        self.ipi_pending
+    }
+
+    /// Returns the number of remote fences the hart was sent.
+    pub fn fences(&self) -> u64 {
+        self.fences
+    }
+
+    /// Returns the type of the hart's last suspend. Suspended harts resume immediately.
+    pub fn last_suspend(&self) -> Option<SuspendType> {
+        self.last_suspend
+    }
+}
+
+/// An in-memory model of a machine running an SBI implementation.
+#[derive(Clone, Debug)]
+pub struct MockMachine {
+    harts: Vec<MockHart>,
+    current_hart: u64,
+    time: u64,
+    console: Vec<u8>,
+    reset: Option<(ResetType, ResetReason)>,
+    faults: Vec<InjectedError>,
+    console_write_limit: Option<usize>,
+    hsm_transition_delay: u32,
+}
+
+// An error to return from the next `count` calls to function `fid` of extension `eid`.
+#[derive(Clone, Copy, Debug)]
+struct InjectedError {
+    eid: u64,
+    fid: u64,
+    error: Error,
+    count: usize,
+}
+
+impl MockMachine {
+    /// Creates a machine with `num_harts` harts. Hart 0 is started and the others stopped.
+    pub fn new(num_harts: u64) -> Self {
+        let harts = (0..num_harts)
+            .map(|hart_id| {
+                MockHart::new(if hart_id == 0 {
+                    HartState::Started
+                } else {
+                    HartState::Stopped
+                })
+            })
+            .collect();
+        Self {
+            harts,
+            current_hart: 0,
+            time: 0,
+            console: Vec::new(),
+            reset: None,
+            faults: Vec::new(),
+            console_write_limit: None,
+            hsm_transition_delay: 0,
+        }
+    }
+
+    /// Returns the hart with ID `hart_id`, if it exists.
+    pub fn hart(&self, hart_id: u64) -> Option<&MockHart> {
+        self.harts.get(usize::try_from(hart_id).ok()?)
+    }
+
+    fn hart_mut(&mut self, hart_id: u64) -> Result<&mut MockHart> {
+        let index = usize::try_from(hart_id).map_err(|_| Error::InvalidParam)?;
+        self.harts.get_mut(index).ok_or(Error::InvalidParam)
+    }
+
+    /// Returns the number of harts of the machine.
+    pub fn num_harts(&self) -> u64 {
+        self.harts.len() as u64
+    }
+
+    /// Returns the ID of the hart making calls.
+    pub fn current_hart(&self) -> u64 {
+        self.current_hart
+    }
+
+    /// Makes subsequent calls from the hart with ID `hart_id`. Returns `Error::InvalidParam` if
+    /// there is no such hart.
+    pub fn set_current_hart(&mut self, hart_id: u64) -> Result<()> {
+        self.hart_mut(hart_id)?;
+        self.current_hart = hart_id;
+        Ok(())
+    }
+
+    /// Returns the current time, in timer ticks.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Advances the time by `ticks`.
+    pub fn advance_time(&mut self, ticks: u64) {
+        self.time = self.time.saturating_add(ticks);
+    }
+
+    /// Returns if the timer of the hart with ID `hart_id` has fired.
+    pub fn timer_pending(&self, hart_id: u64) -> bool {
+        self.hart(hart_id)
+            .and_then(MockHart::timer_deadline)
+            .is_some_and(|deadline| self.time >= deadline)
+    }
+
+    /// Clears the pending IPI of the hart with ID `hart_id`, returning if there was one.
+    pub fn take_ipi(&mut self, hart_id: u64) -> bool {
+        self.hart_mut(hart_id)
+            .is_ok_and(|hart| core::mem::take(&mut hart.ipi_pending))
+    }
+
+    /// Returns the bytes written to the console.
+    pub fn console(&self) -> &[u8] {
+        &self.console
+    }
+
+    /// Removes and returns the bytes written to the console.
+    pub fn take_console(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.console)
+    }
+
+    /// Returns the last reset requested, if any.
+    pub fn reset_request(&self) -> Option<(ResetType, ResetReason)> {
+        self.reset
+    }
+
+    /// Makes the next `count` calls to function `fid` of extension `eid` fail with `error`
+    /// without otherwise being handled. Errors injected for the same function are returned in
+    /// the order they were injected.
+    pub fn inject_error(&mut self, eid: u64, fid: u64, error: Error, count: usize) {
+        self.faults.push(InjectedError {
+            eid,
+            fid,
+            error,
+            count,
+        });
+    }
+
+    /// Writes at most `limit` bytes per Debug Console write, or the whole string if `None`, as
+    /// implementations that can't write a string all at once do.
+    pub fn set_console_write_limit(&mut self, limit: Option<usize>) {
+        self.console_write_limit = limit;
+    }
+
+    /// Leaves harts that are started or stopped in the `StartPending` or `StopPending` state for
+    /// the next `polls` `HartStatus` queries for them, as implementations that start and stop
+    /// harts asynchronously do.
+    pub fn set_hsm_transition_delay(&mut self, polls: u32) {
+        self.hsm_transition_delay = polls;
+    }
+
+    /// Writes `bytes` to the console, as the Debug Console `console_write` function does,
+    /// returning the number of bytes written.
+    pub fn console_write_bytes(&mut self, bytes: &[u8]) -> Result<u64> {
+        let len = self
+            .console_write_limit
+            .map_or(bytes.len(), |limit| bytes.len().min(limit));
+        self.console.extend_from_slice(&bytes[..len]);
+        Ok(len as u64)
+    }
+
+    // Returns the error injected for the next call to `msg`'s function, if any.
+    fn take_injected_error(&mut self, msg: &SbiMessage) -> Option<Error> {
+        let (eid, fid) = (msg.extension_id(), msg.function_id());
+        let index = self
+            .faults
+            .iter()
+            .position(|fault| fault.eid == eid && fault.fid == fid && fault.count > 0)?;
+        let fault = &mut self.faults[index];
+        fault.count -= 1;
+        Some(fault.error)
+    }
+
+    /// Handles `msg` as if made from the current hart. Debug Console writes fail with
+    /// `Error::NotSupported` as they refer to the caller's memory; `MockFirmware` handles them.
+    pub fn handle(&mut self, msg: SbiMessage) -> SbiReturn {
+        if let Some(error) = self.take_injected_error(&msg) {
+            return error.into();
+        }
+        let result = match msg {
+            SbiMessage::Base(func) => BaseHandler::handle(self, func),
+            SbiMessage::PutChar(ch) => PutCharHandler::handle(self, ch),
+            SbiMessage::Time(func) => TimeHandler::handle(self, func),
+            SbiMessage::Ipi(func) => IpiHandler::handle(self, func),
+            SbiMessage::Rfence(func) => RfenceHandler::handle(self, func),
+            SbiMessage::HartState(func) => HsmHandler::handle(self, func),
+            SbiMessage::Reset(func) => ResetHandler::handle(self, func),
+            _ => Err(Error::NotSupported),
+        };
+        result.into()
+    }
+
+    // Records a remote fence on each hart selected by `hart_mask`.
+    fn fence(&mut self, hart_mask: HartMask) -> Result<u64> {
+        hart_mask.check(self.num_harts())?;
+        for hart_id in hart_mask.iter(self.num_harts()) {
+            self.hart_mut(hart_id)?.fences += 1;
+        }
+        Ok(0)
+    }
+}
+
+impl BaseHandler for MockMachine {
+    fn get_spec_version(&mut self) -> Result<u64> {
+        Ok(0x0200_0000)
+    }
+
+    fn get_impl_id(&mut self) -> Result<u64> {
+        Ok(MOCK_IMPL_ID)
+    }
+
+    fn get_impl_version(&mut self) -> Result<u64> {
+        Ok(0)
+    }
+
+    fn probe_extension(&mut self, extension_id: u64) -> Result<u64> {
+        let implemented = matches!(
+            extension_id,
+            EXT_BASE
+                | EXT_PUT_CHAR
+                | EXT_TIME
+                | EXT_IPI
+                | EXT_RFENCE
+                | EXT_HART_STATE
+                | EXT_RESET
+                | EXT_DBCN
+        );
+        Ok(implemented as u64)
+    }
+
+    fn get_mvendorid(&mut self) -> Result<u64> {
+        Ok(0)
+    }
+
+    fn get_marchid(&mut self) -> Result<u64> {
+        Ok(0)
+    }
+
+    fn get_mimpid(&mut self) -> Result<u64> {
+        Ok(0)
+    }
+}
+
+impl PutCharHandler for MockMachine {
+    fn console_putchar(&mut self, ch: u64) -> Result<u64> {
+        self.console.push(ch as u8);
+        Ok(0)
+    }
+}
+
+impl TimeHandler for MockMachine {
+    fn set_timer(&mut self, stime_value: u64) -> Result<u64> {
+        let current_hart = self.current_hart;
+        self.hart_mut(current_hart)?.timer_deadline =
+            (stime_value != u64::MAX).then_some(stime_value);
+        Ok(0)
+    }
+}
+
+impl IpiHandler for MockMachine {
+    fn send_ipi(&mut self, hart_mask: HartMask) -> Result<u64> {
+        hart_mask.check(self.num_harts())?;
+        for hart_id in hart_mask.iter(self.num_harts()) {
+            self.hart_mut(hart_id)?.ipi_pending = true;
+        }
+        Ok(0)
+    }
+}
+
+impl RfenceHandler for MockMachine {
+    fn remote_fence_i(&mut self, hart_mask: HartMask) -> Result<u64> {
+        self.fence(hart_mask)
+    }
+
+    fn remote_sfence_vma(&mut self, hart_mask: HartMask, _: u64, _: u64) -> Result<u64> {
+        self.fence(hart_mask)
+    }
+
+    fn remote_sfence_vma_asid(
+        &mut self,
+        hart_mask: HartMask,
+        _: u64,
+        _: u64,
+        _: u64,
+    ) -> Result<u64> {
+        self.fence(hart_mask)
+    }
+}
+
+impl HsmHandler for MockMachine {
+    fn hart_start(&mut self, hart_id: u64, start_addr: u64, opaque: u64) -> Result<u64> {
+        let delay = self.hsm_transition_delay;
+        let hart = self.hart_mut(hart_id)?;
+        if hart.state != HartState::Stopped {
+            return Err(Error::AlreadyAvailable);
+        }
+        hart.state = if delay == 0 {
+            HartState::Started
+        } else {
+            HartState::StartPending
+        };
+        hart.pending_polls = delay;
+        hart.start_addr = start_addr;
+        hart.opaque = opaque;
+        Ok(0)
+    }
+
+    fn hart_stop(&mut self) -> Result<u64> {
+        let (current_hart, delay) = (self.current_hart, self.hsm_transition_delay);
+        let hart = self.hart_mut(current_hart)?;
+        hart.state = if delay == 0 {
+            HartState::Stopped
+        } else {
+            HartState::StopPending
+        };
+        hart.pending_polls = delay;
+        Ok(0)
+    }
+
+    fn hart_get_status(&mut self, hart_id: u64) -> Result<u64> {
+        let hart = self.hart_mut(hart_id)?;
+        let state = hart.state;
+        if hart.pending_polls > 0 {
+            hart.pending_polls -= 1;
+            if hart.pending_polls == 0 {
+                hart.state = match state {
+                    HartState::StartPending => HartState::Started,
+                    HartState::StopPending => HartState::Stopped,
+                    state => state,
+                };
+            }
+        }
+        Ok(state as u64)
+    }
+
+    fn hart_suspend(&mut self, suspend_type: SuspendType, _: u64, _: u64) -> Result<u64> {
+        let current_hart = self.current_hart;
+        self.hart_mut(current_hart)?.last_suspend = Some(suspend_type);
+        Ok(0)
+    }
+}
+
+impl ResetHandler for MockMachine {
+    fn system_reset(&mut self, reset_type: ResetType, reason: ResetReason) -> Result<u64> {
+        self.reset = Some((reset_type, reason));
+        Ok(0)
+    }
+}
+
+/// Serves the SBI calls of code running on the host from a `MockMachine`.
+#[derive(Debug)]
+pub struct MockFirmware {
+    machine: RefCell<MockMachine>,
+}
+
+impl MockFirmware {
+    /// Creates firmware serving calls from `machine`.
+    pub fn new(machine: MockMachine) -> Self {
+        Self {
+            machine: RefCell::new(machine),
+        }
+    }
+
+    /// Returns the machine, to check or change its state between calls.
+    pub fn machine_mut(&self) -> RefMut<'_, MockMachine> {
+        self.machine.borrow_mut()
+    }
+
+    /// Consumes the firmware, returning its machine.
+    pub fn into_machine(self) -> MockMachine {
+        self.machine.into_inner()
+    }
+
+    /// Runs `f` with the firmware serving the calls made with `ecall_send` on the current
+    /// thread.
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        with_ecall_sender(self, f)
+    }
+}
+
+impl EcallSender for MockFirmware {
+    unsafe fn send(&self, regs: &[u64; 8]) -> SbiReturn {
+        let msg = match SbiMessage::from_regs(regs) {
+            Ok(msg) => msg,
+            Err(e) => return e.into(),
+        };
+        let mut machine = self.machine.borrow_mut();
+        if let Some(error) = machine.take_injected_error(&msg) {
+            return error.into();
+        }
+        match msg {
+            SbiMessage::DebugConsole(DebugConsoleFunction::PutString { len, addr }) => {
+                let (Ok(len), Ok(addr)) = (len.as_usize(), addr.as_usize()) else {
+                    return Error::InvalidParam.into();
+                };
+                if len == 0 {
+                    return SbiReturn::success(0);
+                }
+                // Safety: Code running on the host passes the addresses of its own memory, which
+                // the caller of `ecall_send` guarantees is valid to read for the length of the
+                // string.
+                let bytes = unsafe { core::slice::from_raw_parts(addr as *const u8, len) };
+                machine.console_write_bytes(bytes).into()
+            }
+            msg => machine.handle(msg),
+        }
+    }
+}
@@ -2,6 +2,7 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "handler")]
 use crate::error::*;
 use crate::function::*;
 
@@ -9,6 +10,11 @@ use crate::function::*;
 pub const NACL_SCRATCH_BYTES: usize = 2048;
 
 /// Layout of the shared-memory area registered with `SetShmem`.
+#[repr(C)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
 pub struct NaclShmem {
     /// Scratch space. The layout of this scratch space is defined by the particular function being
     /// invoked.
@@ -35,6 +41,13 @@ impl NaclShmem {
     pub fn csr_index(csr_num: u16) -> usize {
         (((csr_num & 0xc00) >> 2) | (csr_num & 0xff)) as usize
     }
+
+    /// Returns the index in `dirty_bitmap` of the word holding the dirty bit for the HS or VS CSR
+    /// at `csr_num`, along with the mask of the bit within that word.
+    pub fn dirty_bitmap_bit(csr_num: u16) -> (usize, u64) {
+        let index = Self::csr_index(csr_num);
+        (index / 64, 1 << (index % 64))
+    }
 }
 
 impl Default for NaclShmem {
@@ -48,50 +61,34 @@ impl Default for NaclShmem {
     }
 }
 
-/// Functions provided by the Nested Virtualization Acceleration (NACL) extension.
-#[derive(Copy, Clone, Debug)]
-pub enum NaclFunction {
-    /// Registers the nested hypervisor <-> host hypervisor shared memory area for the calling CPU.
-    /// `shmem_pfn` is the base PFN of where the `NaclShmem` struct will be placed in the caller's
-    /// physical address space. The entire range of memory occupied by the `NaclShmem` struct must
-    /// remain accessible to the caller until the `NaclShmem` strucutre is unregistered by calling
-    /// this function with `shmem_pfn` set to -1. In particular this means that, in the presence of
-    /// the TEE-Host extension, the memory occupied by the `NaclShmem` structure is "pinned" in
-    /// the non-confidential state and cannot be converted.
-    ///
-    /// a6 = 0
-    SetShmem {
-        /// a0 = PFN of shared memory area
-        shmem_pfn: u64,
-    },
-    // There are other functions in the proposed NACL extension, but we ignore them as they aren't
-    // relevant to the TEE extensions. Note that this violates SBI policy, but since both the TEE and
-    // NACL extensions are in active development, we let it go for now.
-}
-
-impl NaclFunction {
-    /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
-        use NaclFunction::*;
-        match args[6] {
-            0 => Ok(SetShmem { shmem_pfn: args[0] }),
-            _ => Err(Error::NotSupported),
-        }
-    }
-}
-
-impl SbiFunction for NaclFunction {
-    fn a6(&self) -> u64 {
-        use NaclFunction::*;
-        match self {
-            SetShmem { .. } => 0,
-        }
-    }
-
-    fn a0(&self) -> u64 {
-        use NaclFunction::*;
-        match self {
-            SetShmem { shmem_pfn } => *shmem_pfn,
-        }
+sbi_function! {
+    /// Functions provided by the Nested Virtualization Acceleration (NACL) extension.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum NaclFunction {
+        /// Registers the nested hypervisor <-> host hypervisor shared memory area for the calling
+        /// CPU. `shmem_pfn` is the base PFN of where the `NaclShmem` struct will be placed in the
+        /// caller's physical address space. The entire range of memory occupied by the `NaclShmem`
+        /// struct must remain accessible to the caller until the `NaclShmem` strucutre is
+        /// unregistered by calling this function with `shmem_pfn` set to -1. In particular this
+        /// means that, in the presence of the TEE-Host extension, the memory occupied by the
+        /// `NaclShmem` structure is "pinned" in the non-confidential state and cannot be converted.
+        ///
+        /// a6 = 0
+        SetShmem(set_shmem) = 0 => Empty {
+            /// a0 = PFN of shared memory area
+            shmem_pfn => a0,
+        },
+        /// Synchronizes the HS or VS CSR `csr_num` between the shared memory area and the host
+        /// hypervisor, or all CSRs marked in `dirty_bitmap` if `csr_num` is -1. Dirty bits of the
+        /// synchronized CSRs are cleared.
+        ///
+        /// a6 = 2
+        SyncCsr(sync_csr) = 2 => Empty {
+            /// a0 = CSR number to synchronize, or -1 for all CSRs
+            csr_num => a0,
+        },
+        // There are other functions in the proposed NACL extension, but we ignore them as they
+        // aren't relevant to the TEE extensions. Note that this violates SBI policy, but since
+        // both the TEE and NACL extensions are in active development, we let it go for now.
     }
 }
@@ -0,0 +1,623 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Extensions defined outside of this crate, in the extension ID ranges the SBI specification
+//! reserves for SBI implementation vendors (`EXT_VENDOR_START..=EXT_VENDOR_END`) and for
+//! firmware-specific extensions (`EXT_FIRMWARE_START..=EXT_FIRMWARE_END`).
+//!
+//! Downstream users model their extensions by implementing `VendorExtension` or
+//! `FirmwareExtension` and decoding calls into a `PlatformSbiMessage`, which routes each call to
+//! the standard extensions or to the user's types based on the range its extension ID falls in.
+//! Independent extensions in the same range are combined with `EitherExtension`.
+
+use core::fmt;
+use core::ops::RangeInclusive;
+
+use crate::consts::*;
+use crate::error::*;
+use crate::function::*;
+use crate::SbiMessage;
+
+/// An SBI extension, or set of extensions, with extension IDs in the vendor range.
+pub trait VendorExtension: SbiFunction + Sized {
+    /// The extension IDs implemented by this type, within the vendor range. Defaults to the whole
+    /// range.
+    const EIDS: RangeInclusive<u64> = EXT_VENDOR_START..=EXT_VENDOR_END;
+
+    /// Returns if `eid`, an extension ID in the vendor range, is implemented by this type. Calls to
+    /// extensions not owned by any type fail with `Error::NotSupported` without being decoded.
+    /// Defaults to owning the extension IDs in `EIDS`, for types whose extension IDs aren't
+    /// contiguous to override.
+    fn owns(eid: u64) -> bool {
+        Self::EIDS.contains(&eid)
+    }
+
+    /// Attempts to parse a call from `a0-a7`. `a7` is known to be an extension ID owned by this
+    /// type; returns `Error::NotSupported` if the function isn't implemented.
+    fn from_regs(args: &[u64; 8]) -> Result<Self>;
+
+    /// Returns the extension ID (the value of a7) of the call.
+    fn eid(&self) -> u64;
+}
+
+/// An SBI extension, or set of extensions, with extension IDs in the firmware-specific range,
+/// such as a test extension implemented by a particular firmware.
+pub trait FirmwareExtension: SbiFunction + Sized {
+    /// The extension IDs implemented by this type, within the firmware range. Defaults to the whole
+    /// range.
+    const EIDS: RangeInclusive<u64> = EXT_FIRMWARE_START..=EXT_FIRMWARE_END;
+
+    /// Returns if `eid`, an extension ID in the firmware range, is implemented by this type. Calls to
+    /// extensions not owned by any type fail with `Error::NotSupported` without being decoded.
+    /// Defaults to owning the extension IDs in `EIDS`, for types whose extension IDs aren't
+    /// contiguous to override.
+    fn owns(eid: u64) -> bool {
+        Self::EIDS.contains(&eid)
+    }
+
+    /// Attempts to parse a call from `a0-a7`. `a7` is known to be an extension ID owned by this
+    /// type; returns `Error::NotSupported` if the function isn't implemented.
+    fn from_regs(args: &[u64; 8]) -> Result<Self>;
+
+    /// Returns the extension ID (the value of a7) of the call.
+    fn eid(&self) -> u64;
+}
+
+/// An extension with no functions, for platforms without vendor or firmware extensions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoExtension {}
+
+impl SbiFunction for NoExtension {}
+
+impl VendorExtension for NoExtension {
+    // No extension IDs.
+    #[allow(clippy::reversed_empty_ranges)]
+    const EIDS: RangeInclusive<u64> = 1..=0;
+
+    fn from_regs(_args: &[u64; 8]) -> Result<Self> {
+        Err(Error::NotSupported)
+    }
+
+    fn eid(&self) -> u64 {
+        match *self {}
+    }
+}
+
+impl FirmwareExtension for NoExtension {
+    // No extension IDs.
+    #[allow(clippy::reversed_empty_ranges)]
+    const EIDS: RangeInclusive<u64> = 1..=0;
+
+    fn from_regs(_args: &[u64; 8]) -> Result<Self> {
+        Err(Error::NotSupported)
+    }
+
+    fn eid(&self) -> u64 {
+        match *self {}
+    }
+}
+
+/// Declares a vendor extension with a single extension ID whose arguments are all plain `u64`s:
+/// the enum of its functions along with its `SbiFunction` and `VendorExtension` implementations.
+///
+/// The enum is followed by its extension ID. Each variant is followed by the name of the function
+/// for display, its function ID and, for functions that don't return `ReturnKind::Value`, by its
+/// `ReturnKind`. Each field is followed by the argument register it is passed in. Calls with an
+/// unknown function ID fail to decode with `Error::NotSupported`.
+///
+/// Functions that return their result in a vendor-specific way, such as with a custom error
+/// space, name the function reading it from `a0` and `a1` after their `ReturnKind`, which still
+/// determines how handlers write the result, as `Legacy(decoder)`.
+///
+/// ```rust
+/// use sbi_rs::*;
+///
+/// define_vendor_extension! {
+///     /// The functions of a vendor test extension.
+///     #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///     pub enum TestFunction(0x0900_0001) {
+///         /// Copies `len` bytes from `from` to `to`.
+///         MemCopy(mem_copy) = 0 {
+///             /// The destination address.
+///             to => a0,
+///             /// The source address.
+///             from => a1,
+///             /// The number of bytes to copy.
+///             len => a2,
+///         },
+///         /// Stops the test.
+///         Stop(stop) = 1 => Empty,
+///         /// Returns the status of the last test operation, as a positive code in `a0`.
+///         GetLastStatus(get_last_status) = 2 => Legacy(decode_status),
+///     }
+/// }
+///
+/// fn decode_status(a0: u64, _a1: u64) -> Result<u64> {
+///     match a0 as i64 {
+///         0 => Ok(0),
+///         code => Err(Error::Unknown(code)),
+///     }
+/// }
+///
+/// let regs = [0x2000, 0x1000, 16, 0, 0, 0, 0, 0x0900_0001];
+/// let msg = PlatformSbiMessage::<TestFunction>::from_regs(&regs).unwrap();
+/// assert_eq!(msg.to_regs(), regs);
+/// # #[cfg(not(feature = "tiny"))]
+/// assert_eq!(msg.to_string(), "0x9000001::mem_copy(to=0x2000, from=0x1000, len=16)");
+/// assert_eq!(
+///     TestFunction::from_regs(&[0, 0, 0, 0, 0, 0, 1, 0x0900_0001]),
+///     Ok(TestFunction::Stop)
+/// );
+/// assert_eq!(TestFunction::GetLastStatus.result(5, 0), Err(Error::Unknown(5)));
+/// ```
+#[macro_export]
+macro_rules! define_vendor_extension {
+    ($($tokens:tt)*) => {
+        $crate::__sbi_define_extension!(VendorExtension $($tokens)*);
+    };
+}
+
+/// Declares a firmware extension with a single extension ID whose arguments are all plain
+/// `u64`s, as `define_vendor_extension!` does for vendor extensions.
+///
+/// ```rust
+/// use sbi_rs::*;
+///
+/// define_firmware_extension! {
+///     /// The functions of a firmware test extension.
+///     #[derive(Clone, Copy, Debug)]
+///     pub enum FirmwareTest(0x0A00_0001) {
+///         /// Reports the number of tests run.
+///         TestCount(test_count) = 0,
+///     }
+/// }
+///
+/// let regs = [0, 0, 0, 0, 0, 0, 0, 0x0A00_0001];
+/// let msg = PlatformSbiMessage::<NoVendor, FirmwareTest>::from_regs(&regs);
+/// assert!(matches!(msg, Ok(PlatformSbiMessage::Firmware(FirmwareTest::TestCount))));
+/// ```
+#[macro_export]
+macro_rules! define_firmware_extension {
+    ($($tokens:tt)*) => {
+        $crate::__sbi_define_extension!(FirmwareExtension $($tokens)*);
+    };
+}
+
+// Declares an extension for `define_vendor_extension!` or `define_firmware_extension!`,
+// implementing the extension trait `$trait`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sbi_define_extension {
+    (
+        $trait:ident
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident($eid:expr) {
+            $(
+                $(#[$vmeta:meta])*
+                $variant:ident($fname:ident) = $fid:literal
+                    $(=> $kind:ident $(($decoder:path))?)? $({
+                    $(
+                        $(#[$fmeta:meta])*
+                        $field:ident => $reg:ident
+                    ),* $(,)?
+                })?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $(
+                $(#[$vmeta])*
+                $variant $({
+                    $(
+                        $(#[$fmeta])*
+                        $field: u64,
+                    )*
+                })?,
+            )*
+        }
+
+        impl $name {
+            // Returns the values of a0-a6 for the call.
+            #[inline(always)]
+            fn sbi_regs(&self) -> [u64; 7] {
+                #[allow(unused_mut)]
+                let mut regs = [0; 7];
+                match self {
+                    $(
+                        $name::$variant $({ $($field,)* })? => {
+                            regs[6] = $fid;
+                            $($(regs[$crate::__sbi_reg_index!($reg)] = *$field;)*)?
+                        }
+                    )*
+                }
+                regs
+            }
+        }
+
+        impl $crate::SbiFunction for $name {
+            fn signature(&self) -> Option<$crate::FunctionSignature> {
+                match self {
+                    $(
+                        $name::$variant { .. } => Some($crate::FunctionSignature::new(
+                            stringify!($fname),
+                            &[$($(stringify!($field)),*)?],
+                        )),
+                    )*
+                }
+            }
+
+            fn return_kind(&self) -> $crate::ReturnKind {
+                match self {
+                    $($name::$variant { .. } => $crate::__sbi_return_kind!($($kind)?),)*
+                }
+            }
+
+            fn result(&self, a0: u64, a1: u64) -> $crate::Result<u64> {
+                match self {
+                    $(
+                        $name::$variant { .. } => {
+                            $crate::__sbi_decode_result!(self, a0, a1 $($(, $decoder)?)?)
+                        }
+                    )*
+                }
+            }
+
+            #[inline(always)]
+            fn regs(&self) -> [u64; 7] {
+                self.sbi_regs()
+            }
+
+            #[inline(always)]
+            fn a6(&self) -> u64 {
+                self.sbi_regs()[6]
+            }
+
+            #[inline(always)]
+            fn a5(&self) -> u64 {
+                self.sbi_regs()[5]
+            }
+
+            #[inline(always)]
+            fn a4(&self) -> u64 {
+                self.sbi_regs()[4]
+            }
+
+            #[inline(always)]
+            fn a3(&self) -> u64 {
+                self.sbi_regs()[3]
+            }
+
+            #[inline(always)]
+            fn a2(&self) -> u64 {
+                self.sbi_regs()[2]
+            }
+
+            #[inline(always)]
+            fn a1(&self) -> u64 {
+                self.sbi_regs()[1]
+            }
+
+            #[inline(always)]
+            fn a0(&self) -> u64 {
+                self.sbi_regs()[0]
+            }
+        }
+
+        impl $crate::$trait for $name {
+            const EIDS: ::core::ops::RangeInclusive<u64> = $eid..=$eid;
+
+            fn from_regs(args: &[u64; 8]) -> $crate::Result<Self> {
+                match args[6] {
+                    $(
+                        $fid => Ok($name::$variant $({
+                            $($field: args[$crate::__sbi_reg_index!($reg)],)*
+                        })?),
+                    )*
+                    _ => Err($crate::Error::NotSupported),
+                }
+            }
+
+            fn eid(&self) -> u64 {
+                $eid
+            }
+        }
+    };
+}
+
+// Reads the result of a `define_vendor_extension!` function from `a0` and `a1`, with the
+// function's decoder if it has one.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sbi_decode_result {
+    ($func:expr, $a0:expr, $a1:expr) => {
+        $crate::SbiFunction::return_kind($func).decode($a0, $a1)
+    };
+    ($func:expr, $a0:expr, $a1:expr, $decoder:path) => {
+        $decoder($a0, $a1)
+    };
+}
+
+/// The vendor extension of platforms without any, for using `PlatformSbiMessage` APIs with plain
+/// `SbiMessage` semantics.
+pub type NoVendor = NoExtension;
+
+/// The firmware extension of platforms without any.
+pub type NoFirmware = NoExtension;
+
+/// A call to one of two independent extensions sharing a range, for platforms with several vendor
+/// or firmware extensions. Calls are decoded by whichever of `A` and `B` owns their extension ID,
+/// trying `A` first. Calls to extension IDs both own are passed on to `B` if `A` doesn't
+/// implement the function, so that `B` can add functions to an extension defined by `A`. Pairs
+/// nest to combine more than two extensions.
+///
+/// ```rust
+/// use sbi_rs::*;
+///
+/// #[derive(Debug)]
+/// struct Ping;
+///
+/// impl SbiFunction for Ping {}
+///
+/// impl VendorExtension for Ping {
+///     const EIDS: core::ops::RangeInclusive<u64> = 0x0900_0001..=0x0900_0001;
+///
+///     fn from_regs(args: &[u64; 8]) -> Result<Self> {
+///         match args[6] {
+///             0 => Ok(Ping),
+///             _ => Err(Error::NotSupported),
+///         }
+///     }
+///
+///     fn eid(&self) -> u64 {
+///         0x0900_0001
+///     }
+/// }
+///
+/// type Vendors = EitherExtension<Ping, NoExtension>;
+/// let msg = PlatformSbiMessage::<Vendors>::from_regs(&[0, 0, 0, 0, 0, 0, 0, 0x0900_0001]);
+/// assert!(matches!(msg, Ok(PlatformSbiMessage::Vendor(EitherExtension::First(Ping)))));
+/// let msg = PlatformSbiMessage::<Vendors>::from_regs(&[0, 0, 0, 0, 0, 0, 0, 0x0900_0002]);
+/// assert_eq!(msg.err(), Some(Error::NotSupported));
+///
+/// // Functions added to Ping's extension ID by another extension.
+/// define_vendor_extension! {
+///     /// More functions of the Ping extension.
+///     #[derive(Debug)]
+///     pub enum PingMore(0x0900_0001) {
+///         /// Pings twice.
+///         PingTwice(ping_twice) = 1,
+///     }
+/// }
+///
+/// type Layered = EitherVendor<Ping, PingMore>;
+/// let msg = PlatformSbiMessage::<Layered>::from_regs(&[0, 0, 0, 0, 0, 0, 1, 0x0900_0001]);
+/// assert!(matches!(
+///     msg,
+///     Ok(PlatformSbiMessage::Vendor(EitherExtension::Second(PingMore::PingTwice)))
+/// ));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EitherExtension<A, B> {
+    /// A call to `A`.
+    First(A),
+    /// A call to `B`.
+    Second(B),
+}
+
+impl<A: SbiFunction, B: SbiFunction> EitherExtension<A, B> {
+    fn function(&self) -> &dyn SbiFunction {
+        match self {
+            Self::First(a) => a,
+            Self::Second(b) => b,
+        }
+    }
+}
+
+impl<A: SbiFunction, B: SbiFunction> SbiFunction for EitherExtension<A, B> {
+    #[inline(always)]
+    fn regs(&self) -> [u64; 7] {
+        match self {
+            Self::First(a) => a.regs(),
+            Self::Second(b) => b.regs(),
+        }
+    }
+
+    #[inline(always)]
+    fn a6(&self) -> u64 {
+        self.function().a6()
+    }
+
+    #[inline(always)]
+    fn a5(&self) -> u64 {
+        self.function().a5()
+    }
+
+    #[inline(always)]
+    fn a4(&self) -> u64 {
+        self.function().a4()
+    }
+
+    #[inline(always)]
+    fn a3(&self) -> u64 {
+        self.function().a3()
+    }
+
+    #[inline(always)]
+    fn a2(&self) -> u64 {
+        self.function().a2()
+    }
+
+    #[inline(always)]
+    fn a1(&self) -> u64 {
+        self.function().a1()
+    }
+
+    #[inline(always)]
+    fn a0(&self) -> u64 {
+        self.function().a0()
+    }
+
+    fn signature(&self) -> Option<FunctionSignature> {
+        self.function().signature()
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        self.function().return_kind()
+    }
+
+    fn result(&self, a0: u64, a1: u64) -> Result<u64> {
+        self.function().result(a0, a1)
+    }
+}
+
+// Implements an extension trait for `EitherExtension`, for either range.
+macro_rules! either_extension {
+    ($trait:ident) => {
+        impl<A: $trait, B: $trait> $trait for EitherExtension<A, B> {
+            fn owns(eid: u64) -> bool {
+                A::owns(eid) || B::owns(eid)
+            }
+
+            fn from_regs(args: &[u64; 8]) -> Result<Self> {
+                if A::owns(args[7]) {
+                    match A::from_regs(args) {
+                        Err(Error::NotSupported) if B::owns(args[7]) => (),
+                        result => return result.map(Self::First),
+                    }
+                }
+                if B::owns(args[7]) {
+                    B::from_regs(args).map(Self::Second)
+                } else {
+                    Err(Error::NotSupported)
+                }
+            }
+
+            fn eid(&self) -> u64 {
+                match self {
+                    Self::First(a) => a.eid(),
+                    Self::Second(b) => b.eid(),
+                }
+            }
+        }
+    };
+}
+
+either_extension!(VendorExtension);
+either_extension!(FirmwareExtension);
+
+/// A pair of vendor extensions sharing the vendor range.
+pub type EitherVendor<A, B> = EitherExtension<A, B>;
+
+/// A pair of firmware extensions sharing the firmware range.
+pub type EitherFirmware<A, B> = EitherExtension<A, B>;
+
+/// An SBI call to a standard extension, to a vendor extension `V` or to a firmware extension `F`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlatformSbiMessage<V = NoExtension, F = NoExtension> {
+    /// A call to an extension defined by the SBI specification or known to this crate.
+    Standard(SbiMessage),
+    /// A call to a vendor extension.
+    Vendor(V),
+    /// A call to a firmware-specific extension.
+    Firmware(F),
+}
+
+impl<V: VendorExtension, F: FirmwareExtension> PlatformSbiMessage<V, F> {
+    /// Creates a message from the given GPRs, decoding calls in the vendor and firmware ranges
+    /// with `V` and `F` and all other calls as `SbiMessage::from_regs()` does.
+    #[cfg(feature = "handler")]
+    pub fn from_regs(args: &[u64; 8]) -> Result<Self> {
+        match args[7] {
+            eid @ EXT_VENDOR_START..=EXT_VENDOR_END if V::owns(eid) => {
+                V::from_regs(args).map(Self::Vendor)
+            }
+            eid @ EXT_FIRMWARE_START..=EXT_FIRMWARE_END if F::owns(eid) => {
+                F::from_regs(args).map(Self::Firmware)
+            }
+            EXT_VENDOR_START..=EXT_VENDOR_END | EXT_FIRMWARE_START..=EXT_FIRMWARE_END => {
+                Err(Error::NotSupported)
+            }
+            _ => SbiMessage::from_regs(args).map(Self::Standard),
+        }
+    }
+
+    /// Returns the register value for this message.
+    #[inline(always)]
+    pub fn a7(&self) -> u64 {
+        match self {
+            Self::Standard(msg) => msg.a7(),
+            Self::Vendor(v) => v.eid(),
+            Self::Firmware(f) => f.eid(),
+        }
+    }
+
+    /// Returns the extension ID of the call, as passed in a7.
+    pub fn extension_id(&self) -> u64 {
+        self.a7()
+    }
+
+    /// Returns the function ID of the call, as passed in a6.
+    pub fn function_id(&self) -> u64 {
+        match self {
+            Self::Standard(msg) => msg.a6(),
+            Self::Vendor(v) => v.a6(),
+            Self::Firmware(f) => f.a6(),
+        }
+    }
+
+    /// Returns the registers to be passed to the ECALL instruction for this message.
+    #[inline(always)]
+    pub fn to_regs(&self) -> [u64; 8] {
+        match self {
+            Self::Standard(msg) => msg.to_regs(),
+            Self::Vendor(v) => function_regs(v, v.eid()),
+            Self::Firmware(f) => function_regs(f, f.eid()),
+        }
+    }
+
+    /// Returns the result of the call from the values left in `a0` and `a1` by the ECALL
+    /// instruction.
+    pub fn result(&self, a0: u64, a1: u64) -> Result<u64> {
+        match self {
+            Self::Standard(msg) => msg.result(a0, a1),
+            Self::Vendor(v) => v.result(a0, a1),
+            Self::Firmware(f) => f.result(a0, a1),
+        }
+    }
+}
+
+/// Decodes a call from its `a0-a7` registers, as `PlatformSbiMessage::from_regs()` does.
+#[cfg(feature = "handler")]
+impl<V: VendorExtension, F: FirmwareExtension> TryFrom<[u64; 8]> for PlatformSbiMessage<V, F> {
+    type Error = Error;
+
+    fn try_from(regs: [u64; 8]) -> Result<Self> {
+        Self::from_regs(&regs)
+    }
+}
+
+impl<V, F> From<SbiMessage> for PlatformSbiMessage<V, F> {
+    fn from(msg: SbiMessage) -> Self {
+        Self::Standard(msg)
+    }
+}
+
+/// Displays calls to standard extensions as `SbiMessage` does, and calls to vendor and firmware
+/// extensions with their extension ID in place of the extension name.
+impl<V: VendorExtension, F: FirmwareExtension> fmt::Display for PlatformSbiMessage<V, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standard(msg) => msg.fmt(f),
+            Self::Vendor(v) => fmt_call(f, &format_args!("{:#x}", v.eid()), v),
+            Self::Firmware(fw) => fmt_call(f, &format_args!("{:#x}", fw.eid()), fw),
+        }
+    }
+}
+
+#[inline(always)]
+pub(crate) fn function_regs(f: &(impl SbiFunction + ?Sized), eid: u64) -> [u64; 8] {
+    let [a0, a1, a2, a3, a4, a5, a6] = f.regs();
+    [a0, a1, a2, a3, a4, a5, a6, eid]
+}
@@ -4,11 +4,13 @@
 
 use crate::error::*;
 use crate::function::*;
-use ConfigFlagsValues::*;
+use crate::xlen::*;
+
+use flagset::{flags, FlagSet};
 
 /// Functions for the Performance Monitoring Unit (PMU) extension
 /// Specific details can be found in the SBI documentation for the PMU extension.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PmuFunction {
     /// Returns the total number of performance counters (hardware and firmware).
     GetNumCounters,
@@ -25,7 +27,7 @@ pub enum PmuFunction {
         config_flags: PmuCounterConfigFlags,
         /// Counter event type.
         event_type: PmuEventType,
-        /// Counter event data.
+        /// Counter event data, split into low and high halves in `a4` and `a5` on RV32.
         event_data: u64,
     },
     /// Starts the counters selected by counter_index and counter_mask.
@@ -37,7 +39,8 @@ pub enum PmuFunction {
         counter_mask: u64,
         /// Counter start flags.
         start_flags: PmuCounterStartFlags,
-        /// Counter initial value (used in conjunction with start_flags).
+        /// Counter initial value (used in conjunction with start_flags). Passed as a pair of
+        /// registers holding its low and high halves on RV32.
         initial_value: u64,
     },
     /// Stops the counters selected by counter_index and counter_mask.
@@ -50,33 +53,92 @@ pub enum PmuFunction {
         /// Counter stop flags.
         stop_flags: PmuCounterStopFlags,
     },
-    /// Returns the current value firmware counter specified by the inner value.
+    /// Returns the current value firmware counter specified by the inner value. Only the low
+    /// XLEN bits of the value are returned on RV32.
     ReadFirmwareCounter(u64),
+    /// Returns the upper 32 bits of the current value of the firmware counter specified by the
+    /// inner value on RV32. Always returns 0 on RV64.
+    ReadFirmwareCounterHigh(u64),
+}
+
+flags! {
+    /// The bits of the config_flags parameter of sbi_pmu_counter_config_matching.
+    pub enum PmuConfigFlag: u64 {
+        /// Skips counter matching and configures the counter selected by the mask.
+        SkipMatch = PmuCounterConfigFlags::SKIP_MATCH,
+        /// Clears the counter value after configuring it.
+        ClearValue = PmuCounterConfigFlags::CLEAR_VALUE,
+        /// Starts the counter after configuring it.
+        AutoStart = PmuCounterConfigFlags::AUTO_START,
+        /// Inhibits counting in VU-mode.
+        Vuinh = PmuCounterConfigFlags::VUINH,
+        /// Inhibits counting in VS-mode.
+        Vsinh = PmuCounterConfigFlags::VSINH,
+        /// Inhibits counting in U-mode.
+        Uinh = PmuCounterConfigFlags::UINH,
+        /// Inhibits counting in S-mode.
+        Sinh = PmuCounterConfigFlags::SINH,
+        /// Inhibits counting in M-mode.
+        Minh = PmuCounterConfigFlags::MINH,
+    }
+
+    /// The bits of the start_flags parameter of sbi_pmu_counter_start.
+    pub enum PmuStartFlag: u64 {
+        /// Sets the counters to the passed initial value before starting them.
+        InitValue = PmuCounterStartFlags::INIT_VALUE,
+        /// Sets the counters to their values in the snapshot shared memory before starting them.
+        InitSnapshot = PmuCounterStartFlags::INIT_SNAPSHOT,
+    }
+
+    /// The bits of the stop_flags parameter of sbi_pmu_counter_stop.
+    pub enum PmuStopFlag: u64 {
+        /// Resets the counters after stopping them.
+        Reset = PmuCounterStopFlags::RESET,
+        /// Saves the counter values to the snapshot shared memory after stopping them.
+        TakeSnapshot = PmuCounterStopFlags::TAKE_SNAPSHOT,
+    }
 }
 
 /// This encapsulates the bit-fields for PMU config_flags parameter as described in the SBI documentation
 /// for sbi_pmu_counter_config_matching
-#[derive(Copy, Clone, Default, Debug)]
+///
+/// ```
+/// use sbi_rs::*;
+///
+/// let flags = PmuCounterConfigFlags::new(PmuConfigFlag::ClearValue | PmuConfigFlag::Vsinh);
+/// assert_eq!(flags.raw(), 0x12);
+/// assert!(flags.flags().contains(PmuConfigFlag::Vsinh));
+/// assert_eq!(PmuCounterConfigFlags::from_raw_value(0x12), Ok(flags));
+/// assert_eq!(PmuCounterConfigFlags::default().set_clear_value().set_vsinh(), flags);
+/// assert_eq!(
+///     PmuCounterConfigFlags::from_raw_value(0x100),
+///     Err(Error::InvalidParam)
+/// );
+/// ```
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 pub struct PmuCounterConfigFlags(u64);
 
-#[derive(Copy, Clone)]
-#[repr(u64)]
-enum ConfigFlagsValues {
-    SkipMatch = 1,
-    ClearValue = (1 << 1),
-    AutoStart = (1 << 2),
-    Vuinh = (1 << 3),
-    Vsinh = (1 << 4),
-    Uinh = (1 << 5),
-    Sinh = (1 << 6),
-    Minh = (1 << 7),
-}
-
 impl PmuCounterConfigFlags {
-    /// Constructs a new PmuCounterConfigFlags from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
-        const CONFIG_FLAG_INVERSE_MASK: u64 = !0xEF;
-        if value & CONFIG_FLAG_INVERSE_MASK == 0 {
+    const SKIP_MATCH: u64 = 1;
+    const CLEAR_VALUE: u64 = 1 << 1;
+    const AUTO_START: u64 = 1 << 2;
+    const VUINH: u64 = 1 << 3;
+    const VSINH: u64 = 1 << 4;
+    const UINH: u64 = 1 << 5;
+    const SINH: u64 = 1 << 6;
+    const MINH: u64 = 1 << 7;
+    /// The bits of config_flags that are defined by the SBI specification.
+    const VALID_MASK: u64 = 0xff;
+
+    /// Constructs a new PmuCounterConfigFlags with `flags` set.
+    pub fn new(flags: impl Into<FlagSet<PmuConfigFlag>>) -> Self {
+        PmuCounterConfigFlags(flags.into().bits())
+    }
+
+    /// Constructs a new PmuCounterConfigFlags from a valid passed-in value. Returns
+    /// `Error::InvalidParam` if any reserved bits are set.
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
+        if value & !Self::VALID_MASK == 0 {
             Ok(PmuCounterConfigFlags(value))
         } else {
             Err(Error::InvalidParam)
@@ -84,163 +146,222 @@ impl PmuCounterConfigFlags {
     }
 
     /// Returns the raw inner value.
-    pub fn raw(&self) -> u64 {
+    pub const fn raw(&self) -> u64 {
         self.0
     }
 
+    /// Returns the set flags.
+    pub fn flags(&self) -> FlagSet<PmuConfigFlag> {
+        FlagSet::new_truncated(self.0)
+    }
+
     /// Sets the skip_match bit-flag (skips counter matching).
-    pub fn set_skip_match(self) -> Self {
-        PmuCounterConfigFlags(self.0 | SkipMatch as u64)
+    pub const fn set_skip_match(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::SKIP_MATCH)
     }
 
     /// Returns if the skip_match bit-flag is set.
-    pub fn is_skip_match(&self) -> bool {
-        self.0 & SkipMatch as u64 != 0
+    pub const fn is_skip_match(&self) -> bool {
+        self.0 & Self::SKIP_MATCH != 0
     }
 
-    /// Returns if the skip_match bit-flag is set.
-    pub fn unset_skip_match(&self) -> Self {
-        PmuCounterConfigFlags(self.0 & !(SkipMatch as u64))
+    /// Clears the skip_match bit-flag.
+    pub const fn unset_skip_match(&self) -> Self {
+        PmuCounterConfigFlags(self.0 & !Self::SKIP_MATCH)
     }
 
     /// Sets the clear_value bit-flag (clears the counter value).
-    pub fn set_clear_value(self) -> Self {
-        PmuCounterConfigFlags(self.0 | (ClearValue as u64))
+    pub const fn set_clear_value(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::CLEAR_VALUE)
     }
 
     /// Returns if the clear_value bit-flag is set.
-    pub fn is_clear_value(&self) -> bool {
-        self.0 & (ClearValue as u64) != 0
+    pub const fn is_clear_value(&self) -> bool {
+        self.0 & Self::CLEAR_VALUE != 0
     }
 
-    /// Returns if the skip_match bit-flag is set.
-    pub fn unset_clear_value(&self) -> Self {
-        PmuCounterConfigFlags(self.0 & !(ClearValue as u64))
+    /// Clears the clear_value bit-flag.
+    pub const fn unset_clear_value(&self) -> Self {
+        PmuCounterConfigFlags(self.0 & !Self::CLEAR_VALUE)
     }
 
     /// Sets the auto_start bit-flag (automatically starts the counter).
-    pub fn set_auto_start(self) -> Self {
-        PmuCounterConfigFlags(self.0 | (AutoStart as u64))
+    pub const fn set_auto_start(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::AUTO_START)
     }
 
     /// Returns if the auto_start bit-flag is set.
-    pub fn is_auto_start(&self) -> bool {
-        self.0 & (AutoStart as u64) != 0
+    pub const fn is_auto_start(&self) -> bool {
+        self.0 & Self::AUTO_START != 0
     }
 
-    /// Returns if the auto_start bit-flag is set.
-    pub fn unset_auto_start(&self) -> Self {
-        PmuCounterConfigFlags(self.0 & !(AutoStart as u64))
+    /// Clears the auto_start bit-flag.
+    pub const fn unset_auto_start(&self) -> Self {
+        PmuCounterConfigFlags(self.0 & !Self::AUTO_START)
     }
 
     /// Sets the vuinh bit-flag (inhibit counter in VU-mode).
-    pub fn set_vuinh(self) -> Self {
-        PmuCounterConfigFlags(self.0 | (Vuinh as u64))
+    pub const fn set_vuinh(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::VUINH)
     }
 
     /// Returns if the vuinh bit-flag is set.
-    pub fn is_vuinh(&self) -> bool {
-        self.0 & (Vuinh as u64) != 0
+    pub const fn is_vuinh(&self) -> bool {
+        self.0 & Self::VUINH != 0
     }
 
     /// Sets the vsinh bit-flag (inhibit counter in VS-mode).
-    pub fn set_vsinh(self) -> Self {
-        PmuCounterConfigFlags(self.0 | (Vsinh as u64))
+    pub const fn set_vsinh(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::VSINH)
     }
 
     /// Returns if the vsinh bit-flag is set.
-    pub fn is_vsinh(&self) -> bool {
-        self.0 & (Vsinh as u64) != 0
+    pub const fn is_vsinh(&self) -> bool {
+        self.0 & Self::VSINH != 0
     }
 
     /// Sets the uinh bit-flag (inhibit counter in U-mode).
-    pub fn set_uinh(self) -> Self {
-        PmuCounterConfigFlags(self.0 | (Uinh as u64))
+    pub const fn set_uinh(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::UINH)
     }
 
     /// Returns if the uinh bit-flag is set.
-    pub fn is_uinh(&self) -> bool {
-        self.0 & (Uinh as u64) != 0
+    pub const fn is_uinh(&self) -> bool {
+        self.0 & Self::UINH != 0
     }
 
     /// Sets the sinh bit-flag (inhibit counter in S-mode).
-    pub fn set_sinh(self) -> Self {
-        PmuCounterConfigFlags(self.0 | (Sinh as u64))
+    pub const fn set_sinh(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::SINH)
     }
 
     /// Returns if the sinh bit-flag is set.
-    pub fn is_sinh(&self) -> bool {
-        self.0 & (Sinh as u64) != 0
+    pub const fn is_sinh(&self) -> bool {
+        self.0 & Self::SINH != 0
     }
 
     /// Sets the minh bit-flag (inhibit counter in M-mode).
-    pub fn set_minh(self) -> Self {
-        PmuCounterConfigFlags(self.0 | (Minh as u64))
+    pub const fn set_minh(self) -> Self {
+        PmuCounterConfigFlags(self.0 | Self::MINH)
     }
 
     /// Returns if the minh bit-flag is set.
-    pub fn is_minh(&self) -> bool {
-        self.0 & (Minh as u64) != 0
+    pub const fn is_minh(&self) -> bool {
+        self.0 & Self::MINH != 0
     }
 }
 
 /// This encapsulates the bit-fields for PMU start_flags parameter as described in the SBI documentation
 /// for sbi_pmu_counter_start
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct PmuCounterStartFlags(u64);
 
 impl PmuCounterStartFlags {
-    /// Constructs a new PmuCounterStartFlags from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
-        match value {
-            0 | 1 => Ok(PmuCounterStartFlags(value)),
-            _ => Err(Error::InvalidParam),
+    const INIT_VALUE: u64 = 1;
+    const INIT_SNAPSHOT: u64 = 1 << 1;
+    /// The bits of start_flags that are defined by the SBI specification.
+    const VALID_MASK: u64 = 0x3;
+
+    /// Constructs a new PmuCounterStartFlags with `flags` set.
+    pub fn new(flags: impl Into<FlagSet<PmuStartFlag>>) -> Self {
+        PmuCounterStartFlags(flags.into().bits())
+    }
+
+    /// Constructs a new PmuCounterStartFlags from a valid passed-in value. Returns
+    /// `Error::InvalidParam` if any reserved bits are set.
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
+        if value & !Self::VALID_MASK == 0 {
+            Ok(PmuCounterStartFlags(value))
+        } else {
+            Err(Error::InvalidParam)
         }
     }
 
     /// Returns the raw value of the inner bit-flag field.
-    pub fn raw(&self) -> u64 {
+    pub const fn raw(&self) -> u64 {
         self.0
     }
 
+    /// Returns the set flags.
+    pub fn flags(&self) -> FlagSet<PmuStartFlag> {
+        FlagSet::new_truncated(self.0)
+    }
+
     /// Sets the set_init_value bit-flag (set initial counter value).
-    pub fn set_init_value(self) -> Self {
-        PmuCounterStartFlags(self.0 | 1)
+    pub const fn set_init_value(self) -> Self {
+        PmuCounterStartFlags(self.0 | Self::INIT_VALUE)
     }
 
     /// Returns if set_init_value bit-flag is set.
-    pub fn is_init_value(&self) -> bool {
-        self.0 & 1 != 0
+    pub const fn is_init_value(&self) -> bool {
+        self.0 & Self::INIT_VALUE != 0
+    }
+
+    /// Sets the init_snapshot bit-flag (set initial counter values from the snapshot).
+    pub const fn set_init_snapshot(self) -> Self {
+        PmuCounterStartFlags(self.0 | Self::INIT_SNAPSHOT)
+    }
+
+    /// Returns if the init_snapshot bit-flag is set.
+    pub const fn is_init_snapshot(&self) -> bool {
+        self.0 & Self::INIT_SNAPSHOT != 0
     }
 }
 
 /// This encapsulates the bit-fields for PMU stop_flags parameter as described in the SBI documentation
 /// for sbi_pmu_counter_stop
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct PmuCounterStopFlags(u64);
 
 impl PmuCounterStopFlags {
-    /// Constructs a new PmuCounterStopFlags from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
-        match value {
-            0 | 1 => Ok(PmuCounterStopFlags(value)),
-            _ => Err(Error::InvalidParam),
+    const RESET: u64 = 1;
+    const TAKE_SNAPSHOT: u64 = 1 << 1;
+    /// The bits of stop_flags that are defined by the SBI specification.
+    const VALID_MASK: u64 = 0x3;
+
+    /// Constructs a new PmuCounterStopFlags with `flags` set.
+    pub fn new(flags: impl Into<FlagSet<PmuStopFlag>>) -> Self {
+        PmuCounterStopFlags(flags.into().bits())
+    }
+
+    /// Constructs a new PmuCounterStopFlags from a valid passed-in value. Returns
+    /// `Error::InvalidParam` if any reserved bits are set.
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
+        if value & !Self::VALID_MASK == 0 {
+            Ok(PmuCounterStopFlags(value))
+        } else {
+            Err(Error::InvalidParam)
         }
     }
 
     /// Returns the raw value of the inner bit-flag field.
-    pub fn raw(&self) -> u64 {
+    pub const fn raw(&self) -> u64 {
         self.0
     }
 
+    /// Returns the set flags.
+    pub fn flags(&self) -> FlagSet<PmuStopFlag> {
+        FlagSet::new_truncated(self.0)
+    }
+
     /// Sets the stop_reset bit-flag (resets the counter after stopping).
-    pub fn set_reset_flag(self) -> Self {
-        PmuCounterStopFlags(self.0 | 1)
+    pub const fn set_reset_flag(self) -> Self {
+        PmuCounterStopFlags(self.0 | Self::RESET)
     }
 
     /// Returns if the stop_reset bit flag is set.
-    pub fn is_reset_flag(&self) -> bool {
-        self.0 & 1 != 0
+    pub const fn is_reset_flag(&self) -> bool {
+        self.0 & Self::RESET != 0
+    }
+
+    /// Sets the take_snapshot bit-flag (saves the counter values to the snapshot after stopping).
+    pub const fn set_take_snapshot(self) -> Self {
+        PmuCounterStopFlags(self.0 | Self::TAKE_SNAPSHOT)
+    }
+
+    /// Returns if the take_snapshot bit-flag is set.
+    pub const fn is_take_snapshot(&self) -> bool {
+        self.0 & Self::TAKE_SNAPSHOT != 0
     }
 }
 
@@ -250,38 +371,100 @@ pub struct PmuCounterInfo(u64);
 
 impl PmuCounterInfo {
     /// Constructs a PmuCounterInfo from the passed in value.
-    pub fn new(value: u64) -> Self {
+    pub const fn new(value: u64) -> Self {
         PmuCounterInfo(value)
     }
 
     /// Returns the inner value.
-    pub fn raw(&self) -> u64 {
+    pub const fn raw(&self) -> u64 {
         self.0
     }
 
     /// Returns if the counter is a hardware counter.
-    pub fn is_hardware_counter(&self) -> bool {
+    pub const fn is_hardware_counter(&self) -> bool {
         self.0 & (1 << 63) == 0
     }
 
     /// Returns if the counter is a firmware counter.
-    pub fn is_firmware_counter(&self) -> bool {
+    pub const fn is_firmware_counter(&self) -> bool {
         !self.is_hardware_counter()
     }
 
     /// Returns the 12-bit CSR number associated with the counter.
-    pub fn get_csr(&self) -> u64 {
+    pub const fn get_csr(&self) -> u64 {
         self.0 & 0xFFF
     }
 
     /// Returns the counter width (one less than number of CSR-bits).
-    pub fn get_counter_width(&self) -> u64 {
+    pub const fn get_counter_width(&self) -> u64 {
         (self.0 >> 12) & 0x3F
     }
 }
 
+/// The maximum number of counters described by a `PmuSnapshot`.
+pub const PMU_SNAPSHOT_MAX_COUNTERS: usize = 64;
+
+/// The layout of the 4kB PMU snapshot shared memory area defined by the SBI PMU extension, in
+/// which the SBI implementation saves counter state when counters are stopped.
+///
+/// With the `zerocopy` feature, a snapshot area can be read straight from the page that backs it:
+///
+/// ```
+/// # #[cfg(feature = "zerocopy")] {
+/// use sbi_rs::PmuSnapshot;
+/// use zerocopy::{AsBytes, FromBytes};
+///
+/// let mut page = [0u8; 4096];
+/// page[..8].copy_from_slice(&0b100u64.to_le_bytes());
+/// let snapshot = PmuSnapshot::ref_from(&page[..]).unwrap();
+/// assert_eq!(u64::from_le(snapshot.counter_overflow_bitmap), 0b100);
+/// assert_eq!(snapshot.as_bytes(), &page[..]);
+/// # }
+/// ```
+#[repr(C)]
 #[derive(Copy, Clone, Debug)]
-/// Enumeration of the event types.
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+pub struct PmuSnapshot {
+    /// Bit `i` is set if counter `i` has overflowed.
+    pub counter_overflow_bitmap: u64,
+    /// The value of each counter, indexed by counter index.
+    pub counter_values: [u64; PMU_SNAPSHOT_MAX_COUNTERS],
+    _reserved: [u64; 447],
+}
+
+impl Default for PmuSnapshot {
+    fn default() -> Self {
+        Self {
+            counter_overflow_bitmap: 0,
+            counter_values: [0; PMU_SNAPSHOT_MAX_COUNTERS],
+            _reserved: [0; 447],
+        }
+    }
+}
+
+/// Enumeration of the event types, encoded in the `event_idx` argument of
+/// `sbi_pmu_counter_config_matching` as a 4-bit type in bits 16-19 above a 16-bit event code.
+///
+/// ```
+/// use sbi_rs::*;
+///
+/// let event = PmuEventType::Cache(PmuHwCacheParams::new(
+///     PmuHwCache::L1DataCache,
+///     PmuHwCacheOpId::Write,
+///     PmuHwCacheResultId::Miss,
+/// ));
+/// assert_eq!(event.raw(), 0x1_0003);
+/// assert_eq!(PmuEventType::from_raw_value(0x1_0003), Ok(event));
+/// assert_eq!(
+///     PmuEventType::from_raw_value(0xf_0005),
+///     Ok(PmuEventType::Firmware(PmuFirmware::SetTimer))
+/// );
+/// assert_eq!(PmuEventType::from_raw_value(0x3_0000), Err(Error::InvalidParam));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PmuEventType {
     /// Represents the hardware general events (type #0) in the SBI documentation.
     Hardware(PmuHardware),
@@ -302,41 +485,46 @@ impl Default for PmuEventType {
 const EVENT_TYPE_SHIFT: u64 = 16;
 impl PmuEventType {
     /// Returns the encoded representation of the type.
-    pub fn raw(&self) -> u64 {
+    pub const fn raw(&self) -> u64 {
         const HARDWARE_CACHE_EVENT_TYPE: u64 = 1;
         const HARDWARE_RAW_EVENT_TYPE: u64 = 2;
         const FIRMWARE_EVENT_TYPE: u64 = 0xF;
         use PmuEventType::*;
         match self {
-            Hardware(p) => *p as u64,
+            Hardware(p) => p.raw(),
             Cache(p) => p.raw() | (HARDWARE_CACHE_EVENT_TYPE << EVENT_TYPE_SHIFT),
             RawEvent => HARDWARE_RAW_EVENT_TYPE << EVENT_TYPE_SHIFT,
-            Firmware(p) => *p as u64 | (FIRMWARE_EVENT_TYPE << EVENT_TYPE_SHIFT),
+            Firmware(p) => p.raw() | (FIRMWARE_EVENT_TYPE << EVENT_TYPE_SHIFT),
         }
     }
 
     /// Constructs PmuEventType from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
         use PmuEventType::*;
         const EVENT_TYPE_INVERSE_MASK: u64 = !(0xF << EVENT_TYPE_SHIFT);
+        let code = value & EVENT_TYPE_INVERSE_MASK;
+        // `?` and `Result::map` aren't available in const fns.
         match value >> EVENT_TYPE_SHIFT {
-            0 => Ok(Hardware(PmuHardware::from_raw_value(
-                value & EVENT_TYPE_INVERSE_MASK,
-            )?)),
-            1 => Ok(Cache(PmuHwCacheParams::from_raw_value(
-                value & EVENT_TYPE_INVERSE_MASK,
-            )?)),
-            2 if (value & EVENT_TYPE_INVERSE_MASK) == 0 => Ok(RawEvent),
-            0xF => Ok(Firmware(PmuFirmware::from_raw_value(
-                value & EVENT_TYPE_INVERSE_MASK,
-            )?)),
+            0 => match PmuHardware::from_raw_value(code) {
+                Ok(p) => Ok(Hardware(p)),
+                Err(e) => Err(e),
+            },
+            1 => match PmuHwCacheParams::from_raw_value(code) {
+                Ok(p) => Ok(Cache(p)),
+                Err(e) => Err(e),
+            },
+            2 if code == 0 => Ok(RawEvent),
+            0xF => match PmuFirmware::from_raw_value(code) {
+                Ok(p) => Ok(Firmware(p)),
+                Err(e) => Err(e),
+            },
             _ => Err(Error::InvalidParam),
         }
     }
 }
 
 /// Enumeration of the hardware event types.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u64)]
 pub enum PmuHardware {
     /// Identifier for CPU cycle events.
@@ -357,13 +545,18 @@ pub enum PmuHardware {
     StalledCyclesFrontEnd = 8,
     /// Identifier for stalled back-end cycle events.
     StalledCyclesBackEnd = 9,
-    /// Identifier for reference CPU cycle  events.
+    /// Identifier for reference CPU cycle events.
     ReferenceCpuCycles = 10,
 }
 
 impl PmuHardware {
+    /// Returns the event code of the event.
+    pub const fn raw(&self) -> u64 {
+        *self as u64
+    }
+
     /// Constructs PmuHardware from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
         use PmuHardware::*;
         match value {
             1 => Ok(CpuCycles),
@@ -382,7 +575,7 @@ impl PmuHardware {
 }
 
 /// Enumeration of cache event types (for use with PmuHardware of type CacheReferences/CacheMisses).
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u64)]
 pub enum PmuHwCache {
     /// Identifier for first level data cache.
@@ -403,7 +596,7 @@ pub enum PmuHwCache {
 
 impl PmuHwCache {
     /// Constructs PmuHwCache from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
         use PmuHwCache::*;
         match value {
             0 => Ok(L1DataCache),
@@ -419,7 +612,7 @@ impl PmuHwCache {
 }
 
 /// Enumeration of cache op_ids (for use with PmuHardware of type CacheReferences/CacheMisses).
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u64)]
 pub enum PmuHwCacheOpId {
     /// Identifier for a cache read op_id.
@@ -432,7 +625,7 @@ pub enum PmuHwCacheOpId {
 
 impl PmuHwCacheOpId {
     /// Constructs PmuHwCacheOpId from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
         use PmuHwCacheOpId::*;
         match value {
             0 => Ok(Read),
@@ -444,29 +637,29 @@ impl PmuHwCacheOpId {
 }
 
 /// Enumeration of results returned by cache counter reads.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u64)]
 pub enum PmuHwCacheResultId {
-    /// Cache miss.
-    CacheMiss = 0,
-    /// Cache hit.
-    CacheHit = 1,
+    /// The access was counted, whether it hit or missed (`SBI_PMU_HW_CACHE_RESULT_ACCESS`).
+    Access = 0,
+    /// The access missed in the cache (`SBI_PMU_HW_CACHE_RESULT_MISS`).
+    Miss = 1,
 }
 
 impl PmuHwCacheResultId {
     /// Constructs PmuHwCacheResultId from a valid passed-in value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
         use PmuHwCacheResultId::*;
         match value {
-            0 => Ok(CacheMiss),
-            1 => Ok(CacheHit),
+            0 => Ok(Access),
+            1 => Ok(Miss),
             _ => Err(Error::InvalidParam),
         }
     }
 }
 
 /// Structure to encapsulate parameters for PmuHardware of type CacheReferences/CacheMisses).
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct PmuHwCacheParams {
     cache_id: PmuHwCache,
     op_id: PmuHwCacheOpId,
@@ -475,7 +668,11 @@ pub struct PmuHwCacheParams {
 
 impl PmuHwCacheParams {
     /// Constructs PmuHwCacheParams with the passed-in parameters.
-    pub fn new(cache_id: PmuHwCache, op_id: PmuHwCacheOpId, result_id: PmuHwCacheResultId) -> Self {
+    pub const fn new(
+        cache_id: PmuHwCache,
+        op_id: PmuHwCacheOpId,
+        result_id: PmuHwCacheResultId,
+    ) -> Self {
         Self {
             cache_id,
             op_id,
@@ -484,23 +681,46 @@ impl PmuHwCacheParams {
     }
 
     /// Constructs PmuHwCacheParams from a valid encoded value.
-    pub fn from_raw_value(value: u64) -> Result<Self> {
-        Ok(Self {
-            cache_id: PmuHwCache::from_raw_value(value >> 3)?,
-            op_id: PmuHwCacheOpId::from_raw_value((value >> 1) & 3)?,
-            result_id: PmuHwCacheResultId::from_raw_value(value & 1)?,
-        })
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
+        // `?` isn't available in const fns.
+        match (
+            PmuHwCache::from_raw_value(value >> 3),
+            PmuHwCacheOpId::from_raw_value((value >> 1) & 3),
+            PmuHwCacheResultId::from_raw_value(value & 1),
+        ) {
+            (Ok(cache_id), Ok(op_id), Ok(result_id)) => Ok(Self {
+                cache_id,
+                op_id,
+                result_id,
+            }),
+            _ => Err(Error::InvalidParam),
+        }
     }
 
     /// Returns the encoded value corresponding to the inner values.
-    pub fn raw(&self) -> u64 {
+    pub const fn raw(&self) -> u64 {
         (self.result_id as u64) | ((self.op_id as u64) << 1) | ((self.cache_id as u64) << 3)
     }
+
+    /// Returns the cache the event counts accesses to.
+    pub const fn cache_id(&self) -> PmuHwCache {
+        self.cache_id
+    }
+
+    /// Returns the type of access counted.
+    pub const fn op_id(&self) -> PmuHwCacheOpId {
+        self.op_id
+    }
+
+    /// Returns whether hits or misses are counted.
+    pub const fn result_id(&self) -> PmuHwCacheResultId {
+        self.result_id
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
-#[repr(u64)]
 /// Enumeration of the firmware event types.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u64)]
 pub enum PmuFirmware {
     /// Misaligned load trap event.
     MisalignedLoad = 0,
@@ -532,24 +752,32 @@ pub enum PmuFirmware {
     SfenceAsidReceived = 13,
     /// HFENCE.GVMA request sent event.
     HfenceGvmaSent = 14,
-    /// H
+    /// HFENCE.GVMA request received event.
     HfenceGvmaReceived = 15,
-    /// HFENCE.GVMA request sent event.
+    /// HFENCE.GVMA with VMID request sent event.
     HfenceVmidSent = 16,
-    /// HFENCE.GVMA request received event.
+    /// HFENCE.GVMA with VMID request received event.
     HfenceVmidReceived = 17,
     /// HFENCE.VVMA request sent event.
     HfenceVvmaSent = 18,
-    /// HFEMA request received event.
+    /// HFENCE.VVMA request received event.
     HfenceVvmaReceived = 19,
-    /// HFENCE.ASID request sent event.
+    /// HFENCE.VVMA with ASID request sent event.
     HfenceVvmaAsidSent = 20,
-    /// HFENCE.ASID request received event.
+    /// HFENCE.VVMA with ASID request received event.
     HfenceVvmaAsidReceived = 21,
+    /// Platform-specific firmware event, identified by the `event_data` argument.
+    Platform = 0xffff,
 }
 
 impl PmuFirmware {
-    fn from_raw_value(value: u64) -> Result<Self> {
+    /// Returns the event code of the event.
+    pub const fn raw(&self) -> u64 {
+        *self as u64
+    }
+
+    /// Constructs PmuFirmware from a valid passed-in value.
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
         use PmuFirmware::*;
         match value {
             0 => Ok(MisalignedLoad),
@@ -574,6 +802,7 @@ impl PmuFirmware {
             19 => Ok(HfenceVvmaReceived),
             20 => Ok(HfenceVvmaAsidSent),
             21 => Ok(HfenceVvmaAsidReceived),
+            0xffff => Ok(Platform),
             _ => Err(Error::InvalidParam),
         }
     }
@@ -581,7 +810,8 @@ impl PmuFirmware {
 
 impl PmuFunction {
     /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         use PmuFunction::*;
         match args[6] {
             0 => Ok(GetNumCounters),
@@ -591,13 +821,13 @@ impl PmuFunction {
                 counter_mask: args[1],
                 config_flags: PmuCounterConfigFlags::from_raw_value(args[2])?,
                 event_type: PmuEventType::from_raw_value(args[3])?,
-                event_data: args[4],
+                event_data: join_rv32_reg_pair(args[4], args[5]),
             }),
             3 => Ok(StartCounters {
                 counter_index: args[0],
                 counter_mask: args[1],
                 start_flags: PmuCounterStartFlags::from_raw_value(args[2])?,
-                initial_value: args[3],
+                initial_value: join_rv32_reg_pair(args[3], args[4]),
             }),
             4 => Ok(StopCounters {
                 counter_index: args[0],
@@ -605,12 +835,50 @@ impl PmuFunction {
                 stop_flags: PmuCounterStopFlags::from_raw_value(args[2])?,
             }),
             5 => Ok(ReadFirmwareCounter(args[0])),
+            6 => Ok(ReadFirmwareCounterHigh(args[0])),
             _ => Err(Error::NotSupported),
         }
     }
 }
 
 impl SbiFunction for PmuFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        use PmuFunction::*;
+        let (name, args): (_, &[_]) = match self {
+            GetNumCounters => ("num_counters", &[]),
+            GetCounterInfo(_) => ("counter_get_info", &["counter_index"]),
+            ConfigureMatchingCounters { .. } => (
+                "counter_config_matching",
+                &[
+                    "counter_index",
+                    "counter_mask",
+                    "config_flags",
+                    "event_type",
+                    "event_data_lo",
+                    "event_data_hi",
+                ],
+            ),
+            StartCounters { .. } => (
+                "counter_start",
+                &[
+                    "counter_index",
+                    "counter_mask",
+                    "start_flags",
+                    "initial_value_lo",
+                    "initial_value_hi",
+                ],
+            ),
+            StopCounters { .. } => (
+                "counter_stop",
+                &["counter_index", "counter_mask", "stop_flags"],
+            ),
+            ReadFirmwareCounter(_) => ("counter_fw_read", &["counter_index"]),
+            ReadFirmwareCounterHigh(_) => ("counter_fw_read_hi", &["counter_index"]),
+        };
+        Some(FunctionSignature::new(name, args))
+    }
+
+    #[inline(always)]
     fn a6(&self) -> u64 {
         use PmuFunction::*;
         match self {
@@ -635,13 +903,26 @@ impl SbiFunction for PmuFunction {
                 stop_flags: _,
             } => 4,
             ReadFirmwareCounter(_) => 5,
+            ReadFirmwareCounterHigh(_) => 6,
         }
     }
 
+    #[inline(always)]
     fn a5(&self) -> u64 {
-        0
+        use PmuFunction::*;
+        match self {
+            ConfigureMatchingCounters {
+                counter_index: _,
+                counter_mask: _,
+                config_flags: _,
+                event_type: _,
+                event_data,
+            } => split_reg_pair(*event_data).1,
+            _ => 0,
+        }
     }
 
+    #[inline(always)]
     fn a4(&self) -> u64 {
         use PmuFunction::*;
         match self {
@@ -651,11 +932,18 @@ impl SbiFunction for PmuFunction {
                 config_flags: _,
                 event_type: _,
                 event_data,
-            } => *event_data,
+            } => split_reg_pair(*event_data).0,
+            StartCounters {
+                counter_index: _,
+                counter_mask: _,
+                start_flags: _,
+                initial_value,
+            } => split_reg_pair(*initial_value).1,
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a3(&self) -> u64 {
         use PmuFunction::*;
         match self {
@@ -671,11 +959,12 @@ impl SbiFunction for PmuFunction {
                 counter_mask: _,
                 start_flags: _,
                 initial_value,
-            } => *initial_value,
+            } => split_reg_pair(*initial_value).0,
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a2(&self) -> u64 {
         use PmuFunction::*;
         match self {
@@ -701,6 +990,7 @@ impl SbiFunction for PmuFunction {
         }
     }
 
+    #[inline(always)]
     fn a1(&self) -> u64 {
         use PmuFunction::*;
         match self {
@@ -726,6 +1016,7 @@ impl SbiFunction for PmuFunction {
         }
     }
 
+    #[inline(always)]
     fn a0(&self) -> u64 {
         use PmuFunction::*;
         match self {
@@ -749,14 +1040,16 @@ impl SbiFunction for PmuFunction {
                 stop_flags: _,
             } => *counter_index,
             ReadFirmwareCounter(counter_index) => *counter_index,
+            ReadFirmwareCounterHigh(counter_index) => *counter_index,
             _ => 0,
         }
     }
 
-    fn result(&self, a0: u64, a1: u64) -> Result<u64> {
-        match a0 {
-            0 => Ok(a1),
-            e => Err(Error::from_code(e as i64)),
+    fn return_kind(&self) -> ReturnKind {
+        use PmuFunction::*;
+        match self {
+            StartCounters { .. } | StopCounters { .. } => ReturnKind::Empty,
+            _ => ReturnKind::Value,
         }
     }
 }
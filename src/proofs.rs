@@ -0,0 +1,45 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Kani proof harnesses for the decoding of untrusted register frames. Run with
+//! `cargo kani --features handler`; the harnesses are only built by Kani.
+
+use crate::*;
+
+/// Decoding any register frame returns rather than panicking.
+#[kani::proof]
+fn from_regs_never_panics() {
+    let regs: [u64; 8] = kani::any();
+    let _ = SbiMessage::from_regs(&regs);
+}
+
+/// Strict decoding, which also validates the arguments, returns rather than panicking.
+#[kani::proof]
+fn from_regs_strict_never_panics() {
+    let regs: [u64; 8] = kani::any();
+    let _ = SbiMessage::from_regs_strict(&regs);
+}
+
+/// A decoded message encodes back to the extension and function IDs it was decoded from. Calls
+/// to the legacy PutChar extension don't take a function ID, which encodes as 0.
+#[kani::proof]
+fn from_regs_preserves_ids() {
+    let regs: [u64; 8] = kani::any();
+    if let Ok(msg) = SbiMessage::from_regs(&regs) {
+        let encoded = msg.to_regs();
+        assert_eq!(encoded[7], regs[7]);
+        if regs[7] != EXT_PUT_CHAR {
+            assert_eq!(encoded[6], regs[6]);
+        }
+    }
+}
+
+/// Reading the result of a decoded call from any `a0` and `a1` returns rather than panicking.
+#[kani::proof]
+fn result_never_panics() {
+    let regs: [u64; 8] = kani::any();
+    if let Ok(msg) = SbiMessage::from_regs(&regs) {
+        let _ = msg.result(kani::any(), kani::any());
+    }
+}
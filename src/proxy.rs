@@ -0,0 +1,128 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Framing for tunneling SBI calls over a byte stream such as a virtio console or a serial port.
+//!
+//! Every frame starts with a little-endian `u32` holding the length of the payload that follows.
+//! The first byte of the payload identifies the frame type:
+//!
+//! - `PROXY_FRAME_CALL`: the `a0-a7` registers of an SBI call, as eight little-endian `u64`s.
+//! - `PROXY_FRAME_RETURN`: the `SbiReturn` of a completed call, as a little-endian `i64` error
+//!   code followed by a little-endian `u64` return value.
+
+use crate::error::*;
+use crate::{SbiMessage, SbiReturn};
+
+/// Number of bytes in the length header preceding every frame.
+pub const PROXY_HEADER_BYTES: usize = 4;
+
+/// Frame type for a frame carrying the registers of an SBI call.
+pub const PROXY_FRAME_CALL: u8 = 0;
+
+/// Frame type for a frame carrying the result of an SBI call.
+pub const PROXY_FRAME_RETURN: u8 = 1;
+
+const CALL_PAYLOAD_BYTES: usize = 1 + 8 * 8;
+const RETURN_PAYLOAD_BYTES: usize = 1 + 8 * 2;
+
+/// The maximum number of bytes in an encoded frame, including the header.
+pub const PROXY_MAX_FRAME_BYTES: usize = PROXY_HEADER_BYTES + CALL_PAYLOAD_BYTES;
+
+/// A frame exchanged between a test controller and a device-under-test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyFrame {
+    /// The `a0-a7` registers of an SBI call to be made by the receiver.
+    Call([u64; 8]),
+    /// The result of an SBI call previously sent in a `Call` frame.
+    Return(SbiReturn),
+}
+
+impl ProxyFrame {
+    /// Creates a `Call` frame carrying the registers for `msg`.
+    pub fn call(msg: &SbiMessage) -> Self {
+        ProxyFrame::Call(msg.to_regs())
+    }
+
+    /// Parses the `SbiMessage` carried in a `Call` frame. Returns an error if this is not a
+    /// `Call` frame or if the registers don't describe a valid SBI call.
+    #[cfg(feature = "handler")]
+    pub fn message(&self) -> Result<SbiMessage> {
+        match self {
+            ProxyFrame::Call(regs) => SbiMessage::from_regs(regs),
+            ProxyFrame::Return(_) => Err(Error::InvalidParam),
+        }
+    }
+
+    /// Returns the number of bytes needed to encode this frame, including the header.
+    pub fn encoded_len(&self) -> usize {
+        PROXY_HEADER_BYTES
+            + match self {
+                ProxyFrame::Call(_) => CALL_PAYLOAD_BYTES,
+                ProxyFrame::Return(_) => RETURN_PAYLOAD_BYTES,
+            }
+    }
+
+    /// Encodes this frame into `buf`, returning the number of bytes written. Returns an error if
+    /// `buf` is too small to hold the frame.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.encoded_len();
+        let frame = buf
+            .get_mut(..len)
+            .ok_or(Error::InsufficientBufferCapacity)?;
+        let (header, payload) = frame.split_at_mut(PROXY_HEADER_BYTES);
+        header.copy_from_slice(&((len - PROXY_HEADER_BYTES) as u32).to_le_bytes());
+        let (frame_type, words) = payload.split_at_mut(1);
+        match self {
+            ProxyFrame::Call(regs) => {
+                frame_type[0] = PROXY_FRAME_CALL;
+                for (chunk, reg) in words.chunks_exact_mut(8).zip(regs.iter()) {
+                    chunk.copy_from_slice(&reg.to_le_bytes());
+                }
+            }
+            ProxyFrame::Return(ret) => {
+                frame_type[0] = PROXY_FRAME_RETURN;
+                words[..8].copy_from_slice(&ret.error_code.to_le_bytes());
+                words[8..].copy_from_slice(&ret.return_value.to_le_bytes());
+            }
+        }
+        Ok(len)
+    }
+
+    /// Attempts to decode a frame from the start of `buf`. Returns the frame along with the
+    /// number of bytes it occupied, or `None` if `buf` doesn't yet hold a complete frame. Returns
+    /// an error if the frame is malformed.
+    pub fn decode(buf: &[u8]) -> Result<Option<(Self, usize)>> {
+        let Some(header) = buf.get(..PROXY_HEADER_BYTES) else {
+            return Ok(None);
+        };
+        // Unwrap ok: `header` is exactly `PROXY_HEADER_BYTES` long.
+        let payload_len = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+        if payload_len != CALL_PAYLOAD_BYTES && payload_len != RETURN_PAYLOAD_BYTES {
+            return Err(Error::InvalidParam);
+        }
+        let len = PROXY_HEADER_BYTES + payload_len;
+        let Some(payload) = buf.get(PROXY_HEADER_BYTES..len) else {
+            return Ok(None);
+        };
+        let word = |i: usize| {
+            // Unwrap ok: the payload length was checked against the frame type below.
+            u64::from_le_bytes(payload[1 + i * 8..1 + (i + 1) * 8].try_into().unwrap())
+        };
+        let frame = match (payload[0], payload_len) {
+            (PROXY_FRAME_CALL, CALL_PAYLOAD_BYTES) => {
+                let mut regs = [0; 8];
+                for (i, reg) in regs.iter_mut().enumerate() {
+                    *reg = word(i);
+                }
+                ProxyFrame::Call(regs)
+            }
+            (PROXY_FRAME_RETURN, RETURN_PAYLOAD_BYTES) => ProxyFrame::Return(SbiReturn {
+                error_code: word(0) as i64,
+                return_value: word(1),
+            }),
+            _ => return Err(Error::InvalidParam),
+        };
+        Ok(Some((frame, len)))
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Decoding of calls to vendor and firmware extensions registered at runtime, for SBI
+//! implementations that load their extensions from policy or plugins rather than compiling them
+//! into a `PlatformSbiMessage`.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use crate::consts::*;
+use crate::error::*;
+use crate::function::*;
+use crate::platform::function_regs;
+use crate::SbiMessage;
+
+/// Decodes the calls to an extension registered with an `ExtensionRegistry`. Implemented by
+/// closures taking the caller's `a0-a7`.
+pub trait DynExtension {
+    /// Attempts to parse a call from `a0-a7`. `a7` is known to be the extension ID the decoder is
+    /// registered for; returns `Error::NotSupported` if the function isn't implemented.
+    fn decode(&self, args: &[u64; 8]) -> Result<Box<dyn SbiFunction>>;
+}
+
+impl<F> DynExtension for F
+where
+    F: Fn(&[u64; 8]) -> Result<Box<dyn SbiFunction>>,
+{
+    fn decode(&self, args: &[u64; 8]) -> Result<Box<dyn SbiFunction>> {
+        self(args)
+    }
+}
+
+/// The decoders of the vendor and firmware extensions known at runtime, keyed by extension ID.
+///
+/// ```rust
+/// use sbi_rs::*;
+///
+/// struct Ping;
+///
+/// impl SbiFunction for Ping {
+///     fn signature(&self) -> Option<FunctionSignature> {
+///         Some(FunctionSignature::new("ping", &[]))
+///     }
+/// }
+///
+/// let mut registry = ExtensionRegistry::new();
+/// registry
+///     .register(0x0900_0001, |args: &[u64; 8]| match args[6] {
+///         0 => Ok(Box::new(Ping) as Box<dyn SbiFunction>),
+///         _ => Err(Error::NotSupported),
+///     })
+///     .unwrap();
+///
+/// let msg = registry.decode(&[0, 0, 0, 0, 0, 0, 0, 0x0900_0001]).unwrap();
+/// # #[cfg(not(feature = "tiny"))]
+/// assert_eq!(msg.to_string(), "0x9000001::ping()");
+/// let msg = registry.decode(&[0, 0, 0, 0, 0, 0, 0, 0x0900_0002]);
+/// assert_eq!(msg.err(), Some(Error::NotSupported));
+/// ```
+#[derive(Default)]
+pub struct ExtensionRegistry {
+    decoders: BTreeMap<u64, Box<dyn DynExtension>>,
+}
+
+impl ExtensionRegistry {
+    /// Creates a registry with no extensions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `decoder` for calls to the extension with ID `eid`. Returns
+    /// `Error::InvalidParam` if `eid` is outside the vendor and firmware ranges, and
+    /// `Error::AlreadyAvailable` if a decoder is already registered for it.
+    pub fn register<D: DynExtension + 'static>(&mut self, eid: u64, decoder: D) -> Result<()> {
+        if !matches!(
+            eid,
+            EXT_VENDOR_START..=EXT_VENDOR_END | EXT_FIRMWARE_START..=EXT_FIRMWARE_END
+        ) {
+            return Err(Error::InvalidParam);
+        }
+        if self.decoders.contains_key(&eid) {
+            return Err(Error::AlreadyAvailable);
+        }
+        self.decoders.insert(eid, Box::new(decoder));
+        Ok(())
+    }
+
+    /// Removes the decoder registered for the extension with ID `eid`, returning it if there was
+    /// one.
+    pub fn unregister(&mut self, eid: u64) -> Option<Box<dyn DynExtension>> {
+        self.decoders.remove(&eid)
+    }
+
+    /// Returns if a decoder is registered for the extension with ID `eid`.
+    pub fn is_registered(&self, eid: u64) -> bool {
+        self.decoders.contains_key(&eid)
+    }
+
+    /// Creates a message from the given GPRs, decoding calls to registered extensions with their
+    /// decoder and all other calls as `SbiMessage::from_regs()` does. Calls to other extensions in
+    /// the vendor and firmware ranges fail with `Error::NotSupported`.
+    #[cfg(feature = "handler")]
+    pub fn decode(&self, args: &[u64; 8]) -> Result<DynSbiMessage> {
+        match args[7] {
+            EXT_VENDOR_START..=EXT_VENDOR_END | EXT_FIRMWARE_START..=EXT_FIRMWARE_END => {
+                let decoder = self.decoders.get(&args[7]).ok_or(Error::NotSupported)?;
+                decoder
+                    .decode(args)
+                    .map(|func| DynSbiMessage::Registered { eid: args[7], func })
+            }
+            _ => SbiMessage::from_regs(args).map(DynSbiMessage::Standard),
+        }
+    }
+}
+
+/// An SBI call decoded by an `ExtensionRegistry`.
+pub enum DynSbiMessage {
+    /// A call to an extension defined by the SBI specification or known to this crate.
+    Standard(SbiMessage),
+    /// A call to a registered extension.
+    Registered {
+        /// The extension ID of the call.
+        eid: u64,
+        /// The function called, as decoded by the extension's decoder.
+        func: Box<dyn SbiFunction>,
+    },
+}
+
+impl DynSbiMessage {
+    /// Returns the extension ID of the call, as passed in a7.
+    pub fn extension_id(&self) -> u64 {
+        match self {
+            Self::Standard(msg) => msg.a7(),
+            Self::Registered { eid, .. } => *eid,
+        }
+    }
+
+    /// Returns the function ID of the call, as passed in a6.
+    pub fn function_id(&self) -> u64 {
+        match self {
+            Self::Standard(msg) => msg.a6(),
+            Self::Registered { func, .. } => func.a6(),
+        }
+    }
+
+    /// Returns the registers to be passed to the ECALL instruction for this message.
+    pub fn to_regs(&self) -> [u64; 8] {
+        match self {
+            Self::Standard(msg) => msg.to_regs(),
+            Self::Registered { eid, func } => function_regs(func.as_ref(), *eid),
+        }
+    }
+
+    /// Returns the result of the call from the values left in `a0` and `a1` by the ECALL
+    /// instruction.
+    pub fn result(&self, a0: u64, a1: u64) -> Result<u64> {
+        match self {
+            Self::Standard(msg) => msg.result(a0, a1),
+            Self::Registered { func, .. } => func.result(a0, a1),
+        }
+    }
+}
+
+impl From<SbiMessage> for DynSbiMessage {
+    fn from(msg: SbiMessage) -> Self {
+        Self::Standard(msg)
+    }
+}
+
+/// Displays calls as `PlatformSbiMessage` does.
+impl fmt::Display for DynSbiMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standard(msg) => msg.fmt(f),
+            Self::Registered { eid, func } => fmt_call(f, &format_args!("{eid:#x}"), func.as_ref()),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A binary format for recording the SBI calls made by a guest and replaying them against
+//! handler code, for reproducing guest/firmware interaction bugs offline.
+//!
+//! A trace is a `TRACE_HEADER_BYTES` header, holding `TRACE_MAGIC` followed by the format
+//! version as a little-endian `u32`, and then a sequence of `TRACE_RECORD_BYTES` records. Each
+//! record holds, as little-endian 64-bit words, the `a0-a7` registers of a call, the error code
+//! and return value it returned, and the time at which it was made.
+//!
+//! A `Recorder` wraps the `EcallSender` the guest runs against and records every call made
+//! through it. `replay()` runs a recorded trace through a handler and reports the first call it
+//! returned a different result for.
+//!
+//! ```rust
+//! # #[cfg(feature = "caller")] {
+//! use sbi_rs::api::{base, time};
+//! use sbi_rs::replay::{decode_trace, replay, Recorder};
+//! use sbi_rs::*;
+//!
+//! struct Firmware;
+//!
+//! impl EcallSender for Firmware {
+//!     unsafe fn send(&self, regs: &[u64; 8]) -> SbiReturn {
+//!         match regs[7] {
+//!             EXT_BASE => SbiReturn::success(2),
+//!             _ => Error::NotSupported.into(),
+//!         }
+//!     }
+//! }
+//!
+//! let recorder = Recorder::new(&Firmware);
+//! with_ecall_sender(&recorder, || {
+//!     base::get_specification_version().unwrap();
+//!     assert_eq!(time::set_timer(0x1000), Err(Error::NotSupported));
+//! });
+//! let trace = decode_trace(&recorder.to_bytes()).unwrap();
+//! assert_eq!(trace.len(), 2);
+//!
+//! // A handler that has since gained support for the Timer extension.
+//! let handler = |regs: &[u64; 8]| SbiReturn::success(if regs[7] == EXT_BASE { 2 } else { 0 });
+//! let mismatch = replay(&trace, handler).unwrap();
+//! assert_eq!(mismatch.index, 1);
+//! assert_eq!(mismatch.actual, SbiReturn::success(0));
+//! # }
+//! ```
+
+#[cfg(feature = "caller")]
+use core::cell::RefCell;
+use std::vec::Vec;
+
+use crate::error::*;
+use crate::SbiReturn;
+#[cfg(feature = "caller")]
+use crate::{EcallSender, TimeSource, DEFAULT_TIME_SOURCE};
+
+/// The bytes identifying a trace.
+pub const TRACE_MAGIC: [u8; 4] = *b"SBIT";
+
+/// The version of the trace format written by this crate.
+pub const TRACE_VERSION: u32 = 1;
+
+/// Number of bytes in the header at the start of every trace.
+pub const TRACE_HEADER_BYTES: usize = 8;
+
+/// Number of bytes in each record of a trace.
+pub const TRACE_RECORD_BYTES: usize = 8 * 11;
+
+/// An SBI call recorded in a trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// The `a0-a7` registers of the call.
+    pub regs: [u64; 8],
+    /// The values returned by the call in `a0` and `a1`.
+    pub ret: SbiReturn,
+    /// The time at which the call was made, or 0 if the recorder has no time source.
+    pub timestamp: u64,
+}
+
+impl TraceRecord {
+    /// Encodes the record.
+    pub fn to_bytes(&self) -> [u8; TRACE_RECORD_BYTES] {
+        let words = self.regs.iter().copied().chain([
+            self.ret.error_code as u64,
+            self.ret.return_value,
+            self.timestamp,
+        ]);
+        let mut bytes = [0; TRACE_RECORD_BYTES];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a record encoded with `to_bytes()`.
+    pub fn from_bytes(bytes: &[u8; TRACE_RECORD_BYTES]) -> Self {
+        let word = |i: usize| {
+            // Unwrap ok: every word lies within the fixed-size record.
+            u64::from_le_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap())
+        };
+        let mut regs = [0; 8];
+        for (i, reg) in regs.iter_mut().enumerate() {
+            *reg = word(i);
+        }
+        Self {
+            regs,
+            ret: SbiReturn {
+                error_code: word(8) as i64,
+                return_value: word(9),
+            },
+            timestamp: word(10),
+        }
+    }
+}
+
+/// Encodes `records` as a trace, header included.
+pub fn encode_trace(records: &[TraceRecord]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(TRACE_HEADER_BYTES + records.len() * TRACE_RECORD_BYTES);
+    bytes.extend_from_slice(&TRACE_MAGIC);
+    bytes.extend_from_slice(&TRACE_VERSION.to_le_bytes());
+    for record in records {
+        bytes.extend_from_slice(&record.to_bytes());
+    }
+    bytes
+}
+
+/// Decodes the records of a trace encoded with `encode_trace()`. Returns `Error::InvalidParam`
+/// if `bytes` doesn't start with a header for this version of the format or doesn't hold a
+/// whole number of records.
+pub fn decode_trace(bytes: &[u8]) -> Result<Vec<TraceRecord>> {
+    let (header, records) = bytes
+        .split_at_checked(TRACE_HEADER_BYTES)
+        .ok_or(Error::InvalidParam)?;
+    if header[..4] != TRACE_MAGIC || header[4..] != TRACE_VERSION.to_le_bytes() {
+        return Err(Error::InvalidParam);
+    }
+    if !records.len().is_multiple_of(TRACE_RECORD_BYTES) {
+        return Err(Error::InvalidParam);
+    }
+    Ok(records
+        .chunks_exact(TRACE_RECORD_BYTES)
+        // Unwrap ok: `chunks_exact()` yields chunks of exactly `TRACE_RECORD_BYTES`.
+        .map(|chunk| TraceRecord::from_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+/// An `EcallSender` that passes every call on to another sender and records it along with its
+/// result.
+#[cfg(feature = "caller")]
+pub struct Recorder<'a> {
+    inner: &'a dyn EcallSender,
+    now: Option<TimeSource>,
+    records: RefCell<Vec<TraceRecord>>,
+}
+
+#[cfg(feature = "caller")]
+impl<'a> Recorder<'a> {
+    /// Creates a recorder passing calls on to `inner`, timestamping them from
+    /// `DEFAULT_TIME_SOURCE`.
+    pub fn new(inner: &'a dyn EcallSender) -> Self {
+        Self {
+            inner,
+            now: DEFAULT_TIME_SOURCE,
+            records: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Sets the time source used to timestamp calls.
+    pub fn with_time_source(self, now: TimeSource) -> Self {
+        Self {
+            now: Some(now),
+            ..self
+        }
+    }
+
+    /// Returns the calls recorded so far.
+    pub fn records(&self) -> Vec<TraceRecord> {
+        self.records.borrow().clone()
+    }
+
+    /// Returns the calls recorded so far, encoded as a trace.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_trace(&self.records.borrow())
+    }
+
+    /// Consumes the recorder, returning the calls it recorded.
+    pub fn into_records(self) -> Vec<TraceRecord> {
+        self.records.into_inner()
+    }
+}
+
+#[cfg(feature = "caller")]
+impl EcallSender for Recorder<'_> {
+    unsafe fn send(&self, regs: &[u64; 8]) -> SbiReturn {
+        let timestamp = self.now.map_or(0, |now| now());
+        let ret = self.inner.send(regs);
+        self.records.borrow_mut().push(TraceRecord {
+            regs: *regs,
+            ret,
+            timestamp,
+        });
+        ret
+    }
+}
+
+/// A recorded call for which the handler returned a different result on replay.
+#[derive(Clone, Copy, Debug)]
+pub struct ReplayMismatch {
+    /// The position of the call in the trace.
+    pub index: usize,
+    /// The recorded call.
+    pub record: TraceRecord,
+    /// The result returned by the handler.
+    pub actual: SbiReturn,
+}
+
+/// Passes the registers of every call in `trace` to `handler` in order, returning the first call
+/// for which it returns a different result from the recorded one, or `None` if it returns the
+/// recorded result for the whole trace. The handler isn't called again after a mismatch, as its
+/// state may no longer match the recorded firmware's.
+pub fn replay<H>(trace: &[TraceRecord], mut handler: H) -> Option<ReplayMismatch>
+where
+    H: FnMut(&[u64; 8]) -> SbiReturn,
+{
+    trace.iter().enumerate().find_map(|(index, record)| {
+        let actual = handler(&record.regs);
+        (actual != record.ret).then_some(ReplayMismatch {
+            index,
+            record: *record,
+            actual,
+        })
+    })
+}
@@ -2,11 +2,12 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+#[cfg(feature = "handler")]
 use crate::error::*;
 use crate::function::*;
 
 /// Functions for the Reset extension
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ResetFunction {
     /// Performs a system reset.
     Reset {
@@ -32,7 +33,8 @@ pub enum ResetType {
 impl ResetType {
     // Creates a reset type from the a0 register value or returns an error if no mapping is
     // known for the given value.
-    fn from_reg(a0: u64) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    const fn from_reg(a0: u64) -> Result<Self> {
         use ResetType::*;
         Ok(match a0 {
             0 => Shutdown,
@@ -56,11 +58,12 @@ pub enum ResetReason {
 impl ResetReason {
     // Creates a reset reason from the a1 register value or returns an error if no mapping is
     // known for the given value.
-    fn from_reg(a1: u64) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    const fn from_reg(a1: u64) -> Result<Self> {
         use ResetReason::*;
         Ok(match a1 {
             0 => NoReason,
-            2 => SystemFailure,
+            1 => SystemFailure,
             _ => return Err(Error::InvalidParam),
         })
     }
@@ -68,7 +71,8 @@ impl ResetReason {
 
 impl ResetFunction {
     /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         use ResetFunction::*;
 
         Ok(match args[6] {
@@ -81,7 +85,7 @@ impl ResetFunction {
     }
 
     /// Creates an operation to shutdown the machine.
-    pub fn shutdown() -> Self {
+    pub const fn shutdown() -> Self {
         ResetFunction::Reset {
             reset_type: ResetType::Shutdown,
             reason: ResetReason::NoReason,
@@ -90,21 +94,34 @@ impl ResetFunction {
 }
 
 impl SbiFunction for ResetFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        Some(FunctionSignature::new(
+            "system_reset",
+            &["reset_type", "reason"],
+        ))
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        ReturnKind::Empty
+    }
+
+    #[inline(always)]
     fn a0(&self) -> u64 {
         match self {
             ResetFunction::Reset {
-                reset_type: _,
-                reason,
-            } => *reason as u64,
+                reset_type,
+                reason: _,
+            } => *reset_type as u64,
         }
     }
 
+    #[inline(always)]
     fn a1(&self) -> u64 {
         match self {
             ResetFunction::Reset {
-                reset_type,
-                reason: _,
-            } => *reset_type as u64,
+                reset_type: _,
+                reason,
+            } => *reason as u64,
         }
     }
 }
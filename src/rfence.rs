@@ -0,0 +1,268 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(feature = "handler")]
+use crate::error::*;
+use crate::function::*;
+use crate::hart_mask::*;
+
+/// Functions for the RFENCE extension.
+///
+/// Functions that take an address range flush the whole address space if both `start_addr` and
+/// `size` are 0, or if `size` is `u64::MAX`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RfenceFunction {
+    /// Executes a `FENCE.I` instruction on the selected harts.
+    ///
+    /// a6 = 0
+    RemoteFenceI {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+    },
+    /// Executes an `SFENCE.VMA` covering the given range of virtual addresses on the selected
+    /// harts.
+    ///
+    /// a6 = 1
+    RemoteSfenceVma {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+        /// a2 = start of the virtual address range
+        start_addr: u64,
+        /// a3 = size of the virtual address range
+        size: u64,
+    },
+    /// Executes an `SFENCE.VMA` covering the given range of virtual addresses for a single ASID
+    /// on the selected harts.
+    ///
+    /// a6 = 2
+    RemoteSfenceVmaAsid {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+        /// a2 = start of the virtual address range
+        start_addr: u64,
+        /// a3 = size of the virtual address range
+        size: u64,
+        /// a4 = address space ID
+        asid: u64,
+    },
+    /// Executes an `HFENCE.GVMA` covering the given range of guest physical addresses for a
+    /// single VMID on the selected harts.
+    ///
+    /// a6 = 3
+    RemoteHfenceGvmaVmid {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+        /// a2 = start of the guest physical address range
+        start_addr: u64,
+        /// a3 = size of the guest physical address range
+        size: u64,
+        /// a4 = virtual machine ID
+        vmid: u64,
+    },
+    /// Executes an `HFENCE.GVMA` covering the given range of guest physical addresses for all
+    /// VMIDs on the selected harts.
+    ///
+    /// a6 = 4
+    RemoteHfenceGvma {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+        /// a2 = start of the guest physical address range
+        start_addr: u64,
+        /// a3 = size of the guest physical address range
+        size: u64,
+    },
+    /// Executes an `HFENCE.VVMA` covering the given range of guest virtual addresses for a single
+    /// ASID of the current VMID on the selected harts.
+    ///
+    /// a6 = 5
+    RemoteHfenceVvmaAsid {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+        /// a2 = start of the guest virtual address range
+        start_addr: u64,
+        /// a3 = size of the guest virtual address range
+        size: u64,
+        /// a4 = address space ID
+        asid: u64,
+    },
+    /// Executes an `HFENCE.VVMA` covering the given range of guest virtual addresses for all
+    /// ASIDs of the current VMID on the selected harts.
+    ///
+    /// a6 = 6
+    RemoteHfenceVvma {
+        /// a0 = hart_mask, a1 = hart_mask_base
+        hart_mask: HartMask,
+        /// a2 = start of the guest virtual address range
+        start_addr: u64,
+        /// a3 = size of the guest virtual address range
+        size: u64,
+    },
+}
+
+impl RfenceFunction {
+    /// Attempts to parse `Self` from the passed in `a0-a7`.
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
+        use RfenceFunction::*;
+        let hart_mask = HartMask::from_raw(args[0], args[1]);
+        let start_addr = args[2];
+        let size = args[3];
+        match args[6] {
+            0 => Ok(RemoteFenceI { hart_mask }),
+            1 => Ok(RemoteSfenceVma {
+                hart_mask,
+                start_addr,
+                size,
+            }),
+            2 => Ok(RemoteSfenceVmaAsid {
+                hart_mask,
+                start_addr,
+                size,
+                asid: args[4],
+            }),
+            3 => Ok(RemoteHfenceGvmaVmid {
+                hart_mask,
+                start_addr,
+                size,
+                vmid: args[4],
+            }),
+            4 => Ok(RemoteHfenceGvma {
+                hart_mask,
+                start_addr,
+                size,
+            }),
+            5 => Ok(RemoteHfenceVvmaAsid {
+                hart_mask,
+                start_addr,
+                size,
+                asid: args[4],
+            }),
+            6 => Ok(RemoteHfenceVvma {
+                hart_mask,
+                start_addr,
+                size,
+            }),
+            _ => Err(Error::NotSupported),
+        }
+    }
+
+    /// Returns the harts selected by the call.
+    pub const fn hart_mask(&self) -> HartMask {
+        use RfenceFunction::*;
+        match *self {
+            RemoteFenceI { hart_mask }
+            | RemoteSfenceVma { hart_mask, .. }
+            | RemoteSfenceVmaAsid { hart_mask, .. }
+            | RemoteHfenceGvmaVmid { hart_mask, .. }
+            | RemoteHfenceGvma { hart_mask, .. }
+            | RemoteHfenceVvmaAsid { hart_mask, .. }
+            | RemoteHfenceVvma { hart_mask, .. } => hart_mask,
+        }
+    }
+
+    /// Returns if the call has no effect: it selects no harts.
+    pub const fn is_noop(&self) -> bool {
+        self.hart_mask().is_empty()
+    }
+}
+
+impl SbiFunction for RfenceFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        use RfenceFunction::*;
+        let (name, args): (_, &[_]) = match self {
+            RemoteFenceI { .. } => ("remote_fence_i", &["hart_mask", "hart_mask_base"]),
+            RemoteSfenceVma { .. } => (
+                "remote_sfence_vma",
+                &["hart_mask", "hart_mask_base", "start_addr", "size"],
+            ),
+            RemoteSfenceVmaAsid { .. } => (
+                "remote_sfence_vma_asid",
+                &["hart_mask", "hart_mask_base", "start_addr", "size", "asid"],
+            ),
+            RemoteHfenceGvmaVmid { .. } => (
+                "remote_hfence_gvma_vmid",
+                &["hart_mask", "hart_mask_base", "start_addr", "size", "vmid"],
+            ),
+            RemoteHfenceGvma { .. } => (
+                "remote_hfence_gvma",
+                &["hart_mask", "hart_mask_base", "start_addr", "size"],
+            ),
+            RemoteHfenceVvmaAsid { .. } => (
+                "remote_hfence_vvma_asid",
+                &["hart_mask", "hart_mask_base", "start_addr", "size", "asid"],
+            ),
+            RemoteHfenceVvma { .. } => (
+                "remote_hfence_vvma",
+                &["hart_mask", "hart_mask_base", "start_addr", "size"],
+            ),
+        };
+        Some(FunctionSignature::new(name, args))
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        ReturnKind::Empty
+    }
+
+    #[inline(always)]
+    fn a6(&self) -> u64 {
+        use RfenceFunction::*;
+        match self {
+            RemoteFenceI { .. } => 0,
+            RemoteSfenceVma { .. } => 1,
+            RemoteSfenceVmaAsid { .. } => 2,
+            RemoteHfenceGvmaVmid { .. } => 3,
+            RemoteHfenceGvma { .. } => 4,
+            RemoteHfenceVvmaAsid { .. } => 5,
+            RemoteHfenceVvma { .. } => 6,
+        }
+    }
+
+    #[inline(always)]
+    fn a0(&self) -> u64 {
+        self.hart_mask().mask()
+    }
+
+    #[inline(always)]
+    fn a1(&self) -> u64 {
+        self.hart_mask().base()
+    }
+
+    #[inline(always)]
+    fn a2(&self) -> u64 {
+        use RfenceFunction::*;
+        match *self {
+            RemoteFenceI { .. } => 0,
+            RemoteSfenceVma { start_addr, .. }
+            | RemoteSfenceVmaAsid { start_addr, .. }
+            | RemoteHfenceGvmaVmid { start_addr, .. }
+            | RemoteHfenceGvma { start_addr, .. }
+            | RemoteHfenceVvmaAsid { start_addr, .. }
+            | RemoteHfenceVvma { start_addr, .. } => start_addr,
+        }
+    }
+
+    #[inline(always)]
+    fn a3(&self) -> u64 {
+        use RfenceFunction::*;
+        match *self {
+            RemoteFenceI { .. } => 0,
+            RemoteSfenceVma { size, .. }
+            | RemoteSfenceVmaAsid { size, .. }
+            | RemoteHfenceGvmaVmid { size, .. }
+            | RemoteHfenceGvma { size, .. }
+            | RemoteHfenceVvmaAsid { size, .. }
+            | RemoteHfenceVvma { size, .. } => size,
+        }
+    }
+
+    #[inline(always)]
+    fn a4(&self) -> u64 {
+        use RfenceFunction::*;
+        match *self {
+            RemoteSfenceVmaAsid { asid, .. } | RemoteHfenceVvmaAsid { asid, .. } => asid,
+            RemoteHfenceGvmaVmid { vmid, .. } => vmid,
+            _ => 0,
+        }
+    }
+}
@@ -7,22 +7,58 @@
 //! For each extension, a function enum is defined to contain the SBI function data.
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod addr;
+pub use addr::*;
 mod consts;
 pub use consts::*;
 mod debug_console;
 pub use debug_console::*;
+#[cfg(feature = "handler")]
+mod decode;
+#[cfg(feature = "handler")]
+pub use decode::*;
+#[cfg(feature = "caller")]
+mod ecall;
+#[cfg(feature = "caller")]
+pub use ecall::*;
 mod error;
 pub use error::*;
+mod extension;
+pub use extension::*;
 mod function;
 pub use function::*;
+mod gpr;
+pub use gpr::*;
+mod hart_mask;
+pub use hart_mask::*;
+mod xlen;
+pub use xlen::*;
 // The Attestation SBI extension
+#[cfg(feature = "attestation")]
 mod attestation;
+#[cfg(feature = "attestation")]
 pub use attestation::*;
 // The Base SBI extension
 mod base;
 pub use base::*;
+// The Timer SBI extension
+mod time;
+pub use time::*;
+// The IPI SBI extension
+mod ipi;
+pub use ipi::*;
+// The RFENCE SBI extension
+mod rfence;
+pub use rfence::*;
 // The Nested Virtualization Acceleration (NACL) SBI extension
+#[cfg(feature = "nacl")]
 mod nacl;
+#[cfg(feature = "nacl")]
 pub use nacl::*;
 // The reset SBI extension
 mod reset;
@@ -31,23 +67,83 @@ pub use reset::*;
 mod state;
 pub use state::*;
 // The TEE host SBI extension
+#[cfg(feature = "tee")]
 mod tee_host;
+#[cfg(feature = "tee")]
 pub use tee_host::*;
 // The TEE interrupt SBI extension
+#[cfg(feature = "tee")]
 mod tee_interrupt;
+#[cfg(feature = "tee")]
 pub use tee_interrupt::*;
 // The TEE guest SBI extension
+#[cfg(feature = "tee")]
 mod tee_guest;
+#[cfg(feature = "tee")]
 pub use tee_guest::*;
 // The PMU SBI extension
+#[cfg(feature = "pmu")]
 mod pmu;
+#[cfg(feature = "pmu")]
 pub use pmu::*;
+// Vendor and firmware-specific extensions defined outside of this crate
+mod platform;
+pub use platform::*;
+// Vendor and firmware-specific extensions registered at runtime
+#[cfg(feature = "alloc")]
+mod registry;
+#[cfg(feature = "alloc")]
+pub use registry::*;
+// Framing for tunneling SBI calls over a byte stream
+mod proxy;
+pub use proxy::*;
+// Sequence tagging for traces of SBI calls
+mod trace;
+pub use trace::*;
+// Canned SBI calls for golden tests
+mod testcase;
+#[cfg(feature = "handler")]
+#[doc(hidden)]
+pub use testcase::__SBI_TINY;
+// Model-checking harnesses for the decoders
+#[cfg(all(kani, feature = "handler"))]
+mod proofs;
 
 /// Interfaces for invoking SBI functionality.
+#[cfg(feature = "caller")]
 pub mod api;
 
-#[cfg(all(target_arch = "riscv64", target_os = "none"))]
-use core::arch::asm;
+/// Routing of SBI calls to the handlers of an SBI implementation.
+#[cfg(feature = "handler")]
+pub mod handler;
+
+/// Register frames exercising the documented error conditions of each function.
+#[cfg(all(feature = "std", feature = "handler"))]
+pub mod error_cases;
+
+/// Test vectors taken from the examples and corner cases of the SBI specification.
+#[cfg(all(feature = "std", feature = "handler"))]
+pub mod spec_vectors;
+
+/// Differential testing of two handler implementations against a trace of calls.
+#[cfg(all(feature = "std", feature = "handler"))]
+pub mod differential;
+
+/// Checking of SBI implementations against the requirements of the specification.
+#[cfg(all(feature = "std", feature = "handler"))]
+pub mod conformance;
+
+/// Metadata for every SBI function known to the build.
+#[cfg(all(feature = "std", feature = "handler"))]
+pub mod catalog;
+
+/// A simulated SBI implementation for testing guest code on the host.
+#[cfg(all(feature = "std", feature = "caller", feature = "handler"))]
+pub mod mock;
+
+/// Recording of SBI calls into binary traces and their replay against handlers.
+#[cfg(feature = "std")]
+pub mod replay;
 
 /// The values returned from an SBI function call.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -60,7 +156,7 @@ pub struct SbiReturn {
 
 impl SbiReturn {
     /// Returns an `SbiReturn` that indicates success.
-    pub fn success(return_value: u64) -> Self {
+    pub const fn success(return_value: u64) -> Self {
         Self {
             error_code: SBI_SUCCESS,
             return_value,
@@ -77,10 +173,20 @@ impl From<Result<u64>> for SbiReturn {
     }
 }
 
+/// Converts the `a0` and `a1` values returned by `ecall_send_raw()`.
+impl From<(u64, u64)> for SbiReturn {
+    fn from((a0, a1): (u64, u64)) -> SbiReturn {
+        SbiReturn {
+            error_code: a0 as i64,
+            return_value: a1,
+        }
+    }
+}
+
 impl From<Error> for SbiReturn {
     fn from(error: Error) -> SbiReturn {
         SbiReturn {
-            error_code: error as i64,
+            error_code: error.code(),
             return_value: 0,
         }
     }
@@ -106,7 +212,11 @@ pub enum SbiReturnType {
 }
 
 /// SBI Message used to invoke the specified SBI extension in the firmware.
-#[derive(Clone, Copy, Debug)]
+///
+/// With the `tiny` feature, messages are debug-formatted as they are displayed, with their raw
+/// function ID and argument registers.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
 pub enum SbiMessage {
     /// The base SBI extension functions.
     Base(BaseFunction),
@@ -119,43 +229,241 @@ pub enum SbiMessage {
     /// Handles output to the console for debug.
     DebugConsole(DebugConsoleFunction),
     /// Provides functions for accelerating nested virtualization.
+    #[cfg(feature = "nacl")]
     Nacl(NaclFunction),
     /// Provides capabilities for starting confidential virtual machines.
+    #[cfg(feature = "tee")]
     TeeHost(TeeHostFunction),
     /// Provides interrupt virtualization for confidential virtual machines.
+    #[cfg(feature = "tee")]
     TeeInterrupt(TeeInterruptFunction),
     /// Provides capabilities for enlightened confidential virtual machines.
+    #[cfg(feature = "tee")]
     TeeGuest(TeeGuestFunction),
     /// The extension for getting attestation evidences and extending measurements.
+    #[cfg(feature = "attestation")]
     Attestation(AttestationFunction),
     /// The extension for getting performance counter state.
+    #[cfg(feature = "pmu")]
     Pmu(PmuFunction),
+    /// The extension for remote fences.
+    Rfence(RfenceFunction),
+    /// The extension for sending inter-processor interrupts.
+    Ipi(IpiFunction),
+    /// The extension for programming the timer.
+    Time(TimeFunction),
+    /// A call to an extension this crate doesn't model, or that was left out of the build, with
+    /// its raw register values.
+    #[cfg(feature = "unknown")]
+    Unknown {
+        /// The extension ID, from a7.
+        eid: u64,
+        /// The function ID, from a6.
+        fid: u64,
+        /// The arguments, from a0-a5.
+        args: [u64; 6],
+    },
 }
 
 impl SbiMessage {
     /// Creates an SbiMessage struct from the given GPRs. Intended for use from the ECALL handler
     /// and passed the saved register state from the calling OS. A7 must contain a valid SBI
     /// extension and the other A* registers will be interpreted based on the extension A7 selects.
-    pub fn from_regs(args: &[u64]) -> Result<Self> {
-        match args[7] {
+    ///
+    /// Calls with a zero length or count are handled as described for `is_noop()`.
+    #[cfg(feature = "handler")]
+    pub fn from_regs(args: &[u64; 8]) -> Result<Self> {
+        let msg = match args[7] {
             EXT_PUT_CHAR => Ok(SbiMessage::PutChar(args[0])),
             EXT_BASE => BaseFunction::from_regs(args).map(SbiMessage::Base),
             EXT_HART_STATE => StateFunction::from_regs(args).map(SbiMessage::HartState),
             EXT_RESET => ResetFunction::from_regs(args).map(SbiMessage::Reset),
             EXT_DBCN => DebugConsoleFunction::from_regs(args).map(SbiMessage::DebugConsole),
+            #[cfg(feature = "nacl")]
             EXT_NACL => NaclFunction::from_regs(args).map(SbiMessage::Nacl),
+            #[cfg(feature = "tee")]
             EXT_TEE_HOST => TeeHostFunction::from_regs(args).map(SbiMessage::TeeHost),
+            #[cfg(feature = "tee")]
             EXT_TEE_INTERRUPT => {
                 TeeInterruptFunction::from_regs(args).map(SbiMessage::TeeInterrupt)
             }
+            #[cfg(feature = "tee")]
             EXT_TEE_GUEST => TeeGuestFunction::from_regs(args).map(SbiMessage::TeeGuest),
+            #[cfg(feature = "attestation")]
             EXT_ATTESTATION => AttestationFunction::from_regs(args).map(SbiMessage::Attestation),
+            #[cfg(feature = "pmu")]
             EXT_PMU => PmuFunction::from_regs(args).map(SbiMessage::Pmu),
+            EXT_RFENCE => RfenceFunction::from_regs(args).map(SbiMessage::Rfence),
+            EXT_IPI => IpiFunction::from_regs(args).map(SbiMessage::Ipi),
+            EXT_TIME => TimeFunction::from_regs(args).map(SbiMessage::Time),
+            #[cfg(feature = "unknown")]
+            eid => Ok(SbiMessage::Unknown {
+                eid,
+                fid: args[6],
+                args: [args[0], args[1], args[2], args[3], args[4], args[5]],
+            }),
+            #[cfg(not(feature = "unknown"))]
             _ => Err(Error::NotSupported),
+        }?;
+        msg.check_zero_length()?;
+        Ok(msg)
+    }
+
+    /// Returns `Error::InvalidParam` if the call operates on zero pages or on a zero-length
+    /// region, which are rejected by callers and handlers alike.
+    #[inline]
+    #[cfg(any(feature = "caller", feature = "handler"))]
+    pub(crate) const fn check_zero_length(&self) -> Result<()> {
+        match self {
+            #[cfg(feature = "tee")]
+            SbiMessage::TeeHost(f) => f.check_zero_length(),
+            #[cfg(feature = "tee")]
+            SbiMessage::TeeGuest(f) => f.check_zero_length(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns if the call has no effect and succeeds with a return value of 0.
+    ///
+    /// Calls with a zero length or count are handled the same way by `from_regs()` and by
+    /// `ecall_send`, so that callers and handlers agree on them:
+    ///
+    /// - A Debug Console write of zero bytes is a no-op that succeeds having written nothing.
+    ///   Handlers may complete it without touching the caller's memory, and `ecall_send` doesn't
+    ///   issue it.
+    /// - TEE operations on zero pages or on a zero-length region are rejected with
+    ///   `Error::InvalidParam`.
+    /// - A hart mask selecting no harts is a no-op.
+    ///
+    /// ```
+    /// use sbi_rs::*;
+    ///
+    /// let mut regs = [0; 8];
+    /// regs[7] = EXT_DBCN;
+    /// assert!(SbiMessage::from_regs(&regs).unwrap().is_noop());
+    ///
+    /// // Converting zero pages is rejected instead.
+    /// # #[cfg(feature = "tee")] {
+    /// regs[7] = EXT_TEE_HOST;
+    /// regs[6] = 1;
+    /// regs[0] = 0x1000;
+    /// assert_eq!(SbiMessage::from_regs(&regs).unwrap_err(), Error::InvalidParam);
+    /// # }
+    /// ```
+    #[inline]
+    pub const fn is_noop(&self) -> bool {
+        match self {
+            SbiMessage::DebugConsole(f) => f.is_noop(),
+            SbiMessage::Ipi(f) => f.is_noop(),
+            SbiMessage::Rfence(f) => f.is_noop(),
+            _ => false,
         }
     }
 
+    /// Creates an SbiMessage struct from the given GPRs, as `from_regs()` does, and then validates
+    /// the arguments of the call within the limits of `budget`.
+    #[cfg(feature = "handler")]
+    pub fn from_regs_with_budget(args: &[u64; 8], budget: DecodeBudget) -> Result<Self> {
+        let msg = Self::from_regs(args)?;
+        let mut v = decode::Validator::new(budget);
+        match &msg {
+            SbiMessage::DebugConsole(f) => f.validate(&mut v)?,
+            #[cfg(feature = "tee")]
+            SbiMessage::TeeHost(f) => f.validate(&mut v)?,
+            #[cfg(feature = "tee")]
+            SbiMessage::TeeInterrupt(f) => f.validate(&mut v)?,
+            #[cfg(feature = "tee")]
+            SbiMessage::TeeGuest(f) => f.validate(&mut v)?,
+            _ => (),
+        }
+        Ok(msg)
+    }
+
+    /// Creates an SbiMessage struct from the argument registers of `gprs`, such as the caller's
+    /// saved trap frame, as `from_regs()` does.
+    #[cfg(feature = "handler")]
+    pub fn from_gprs<G: GprAccess + ?Sized>(gprs: &G) -> Result<Self> {
+        Self::from_regs(&GprIndex::ARGS.map(|index| gprs.gpr(index)))
+    }
+
+    /// Creates an SbiMessage struct from the given GPRs as `from_regs()` does, but also rejects the
+    /// call with `Error::InvalidParam` if any of the argument registers past the ones the function
+    /// takes is non-zero. Lets handlers catch callers that pass garbage in unused registers.
+    #[cfg(feature = "handler")]
+    pub fn from_regs_strict(args: &[u64; 8]) -> Result<Self> {
+        let msg = Self::from_regs(args)?;
+        let num_args = msg.signature().map_or(6, |sig| sig.args.len());
+        if args[num_args..6].iter().any(|&reg| reg != 0) {
+            return Err(Error::InvalidParam);
+        }
+        Ok(msg)
+    }
+
+    /// Creates an SbiMessage struct from a slice of GPRs holding at least A0-A7. Returns
+    /// `Error::InvalidParam` if `args` is shorter than that; registers past A7 are ignored.
+    #[cfg(feature = "handler")]
+    pub fn from_regs_slice(args: &[u64]) -> Result<Self> {
+        let regs: &[u64; 8] = args
+            .get(..8)
+            .and_then(|regs| regs.try_into().ok())
+            .ok_or(Error::InvalidParam)?;
+        Self::from_regs(regs)
+    }
+
+    /// Returns the extension ID of the call, as passed in a7.
+    pub fn extension_id(&self) -> u64 {
+        self.a7()
+    }
+
+    /// Returns the function ID of the call, as passed in a6.
+    pub fn function_id(&self) -> u64 {
+        self.a6()
+    }
+
+    /// Returns the name and arguments of the function called by this message.
+    pub fn signature(&self) -> Option<FunctionSignature> {
+        #[cfg(feature = "unknown")]
+        if let SbiMessage::Unknown { .. } = self {
+            return None;
+        }
+        match self.function() {
+            Some(func) => func.signature(),
+            None => Some(FunctionSignature::new("console_putchar", &["ch"])),
+        }
+    }
+
+    // Returns the function called by this message, or `None` for the legacy PutChar extension,
+    // which has no function enum, and for calls to unknown extensions.
+    fn function(&self) -> Option<&dyn SbiFunction> {
+        use SbiMessage::*;
+        Some(match self {
+            Base(func) => func,
+            PutChar(_) => return None,
+            HartState(func) => func,
+            Reset(func) => func,
+            DebugConsole(func) => func,
+            #[cfg(feature = "nacl")]
+            Nacl(func) => func,
+            #[cfg(feature = "tee")]
+            TeeHost(func) => func,
+            #[cfg(feature = "tee")]
+            TeeInterrupt(func) => func,
+            #[cfg(feature = "tee")]
+            TeeGuest(func) => func,
+            #[cfg(feature = "attestation")]
+            Attestation(func) => func,
+            #[cfg(feature = "pmu")]
+            Pmu(func) => func,
+            Rfence(func) => func,
+            Ipi(func) => func,
+            Time(func) => func,
+            #[cfg(feature = "unknown")]
+            Unknown { .. } => return None,
+        })
+    }
+
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a7(&self) -> u64 {
         use SbiMessage::*;
         match self {
@@ -164,18 +472,28 @@ impl SbiMessage {
             HartState(_) => EXT_HART_STATE,
             Reset(_) => EXT_RESET,
             DebugConsole(_) => EXT_DBCN,
+            #[cfg(feature = "nacl")]
             Nacl(_) => EXT_NACL,
+            #[cfg(feature = "tee")]
             TeeHost(_) => EXT_TEE_HOST,
+            #[cfg(feature = "tee")]
             TeeInterrupt(_) => EXT_TEE_INTERRUPT,
+            #[cfg(feature = "tee")]
             TeeGuest(_) => EXT_TEE_GUEST,
+            #[cfg(feature = "attestation")]
             Attestation(_) => EXT_ATTESTATION,
+            #[cfg(feature = "pmu")]
             Pmu(_) => EXT_PMU,
+            Rfence(_) => EXT_RFENCE,
+            Ipi(_) => EXT_IPI,
+            Time(_) => EXT_TIME,
+            #[cfg(feature = "unknown")]
+            Unknown { eid, .. } => *eid,
         }
     }
 
-    // TODO: Consider using enum_dispatch to avoid the repetition.
-
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a6(&self) -> u64 {
         use SbiMessage::*;
         match self {
@@ -184,126 +502,102 @@ impl SbiMessage {
             HartState(f) => f.a6(),
             Reset(f) => f.a6(),
             DebugConsole(f) => f.a6(),
+            #[cfg(feature = "nacl")]
             Nacl(f) => f.a6(),
+            #[cfg(feature = "tee")]
             TeeHost(f) => f.a6(),
+            #[cfg(feature = "tee")]
             TeeInterrupt(f) => f.a6(),
+            #[cfg(feature = "tee")]
             TeeGuest(f) => f.a6(),
+            #[cfg(feature = "attestation")]
             Attestation(f) => f.a6(),
+            #[cfg(feature = "pmu")]
             Pmu(f) => f.a6(),
+            Rfence(f) => f.a6(),
+            Ipi(f) => f.a6(),
+            Time(f) => f.a6(),
+            #[cfg(feature = "unknown")]
+            Unknown { fid, .. } => *fid,
         }
     }
 
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a5(&self) -> u64 {
-        use SbiMessage::*;
-        match self {
-            PutChar(_) => 0,
-            Base(f) => f.a5(),
-            HartState(f) => f.a5(),
-            Reset(f) => f.a5(),
-            DebugConsole(f) => f.a5(),
-            Nacl(f) => f.a5(),
-            TeeHost(f) => f.a5(),
-            TeeInterrupt(f) => f.a5(),
-            TeeGuest(f) => f.a5(),
-            Attestation(f) => f.a5(),
-            Pmu(f) => f.a5(),
-        }
+        self.to_regs()[5]
     }
 
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a4(&self) -> u64 {
-        use SbiMessage::*;
-        match self {
-            PutChar(_) => 0,
-            Base(f) => f.a4(),
-            HartState(f) => f.a4(),
-            Reset(f) => f.a4(),
-            DebugConsole(f) => f.a4(),
-            Nacl(f) => f.a4(),
-            TeeHost(f) => f.a4(),
-            TeeInterrupt(f) => f.a4(),
-            TeeGuest(f) => f.a4(),
-            Attestation(f) => f.a4(),
-            Pmu(f) => f.a4(),
-        }
+        self.to_regs()[4]
     }
 
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a3(&self) -> u64 {
-        use SbiMessage::*;
-        match self {
-            PutChar(_) => 0,
-            Base(f) => f.a3(),
-            HartState(f) => f.a3(),
-            Reset(f) => f.a3(),
-            DebugConsole(f) => f.a3(),
-            Nacl(f) => f.a3(),
-            TeeHost(f) => f.a3(),
-            TeeInterrupt(f) => f.a3(),
-            TeeGuest(f) => f.a3(),
-            Attestation(f) => f.a3(),
-            Pmu(f) => f.a3(),
-        }
+        self.to_regs()[3]
     }
 
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a2(&self) -> u64 {
-        use SbiMessage::*;
-        match self {
-            PutChar(_) => 0,
-            Base(f) => f.a2(),
-            HartState(f) => f.a2(),
-            Reset(f) => f.a2(),
-            DebugConsole(f) => f.a2(),
-            Nacl(f) => f.a2(),
-            TeeHost(f) => f.a2(),
-            TeeInterrupt(f) => f.a2(),
-            TeeGuest(f) => f.a2(),
-            Attestation(f) => f.a2(),
-            Pmu(f) => f.a2(),
-        }
+        self.to_regs()[2]
     }
 
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a1(&self) -> u64 {
-        use SbiMessage::*;
-        match self {
-            PutChar(_) => 0,
-            Base(f) => f.a1(),
-            HartState(f) => f.a1(),
-            Reset(f) => f.a1(),
-            DebugConsole(f) => f.a1(),
-            Nacl(f) => f.a1(),
-            TeeHost(f) => f.a1(),
-            TeeInterrupt(f) => f.a1(),
-            TeeGuest(f) => f.a1(),
-            Attestation(f) => f.a1(),
-            Pmu(f) => f.a1(),
-        }
+        self.to_regs()[1]
     }
 
     /// Returns the register value for this `SbiMessage`.
+    #[inline(always)]
     pub fn a0(&self) -> u64 {
+        self.to_regs()[0]
+    }
+
+    /// Returns the `a0-a7` register values for this `SbiMessage`. Every register is computed in
+    /// a single match on the message, so that encoding a message known at compile time, as in
+    /// the wrappers in `api`, reduces to moving its arguments into the registers. The extension
+    /// and function IDs are cheaper to read on their own with `a7()` and `a6()`.
+    #[inline(always)]
+    pub fn to_regs(&self) -> [u64; 8] {
         use SbiMessage::*;
-        match self {
-            PutChar(c) => *c,
-            Base(f) => f.a0(),
-            Reset(f) => f.a0(),
-            DebugConsole(f) => f.a0(),
-            HartState(f) => f.a0(),
-            Nacl(f) => f.a0(),
-            TeeHost(f) => f.a0(),
-            TeeInterrupt(f) => f.a0(),
-            TeeGuest(f) => f.a0(),
-            Attestation(f) => f.a0(),
-            Pmu(f) => f.a0(),
-        }
+        let (eid, [a0, a1, a2, a3, a4, a5, a6]) = match self {
+            PutChar(c) => (EXT_PUT_CHAR, [*c, 0, 0, 0, 0, 0, 0]),
+            Base(f) => (EXT_BASE, f.regs()),
+            HartState(f) => (EXT_HART_STATE, f.regs()),
+            Reset(f) => (EXT_RESET, f.regs()),
+            DebugConsole(f) => (EXT_DBCN, f.regs()),
+            #[cfg(feature = "nacl")]
+            Nacl(f) => (EXT_NACL, f.regs()),
+            #[cfg(feature = "tee")]
+            TeeHost(f) => (EXT_TEE_HOST, f.regs()),
+            #[cfg(feature = "tee")]
+            TeeInterrupt(f) => (EXT_TEE_INTERRUPT, f.regs()),
+            #[cfg(feature = "tee")]
+            TeeGuest(f) => (EXT_TEE_GUEST, f.regs()),
+            #[cfg(feature = "attestation")]
+            Attestation(f) => (EXT_ATTESTATION, f.regs()),
+            #[cfg(feature = "pmu")]
+            Pmu(f) => (EXT_PMU, f.regs()),
+            Rfence(f) => (EXT_RFENCE, f.regs()),
+            Ipi(f) => (EXT_IPI, f.regs()),
+            Time(f) => (EXT_TIME, f.regs()),
+            #[cfg(feature = "unknown")]
+            Unknown { eid, fid, args } => {
+                let [a0, a1, a2, a3, a4, a5] = *args;
+                (*eid, [a0, a1, a2, a3, a4, a5, *fid])
+            }
+        };
+        [a0, a1, a2, a3, a4, a5, a6, eid]
     }
 
     /// Returns the result returned in the SbiMessage. Intended for use after an SbiMessage has been
     /// handled by the firmware. Interprets the given registers based on the extension and function
-    /// and returns the approprate result.
+    /// and returns the approprate result, as described by `return_kind()`.
     ///
     /// # Example
     ///
@@ -323,49 +617,189 @@ impl SbiMessage {
     ///     msg.result(a0, a1)
     /// }
     /// ```
+    #[inline]
     pub fn result(&self, a0: u64, a1: u64) -> Result<u64> {
-        let ret = SbiReturn {
-            error_code: a0 as i64,
-            return_value: a1,
-        };
+        use SbiMessage::*;
+        match self {
+            PutChar(_) => self.return_kind().decode(a0, a1),
+            Base(f) => f.result(a0, a1),
+            HartState(f) => f.result(a0, a1),
+            Reset(f) => f.result(a0, a1),
+            DebugConsole(f) => f.result(a0, a1),
+            #[cfg(feature = "nacl")]
+            Nacl(f) => f.result(a0, a1),
+            #[cfg(feature = "tee")]
+            TeeHost(f) => f.result(a0, a1),
+            #[cfg(feature = "tee")]
+            TeeInterrupt(f) => f.result(a0, a1),
+            #[cfg(feature = "tee")]
+            TeeGuest(f) => f.result(a0, a1),
+            #[cfg(feature = "attestation")]
+            Attestation(f) => f.result(a0, a1),
+            #[cfg(feature = "pmu")]
+            Pmu(f) => f.result(a0, a1),
+            Rfence(f) => f.result(a0, a1),
+            Ipi(f) => f.result(a0, a1),
+            Time(f) => f.result(a0, a1),
+            #[cfg(feature = "unknown")]
+            Unknown { .. } => self.return_kind().decode(a0, a1),
+        }
+    }
+
+    /// Writes the result of handling this message into the caller's registers `regs`: `a0`
+    /// receives the error code and, unless the function follows the legacy convention, `a1`
+    /// receives the return value. No other register is modified; in particular, advancing the
+    /// caller's `sepc` past the `ecall` is left to the caller of this function.
+    ///
+    /// ```
+    /// use sbi_rs::*;
+    ///
+    /// let mut regs = [0; 8];
+    /// regs[7] = EXT_BASE;
+    /// let msg = SbiMessage::from_regs(&regs).unwrap();
+    /// msg.write_result(Ok(2 << 24), &mut regs);
+    /// assert_eq!(regs[..2], [0, 2 << 24]);
+    /// msg.write_result(Error::NotSupported, &mut regs);
+    /// assert_eq!(regs[0] as i64, Error::NotSupported.code());
+    /// ```
+    #[cfg(feature = "handler")]
+    pub fn write_result<R>(&self, ret: impl Into<SbiReturn>, regs: &mut R)
+    where
+        R: GprAccess + ?Sized,
+    {
+        let ret = ret.into();
+        regs.set_gpr(GprIndex::A0, ret.error_code as u64);
+        if self.return_kind() != ReturnKind::Legacy {
+            regs.set_gpr(GprIndex::A1, ret.return_value);
+        }
+    }
+
+    /// Returns how the function called by this message returns its result.
+    pub fn return_kind(&self) -> ReturnKind {
+        use SbiMessage::*;
         match self {
-            // For legacy messages, a0 is 0 on success and an implementation-defined error value on
-            // failure. Nothing is returned in a1.
-            SbiMessage::PutChar(_) => match a0 as i64 {
-                SBI_SUCCESS => Ok(0),
-                _ => Err(Error::Failed),
+            PutChar(_) => ReturnKind::Legacy,
+            Base(f) => f.return_kind(),
+            HartState(f) => f.return_kind(),
+            Reset(f) => f.return_kind(),
+            DebugConsole(f) => f.return_kind(),
+            #[cfg(feature = "nacl")]
+            Nacl(f) => f.return_kind(),
+            #[cfg(feature = "tee")]
+            TeeHost(f) => f.return_kind(),
+            #[cfg(feature = "tee")]
+            TeeInterrupt(f) => f.return_kind(),
+            #[cfg(feature = "tee")]
+            TeeGuest(f) => f.return_kind(),
+            #[cfg(feature = "attestation")]
+            Attestation(f) => f.return_kind(),
+            #[cfg(feature = "pmu")]
+            Pmu(f) => f.return_kind(),
+            Rfence(f) => f.return_kind(),
+            Ipi(f) => f.return_kind(),
+            Time(f) => f.return_kind(),
+            #[cfg(feature = "unknown")]
+            Unknown { eid, .. } => match eid {
+                EXT_LEGACY_START..=EXT_LEGACY_END => ReturnKind::Legacy,
+                _ => ReturnKind::Value,
             },
-            _ => ret.into(),
         }
     }
 }
 
-/// Send an ecall to the firmware or hypervisor.
+/// Displays the call with the name of its extension and function and the value of each of its
+/// arguments, e.g. `HSM::hart_start(hart_id=1, start_addr=0x8020_0000, opaque=0)`. Values are
+/// displayed as they are passed in registers. With the `tiny` feature, calls are displayed with
+/// their extension ID, function ID and raw argument registers instead, e.g.
+/// `0x48534d::function_0(a0=1, a1=0x8020_0000, a2=0, a3=0, a4=0, a5=0)`.
 ///
-/// # Safety
-///
-/// The caller must verify that any memory references contained in `msg` obey Rust's memory
-/// safety rules. For example, any pointers to memory that will be modified in the handling of
-/// the ecall must be uniquely owned. Similarly any pointers read by the ecall must not be
-/// mutably borrowed.
-///
-/// In addition the caller is placing trust in the firmware or hypervisor to maintain the promises
-/// of the interface w.r.t. reading and writing only within the provided bounds.
-#[cfg(all(target_arch = "riscv64", target_os = "none"))]
-pub unsafe fn ecall_send(msg: &SbiMessage) -> Result<u64> {
-    // normally error code
-    let mut a0;
-    // normally return value
-    let mut a1;
-    asm!("ecall", inlateout("a0") msg.a0()=>a0, inlateout("a1")msg.a1()=>a1,
-                in("a2")msg.a2(), in("a3") msg.a3(),
-                in("a4")msg.a4(), in("a5") msg.a5(),
-                in("a6")msg.a6(), in("a7") msg.a7(), options(nostack));
-
-    msg.result(a0, a1)
+/// ```
+/// # use sbi_rs::*;
+/// let msg = SbiMessage::HartState(StateFunction::HartStart {
+///     hart_id: 1,
+///     start_addr: 0x8020_0000,
+///     opaque: 0,
+/// });
+/// # #[cfg(not(feature = "tiny"))]
+/// assert_eq!(
+///     format!("{msg}"),
+///     "HSM::hart_start(hart_id=1, start_addr=0x8020_0000, opaque=0)"
+/// );
+/// ```
+impl core::fmt::Display for SbiMessage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        #[cfg(feature = "unknown")]
+        if let SbiMessage::Unknown { eid, fid, args } = self {
+            return fmt_raw_call(f, &format_args!("{eid:#x}"), *fid, *args);
+        }
+        let Some(func) = self.function() else {
+            #[cfg(feature = "tiny")]
+            return fmt_raw_call(f, &SbiExtension::PutChar, 0, [self.a0(), 0, 0, 0, 0, 0]);
+            #[cfg(not(feature = "tiny"))]
+            return write!(f, "PUTCHAR::console_putchar(ch={})", ArgValue(self.a0()));
+        };
+        match SbiExtension::from_eid(self.a7()) {
+            Some(ext) => fmt_call(f, &ext, func),
+            None => fmt_call(f, &format_args!("{:#x}", self.a7()), func),
+        }
+    }
+}
+
+#[cfg(feature = "tiny")]
+impl core::fmt::Debug for SbiMessage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+}
+
+/// Decodes a call from its `a0-a7` registers, as `SbiMessage::from_regs()` does.
+#[cfg(feature = "handler")]
+impl TryFrom<[u64; 8]> for SbiMessage {
+    type Error = Error;
+
+    fn try_from(regs: [u64; 8]) -> Result<Self> {
+        Self::from_regs(&regs)
+    }
 }
 
-#[cfg(not(all(target_arch = "riscv64", target_os = "none")))]
-unsafe fn ecall_send(_msg: &SbiMessage) -> Result<u64> {
-    panic!("ecall_send called");
+// Implements `TryFrom<SbiMessage>` for the function type of each extension, failing with
+// `Error::NotSupported` if the message is for a different extension.
+macro_rules! impl_try_from_message {
+    ($($(#[$meta:meta])* $variant:ident($function:ty)),* $(,)?) => {
+        $(
+            $(#[$meta])*
+            impl TryFrom<SbiMessage> for $function {
+                type Error = Error;
+
+                fn try_from(msg: SbiMessage) -> Result<Self> {
+                    match msg {
+                        SbiMessage::$variant(f) => Ok(f),
+                        _ => Err(Error::NotSupported),
+                    }
+                }
+            }
+        )*
+    };
 }
+
+impl_try_from_message!(
+    Base(BaseFunction),
+    HartState(StateFunction),
+    Reset(ResetFunction),
+    DebugConsole(DebugConsoleFunction),
+    #[cfg(feature = "nacl")]
+    Nacl(NaclFunction),
+    #[cfg(feature = "tee")]
+    TeeHost(TeeHostFunction),
+    #[cfg(feature = "tee")]
+    TeeInterrupt(TeeInterruptFunction),
+    #[cfg(feature = "tee")]
+    TeeGuest(TeeGuestFunction),
+    #[cfg(feature = "attestation")]
+    Attestation(AttestationFunction),
+    #[cfg(feature = "pmu")]
+    Pmu(PmuFunction),
+    Time(TimeFunction),
+    Ipi(IpiFunction),
+    Rfence(RfenceFunction),
+);
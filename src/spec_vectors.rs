@@ -0,0 +1,423 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Test vectors derived from the worked examples and corner cases of the SBI specification, for
+//! checking decoders and handlers against the specification rather than against this crate.
+//!
+//! `spec_vectors()` lists register frames along with the message they encode, or the error a
+//! conforming implementation fails them with. `HART_MASK_VECTORS` lists hart masks along with the
+//! harts they select on a system with a given number of harts. Register pair vectors depend on
+//! the register width, so those for the target's `XLEN` are listed.
+//!
+//! ```rust
+//! use sbi_rs::spec_vectors::{spec_vectors, HART_MASK_VECTORS};
+//!
+//! for vector in spec_vectors() {
+//!     assert!(vector.check(), "{}: {}", vector.section, vector.description);
+//! }
+//! for vector in HART_MASK_VECTORS {
+//!     assert!(vector.check(), "{}", vector.description);
+//! }
+//! ```
+
+use std::vec::Vec;
+
+use crate::{
+    consts::*, DebugConsoleFunction, Error, HartMask, IpiFunction, Len, PhysAddr,
+    PlatformRetentiveType, ResetFunction, ResetReason, ResetType, Result, RfenceFunction,
+    SbiMessage, StateFunction, SuspendType, HART_MASK_BASE_ALL,
+};
+#[cfg(feature = "pmu")]
+use crate::{
+    PmuConfigFlag, PmuCounterConfigFlags, PmuCounterStartFlags, PmuCounterStopFlags, PmuEventType,
+    PmuFirmware, PmuFunction, PmuHardware, PmuHwCache, PmuHwCacheOpId, PmuHwCacheParams,
+    PmuHwCacheResultId, PmuStartFlag, PmuStopFlag,
+};
+
+/// A register frame from the SBI specification along with its meaning.
+#[derive(Clone, Copy, Debug)]
+pub struct SpecVector {
+    /// The section of the specification the vector is taken from.
+    pub section: &'static str,
+    /// What the vector exercises.
+    pub description: &'static str,
+    /// The `a0-a7` registers of the call.
+    pub regs: [u64; 8],
+    /// The message encoded by the registers, or the error a conforming implementation fails the
+    /// call with.
+    pub expected: Result<SbiMessage>,
+}
+
+impl SpecVector {
+    /// Returns if this crate's decoder agrees with the vector.
+    pub fn check(&self) -> bool {
+        SbiMessage::from_regs(&self.regs) == self.expected
+    }
+}
+
+/// A hart mask from the SBI specification along with the harts it selects.
+#[derive(Clone, Copy, Debug)]
+pub struct HartMaskVector {
+    /// What the vector exercises.
+    pub description: &'static str,
+    /// The `hart_mask` argument.
+    pub mask: u64,
+    /// The `hart_mask_base` argument.
+    pub base: u64,
+    /// The number of harts in the system.
+    pub num_harts: u64,
+    /// The IDs of the harts selected by the mask, in increasing order, or `None` if the mask
+    /// selects a hart that doesn't exist and must be failed with `Error::InvalidParam`.
+    pub harts: Option<&'static [u64]>,
+}
+
+impl HartMaskVector {
+    /// Returns the mask described by the vector.
+    pub const fn hart_mask(&self) -> HartMask {
+        HartMask::from_raw(self.mask, self.base)
+    }
+
+    /// Returns if `HartMask` agrees with the vector.
+    pub fn check(&self) -> bool {
+        let mask = self.hart_mask();
+        match self.harts {
+            Some(harts) => {
+                mask.check(self.num_harts).is_ok()
+                    && mask.iter(self.num_harts).eq(harts.iter().copied())
+            }
+            None => mask.check(self.num_harts) == Err(Error::InvalidParam),
+        }
+    }
+}
+
+/// Hart masks from the "Hart List Parameter" section of the specification.
+pub const HART_MASK_VECTORS: &[HartMaskVector] = &[
+    HartMaskVector {
+        description: "mask bits select harts relative to the base",
+        mask: 0b101,
+        base: 0,
+        num_harts: 4,
+        harts: Some(&[0, 2]),
+    },
+    HartMaskVector {
+        description: "a non-zero base offsets every selected hart",
+        mask: 0b11,
+        base: 2,
+        num_harts: 4,
+        harts: Some(&[2, 3]),
+    },
+    HartMaskVector {
+        description: "an empty mask selects no harts",
+        mask: 0,
+        base: 1,
+        num_harts: 4,
+        harts: Some(&[]),
+    },
+    HartMaskVector {
+        description: "a base of -1 selects every hart",
+        mask: 0,
+        base: HART_MASK_BASE_ALL,
+        num_harts: 4,
+        harts: Some(&[0, 1, 2, 3]),
+    },
+    HartMaskVector {
+        description: "the mask is ignored when the base is -1",
+        mask: 0b10,
+        base: HART_MASK_BASE_ALL,
+        num_harts: 3,
+        harts: Some(&[0, 1, 2]),
+    },
+    HartMaskVector {
+        description: "selecting a hart past the last one is invalid",
+        mask: 0b11,
+        base: 3,
+        num_harts: 4,
+        harts: None,
+    },
+];
+
+fn regs(eid: u64, fid: u64, args: &[u64]) -> [u64; 8] {
+    let mut regs = [0; 8];
+    regs[..args.len()].copy_from_slice(args);
+    regs[6] = fid;
+    regs[7] = eid;
+    regs
+}
+
+/// Returns the vectors for every extension known to this crate.
+pub fn spec_vectors() -> Vec<SpecVector> {
+    const HART_LIST: &str = "Hart List Parameter";
+    const HSM: &str = "Hart State Management Extension";
+    const SRST: &str = "System Reset Extension";
+    const DBCN: &str = "Debug Console Extension";
+
+    let vector = |section, description, regs, expected| SpecVector {
+        section,
+        description,
+        regs,
+        expected,
+    };
+
+    let mut vectors = Vec::new();
+    vectors.extend([
+        // Hart masks
+        vector(
+            HART_LIST,
+            "hart_mask_base of -1 selects every hart",
+            regs(EXT_IPI, 0, &[0, HART_MASK_BASE_ALL]),
+            Ok(SbiMessage::Ipi(IpiFunction::SendIpi {
+                hart_mask: HartMask::all(),
+            })),
+        ),
+        vector(
+            HART_LIST,
+            "hart_mask is ignored when hart_mask_base is -1",
+            regs(EXT_IPI, 0, &[0xdead, HART_MASK_BASE_ALL]),
+            Ok(SbiMessage::Ipi(IpiFunction::SendIpi {
+                hart_mask: HartMask::all(),
+            })),
+        ),
+        vector(
+            HART_LIST,
+            "remote fences take the same hart list as IPIs",
+            regs(EXT_RFENCE, 0, &[0b110, 4]),
+            Ok(SbiMessage::Rfence(RfenceFunction::RemoteFenceI {
+                hart_mask: HartMask::from_raw(0b110, 4),
+            })),
+        ),
+        // Suspend types
+        vector(
+            HSM,
+            "suspend type 0x0 is the default retentive suspend",
+            regs(EXT_HART_STATE, 3, &[0, 0, 0]),
+            Ok(SbiMessage::HartState(StateFunction::HartSuspend {
+                suspend_type: SuspendType::DefaultRetentive,
+                resume_addr: 0,
+                opaque: 0,
+            })),
+        ),
+        vector(
+            HSM,
+            "suspend type 0x80000000 is the default non-retentive suspend",
+            regs(EXT_HART_STATE, 3, &[0x8000_0000, 0x8020_0000, 1]),
+            Ok(SbiMessage::HartState(StateFunction::HartSuspend {
+                suspend_type: SuspendType::DefaultNonRetentive,
+                resume_addr: 0x8020_0000,
+                opaque: 1,
+            })),
+        ),
+        vector(
+            HSM,
+            "suspend types 0x10000000-0x7fffffff are platform-specific retentive suspends",
+            regs(EXT_HART_STATE, 3, &[0x1000_0000, 0, 0]),
+            Ok(SbiMessage::HartState(StateFunction::HartSuspend {
+                suspend_type: SuspendType::PlatformRetentive(
+                    PlatformRetentiveType::from_raw_value(0x1000_0000).unwrap(),
+                ),
+                resume_addr: 0,
+                opaque: 0,
+            })),
+        ),
+        vector(
+            HSM,
+            "suspend types 0x00000001-0x0fffffff are reserved",
+            regs(EXT_HART_STATE, 3, &[0x1, 0, 0]),
+            Err(Error::InvalidParam),
+        ),
+        vector(
+            HSM,
+            "suspend types 0x80000001-0x8fffffff are reserved",
+            regs(EXT_HART_STATE, 3, &[0x8000_0001, 0, 0]),
+            Err(Error::InvalidParam),
+        ),
+        // Reset types and reasons
+        vector(
+            SRST,
+            "reset type 0x0 is a shutdown",
+            regs(EXT_RESET, 0, &[0, 0]),
+            Ok(SbiMessage::Reset(ResetFunction::Reset {
+                reset_type: ResetType::Shutdown,
+                reason: ResetReason::NoReason,
+            })),
+        ),
+        vector(
+            SRST,
+            "reset type 0x2 is a warm reset and reason 0x1 a system failure",
+            regs(EXT_RESET, 0, &[2, 1]),
+            Ok(SbiMessage::Reset(ResetFunction::Reset {
+                reset_type: ResetType::WarmReset,
+                reason: ResetReason::SystemFailure,
+            })),
+        ),
+        vector(
+            SRST,
+            "reset types 0x3-0xefffffff are reserved",
+            regs(EXT_RESET, 0, &[3, 0]),
+            Err(Error::InvalidParam),
+        ),
+        // Register pairs
+        #[cfg(target_pointer_width = "64")]
+        vector(
+            DBCN,
+            "the high half of the address must be zero on RV64",
+            regs(EXT_DBCN, 0, &[5, 0x8000_1000, 1]),
+            Err(Error::InvalidParam),
+        ),
+        #[cfg(target_pointer_width = "32")]
+        vector(
+            DBCN,
+            "the address is split into low and high halves on RV32",
+            regs(EXT_DBCN, 0, &[5, 0x8000_1000, 1]),
+            Ok(SbiMessage::DebugConsole(DebugConsoleFunction::PutString {
+                len: Len::new(5),
+                addr: PhysAddr::new(0x1_8000_1000),
+            })),
+        ),
+        vector(
+            DBCN,
+            "the length comes before the address",
+            regs(EXT_DBCN, 0, &[5, 0x8000_1000, 0]),
+            Ok(SbiMessage::DebugConsole(DebugConsoleFunction::PutString {
+                len: Len::new(5),
+                addr: PhysAddr::new(0x8000_1000),
+            })),
+        ),
+    ]);
+
+    #[cfg(feature = "pmu")]
+    {
+        const PMU: &str = "Performance Monitoring Unit Extension";
+        vectors.extend([
+            vector(
+                PMU,
+                "event type 0 with code 0x1 counts CPU cycles",
+                regs(EXT_PMU, 2, &[0, 1, 0, 0x1, 0]),
+                Ok(SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    config_flags: PmuCounterConfigFlags::default(),
+                    event_type: PmuEventType::Hardware(PmuHardware::CpuCycles),
+                    event_data: 0,
+                })),
+            ),
+            vector(
+                PMU,
+                "event type 1 with code 0x3 counts L1D write misses",
+                regs(EXT_PMU, 2, &[0, 1, 0, 0x1_0003, 0]),
+                Ok(SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    config_flags: PmuCounterConfigFlags::default(),
+                    event_type: PmuEventType::Cache(PmuHwCacheParams::new(
+                        PmuHwCache::L1DataCache,
+                        PmuHwCacheOpId::Write,
+                        PmuHwCacheResultId::Miss,
+                    )),
+                    event_data: 0,
+                })),
+            ),
+            #[cfg(target_pointer_width = "64")]
+            vector(
+                PMU,
+                "the event data is passed whole in a4 on RV64",
+                regs(EXT_PMU, 2, &[0, 1, 0, 0x1, 0x1234_5678_9abc, 0]),
+                Ok(SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    config_flags: PmuCounterConfigFlags::default(),
+                    event_type: PmuEventType::Hardware(PmuHardware::CpuCycles),
+                    event_data: 0x1234_5678_9abc,
+                })),
+            ),
+            #[cfg(target_pointer_width = "32")]
+            vector(
+                PMU,
+                "the event data is split into low and high halves on RV32",
+                regs(EXT_PMU, 2, &[0, 1, 0, 0x1, 0x5678_9abc, 0x1234]),
+                Ok(SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    config_flags: PmuCounterConfigFlags::default(),
+                    event_type: PmuEventType::Hardware(PmuHardware::CpuCycles),
+                    event_data: 0x1234_5678_9abc,
+                })),
+            ),
+            vector(
+                PMU,
+                "event type 15 selects firmware events",
+                regs(EXT_PMU, 2, &[0, 1, 0, 0xf_0000, 0]),
+                Ok(SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    config_flags: PmuCounterConfigFlags::default(),
+                    event_type: PmuEventType::Firmware(PmuFirmware::MisalignedLoad),
+                    event_data: 0,
+                })),
+            ),
+            vector(
+                PMU,
+                "config flag bits 0-2 are SKIP_MATCH, CLEAR_VALUE and AUTO_START",
+                regs(EXT_PMU, 2, &[3, 1, 0b111, 0x1, 0]),
+                Ok(SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+                    counter_index: 3,
+                    counter_mask: 1,
+                    config_flags: PmuCounterConfigFlags::new(
+                        PmuConfigFlag::SkipMatch
+                            | PmuConfigFlag::ClearValue
+                            | PmuConfigFlag::AutoStart,
+                    ),
+                    event_type: PmuEventType::Hardware(PmuHardware::CpuCycles),
+                    event_data: 0,
+                })),
+            ),
+            vector(
+                PMU,
+                "config flag bits 3-7 inhibit counting in VU, VS, U, S and M modes",
+                regs(EXT_PMU, 2, &[0, 1, 0xf8, 0x1, 0]),
+                Ok(SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    config_flags: PmuCounterConfigFlags::new(
+                        PmuConfigFlag::Vuinh
+                            | PmuConfigFlag::Vsinh
+                            | PmuConfigFlag::Uinh
+                            | PmuConfigFlag::Sinh
+                            | PmuConfigFlag::Minh,
+                    ),
+                    event_type: PmuEventType::Hardware(PmuHardware::CpuCycles),
+                    event_data: 0,
+                })),
+            ),
+            vector(
+                PMU,
+                "config flag bits above 7 are reserved",
+                regs(EXT_PMU, 2, &[0, 1, 1 << 8, 0x1, 0]),
+                Err(Error::InvalidParam),
+            ),
+            vector(
+                PMU,
+                "start flag bit 0 is INIT_VALUE",
+                regs(EXT_PMU, 3, &[0, 1, 1, 0x1234]),
+                Ok(SbiMessage::Pmu(PmuFunction::StartCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    start_flags: PmuCounterStartFlags::new(PmuStartFlag::InitValue),
+                    initial_value: 0x1234,
+                })),
+            ),
+            vector(
+                PMU,
+                "stop flag bit 0 is RESET",
+                regs(EXT_PMU, 4, &[0, 1, 1]),
+                Ok(SbiMessage::Pmu(PmuFunction::StopCounters {
+                    counter_index: 0,
+                    counter_mask: 1,
+                    stop_flags: PmuCounterStopFlags::new(PmuStopFlag::Reset),
+                })),
+            ),
+        ]);
+    }
+
+    vectors
+}
@@ -6,7 +6,7 @@ use crate::error::*;
 use crate::function::*;
 
 /// Functions defined for the State extension
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum StateFunction {
     /// Starts the given hart.
     HartStart {
@@ -27,16 +27,144 @@ pub enum StateFunction {
     /// Requests that the calling hart be suspended.
     HartSuspend {
         /// a0 - Specifies the type of suspend to initiate.
-        suspend_type: u32,
-        /// a1 - The address to jump to on resume.
+        suspend_type: SuspendType,
+        /// a1 - The address to jump to on resume. Only used by non-retentive suspend types.
         resume_addr: u64,
         /// a2 - An opaque value to load in a1 when resuming the hart.
         opaque: u64,
     },
 }
 
+/// The type of suspend requested with `HartSuspend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuspendType {
+    /// The default retentive suspend type. The hart resumes execution after the `HartSuspend`
+    /// call with its state preserved.
+    DefaultRetentive,
+    /// A platform-specific retentive suspend type.
+    PlatformRetentive(PlatformRetentiveType),
+    /// The default non-retentive suspend type. The hart resumes execution at `resume_addr`.
+    DefaultNonRetentive,
+    /// A platform-specific non-retentive suspend type.
+    PlatformNonRetentive(PlatformNonRetentiveType),
+}
+
+/// A platform-specific retentive suspend type, in the range `0x1000_0000..=0x7fff_ffff`. Values
+/// are checked on construction, so a `SuspendType` never encodes to a reserved suspend type.
+///
+/// ```
+/// use sbi_rs::*;
+///
+/// let t = SuspendType::platform_retentive(0x1000_0005).unwrap();
+/// assert_eq!(t.raw(), 0x1000_0005);
+/// assert_eq!(PlatformRetentiveType::from_raw_value(5), Err(Error::InvalidParam));
+/// assert_eq!(SuspendType::platform_non_retentive(0x8000_0005), Err(Error::InvalidParam));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlatformRetentiveType(u32);
+
+impl PlatformRetentiveType {
+    /// Constructs a `PlatformRetentiveType` from a valid passed-in value. Returns an error if
+    /// `value` isn't in the platform-specific retentive range.
+    pub const fn from_raw_value(value: u32) -> Result<Self> {
+        if value >= SuspendType::PLATFORM_RETENTIVE_START
+            && value <= SuspendType::PLATFORM_RETENTIVE_END
+        {
+            Ok(Self(value))
+        } else {
+            Err(Error::InvalidParam)
+        }
+    }
+
+    /// Returns the raw suspend type value.
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+/// A platform-specific non-retentive suspend type, in the range `0x9000_0000..=0xffff_ffff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlatformNonRetentiveType(u32);
+
+impl PlatformNonRetentiveType {
+    /// Constructs a `PlatformNonRetentiveType` from a valid passed-in value. Returns an error if
+    /// `value` isn't in the platform-specific non-retentive range.
+    pub const fn from_raw_value(value: u32) -> Result<Self> {
+        if value >= SuspendType::PLATFORM_NON_RETENTIVE_START {
+            Ok(Self(value))
+        } else {
+            Err(Error::InvalidParam)
+        }
+    }
+
+    /// Returns the raw suspend type value.
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+impl SuspendType {
+    const DEFAULT_RETENTIVE: u32 = 0;
+    const DEFAULT_NON_RETENTIVE: u32 = 0x8000_0000;
+    const PLATFORM_RETENTIVE_START: u32 = 0x1000_0000;
+    const PLATFORM_RETENTIVE_END: u32 = 0x7fff_ffff;
+    const PLATFORM_NON_RETENTIVE_START: u32 = 0x9000_0000;
+
+    /// Creates a suspend type from the a0 register value or returns an error if the value is
+    /// reserved.
+    pub const fn from_reg(a0: u64) -> Result<Self> {
+        if a0 > u32::MAX as u64 {
+            return Err(Error::InvalidParam);
+        }
+        match a0 as u32 {
+            Self::DEFAULT_RETENTIVE => Ok(SuspendType::DefaultRetentive),
+            Self::DEFAULT_NON_RETENTIVE => Ok(SuspendType::DefaultNonRetentive),
+            v if v < Self::DEFAULT_NON_RETENTIVE => Self::platform_retentive(v),
+            v => Self::platform_non_retentive(v),
+        }
+    }
+
+    /// Creates a platform-specific retentive suspend type, returning an error if `value` isn't
+    /// in the platform-specific retentive range.
+    pub const fn platform_retentive(value: u32) -> Result<Self> {
+        match PlatformRetentiveType::from_raw_value(value) {
+            Ok(t) => Ok(SuspendType::PlatformRetentive(t)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Creates a platform-specific non-retentive suspend type, returning an error if `value`
+    /// isn't in the platform-specific non-retentive range.
+    pub const fn platform_non_retentive(value: u32) -> Result<Self> {
+        match PlatformNonRetentiveType::from_raw_value(value) {
+            Ok(t) => Ok(SuspendType::PlatformNonRetentive(t)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the raw suspend type value.
+    pub const fn raw(&self) -> u32 {
+        use SuspendType::*;
+        match self {
+            DefaultRetentive => Self::DEFAULT_RETENTIVE,
+            DefaultNonRetentive => Self::DEFAULT_NON_RETENTIVE,
+            PlatformRetentive(t) => t.raw(),
+            PlatformNonRetentive(t) => t.raw(),
+        }
+    }
+
+    /// Returns if the hart state is preserved across a suspend of this type.
+    pub const fn is_retentive(&self) -> bool {
+        matches!(
+            self,
+            SuspendType::DefaultRetentive | SuspendType::PlatformRetentive(_)
+        )
+    }
+}
+
 /// Return value for the HartStatus SBI call.
 #[repr(u64)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HartState {
     /// The hart is physically powered-up and executing normally.
     Started = 0,
@@ -55,9 +183,28 @@ pub enum HartState {
     ResumePending = 6,
 }
 
+impl HartState {
+    /// Creates a hart state from the value returned by the HartStatus call, or returns
+    /// `Error::Failed` if the value isn't a known state.
+    pub const fn from_reg(value: u64) -> Result<Self> {
+        use HartState::*;
+        Ok(match value {
+            0 => Started,
+            1 => Stopped,
+            2 => StartPending,
+            3 => StopPending,
+            4 => Suspended,
+            5 => SuspendPending,
+            6 => ResumePending,
+            _ => return Err(Error::Failed),
+        })
+    }
+}
+
 impl StateFunction {
     /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         use StateFunction::*;
         match args[6] {
             0 => Ok(HartStart {
@@ -68,7 +215,7 @@ impl StateFunction {
             1 => Ok(HartStop),
             2 => Ok(HartStatus { hart_id: args[0] }),
             3 => Ok(HartSuspend {
-                suspend_type: args[0] as u32,
+                suspend_type: SuspendType::from_reg(args[0])?,
                 resume_addr: args[1],
                 opaque: args[2],
             }),
@@ -78,6 +225,26 @@ impl StateFunction {
 }
 
 impl SbiFunction for StateFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        use StateFunction::*;
+        let (name, args): (_, &[_]) = match self {
+            HartStart { .. } => ("hart_start", &["hart_id", "start_addr", "opaque"]),
+            HartStop => ("hart_stop", &[]),
+            HartStatus { .. } => ("hart_get_status", &["hart_id"]),
+            HartSuspend { .. } => ("hart_suspend", &["suspend_type", "resume_addr", "opaque"]),
+        };
+        Some(FunctionSignature::new(name, args))
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        use StateFunction::*;
+        match self {
+            HartStatus { .. } => ReturnKind::Value,
+            HartStart { .. } | HartStop | HartSuspend { .. } => ReturnKind::Empty,
+        }
+    }
+
+    #[inline(always)]
     fn a6(&self) -> u64 {
         use StateFunction::*;
         match self {
@@ -88,6 +255,7 @@ impl SbiFunction for StateFunction {
         }
     }
 
+    #[inline(always)]
     fn a0(&self) -> u64 {
         use StateFunction::*;
         match self {
@@ -101,11 +269,12 @@ impl SbiFunction for StateFunction {
                 suspend_type,
                 resume_addr: _,
                 opaque: _,
-            } => *suspend_type as u64,
+            } => suspend_type.raw() as u64,
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a1(&self) -> u64 {
         use StateFunction::*;
         match self {
@@ -123,6 +292,7 @@ impl SbiFunction for StateFunction {
         }
     }
 
+    #[inline(always)]
     fn a2(&self) -> u64 {
         use StateFunction::*;
         match self {
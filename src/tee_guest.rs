@@ -2,11 +2,15 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::addr::*;
+#[cfg(feature = "handler")]
+use crate::decode::Validator;
 use crate::error::*;
 use crate::function::*;
+use crate::xlen::AsUsize;
 
 /// Functions provided by the TEE Guest extension to TVM guests.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TeeGuestFunction {
     /// Marks the specified range of guest physical address space as used for emulated MMIO. Upon
     /// return, all accesses by the TVM within the range are trapped and may be emulated by the
@@ -18,9 +22,9 @@ pub enum TeeGuestFunction {
     /// a6 = 0
     AddMmioRegion {
         /// a0 = start address of the region
-        addr: u64,
+        addr: PhysAddr,
         /// a1 = length of the region
-        len: u64,
+        len: Len,
     },
     /// Removes the specified range of guest physical address space from the emulated MMIO regions. Upon
     /// return, all accesses by the TVM within the range will result in a page fault.
@@ -30,9 +34,9 @@ pub enum TeeGuestFunction {
     /// a6 = 1
     RemoveMmioRegion {
         /// a0 = start address of the region
-        addr: u64,
+        addr: PhysAddr,
         /// a1 = length of the region
-        len: u64,
+        len: Len,
     },
     /// Requests conversion of the specified range of guest physical address space from confidential
     /// to shared. The caller is blocked until the host has completed the invalidation and removal
@@ -45,9 +49,9 @@ pub enum TeeGuestFunction {
     /// a6 = 2
     ShareMemory {
         /// a0 = start address of the region
-        addr: u64,
+        addr: PhysAddr,
         /// a1 = length of the region
-        len: u64,
+        len: Len,
     },
     /// Requests conversion of the specified range of guest physical address space from shared to
     /// confidential. The caller is blocked until the host has completed the invalidation and
@@ -60,9 +64,9 @@ pub enum TeeGuestFunction {
     /// a6 = 3
     UnshareMemory {
         /// a0 = start address of the region
-        addr: u64,
+        addr: PhysAddr,
         /// a1 = length of the region
-        len: u64,
+        len: Len,
     },
     /// Allows injection of the specified external interrupt ID into the calling TVM vCPU. Passing
     /// an ID of -1 allows injection of all external interrupts. TVM vCPUs are started with
@@ -88,34 +92,110 @@ pub enum TeeGuestFunction {
 }
 
 impl TeeGuestFunction {
+    /// Returns the length in bytes of a call operating on a range of address space as a `usize`,
+    /// or `Error::InvalidParam` if it doesn't fit. Returns `None` for other calls.
+    ///
+    /// ```
+    /// use sbi_rs::{Len, PhysAddr, TeeGuestFunction};
+    ///
+    /// let f = TeeGuestFunction::ShareMemory {
+    ///     addr: PhysAddr::new(0x8000_0000),
+    ///     len: Len::new(0x1000),
+    /// };
+    /// assert_eq!(f.len_usize(), Some(Ok(0x1000)));
+    /// assert_eq!(TeeGuestFunction::AllowExternalInterrupt { id: 3 }.len_usize(), None);
+    /// ```
+    pub fn len_usize(&self) -> Option<Result<usize>> {
+        use TeeGuestFunction::*;
+        match *self {
+            AddMmioRegion { len, .. }
+            | RemoveMmioRegion { len, .. }
+            | ShareMemory { len, .. }
+            | UnshareMemory { len, .. } => Some(len.as_usize()),
+            _ => None,
+        }
+    }
+
     /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         use TeeGuestFunction::*;
         match args[6] {
             0 => Ok(AddMmioRegion {
-                addr: args[0],
-                len: args[1],
+                addr: PhysAddr::new(args[0]),
+                len: Len::new(args[1]),
             }),
             1 => Ok(RemoveMmioRegion {
-                addr: args[0],
-                len: args[1],
+                addr: PhysAddr::new(args[0]),
+                len: Len::new(args[1]),
             }),
             2 => Ok(ShareMemory {
-                addr: args[0],
-                len: args[1],
+                addr: PhysAddr::new(args[0]),
+                len: Len::new(args[1]),
             }),
             3 => Ok(UnshareMemory {
-                addr: args[0],
-                len: args[1],
+                addr: PhysAddr::new(args[0]),
+                len: Len::new(args[1]),
             }),
             4 => Ok(AllowExternalInterrupt { id: args[0] as i64 }),
             5 => Ok(DenyExternalInterrupt { id: args[0] as i64 }),
             _ => Err(Error::NotSupported),
         }
     }
+
+    /// Validates the arguments of the call within the budget tracked by `v`.
+    #[cfg(feature = "handler")]
+    pub(crate) fn validate(&self, v: &mut Validator) -> Result<()> {
+        use TeeGuestFunction::*;
+        match *self {
+            AddMmioRegion { addr, len }
+            | RemoveMmioRegion { addr, len }
+            | ShareMemory { addr, len }
+            | UnshareMemory { addr, len } => {
+                v.page_aligned_range(addr.bits(), len.bytes())?;
+                v.range(addr.bits(), len.bytes())
+            }
+            AllowExternalInterrupt { id: _ } | DenyExternalInterrupt { id: _ } => Ok(()),
+        }
+    }
+
+    /// Returns `Error::InvalidParam` if the call operates on a zero-length region.
+    #[cfg(any(feature = "caller", feature = "handler"))]
+    pub(crate) const fn check_zero_length(&self) -> Result<()> {
+        use TeeGuestFunction::*;
+        match *self {
+            AddMmioRegion { len, .. }
+            | RemoveMmioRegion { len, .. }
+            | ShareMemory { len, .. }
+            | UnshareMemory { len, .. }
+                if len.is_zero() =>
+            {
+                Err(Error::InvalidParam)
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl SbiFunction for TeeGuestFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        use TeeGuestFunction::*;
+        let (name, args): (_, &[_]) = match self {
+            AddMmioRegion { .. } => ("add_mmio_region", &["addr", "len"]),
+            RemoveMmioRegion { .. } => ("remove_mmio_region", &["addr", "len"]),
+            ShareMemory { .. } => ("share_memory", &["addr", "len"]),
+            UnshareMemory { .. } => ("unshare_memory", &["addr", "len"]),
+            AllowExternalInterrupt { .. } => ("allow_external_interrupt", &["id"]),
+            DenyExternalInterrupt { .. } => ("deny_external_interrupt", &["id"]),
+        };
+        Some(FunctionSignature::new(name, args))
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        ReturnKind::Empty
+    }
+
+    #[inline(always)]
     fn a6(&self) -> u64 {
         use TeeGuestFunction::*;
         match self {
@@ -128,25 +208,27 @@ impl SbiFunction for TeeGuestFunction {
         }
     }
 
+    #[inline(always)]
     fn a0(&self) -> u64 {
         use TeeGuestFunction::*;
         match self {
-            AddMmioRegion { addr, len: _ } => *addr,
-            RemoveMmioRegion { addr, len: _ } => *addr,
-            ShareMemory { addr, len: _ } => *addr,
-            UnshareMemory { addr, len: _ } => *addr,
+            AddMmioRegion { addr, len: _ } => addr.bits(),
+            RemoveMmioRegion { addr, len: _ } => addr.bits(),
+            ShareMemory { addr, len: _ } => addr.bits(),
+            UnshareMemory { addr, len: _ } => addr.bits(),
             AllowExternalInterrupt { id } => *id as u64,
             DenyExternalInterrupt { id } => *id as u64,
         }
     }
 
+    #[inline(always)]
     fn a1(&self) -> u64 {
         use TeeGuestFunction::*;
         match self {
-            AddMmioRegion { addr: _, len } => *len,
-            RemoveMmioRegion { addr: _, len } => *len,
-            ShareMemory { addr: _, len } => *len,
-            UnshareMemory { addr: _, len } => *len,
+            AddMmioRegion { addr: _, len } => len.bytes(),
+            RemoveMmioRegion { addr: _, len } => len.bytes(),
+            ShareMemory { addr: _, len } => len.bytes(),
+            UnshareMemory { addr: _, len } => len.bytes(),
             _ => 0,
         }
     }
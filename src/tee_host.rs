@@ -2,12 +2,21 @@
 // Licensed under the Apache License, Version 2.0, see LICENSE for details.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::addr::*;
+#[cfg(feature = "handler")]
+use crate::decode::Validator;
 use crate::error::*;
 use crate::function::*;
+use crate::xlen::AsUsize;
+use static_assertions::const_assert;
 
 /// Layout of `scratch` in the `NaclShmem` structure when used with `TvmCpuRun`. Used to communicate
 /// a TVM's exit status to the host.
 #[repr(C)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
 pub struct TsmShmemScratch {
     /// General purpose registers for a TVM guest.
     ///
@@ -65,17 +74,96 @@ pub struct TsmInfo {
     pub tvm_vcpu_state_pages: u64,
 }
 
-/// Parameters used for creating a new confidential VM.
-#[repr(C)]
+/// The size in bytes of the confidential memory region holding a TVM's page directory.
+pub const TVM_PAGE_DIRECTORY_BYTES: u64 = 16 * 1024;
+
+/// Parameters used for creating a new confidential VM, as laid out in the structure passed to
+/// `TvmCreate`.
+///
+/// The addresses are checked on construction, so a `TvmCreateParams` always describes a layout
+/// the TSM will accept as far as alignment goes.
+///
+/// With the `zerocopy` feature, the structure implements `AsBytes` only, so that it can't be read
+/// from memory without its addresses being checked; use `from_bytes()` instead.
+///
+/// ```
+/// use sbi_rs::{Error, PhysAddr, TvmCreateParams};
+///
+/// let params =
+///     TvmCreateParams::new(PhysAddr::new(0x8100_4000), PhysAddr::new(0x8200_0000)).unwrap();
+/// assert_eq!(TvmCreateParams::from_bytes(&params.to_bytes()), Ok(params));
+/// assert_eq!(
+///     TvmCreateParams::new(PhysAddr::new(0x8100_1000), PhysAddr::new(0x8200_0000)),
+///     Err(Error::InvalidAddress)
+/// );
+/// ```
+#[repr(C, align(8))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "zerocopy", derive(zerocopy::AsBytes))]
 pub struct TvmCreateParams {
-    /// The base physical address of the 16kB confidential memory region that should be used for the
-    /// TVM's page directory. Must be 16kB-aligned.
-    pub tvm_page_directory_addr: u64,
-    /// The base physical address of the confidential memory region to be used to hold the TVM's
-    /// global state. Must be page-aligned and `TsmInfo::tvm_state_pages` pages in length.
-    pub tvm_state_addr: u64,
+    tvm_page_directory_addr: u64,
+    tvm_state_addr: u64,
+}
+
+impl TvmCreateParams {
+    /// The size in bytes of the structure passed to `TvmCreate`.
+    pub const SIZE: usize = 16;
+
+    /// Creates the parameters for a TVM using the 16kB confidential memory region at
+    /// `tvm_page_directory_addr` for its page directory and the `TsmInfo::tvm_state_pages` pages
+    /// at `tvm_state_addr` for its global state. Returns `Error::InvalidAddress` if the page
+    /// directory isn't 16kB-aligned or the state isn't page-aligned.
+    pub const fn new(tvm_page_directory_addr: PhysAddr, tvm_state_addr: PhysAddr) -> Result<Self> {
+        if !tvm_page_directory_addr
+            .bits()
+            .is_multiple_of(TVM_PAGE_DIRECTORY_BYTES)
+            || !tvm_state_addr.is_aligned(PageSize::Size4k)
+        {
+            return Err(Error::InvalidAddress);
+        }
+        Ok(Self {
+            tvm_page_directory_addr: tvm_page_directory_addr.bits(),
+            tvm_state_addr: tvm_state_addr.bits(),
+        })
+    }
+
+    /// Returns the base physical address of the TVM's page directory.
+    pub const fn tvm_page_directory_addr(&self) -> PhysAddr {
+        PhysAddr::new(self.tvm_page_directory_addr)
+    }
+
+    /// Returns the base physical address of the TVM's global state.
+    pub const fn tvm_state_addr(&self) -> PhysAddr {
+        PhysAddr::new(self.tvm_state_addr)
+    }
+
+    /// Serializes the structure as laid out in memory for the TSM: each address as a
+    /// little-endian 64-bit value, the page directory first. Calls pass the structure itself,
+    /// which is 8-byte aligned for the TSM's 64-bit reads even on hosts that align `u64` to 4
+    /// bytes.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..8].copy_from_slice(&self.tvm_page_directory_addr.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.tvm_state_addr.to_le_bytes());
+        bytes
+    }
+
+    /// Parses the structure as passed to `TvmCreate`, checking the addresses as `new` does.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Result<Self> {
+        let mut addr = [0; 8];
+        addr.copy_from_slice(&bytes[..8]);
+        let tvm_page_directory_addr = PhysAddr::new(u64::from_le_bytes(addr));
+        addr.copy_from_slice(&bytes[8..]);
+        Self::new(
+            tvm_page_directory_addr,
+            PhysAddr::new(u64::from_le_bytes(addr)),
+        )
+    }
 }
 
+const_assert!(core::mem::size_of::<TvmCreateParams>() == TvmCreateParams::SIZE);
+const_assert!(core::mem::align_of::<TvmCreateParams>() == 8);
+
 /// Types of pages allowed to used for creating or managing confidential VMs.
 #[repr(u64)]
 #[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
@@ -94,7 +182,7 @@ pub enum TsmPageType {
 impl TsmPageType {
     /// Attempts to create a page type from the given u64 register value. Returns an error if the
     /// value is greater than 3(512GB).
-    pub fn from_reg(reg: u64) -> Result<Self> {
+    pub const fn from_reg(reg: u64) -> Result<Self> {
         use TsmPageType::*;
         match reg {
             0 => Ok(Page4k),
@@ -106,7 +194,7 @@ impl TsmPageType {
     }
 
     /// Returns the size of this page type in bytes.
-    pub fn size_bytes(&self) -> u64 {
+    pub const fn size_bytes(&self) -> u64 {
         match self {
             TsmPageType::Page4k => 4096,
             TsmPageType::Page2M => 2 * 1024 * 1024,
@@ -114,10 +202,85 @@ impl TsmPageType {
             TsmPageType::Page512G => 512 * 1024 * 1024 * 1024,
         }
     }
+
+    /// Returns the `PageSize` of this page type.
+    pub const fn page_size(&self) -> PageSize {
+        match self {
+            TsmPageType::Page4k => PageSize::Size4k,
+            TsmPageType::Page2M => PageSize::Size2M,
+            TsmPageType::Page1G => PageSize::Size1G,
+            TsmPageType::Page512G => PageSize::Size512G,
+        }
+    }
+}
+
+/// `scause` value for a load guest-page fault.
+const EXCEPTION_LOAD_GUEST_PAGE_FAULT: u64 = 21;
+/// `scause` value for a store/AMO guest-page fault.
+const EXCEPTION_STORE_GUEST_PAGE_FAULT: u64 = 23;
+
+/// The kind of access made by a TVM vCPU to an emulated MMIO region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MmioAccess {
+    /// The vCPU loaded from the region. The host must write the value to be loaded to the
+    /// destination register in `TsmShmemScratch::guest_gprs` before resuming the vCPU.
+    Load,
+    /// The vCPU stored to the region. The TSM has written the value being stored to the source
+    /// register in `TsmShmemScratch::guest_gprs`.
+    Store,
+}
+
+/// Describes an access to an emulated MMIO region that caused a TVM vCPU to exit from
+/// `TvmCpuRun`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TvmMmioExit {
+    /// Whether the access was a load or a store.
+    pub access: MmioAccess,
+    /// The guest physical address that was accessed.
+    pub guest_addr: u64,
+    /// The width of the access in bytes.
+    pub width: u64,
+    /// The GPR in `TsmShmemScratch::guest_gprs` holding the data for the access: `rd` for loads
+    /// and `rs2` for stores.
+    pub gpr: usize,
+}
+
+impl TvmMmioExit {
+    /// Decodes an emulated MMIO access from the exit state of a TVM vCPU, where `scause` and
+    /// `stval` are the exit cause and trap value reported by `TvmCpuRun`, and `htval` and
+    /// `htinst` are the values the TSM wrote to the `NaclShmem` CSRs. Returns `None` if the exit
+    /// wasn't caused by a serviceable access to an emulated MMIO region.
+    pub fn from_exit(scause: u64, stval: u64, htval: u64, htinst: u64) -> Option<Self> {
+        const OPCODE_LOAD: u64 = 0x03;
+        const OPCODE_STORE: u64 = 0x23;
+        // Bit 1 of a transformed instruction is cleared if the original instruction was
+        // compressed, so set it before matching on the opcode.
+        let opcode = (htinst | 0x2) & 0x7f;
+        let funct3 = (htinst >> 12) & 0x7;
+        let (access, gpr) = match (scause, opcode) {
+            (EXCEPTION_LOAD_GUEST_PAGE_FAULT, OPCODE_LOAD) if funct3 != 7 => {
+                (MmioAccess::Load, (htinst >> 7) & 0x1f)
+            }
+            (EXCEPTION_STORE_GUEST_PAGE_FAULT, OPCODE_STORE) if funct3 < 4 => {
+                (MmioAccess::Store, (htinst >> 20) & 0x1f)
+            }
+            _ => return None,
+        };
+        // A zero HTVAL indicates that the fault isn't serviceable by the host.
+        if htval == 0 {
+            return None;
+        }
+        Some(Self {
+            access,
+            guest_addr: (htval << 2) | (stval & 0x3),
+            width: 1 << (funct3 & 0x3),
+            gpr: gpr as usize,
+        })
+    }
 }
 
 /// Functions provided by the TEE Host extension.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TeeHostFunction {
     /// Writes up to `len` bytes of the `TsmInfo` structure to the non-confidential physical address
     /// `dest_addr`. Returns the number of bytes written.
@@ -125,9 +288,9 @@ pub enum TeeHostFunction {
     /// a6 = 0
     TsmGetInfo {
         /// a0 = destination address of the `TsmInfo` structure
-        dest_addr: u64,
+        dest_addr: PhysAddr,
         /// a1 = maximum number of bytes to be written
-        len: u64,
+        len: Len,
     },
     /// Converts `num_pages` of 4kB page-size non-confidential memory starting at `page_addr`. The converted pages
     /// remain non-confidential, and thus may not be assigned for use by a child TVM, until the
@@ -136,7 +299,7 @@ pub enum TeeHostFunction {
     /// a6 = 1
     TsmConvertPages {
         /// a0 = base address of pages to convert
-        page_addr: u64,
+        page_addr: PhysAddr,
         /// a1 = number of pages
         num_pages: u64,
     },
@@ -146,7 +309,7 @@ pub enum TeeHostFunction {
     /// a6 = 2
     TsmReclaimPages {
         /// a0 = base address of pages to reclaim
-        page_addr: u64,
+        page_addr: PhysAddr,
         /// a1 = number of pages
         num_pages: u64,
     },
@@ -171,9 +334,9 @@ pub enum TeeHostFunction {
     /// a6 = 5
     TvmCreate {
         /// a0 = base physical address of the `TvmCreateParams` structure
-        params_addr: u64,
+        params_addr: PhysAddr,
         /// a1 = length of the `TvmCreateParams` structure in bytes
-        len: u64,
+        len: Len,
     },
     /// Moves a VM from the "Initializing" state to the "Runnable" state, finalizing the
     /// measurement of the TVM's configuration and initial memory contents. Sets the initial
@@ -208,9 +371,9 @@ pub enum TeeHostFunction {
         /// a0 = guest id
         guest_id: u64,
         /// a1 = start of the region
-        guest_addr: u64,
+        guest_addr: PhysAddr,
         /// a2 = length of the region
-        len: u64,
+        len: Len,
     },
     /// Adds `num_pages` 4kB pages of confidential memory starting at `page_addr` to the page-table
     /// page pool for the specified guest.
@@ -220,7 +383,7 @@ pub enum TeeHostFunction {
         /// a0 = guest_id
         guest_id: u64,
         /// a1 = address of the first page
-        page_addr: u64,
+        page_addr: PhysAddr,
         /// a2 = number of pages
         num_pages: u64,
     },
@@ -235,15 +398,15 @@ pub enum TeeHostFunction {
         /// a0 = guest_id
         guest_id: u64,
         /// a1 = physical address of the pages to copy from
-        src_addr: u64,
+        src_addr: PhysAddr,
         /// a2 = physical address of the pages to insert
-        dest_addr: u64,
+        dest_addr: PhysAddr,
         /// a3 = page size
         page_type: TsmPageType,
         /// a4 = number of pages
         num_pages: u64,
         /// a5 = guest physical address
-        guest_addr: u64,
+        guest_addr: PhysAddr,
     },
     /// Maps `num_pages` zero-filled pages of confidential memory starting at `page_addr` into the
     /// specified guest's address space at `guest_addr`. The mapping must lie within a region of
@@ -255,13 +418,13 @@ pub enum TeeHostFunction {
         /// a0 = guest_id
         guest_id: u64,
         /// a1 = physical address of the pages to insert
-        page_addr: u64,
+        page_addr: PhysAddr,
         /// a2 = page size
         page_type: TsmPageType,
         /// a3 = number of pages
         num_pages: u64,
         /// a4 = guest physical address
-        guest_addr: u64,
+        guest_addr: PhysAddr,
     },
     /// Maps non-confidential shared pages in a region of shared memory previously registered by
     /// the guest via `ShareMemory` in the TEE-Guest API.
@@ -271,13 +434,48 @@ pub enum TeeHostFunction {
         /// a0 = guest id
         guest_id: u64,
         /// a1 = start of the shared memory region
-        page_addr: u64,
+        page_addr: PhysAddr,
         /// a2 = page size (must be Page4k for now)
         page_type: TsmPageType,
         /// a3 = number of pages
         num_pages: u64,
         /// a4 = guest physical address
-        guest_addr: u64,
+        guest_addr: PhysAddr,
+    },
+    /// Marks the specified range of guest physical address space in the TVM identified by
+    /// `guest_id` as used for emulated MMIO. Accesses by the TVM within the range cause the vCPU
+    /// to exit from `TvmCpuRun` so that the host can emulate the access; see `TvmMmioExit` for
+    /// decoding the exit.
+    ///
+    /// Both `guest_addr` and `len` must be 4kB-aligned and the range must not overlap with any
+    /// previously-added memory or MMIO regions. Emulated MMIO regions may only be added prior to
+    /// TVM finalization.
+    ///
+    /// a6 = 13
+    TvmAddEmulatedMmioRegion {
+        /// a0 = guest id
+        guest_id: u64,
+        /// a1 = start of the region
+        guest_addr: PhysAddr,
+        /// a2 = length of the region
+        len: Len,
+    },
+    /// Unmaps `num_pages` non-confidential shared pages starting at `guest_addr` that were
+    /// previously mapped into the TVM identified by `guest_id` with `TvmAddSharedPages`. The
+    /// range must exactly match the range of a previous `TvmAddSharedPages` call. The TVM's
+    /// accesses to the range fault upon return, and the pages may be reused by the host once a
+    /// `TvmInitiateFence` sequence for the TVM has completed.
+    ///
+    /// a6 = 14
+    TvmRemoveSharedPages {
+        /// a0 = guest id
+        guest_id: u64,
+        /// a1 = guest physical address
+        guest_addr: PhysAddr,
+        /// a2 = page size (must be Page4k for now)
+        page_type: TsmPageType,
+        /// a3 = number of pages
+        num_pages: u64,
     },
     /// Adds a vCPU with ID `vcpu_id` to the guest `guest_id`, using the memory at `stage_page_addr`
     /// for internal storage of the vCPU's state.
@@ -294,7 +492,7 @@ pub enum TeeHostFunction {
         /// a1 = vCPU id
         vcpu_id: u64,
         /// a2 = address of the first page donated for the vCPU state
-        state_page_addr: u64,
+        state_page_addr: PhysAddr,
     },
     /// Runs the given vCPU in the TVM
     ///
@@ -348,27 +546,68 @@ pub enum TeeHostFunction {
 }
 
 impl TeeHostFunction {
+    /// Returns the number of pages of a call taking a page count as a `usize`, or
+    /// `Error::InvalidParam` if it doesn't fit. Returns `None` for other calls.
+    ///
+    /// ```
+    /// use sbi_rs::{PhysAddr, TeeHostFunction};
+    ///
+    /// let f = TeeHostFunction::TsmConvertPages {
+    ///     page_addr: PhysAddr::new(0x8000_0000),
+    ///     num_pages: 4,
+    /// };
+    /// assert_eq!(f.num_pages_usize(), Some(Ok(4)));
+    /// assert_eq!(TeeHostFunction::TsmLocalFence.num_pages_usize(), None);
+    /// ```
+    pub fn num_pages_usize(&self) -> Option<Result<usize>> {
+        use TeeHostFunction::*;
+        match *self {
+            TsmConvertPages { num_pages, .. }
+            | TsmReclaimPages { num_pages, .. }
+            | AddPageTablePages { num_pages, .. }
+            | TvmAddMeasuredPages { num_pages, .. }
+            | TvmAddZeroPages { num_pages, .. }
+            | TvmAddSharedPages { num_pages, .. }
+            | TvmRemoveSharedPages { num_pages, .. } => Some(num_pages.as_usize()),
+            _ => None,
+        }
+    }
+
+    /// Returns the length in bytes of a call taking a buffer or region length as a `usize`, or
+    /// `Error::InvalidParam` if it doesn't fit. Returns `None` for other calls.
+    pub fn len_usize(&self) -> Option<Result<usize>> {
+        use TeeHostFunction::*;
+        match *self {
+            TsmGetInfo { len, .. }
+            | TvmCreate { len, .. }
+            | TvmAddMemoryRegion { len, .. }
+            | TvmAddEmulatedMmioRegion { len, .. } => Some(len.as_usize()),
+            _ => None,
+        }
+    }
+
     /// Attempts to parse `Self` from the passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         use TeeHostFunction::*;
         match args[6] {
             0 => Ok(TsmGetInfo {
-                dest_addr: args[0],
-                len: args[1],
+                dest_addr: PhysAddr::new(args[0]),
+                len: Len::new(args[1]),
             }),
             1 => Ok(TsmConvertPages {
-                page_addr: args[0],
+                page_addr: PhysAddr::new(args[0]),
                 num_pages: args[1],
             }),
             2 => Ok(TsmReclaimPages {
-                page_addr: args[0],
+                page_addr: PhysAddr::new(args[0]),
                 num_pages: args[1],
             }),
             3 => Ok(TsmInitiateFence),
             4 => Ok(TsmLocalFence),
             5 => Ok(TvmCreate {
-                params_addr: args[0],
-                len: args[1],
+                params_addr: PhysAddr::new(args[0]),
+                len: Len::new(args[1]),
             }),
             6 => Ok(Finalize {
                 guest_id: args[0],
@@ -378,40 +617,51 @@ impl TeeHostFunction {
             7 => Ok(TvmDestroy { guest_id: args[0] }),
             8 => Ok(TvmAddMemoryRegion {
                 guest_id: args[0],
-                guest_addr: args[1],
-                len: args[2],
+                guest_addr: PhysAddr::new(args[1]),
+                len: Len::new(args[2]),
             }),
             9 => Ok(AddPageTablePages {
                 guest_id: args[0],
-                page_addr: args[1],
+                page_addr: PhysAddr::new(args[1]),
                 num_pages: args[2],
             }),
             10 => Ok(TvmAddMeasuredPages {
                 guest_id: args[0],
-                src_addr: args[1],
-                dest_addr: args[2],
+                src_addr: PhysAddr::new(args[1]),
+                dest_addr: PhysAddr::new(args[2]),
                 page_type: TsmPageType::from_reg(args[3])?,
                 num_pages: args[4],
-                guest_addr: args[5],
+                guest_addr: PhysAddr::new(args[5]),
             }),
             11 => Ok(TvmAddZeroPages {
                 guest_id: args[0],
-                page_addr: args[1],
+                page_addr: PhysAddr::new(args[1]),
                 page_type: TsmPageType::from_reg(args[2])?,
                 num_pages: args[3],
-                guest_addr: args[4],
+                guest_addr: PhysAddr::new(args[4]),
             }),
             12 => Ok(TvmAddSharedPages {
                 guest_id: args[0],
-                page_addr: args[1],
+                page_addr: PhysAddr::new(args[1]),
+                page_type: TsmPageType::from_reg(args[2])?,
+                num_pages: args[3],
+                guest_addr: PhysAddr::new(args[4]),
+            }),
+            13 => Ok(TvmAddEmulatedMmioRegion {
+                guest_id: args[0],
+                guest_addr: PhysAddr::new(args[1]),
+                len: Len::new(args[2]),
+            }),
+            14 => Ok(TvmRemoveSharedPages {
+                guest_id: args[0],
+                guest_addr: PhysAddr::new(args[1]),
                 page_type: TsmPageType::from_reg(args[2])?,
                 num_pages: args[3],
-                guest_addr: args[4],
             }),
             15 => Ok(TvmCpuCreate {
                 guest_id: args[0],
                 vcpu_id: args[1],
-                state_page_addr: args[2],
+                state_page_addr: PhysAddr::new(args[2]),
             }),
             16 => Ok(TvmCpuRun {
                 guest_id: args[0],
@@ -421,9 +671,205 @@ impl TeeHostFunction {
             _ => Err(Error::NotSupported),
         }
     }
+
+    /// Validates the arguments of the call within the budget tracked by `v`.
+    #[cfg(feature = "handler")]
+    pub(crate) fn validate(&self, v: &mut Validator) -> Result<()> {
+        use TeeHostFunction::*;
+        match *self {
+            TsmGetInfo { dest_addr, len } => v.range(dest_addr.bits(), len.bytes()),
+            TsmConvertPages {
+                page_addr,
+                num_pages,
+            }
+            | TsmReclaimPages {
+                page_addr,
+                num_pages,
+            }
+            | AddPageTablePages {
+                page_addr,
+                num_pages,
+                guest_id: _,
+            } => {
+                v.page_aligned(page_addr.bits())?;
+                v.pages(page_addr.bits(), TsmPageType::Page4k, num_pages)
+            }
+            TvmCreate { params_addr, len } => v.range(params_addr.bits(), len.bytes()),
+            TvmAddMemoryRegion {
+                guest_addr,
+                len,
+                guest_id: _,
+            }
+            | TvmAddEmulatedMmioRegion {
+                guest_addr,
+                len,
+                guest_id: _,
+            } => {
+                v.page_aligned_range(guest_addr.bits(), len.bytes())?;
+                v.range(guest_addr.bits(), len.bytes())
+            }
+            TvmAddMeasuredPages {
+                src_addr,
+                dest_addr,
+                page_type,
+                num_pages,
+                guest_addr,
+                guest_id: _,
+            } => {
+                v.page_aligned(src_addr.bits())?;
+                v.aligned(dest_addr.bits(), page_type.size_bytes())?;
+                v.aligned(guest_addr.bits(), page_type.size_bytes())?;
+                v.pages(dest_addr.bits(), page_type, num_pages)?;
+                v.pages(guest_addr.bits(), page_type, num_pages)
+            }
+            TvmAddZeroPages {
+                page_addr,
+                page_type,
+                num_pages,
+                guest_addr,
+                guest_id: _,
+            }
+            | TvmAddSharedPages {
+                page_addr,
+                page_type,
+                num_pages,
+                guest_addr,
+                guest_id: _,
+            } => {
+                v.aligned(page_addr.bits(), page_type.size_bytes())?;
+                v.aligned(guest_addr.bits(), page_type.size_bytes())?;
+                v.pages(page_addr.bits(), page_type, num_pages)?;
+                v.pages(guest_addr.bits(), page_type, num_pages)
+            }
+            TvmRemoveSharedPages {
+                guest_addr,
+                page_type,
+                num_pages,
+                guest_id: _,
+            } => {
+                v.aligned(guest_addr.bits(), page_type.size_bytes())?;
+                v.pages(guest_addr.bits(), page_type, num_pages)
+            }
+            TvmCpuCreate {
+                state_page_addr,
+                guest_id: _,
+                vcpu_id: _,
+            } => v.page_aligned(state_page_addr.bits()),
+            TsmInitiateFence
+            | TsmLocalFence
+            | Finalize {
+                guest_id: _,
+                entry_sepc: _,
+                entry_arg: _,
+            }
+            | TvmDestroy { guest_id: _ }
+            | TvmCpuRun {
+                guest_id: _,
+                vcpu_id: _,
+            }
+            | TvmInitiateFence { guest_id: _ } => Ok(()),
+        }
+    }
+
+    /// Returns `Error::InvalidParam` if the call operates on zero pages or on a zero-length region.
+    #[cfg(any(feature = "caller", feature = "handler"))]
+    pub(crate) const fn check_zero_length(&self) -> Result<()> {
+        use TeeHostFunction::*;
+        match *self {
+            TsmConvertPages { num_pages: 0, .. }
+            | TsmReclaimPages { num_pages: 0, .. }
+            | AddPageTablePages { num_pages: 0, .. }
+            | TvmAddMeasuredPages { num_pages: 0, .. }
+            | TvmAddZeroPages { num_pages: 0, .. }
+            | TvmAddSharedPages { num_pages: 0, .. }
+            | TvmRemoveSharedPages { num_pages: 0, .. } => Err(Error::InvalidParam),
+            TvmAddMemoryRegion { len, .. } | TvmAddEmulatedMmioRegion { len, .. }
+                if len.is_zero() =>
+            {
+                Err(Error::InvalidParam)
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl SbiFunction for TeeHostFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        use TeeHostFunction::*;
+        let (name, args): (_, &[_]) = match self {
+            TsmGetInfo { .. } => ("tsm_get_info", &["dest_addr", "len"]),
+            TsmConvertPages { .. } => ("tsm_convert_pages", &["page_addr", "num_pages"]),
+            TsmReclaimPages { .. } => ("tsm_reclaim_pages", &["page_addr", "num_pages"]),
+            TsmInitiateFence => ("tsm_initiate_fence", &[]),
+            TsmLocalFence => ("tsm_local_fence", &[]),
+            TvmCreate { .. } => ("tvm_create", &["params_addr", "len"]),
+            Finalize { .. } => ("tvm_finalize", &["guest_id", "entry_sepc", "entry_arg"]),
+            TvmDestroy { .. } => ("tvm_destroy", &["guest_id"]),
+            TvmAddMemoryRegion { .. } => {
+                ("tvm_add_memory_region", &["guest_id", "guest_addr", "len"])
+            }
+            AddPageTablePages { .. } => (
+                "tvm_add_page_table_pages",
+                &["guest_id", "page_addr", "num_pages"],
+            ),
+            TvmAddMeasuredPages { .. } => (
+                "tvm_add_measured_pages",
+                &[
+                    "guest_id",
+                    "src_addr",
+                    "dest_addr",
+                    "page_type",
+                    "num_pages",
+                    "guest_addr",
+                ],
+            ),
+            TvmAddZeroPages { .. } => (
+                "tvm_add_zero_pages",
+                &[
+                    "guest_id",
+                    "page_addr",
+                    "page_type",
+                    "num_pages",
+                    "guest_addr",
+                ],
+            ),
+            TvmAddSharedPages { .. } => (
+                "tvm_add_shared_pages",
+                &[
+                    "guest_id",
+                    "page_addr",
+                    "page_type",
+                    "num_pages",
+                    "guest_addr",
+                ],
+            ),
+            TvmAddEmulatedMmioRegion { .. } => (
+                "tvm_add_emulated_mmio_region",
+                &["guest_id", "guest_addr", "len"],
+            ),
+            TvmRemoveSharedPages { .. } => (
+                "tvm_remove_shared_pages",
+                &["guest_id", "guest_addr", "page_type", "num_pages"],
+            ),
+            TvmCpuCreate { .. } => (
+                "tvm_cpu_create",
+                &["guest_id", "vcpu_id", "state_page_addr"],
+            ),
+            TvmCpuRun { .. } => ("tvm_cpu_run", &["guest_id", "vcpu_id"]),
+            TvmInitiateFence { .. } => ("tvm_initiate_fence", &["guest_id"]),
+        };
+        Some(FunctionSignature::new(name, args))
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        use TeeHostFunction::*;
+        match self {
+            TsmGetInfo { .. } | TvmCreate { .. } | TvmCpuRun { .. } => ReturnKind::Value,
+            _ => ReturnKind::Empty,
+        }
+    }
+
+    #[inline(always)]
     fn a6(&self) -> u64 {
         use TeeHostFunction::*;
         match self {
@@ -483,6 +929,17 @@ impl SbiFunction for TeeHostFunction {
                 num_pages: _,
                 guest_addr: _,
             } => 12,
+            TvmAddEmulatedMmioRegion {
+                guest_id: _,
+                guest_addr: _,
+                len: _,
+            } => 13,
+            TvmRemoveSharedPages {
+                guest_id: _,
+                guest_addr: _,
+                page_type: _,
+                num_pages: _,
+            } => 14,
             TvmCpuCreate {
                 guest_id: _,
                 vcpu_id: _,
@@ -496,13 +953,14 @@ impl SbiFunction for TeeHostFunction {
         }
     }
 
+    #[inline(always)]
     fn a0(&self) -> u64 {
         use TeeHostFunction::*;
         match self {
             TvmCreate {
                 params_addr,
                 len: _,
-            } => *params_addr,
+            } => params_addr.bits(),
             TvmDestroy { guest_id } => *guest_id,
             AddPageTablePages {
                 guest_id,
@@ -530,7 +988,7 @@ impl SbiFunction for TeeHostFunction {
                 vcpu_id: _,
                 state_page_addr: _,
             } => *guest_id,
-            TsmGetInfo { dest_addr, len: _ } => *dest_addr,
+            TsmGetInfo { dest_addr, len: _ } => dest_addr.bits(),
             TvmAddMeasuredPages {
                 guest_id,
                 src_addr: _,
@@ -542,11 +1000,11 @@ impl SbiFunction for TeeHostFunction {
             TsmConvertPages {
                 page_addr,
                 num_pages: _,
-            } => *page_addr,
+            } => page_addr.bits(),
             TsmReclaimPages {
                 page_addr,
                 num_pages: _,
-            } => *page_addr,
+            } => page_addr.bits(),
             TvmAddMemoryRegion {
                 guest_id,
                 guest_addr: _,
@@ -559,30 +1017,42 @@ impl SbiFunction for TeeHostFunction {
                 num_pages: _,
                 guest_addr: _,
             } => *guest_id,
+            TvmAddEmulatedMmioRegion {
+                guest_id,
+                guest_addr: _,
+                len: _,
+            } => *guest_id,
+            TvmRemoveSharedPages {
+                guest_id,
+                guest_addr: _,
+                page_type: _,
+                num_pages: _,
+            } => *guest_id,
             TvmInitiateFence { guest_id } => *guest_id,
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a1(&self) -> u64 {
         use TeeHostFunction::*;
         match self {
             TvmCreate {
                 params_addr: _,
                 len,
-            } => *len,
+            } => len.bytes(),
             AddPageTablePages {
                 guest_id: _,
                 page_addr,
                 num_pages: _,
-            } => *page_addr,
+            } => page_addr.bits(),
             TvmAddZeroPages {
                 guest_id: _,
                 page_addr,
                 page_type: _,
                 num_pages: _,
                 guest_addr: _,
-            } => *page_addr,
+            } => page_addr.bits(),
             Finalize {
                 guest_id: _,
                 entry_sepc,
@@ -597,7 +1067,7 @@ impl SbiFunction for TeeHostFunction {
                 vcpu_id,
                 state_page_addr: _,
             } => *vcpu_id,
-            TsmGetInfo { dest_addr: _, len } => *len,
+            TsmGetInfo { dest_addr: _, len } => len.bytes(),
             TvmAddMeasuredPages {
                 guest_id: _,
                 src_addr,
@@ -605,7 +1075,7 @@ impl SbiFunction for TeeHostFunction {
                 page_type: _,
                 num_pages: _,
                 guest_addr: _,
-            } => *src_addr,
+            } => src_addr.bits(),
             TsmConvertPages {
                 page_addr: _,
                 num_pages,
@@ -618,18 +1088,30 @@ impl SbiFunction for TeeHostFunction {
                 guest_id: _,
                 guest_addr,
                 len: _,
-            } => *guest_addr,
+            } => guest_addr.bits(),
             TvmAddSharedPages {
                 guest_id: _,
                 page_addr,
                 page_type: _,
                 num_pages: _,
                 guest_addr: _,
-            } => *page_addr,
+            } => page_addr.bits(),
+            TvmAddEmulatedMmioRegion {
+                guest_id: _,
+                guest_addr,
+                len: _,
+            } => guest_addr.bits(),
+            TvmRemoveSharedPages {
+                guest_id: _,
+                guest_addr,
+                page_type: _,
+                num_pages: _,
+            } => guest_addr.bits(),
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a2(&self) -> u64 {
         use TeeHostFunction::*;
         match self {
@@ -654,7 +1136,7 @@ impl SbiFunction for TeeHostFunction {
                 guest_id: _,
                 vcpu_id: _,
                 state_page_addr,
-            } => *state_page_addr,
+            } => state_page_addr.bits(),
             TvmAddMeasuredPages {
                 guest_id: _,
                 src_addr: _,
@@ -662,12 +1144,12 @@ impl SbiFunction for TeeHostFunction {
                 page_type: _,
                 num_pages: _,
                 guest_addr: _,
-            } => *dest_addr,
+            } => dest_addr.bits(),
             TvmAddMemoryRegion {
                 guest_id: _,
                 guest_addr: _,
                 len,
-            } => *len,
+            } => len.bytes(),
             TvmAddSharedPages {
                 guest_id: _,
                 page_addr: _,
@@ -675,10 +1157,22 @@ impl SbiFunction for TeeHostFunction {
                 num_pages: _,
                 guest_addr: _,
             } => *page_type as u64,
+            TvmAddEmulatedMmioRegion {
+                guest_id: _,
+                guest_addr: _,
+                len,
+            } => len.bytes(),
+            TvmRemoveSharedPages {
+                guest_id: _,
+                guest_addr: _,
+                page_type,
+                num_pages: _,
+            } => *page_type as u64,
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a3(&self) -> u64 {
         use TeeHostFunction::*;
         match self {
@@ -704,10 +1198,17 @@ impl SbiFunction for TeeHostFunction {
                 num_pages,
                 guest_addr: _,
             } => *num_pages,
+            TvmRemoveSharedPages {
+                guest_id: _,
+                guest_addr: _,
+                page_type: _,
+                num_pages,
+            } => *num_pages,
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a4(&self) -> u64 {
         use TeeHostFunction::*;
         match self {
@@ -717,7 +1218,7 @@ impl SbiFunction for TeeHostFunction {
                 page_type: _,
                 num_pages: _,
                 guest_addr,
-            } => *guest_addr,
+            } => guest_addr.bits(),
             TvmAddMeasuredPages {
                 guest_id: _,
                 src_addr: _,
@@ -732,11 +1233,12 @@ impl SbiFunction for TeeHostFunction {
                 page_type: _,
                 num_pages: _,
                 guest_addr,
-            } => *guest_addr,
+            } => guest_addr.bits(),
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a5(&self) -> u64 {
         use TeeHostFunction::*;
         match self {
@@ -747,7 +1249,7 @@ impl SbiFunction for TeeHostFunction {
                 page_type: _,
                 num_pages: _,
                 guest_addr,
-            } => *guest_addr,
+            } => guest_addr.bits(),
             _ => 0,
         }
     }
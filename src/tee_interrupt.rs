@@ -6,8 +6,12 @@
 //! virtualization using the RISC-V Advanced Interrupt Architecture (AIA) on platforms which
 //! support it.
 
+use crate::addr::*;
+#[cfg(feature = "handler")]
+use crate::decode::Validator;
 use crate::error::*;
 use crate::function::*;
+use crate::xlen::AsUsize;
 
 /// Describes a TVM's AIA configuration.
 #[repr(C)]
@@ -39,8 +43,59 @@ pub struct TvmAiaParams {
     pub guests_per_hart: u32,
 }
 
+/// A set of IMSIC guest interrupt files on a physical CPU. The mask is in the same format as the
+/// `hgeie` and `hgeip` CSRs, that is bit N corresponds to guest interrupt file N-1 and bit 0 is
+/// always 0.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImsicFileMask(u64);
+
+impl ImsicFileMask {
+    /// The maximum number of guest interrupt files that can be selected by a mask.
+    pub const MAX_FILES: usize = 63;
+
+    /// Constructs a new `ImsicFileMask` from a valid passed-in value. Returns an error if bit 0,
+    /// which doesn't correspond to any guest interrupt file, is set.
+    pub const fn from_raw_value(value: u64) -> Result<Self> {
+        if value & 1 == 0 {
+            Ok(ImsicFileMask(value))
+        } else {
+            Err(Error::InvalidParam)
+        }
+    }
+
+    /// Returns the raw inner value.
+    pub const fn raw(&self) -> u64 {
+        self.0
+    }
+
+    /// Adds the guest interrupt file at index `file` to the mask. Returns an error if `file` is
+    /// not a valid guest interrupt file index.
+    pub const fn with_file(self, file: usize) -> Result<Self> {
+        if file >= Self::MAX_FILES {
+            return Err(Error::InvalidParam);
+        }
+        Ok(ImsicFileMask(self.0 | (1 << (file + 1))))
+    }
+
+    /// Returns if the guest interrupt file at index `file` is selected by the mask.
+    pub const fn contains(&self, file: usize) -> bool {
+        file < Self::MAX_FILES && self.0 & (1 << (file + 1)) != 0
+    }
+
+    /// Returns the number of guest interrupt files selected by the mask.
+    pub const fn num_files(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns an iterator over the indices of the guest interrupt files selected by the mask.
+    pub fn files(&self) -> impl Iterator<Item = usize> {
+        let mask = *self;
+        (0..Self::MAX_FILES).filter(move |&f| mask.contains(f))
+    }
+}
+
 /// Functions provided by the TEE Interrupt extension.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TeeInterruptFunction {
     /// Configures AIA virtualization for the TVM identified by `tvm_id` from the parameters in
     /// the `TvmAiaParams` structure at the non-confidential physical address `params_addr`.
@@ -54,9 +109,9 @@ pub enum TeeInterruptFunction {
         /// a0 = TVM ID
         tvm_id: u64,
         /// a1 = physical address of the `TvmAiaParams` structure
-        params_addr: u64,
+        params_addr: PhysAddr,
         /// a2 = length of the `TvmAiaParams` structure in bytes
-        len: u64,
+        len: Len,
     },
     /// Sets the guest physical address of the specified vCPU's virtualized IMSIC to `imsic_addr`.
     /// `imsic_addr` must be valid for the AIA configuration that was set in `TvmAiaInit` and no
@@ -68,14 +123,14 @@ pub enum TeeInterruptFunction {
     ///
     /// Returns 0 on success.
     ///
-    /// a6 - 1
+    /// a6 = 1
     TvmCpuSetImsicAddr {
         /// a0 = TVM ID
         tvm_id: u64,
         /// a1 = vCPU ID
         vcpu_id: u64,
         /// a2 = guest physical address of vCPU's IMSIC
-        imsic_addr: u64,
+        imsic_addr: PhysAddr,
     },
     /// Converts the non-confidential guest interrupt file at `imsic_addr` for use with a TVM. A TSM
     /// fence sequence must be completed before the interrupt file may be assigned to a child TVM.
@@ -85,7 +140,7 @@ pub enum TeeInterruptFunction {
     /// a6 = 2
     TsmConvertImsic {
         /// a0 = physical address of interrupt file to be converted
-        imsic_addr: u64,
+        imsic_addr: PhysAddr,
     },
     /// Reclaims the confidential guest interrupt file at `imsic_addr`. The interrupt file must not
     /// currently be assigned to a TVM.
@@ -95,7 +150,7 @@ pub enum TeeInterruptFunction {
     /// a6 = 3
     TsmReclaimImsic {
         /// a0 = physical address of interrupt file to be reclaimed
-        imsic_addr: u64,
+        imsic_addr: PhysAddr,
     },
     /// Binds a TVM vCPU to the current physical CPU using the confidential guest interrupt files
     /// specified in `imsic_mask`, restoring interrupt state from the vCPU's software interrupt
@@ -114,7 +169,7 @@ pub enum TeeInterruptFunction {
         /// a1 = vCPU ID
         vcpu_id: u64,
         /// a2 = bit mask of interrupt files to be bound
-        imsic_mask: u64,
+        imsic_mask: ImsicFileMask,
     },
     /// Begins the unbind process for the specified vCPU from its guest interrupt files. The
     /// translations for the vCPU's virtualized IMSIC are invalidated, and a TLB flush sequence
@@ -177,7 +232,7 @@ pub enum TeeInterruptFunction {
         /// a1 = vCPU ID
         vcpu_id: u64,
         /// a2 = bit mask of interrupt files to be bound
-        imsic_mask: u64,
+        imsic_mask: ImsicFileMask,
     },
     /// Clones the old guest interrupt file of the specified vCPU. Caller must make sure to invoke this from
     /// old physical CPU. The guest interrupt file after this is free to be reclaimed or bound to another
@@ -185,7 +240,7 @@ pub enum TeeInterruptFunction {
     ///
     /// Returns 0 on success.
     ///
-    /// a6 = 9
+    /// a6 = 10
     TvmCpuRebindImsicClone {
         /// a0 = TVM ID
         tvm_id: u64,
@@ -197,7 +252,7 @@ pub enum TeeInterruptFunction {
     ///
     /// Returns 0 on success.
     ///
-    /// a6 = 10
+    /// a6 = 9
     TvmCpuRebindImsicEnd {
         /// a0 = TVM ID
         tvm_id: u64,
@@ -207,30 +262,40 @@ pub enum TeeInterruptFunction {
 }
 
 impl TeeInterruptFunction {
+    /// Returns the length in bytes of the `TvmAiaParams` passed to `TvmAiaInit` as a `usize`, or
+    /// `Error::InvalidParam` if it doesn't fit. Returns `None` for other calls.
+    pub fn len_usize(&self) -> Option<Result<usize>> {
+        match *self {
+            TeeInterruptFunction::TvmAiaInit { len, .. } => Some(len.as_usize()),
+            _ => None,
+        }
+    }
+
     /// Attempts to parse `Self` from the register values passed in `a0-a7`.
-    pub(crate) fn from_regs(args: &[u64]) -> Result<Self> {
+    #[cfg(feature = "handler")]
+    pub(crate) fn from_regs(args: &[u64; 8]) -> Result<Self> {
         use TeeInterruptFunction::*;
         match args[6] {
             0 => Ok(TvmAiaInit {
                 tvm_id: args[0],
-                params_addr: args[1],
-                len: args[2],
+                params_addr: PhysAddr::new(args[1]),
+                len: Len::new(args[2]),
             }),
             1 => Ok(TvmCpuSetImsicAddr {
                 tvm_id: args[0],
                 vcpu_id: args[1],
-                imsic_addr: args[2],
+                imsic_addr: PhysAddr::new(args[2]),
             }),
             2 => Ok(TsmConvertImsic {
-                imsic_addr: args[0],
+                imsic_addr: PhysAddr::new(args[0]),
             }),
             3 => Ok(TsmReclaimImsic {
-                imsic_addr: args[0],
+                imsic_addr: PhysAddr::new(args[0]),
             }),
             4 => Ok(TvmCpuBindImsic {
                 tvm_id: args[0],
                 vcpu_id: args[1],
-                imsic_mask: args[2],
+                imsic_mask: ImsicFileMask::from_raw_value(args[2])?,
             }),
             5 => Ok(TvmCpuUnbindImsicBegin {
                 tvm_id: args[0],
@@ -248,7 +313,7 @@ impl TeeInterruptFunction {
             8 => Ok(TvmCpuRebindImsicBegin {
                 tvm_id: args[0],
                 vcpu_id: args[1],
-                imsic_mask: args[2],
+                imsic_mask: ImsicFileMask::from_raw_value(args[2])?,
             }),
             9 => Ok(TvmCpuRebindImsicEnd {
                 tvm_id: args[0],
@@ -261,9 +326,56 @@ impl TeeInterruptFunction {
             _ => Err(Error::NotSupported),
         }
     }
+
+    /// Validates the arguments of the call within the budget tracked by `v`.
+    #[cfg(feature = "handler")]
+    pub(crate) fn validate(&self, v: &mut Validator) -> Result<()> {
+        use TeeInterruptFunction::*;
+        match *self {
+            TvmAiaInit {
+                params_addr, len, ..
+            } => v.range(params_addr.bits(), len.bytes()),
+            TvmCpuSetImsicAddr { imsic_addr, .. }
+            | TsmConvertImsic { imsic_addr }
+            | TsmReclaimImsic { imsic_addr } => v.page_aligned(imsic_addr.bits()),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl SbiFunction for TeeInterruptFunction {
+    fn signature(&self) -> Option<FunctionSignature> {
+        use TeeInterruptFunction::*;
+        let (name, args): (_, &[_]) = match self {
+            TvmAiaInit { .. } => ("tvm_aia_init", &["tvm_id", "params_addr", "len"]),
+            TvmCpuSetImsicAddr { .. } => (
+                "tvm_cpu_set_imsic_addr",
+                &["tvm_id", "vcpu_id", "imsic_addr"],
+            ),
+            TsmConvertImsic { .. } => ("tsm_convert_imsic", &["imsic_addr"]),
+            TsmReclaimImsic { .. } => ("tsm_reclaim_imsic", &["imsic_addr"]),
+            TvmCpuBindImsic { .. } => ("tvm_cpu_bind_imsic", &["tvm_id", "vcpu_id", "imsic_mask"]),
+            TvmCpuUnbindImsicBegin { .. } => ("tvm_cpu_unbind_imsic_begin", &["tvm_id", "vcpu_id"]),
+            TvmCpuUnbindImsicEnd { .. } => ("tvm_cpu_unbind_imsic_end", &["tvm_id", "vcpu_id"]),
+            TvmCpuInjectExternalInterrupt { .. } => (
+                "tvm_cpu_inject_external_interrupt",
+                &["tvm_id", "vcpu_id", "interrupt_id"],
+            ),
+            TvmCpuRebindImsicBegin { .. } => (
+                "tvm_cpu_rebind_imsic_begin",
+                &["tvm_id", "vcpu_id", "imsic_mask"],
+            ),
+            TvmCpuRebindImsicEnd { .. } => ("tvm_cpu_rebind_imsic_end", &["tvm_id", "vcpu_id"]),
+            TvmCpuRebindImsicClone { .. } => ("tvm_cpu_rebind_imsic_clone", &["tvm_id", "vcpu_id"]),
+        };
+        Some(FunctionSignature::new(name, args))
+    }
+
+    fn return_kind(&self) -> ReturnKind {
+        ReturnKind::Empty
+    }
+
+    #[inline(always)]
     fn a6(&self) -> u64 {
         use TeeInterruptFunction::*;
         match self {
@@ -281,6 +393,7 @@ impl SbiFunction for TeeInterruptFunction {
         }
     }
 
+    #[inline(always)]
     fn a0(&self) -> u64 {
         use TeeInterruptFunction::*;
         match self {
@@ -294,8 +407,8 @@ impl SbiFunction for TeeInterruptFunction {
                 vcpu_id: _,
                 imsic_addr: _,
             } => *tvm_id,
-            TsmConvertImsic { imsic_addr } => *imsic_addr,
-            TsmReclaimImsic { imsic_addr } => *imsic_addr,
+            TsmConvertImsic { imsic_addr } => imsic_addr.bits(),
+            TsmReclaimImsic { imsic_addr } => imsic_addr.bits(),
             TvmCpuBindImsic {
                 tvm_id,
                 vcpu_id: _,
@@ -318,6 +431,7 @@ impl SbiFunction for TeeInterruptFunction {
         }
     }
 
+    #[inline(always)]
     fn a1(&self) -> u64 {
         use TeeInterruptFunction::*;
         match self {
@@ -325,7 +439,7 @@ impl SbiFunction for TeeInterruptFunction {
                 tvm_id: _,
                 params_addr,
                 len: _,
-            } => *params_addr,
+            } => params_addr.bits(),
             TvmCpuSetImsicAddr {
                 tvm_id: _,
                 vcpu_id,
@@ -354,6 +468,7 @@ impl SbiFunction for TeeInterruptFunction {
         }
     }
 
+    #[inline(always)]
     fn a2(&self) -> u64 {
         use TeeInterruptFunction::*;
         match self {
@@ -361,17 +476,17 @@ impl SbiFunction for TeeInterruptFunction {
                 tvm_id: _,
                 params_addr: _,
                 len,
-            } => *len,
+            } => len.bytes(),
             TvmCpuSetImsicAddr {
                 tvm_id: _,
                 vcpu_id: _,
                 imsic_addr,
-            } => *imsic_addr,
+            } => imsic_addr.bits(),
             TvmCpuBindImsic {
                 tvm_id: _,
                 vcpu_id: _,
                 imsic_mask,
-            } => *imsic_mask,
+            } => imsic_mask.raw(),
             TvmCpuInjectExternalInterrupt {
                 tvm_id: _,
                 vcpu_id: _,
@@ -381,19 +496,22 @@ impl SbiFunction for TeeInterruptFunction {
                 tvm_id: _,
                 vcpu_id: _,
                 imsic_mask,
-            } => *imsic_mask,
+            } => imsic_mask.raw(),
             _ => 0,
         }
     }
 
+    #[inline(always)]
     fn a3(&self) -> u64 {
         0
     }
 
+    #[inline(always)]
     fn a4(&self) -> u64 {
         0
     }
 
+    #[inline(always)]
     fn a5(&self) -> u64 {
         0
     }
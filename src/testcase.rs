@@ -0,0 +1,110 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+/// Declares a test for a canned SBI call: the registers a caller passes, the message they
+/// decode to, the registers a handler returns and the result the caller reads from them.
+///
+/// The expanded test checks that `regs_in` decodes to a message whose `Debug` output is
+/// `message`, that the message encodes back to `regs_in`, that a handler writing `result` with
+/// `write_result()` leaves `regs_out` in `a0` and `a1`, and that `result()` reads `result` back
+/// from `regs_out`. The `Debug` output isn't checked with the `tiny` feature, which leaves the
+/// names out of it. Calls that must fail to decode are declared with `error` instead:
+///
+/// ```
+/// use sbi_rs::*;
+///
+/// sbi_testcase! {
+///     name: hart_get_status,
+///     regs_in: [1, 0, 0, 0, 0, 0, 2, EXT_HART_STATE],
+///     message: "HartState(HartStatus { hart_id: 1 })",
+///     regs_out: [0, 2],
+///     result: Ok(2),
+/// }
+///
+/// sbi_testcase! {
+///     name: hart_get_status_unknown_fid,
+///     regs_in: [1, 0, 0, 0, 0, 0, 9, EXT_HART_STATE],
+///     error: Error::NotSupported,
+/// }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "handler")]
+#[macro_export]
+macro_rules! sbi_testcase {
+    (
+        name: $name:ident,
+        regs_in: $regs_in:expr,
+        message: $message:expr,
+        regs_out: $regs_out:expr,
+        result: $result:expr $(,)?
+    ) => {
+        #[test]
+        fn $name() {
+            let regs_in: [u64; 8] = $regs_in;
+            let regs_out: [u64; 2] = $regs_out;
+            let result: $crate::Result<u64> = $result;
+            let msg = $crate::SbiMessage::from_regs(&regs_in).expect("call failed to decode");
+            if !$crate::__SBI_TINY {
+                assert_eq!(::std::format!("{:?}", msg), $message, "decoded message");
+            }
+            assert_eq!(msg.to_regs(), regs_in, "encoded registers");
+            let mut regs = regs_in;
+            msg.write_result(result, &mut regs);
+            assert_eq!([regs[0], regs[1]], regs_out, "returned registers");
+            assert_eq!(msg.result(regs_out[0], regs_out[1]), result, "read result");
+        }
+    };
+    (
+        name: $name:ident,
+        regs_in: $regs_in:expr,
+        error: $error:expr $(,)?
+    ) => {
+        #[test]
+        fn $name() {
+            let regs_in: [u64; 8] = $regs_in;
+            let error: $crate::Error = $error;
+            assert_eq!($crate::SbiMessage::from_regs(&regs_in).unwrap_err(), error);
+        }
+    };
+}
+
+/// Declares a test checking the exact `a0-a7` registers that each message encodes to against a
+/// fixture recorded from the SBI specification, so that changes to the register encoding of a
+/// call are caught even for calls that round-trip through decoding. The check only encodes, so
+/// it is also available to caller-only builds:
+///
+/// ```
+/// use sbi_rs::*;
+///
+/// sbi_encoding_fixtures! {
+///     name: hsm_encoding,
+///     SbiMessage::HartState(StateFunction::HartStatus { hart_id: 3 })
+///         => [3, 0, 0, 0, 0, 0, 2, EXT_HART_STATE],
+///     SbiMessage::HartState(StateFunction::HartStop)
+///         => [0, 0, 0, 0, 0, 0, 1, EXT_HART_STATE],
+/// }
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! sbi_encoding_fixtures {
+    (
+        name: $name:ident,
+        $($msg:expr => $regs:expr),* $(,)?
+    ) => {
+        #[test]
+        fn $name() {
+            $(
+                let msg: $crate::SbiMessage = $msg;
+                let regs: [u64; 8] = $regs;
+                assert_eq!(msg.to_regs(), regs, "encoded registers of {:?}", msg);
+            )*
+        }
+    };
+}
+
+// Lets `sbi_testcase!` check the features of this crate rather than those of the crate it's
+// expanded in.
+#[cfg(feature = "handler")]
+#[doc(hidden)]
+pub const __SBI_TINY: bool = cfg!(feature = "tiny");
@@ -0,0 +1,26 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(feature = "handler")]
+use crate::error::*;
+use crate::function::*;
+
+sbi_function! {
+    /// Functions for the Timer extension
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum TimeFunction {
+        /// Programs the clock for the next event after `stime_value` time. `stime_value` is in
+        /// absolute time. This function also clears the pending timer interrupt bit.
+        ///
+        /// If the supervisor wishes to clear the timer interrupt without scheduling the next timer
+        /// event, it can request a timer interrupt infinitely far into the future (i.e.
+        /// `u64::MAX`).
+        ///
+        /// a6 = 0
+        SetTimer(set_timer) = 0 => Empty {
+            /// a0 = absolute time of the next timer event
+            stime_value => a0,
+        },
+    }
+}
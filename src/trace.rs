@@ -0,0 +1,213 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Building blocks for tracing SBI calls.
+//!
+//! A `SequenceCounter` shared by all harts hands out monotonically increasing `SequenceTag`s.
+//! Tagging each traced message lets traces captured on different harts be merged and put back in
+//! order during offline analysis.
+//!
+//! Traced messages can also be timestamped from a `TimeSource`. Guests, hosts and emulators
+//! tracing the same workload should read the same clock, such as the `time` CSR read by
+//! `rdtime()`, for their timestamps to be comparable.
+
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+use core::arch::asm;
+use core::cmp::Ordering;
+use core::sync::atomic::{self, AtomicU32};
+
+use crate::SbiMessage;
+
+/// A sequence number attached to a traced message. Tags wrap around after `u32::MAX`, so they
+/// are compared with serial number arithmetic: of two tags less than 2^31 apart, the one that
+/// was handed out first compares as less.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SequenceTag(u32);
+
+impl SequenceTag {
+    /// Creates a tag from its raw value, as stored in a trace.
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the raw value of the tag.
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Compares two tags, accounting for wrap-around.
+    pub fn cmp_wrapping(&self, other: &Self) -> Ordering {
+        (self.0.wrapping_sub(other.0) as i32).cmp(&0)
+    }
+}
+
+/// Hands out `SequenceTag`s in increasing order. A single counter is meant to be shared by every
+/// hart whose calls are traced.
+#[derive(Debug, Default)]
+pub struct SequenceCounter(AtomicU32);
+
+impl SequenceCounter {
+    /// Creates a counter whose first tag is 0.
+    pub const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    /// Returns the next tag.
+    pub fn next(&self) -> SequenceTag {
+        SequenceTag(self.0.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+/// Returns the current time in ticks, for timestamping traced messages.
+pub type TimeSource = fn() -> u64;
+
+/// Reads the `time` CSR.
+#[cfg(target_arch = "riscv64")]
+pub fn rdtime() -> u64 {
+    let time: u64;
+    // Safety: reading the time CSR has no side effects.
+    unsafe { asm!("rdtime {}", out(reg) time, options(nomem, nostack)) };
+    time
+}
+
+/// Reads the `time` CSR.
+#[cfg(target_arch = "riscv32")]
+pub fn rdtime() -> u64 {
+    loop {
+        let (hi, lo, hi2): (u32, u32, u32);
+        // Safety: reading the time CSRs has no side effects.
+        unsafe {
+            asm!("rdtimeh {}", "rdtime {}", "rdtimeh {}", out(reg) hi, out(reg) lo, out(reg) hi2,
+                 options(nomem, nostack))
+        };
+        // Re-read if the low half wrapped between the reads of the high half.
+        if hi == hi2 {
+            return ((hi as u64) << 32) | lo as u64;
+        }
+    }
+}
+
+/// The time source used for traces unless another is given: `rdtime()` on riscv targets, and
+/// none elsewhere.
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+pub const DEFAULT_TIME_SOURCE: Option<TimeSource> = Some(rdtime);
+/// The time source used for traces unless another is given: `rdtime()` on riscv targets, and
+/// none elsewhere.
+#[cfg(not(any(target_arch = "riscv64", target_arch = "riscv32")))]
+pub const DEFAULT_TIME_SOURCE: Option<TimeSource> = None;
+
+/// An `SbiMessage` along with an optional sequence tag and timestamp, as recorded in a trace.
+#[derive(Clone, Copy, Debug)]
+pub struct TaggedMessage {
+    /// The traced message.
+    pub msg: SbiMessage,
+    /// The sequence tag of the message, if tagging is enabled for the trace.
+    pub tag: Option<SequenceTag>,
+    /// The time at which the message was traced, if the trace has a time source.
+    pub timestamp: Option<u64>,
+}
+
+impl TaggedMessage {
+    /// Creates an untagged message.
+    pub const fn new(msg: SbiMessage) -> Self {
+        Self {
+            msg,
+            tag: None,
+            timestamp: None,
+        }
+    }
+
+    /// Creates a message tagged with the next tag from `counter`.
+    pub fn tagged(msg: SbiMessage, counter: &SequenceCounter) -> Self {
+        Self {
+            msg,
+            tag: Some(counter.next()),
+            timestamp: None,
+        }
+    }
+
+    /// Timestamps the message with the current time read from `now`.
+    pub fn stamped(self, now: TimeSource) -> Self {
+        Self {
+            timestamp: Some(now()),
+            ..self
+        }
+    }
+
+    /// Compares two messages by tag for merging traces. Untagged messages order before tagged
+    /// ones, keeping their relative order under a stable sort.
+    pub fn cmp_by_tag(&self, other: &Self) -> Ordering {
+        match (self.tag, other.tag) {
+            (Some(a), Some(b)) => a.cmp_wrapping(&b),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// Records messages into a trace, tagging each with a sequence tag and timestamping it from the
+/// trace's time source, if any.
+///
+/// ```rust
+/// use sbi_rs::*;
+///
+/// fn ticks() -> u64 {
+///     42
+/// }
+///
+/// let tracer = Tracer::new().with_time_source(ticks);
+/// let msg = SbiMessage::Base(BaseFunction::GetSpecificationVersion);
+/// let traced = tracer.record(msg);
+/// assert_eq!(traced.tag, Some(SequenceTag::from_raw(0)));
+/// assert_eq!(traced.timestamp, Some(42));
+/// ```
+#[derive(Debug)]
+pub struct Tracer {
+    counter: SequenceCounter,
+    now: Option<TimeSource>,
+}
+
+impl Tracer {
+    /// Creates a tracer timestamping messages from `DEFAULT_TIME_SOURCE`.
+    pub const fn new() -> Self {
+        Self {
+            counter: SequenceCounter::new(),
+            now: DEFAULT_TIME_SOURCE,
+        }
+    }
+
+    /// Sets the time source used to timestamp messages.
+    pub const fn with_time_source(self, now: TimeSource) -> Self {
+        Self {
+            now: Some(now),
+            ..self
+        }
+    }
+
+    /// Stops timestamping messages.
+    pub const fn without_time_source(self) -> Self {
+        Self { now: None, ..self }
+    }
+
+    /// Returns the time source used to timestamp messages, if any.
+    pub const fn time_source(&self) -> Option<TimeSource> {
+        self.now
+    }
+
+    /// Returns `msg` tagged with its sequence tag and timestamp.
+    pub fn record(&self, msg: SbiMessage) -> TaggedMessage {
+        let tagged = TaggedMessage::tagged(msg, &self.counter);
+        match self.now {
+            Some(now) => tagged.stamped(now),
+            None => tagged,
+        }
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
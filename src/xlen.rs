@@ -0,0 +1,81 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers for the register width (XLEN) of the target.
+//!
+//! Register values are held as `u64` throughout this crate. On RV32 only the low 32 bits of each
+//! value are passed, so SBI arguments that may be wider than XLEN, such as physical addresses and
+//! counter values, are split across a pair of registers holding their low and high halves.
+//! Handlers running on a host with a narrower `usize` than the values they are passed convert
+//! them with `AsUsize` rather than `as`.
+
+use crate::addr::*;
+use crate::error::*;
+
+/// The width in bits of the `a0-a7` registers on the target.
+pub const XLEN: u32 = usize::BITS;
+
+/// Splits `val` into the low and high halves passed in a pair of registers. The high half is
+/// always 0 on RV64.
+pub const fn split_reg_pair(val: u64) -> (u64, u64) {
+    if XLEN == 32 {
+        (val & (u32::MAX as u64), val >> 32)
+    } else {
+        (val, 0)
+    }
+}
+
+/// Joins the low and high halves of a value passed in a pair of registers. Returns
+/// `Error::InvalidParam` if the value doesn't fit in 64 bits, as is the case on RV64 if the high
+/// half is non-zero.
+pub const fn join_reg_pair(lo: u64, hi: u64) -> Result<u64> {
+    if XLEN == 32 {
+        if lo > u32::MAX as u64 || hi > u32::MAX as u64 {
+            return Err(Error::InvalidParam);
+        }
+        Ok(lo | (hi << 32))
+    } else if hi != 0 {
+        Err(Error::InvalidParam)
+    } else {
+        Ok(lo)
+    }
+}
+
+/// Joins the low and high halves of a value for arguments where the high half is only passed on
+/// RV32. The high half is ignored on RV64.
+pub const fn join_rv32_reg_pair(lo: u64, hi: u64) -> u64 {
+    if XLEN == 32 {
+        (lo & (u32::MAX as u64)) | (hi << 32)
+    } else {
+        lo
+    }
+}
+
+/// Checked conversion of argument values to `usize`, for handlers and tools using addresses,
+/// lengths and indices passed in registers to access memory on a host whose `usize` may be
+/// narrower than 64 bits. Implemented for `u64`, `PhysAddr` and `Len`; the functions passing
+/// lengths and counts also provide per-field accessors such as `DebugConsoleFunction::len_usize()`
+/// and `TeeHostFunction::num_pages_usize()`.
+pub trait AsUsize {
+    /// Returns the value as a `usize`, or `Error::InvalidParam` if it doesn't fit.
+    fn as_usize(&self) -> Result<usize>;
+}
+
+impl AsUsize for u64 {
+    fn as_usize(&self) -> Result<usize> {
+        usize::try_from(*self).map_err(|_| Error::InvalidParam)
+    }
+}
+
+impl AsUsize for PhysAddr {
+    fn as_usize(&self) -> Result<usize> {
+        self.bits().as_usize()
+    }
+}
+
+impl AsUsize for Len {
+    fn as_usize(&self) -> Result<usize> {
+        self.bytes().as_usize()
+    }
+}
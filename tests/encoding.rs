@@ -0,0 +1,350 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The `a0-a7` registers of every function of each extension, declared with
+//! `sbi_encoding_fixtures!`. The register positions follow the SBI specification, or the draft
+//! for the TEE and Attestation extensions, and are written out here rather than derived from the
+//! decoder so that any change to the encoding of a call shows up as a failed fixture. The error
+//! codes returned in `a0` are checked against the specification's table in the same way.
+
+use sbi_rs::*;
+
+sbi_encoding_fixtures! {
+    name: base_encoding,
+    SbiMessage::Base(BaseFunction::GetSpecificationVersion) => [0, 0, 0, 0, 0, 0, 0, EXT_BASE],
+    SbiMessage::Base(BaseFunction::GetImplementationID) => [0, 0, 0, 0, 0, 0, 1, EXT_BASE],
+    SbiMessage::Base(BaseFunction::GetImplementationVersion) => [0, 0, 0, 0, 0, 0, 2, EXT_BASE],
+    SbiMessage::Base(BaseFunction::ProbeSbiExtension(EXT_TIME))
+        => [EXT_TIME, 0, 0, 0, 0, 0, 3, EXT_BASE],
+    SbiMessage::Base(BaseFunction::GetMachineVendorID) => [0, 0, 0, 0, 0, 0, 4, EXT_BASE],
+    SbiMessage::Base(BaseFunction::GetMachineArchitectureID) => [0, 0, 0, 0, 0, 0, 5, EXT_BASE],
+    SbiMessage::Base(BaseFunction::GetMachineImplementationID) => [0, 0, 0, 0, 0, 0, 6, EXT_BASE],
+}
+
+sbi_encoding_fixtures! {
+    name: legacy_encoding,
+    SbiMessage::PutChar(b'x' as u64) => [0x78, 0, 0, 0, 0, 0, 0, EXT_PUT_CHAR],
+}
+
+sbi_encoding_fixtures! {
+    name: time_encoding,
+    SbiMessage::Time(TimeFunction::SetTimer { stime_value: 0x1234 })
+        => [0x1234, 0, 0, 0, 0, 0, 0, EXT_TIME],
+}
+
+sbi_encoding_fixtures! {
+    name: ipi_encoding,
+    SbiMessage::Ipi(IpiFunction::SendIpi { hart_mask: HartMask::from_raw(0b101, 4) })
+        => [0b101, 4, 0, 0, 0, 0, 0, EXT_IPI],
+}
+
+sbi_encoding_fixtures! {
+    name: rfence_encoding,
+    SbiMessage::Rfence(RfenceFunction::RemoteFenceI { hart_mask: HartMask::from_raw(1, 2) })
+        => [1, 2, 0, 0, 0, 0, 0, EXT_RFENCE],
+    SbiMessage::Rfence(RfenceFunction::RemoteSfenceVma {
+        hart_mask: HartMask::from_raw(1, 2),
+        start_addr: 0x1000,
+        size: 0x2000,
+    }) => [1, 2, 0x1000, 0x2000, 0, 0, 1, EXT_RFENCE],
+    SbiMessage::Rfence(RfenceFunction::RemoteSfenceVmaAsid {
+        hart_mask: HartMask::from_raw(1, 2),
+        start_addr: 0x1000,
+        size: 0x2000,
+        asid: 7,
+    }) => [1, 2, 0x1000, 0x2000, 7, 0, 2, EXT_RFENCE],
+    SbiMessage::Rfence(RfenceFunction::RemoteHfenceGvmaVmid {
+        hart_mask: HartMask::from_raw(1, 2),
+        start_addr: 0x1000,
+        size: 0x2000,
+        vmid: 7,
+    }) => [1, 2, 0x1000, 0x2000, 7, 0, 3, EXT_RFENCE],
+    SbiMessage::Rfence(RfenceFunction::RemoteHfenceGvma {
+        hart_mask: HartMask::from_raw(1, 2),
+        start_addr: 0x1000,
+        size: 0x2000,
+    }) => [1, 2, 0x1000, 0x2000, 0, 0, 4, EXT_RFENCE],
+    SbiMessage::Rfence(RfenceFunction::RemoteHfenceVvmaAsid {
+        hart_mask: HartMask::from_raw(1, 2),
+        start_addr: 0x1000,
+        size: 0x2000,
+        asid: 7,
+    }) => [1, 2, 0x1000, 0x2000, 7, 0, 5, EXT_RFENCE],
+    SbiMessage::Rfence(RfenceFunction::RemoteHfenceVvma {
+        hart_mask: HartMask::from_raw(1, 2),
+        start_addr: 0x1000,
+        size: 0x2000,
+    }) => [1, 2, 0x1000, 0x2000, 0, 0, 6, EXT_RFENCE],
+}
+
+sbi_encoding_fixtures! {
+    name: hsm_encoding,
+    SbiMessage::HartState(StateFunction::HartStart {
+        hart_id: 1,
+        start_addr: 0x8020_0000,
+        opaque: 5,
+    }) => [1, 0x8020_0000, 5, 0, 0, 0, 0, EXT_HART_STATE],
+    SbiMessage::HartState(StateFunction::HartStop) => [0, 0, 0, 0, 0, 0, 1, EXT_HART_STATE],
+    SbiMessage::HartState(StateFunction::HartStatus { hart_id: 1 })
+        => [1, 0, 0, 0, 0, 0, 2, EXT_HART_STATE],
+    SbiMessage::HartState(StateFunction::HartSuspend {
+        suspend_type: SuspendType::DefaultNonRetentive,
+        resume_addr: 0x8020_0000,
+        opaque: 5,
+    }) => [0x8000_0000, 0x8020_0000, 5, 0, 0, 0, 3, EXT_HART_STATE],
+}
+
+sbi_encoding_fixtures! {
+    name: srst_encoding,
+    SbiMessage::Reset(ResetFunction::Reset {
+        reset_type: ResetType::WarmReset,
+        reason: ResetReason::SystemFailure,
+    }) => [2, 1, 0, 0, 0, 0, 0, EXT_RESET],
+}
+
+sbi_encoding_fixtures! {
+    name: dbcn_encoding,
+    SbiMessage::DebugConsole(DebugConsoleFunction::PutString {
+        len: Len::new(5),
+        addr: PhysAddr::new(0x8000_1000),
+    }) => [5, 0x8000_1000, 0, 0, 0, 0, 0, EXT_DBCN],
+}
+
+#[cfg(feature = "pmu")]
+sbi_encoding_fixtures! {
+    name: pmu_encoding,
+    SbiMessage::Pmu(PmuFunction::GetNumCounters) => [0, 0, 0, 0, 0, 0, 0, EXT_PMU],
+    SbiMessage::Pmu(PmuFunction::GetCounterInfo(3)) => [3, 0, 0, 0, 0, 0, 1, EXT_PMU],
+    SbiMessage::Pmu(PmuFunction::ConfigureMatchingCounters {
+        counter_index: 3,
+        counter_mask: 0b11,
+        config_flags: PmuCounterConfigFlags::default().set_clear_value(),
+        event_type: PmuEventType::Firmware(PmuFirmware::SetTimer),
+        event_data: 9,
+    }) => [3, 0b11, 0b10, 0xf_0005, 9, 0, 2, EXT_PMU],
+    SbiMessage::Pmu(PmuFunction::StartCounters {
+        counter_index: 3,
+        counter_mask: 0b11,
+        start_flags: PmuCounterStartFlags::default().set_init_value(),
+        initial_value: 0x100,
+    }) => [3, 0b11, 1, 0x100, 0, 0, 3, EXT_PMU],
+    SbiMessage::Pmu(PmuFunction::StopCounters {
+        counter_index: 3,
+        counter_mask: 0b11,
+        stop_flags: PmuCounterStopFlags::default().set_reset_flag(),
+    }) => [3, 0b11, 1, 0, 0, 0, 4, EXT_PMU],
+    SbiMessage::Pmu(PmuFunction::ReadFirmwareCounter(3)) => [3, 0, 0, 0, 0, 0, 5, EXT_PMU],
+    SbiMessage::Pmu(PmuFunction::ReadFirmwareCounterHigh(3)) => [3, 0, 0, 0, 0, 0, 6, EXT_PMU],
+}
+
+#[cfg(feature = "nacl")]
+sbi_encoding_fixtures! {
+    name: nacl_encoding,
+    SbiMessage::Nacl(NaclFunction::SetShmem { shmem_pfn: 0x80010 })
+        => [0x80010, 0, 0, 0, 0, 0, 0, EXT_NACL],
+    SbiMessage::Nacl(NaclFunction::SyncCsr { csr_num: 0x200 })
+        => [0x200, 0, 0, 0, 0, 0, 2, EXT_NACL],
+}
+
+#[cfg(feature = "tee")]
+sbi_encoding_fixtures! {
+    name: tee_host_encoding,
+    SbiMessage::TeeHost(TeeHostFunction::TsmGetInfo { dest_addr: PhysAddr::new(0x1000), len: Len::new(32) })
+        => [0x1000, 32, 0, 0, 0, 0, 0, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TsmConvertPages { page_addr: PhysAddr::new(0x1000), num_pages: 4 })
+        => [0x1000, 4, 0, 0, 0, 0, 1, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TsmReclaimPages { page_addr: PhysAddr::new(0x1000), num_pages: 4 })
+        => [0x1000, 4, 0, 0, 0, 0, 2, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TsmInitiateFence) => [0, 0, 0, 0, 0, 0, 3, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TsmLocalFence) => [0, 0, 0, 0, 0, 0, 4, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmCreate { params_addr: PhysAddr::new(0x1000), len: Len::new(16) })
+        => [0x1000, 16, 0, 0, 0, 0, 5, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::Finalize {
+        guest_id: 1,
+        entry_sepc: 0x8020_0000,
+        entry_arg: 5,
+    }) => [1, 0x8020_0000, 5, 0, 0, 0, 6, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmDestroy { guest_id: 1 })
+        => [1, 0, 0, 0, 0, 0, 7, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmAddMemoryRegion {
+        guest_id: 1,
+        guest_addr: PhysAddr::new(0x8000_0000),
+        len: Len::new(0x1000),
+    }) => [1, 0x8000_0000, 0x1000, 0, 0, 0, 8, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::AddPageTablePages {
+        guest_id: 1,
+        page_addr: PhysAddr::new(0x1000),
+        num_pages: 4,
+    }) => [1, 0x1000, 4, 0, 0, 0, 9, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmAddMeasuredPages {
+        guest_id: 1,
+        src_addr: PhysAddr::new(0x1000),
+        dest_addr: PhysAddr::new(0x2000),
+        page_type: TsmPageType::Page2M,
+        num_pages: 4,
+        guest_addr: PhysAddr::new(0x8000_0000),
+    }) => [1, 0x1000, 0x2000, 1, 4, 0x8000_0000, 10, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmAddZeroPages {
+        guest_id: 1,
+        page_addr: PhysAddr::new(0x1000),
+        page_type: TsmPageType::Page2M,
+        num_pages: 4,
+        guest_addr: PhysAddr::new(0x8000_0000),
+    }) => [1, 0x1000, 1, 4, 0x8000_0000, 0, 11, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmAddSharedPages {
+        guest_id: 1,
+        page_addr: PhysAddr::new(0x1000),
+        page_type: TsmPageType::Page2M,
+        num_pages: 4,
+        guest_addr: PhysAddr::new(0x8000_0000),
+    }) => [1, 0x1000, 1, 4, 0x8000_0000, 0, 12, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmAddEmulatedMmioRegion {
+        guest_id: 1,
+        guest_addr: PhysAddr::new(0x1000_0000),
+        len: Len::new(0x1000),
+    }) => [1, 0x1000_0000, 0x1000, 0, 0, 0, 13, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmRemoveSharedPages {
+        guest_id: 1,
+        guest_addr: PhysAddr::new(0x8000_0000),
+        page_type: TsmPageType::Page2M,
+        num_pages: 4,
+    }) => [1, 0x8000_0000, 1, 4, 0, 0, 14, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmCpuCreate {
+        guest_id: 1,
+        vcpu_id: 2,
+        state_page_addr: PhysAddr::new(0x1000),
+    }) => [1, 2, 0x1000, 0, 0, 0, 15, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmCpuRun { guest_id: 1, vcpu_id: 2 })
+        => [1, 2, 0, 0, 0, 0, 16, EXT_TEE_HOST],
+    SbiMessage::TeeHost(TeeHostFunction::TvmInitiateFence { guest_id: 1 })
+        => [1, 0, 0, 0, 0, 0, 17, EXT_TEE_HOST],
+}
+
+#[cfg(feature = "tee")]
+sbi_encoding_fixtures! {
+    name: tee_interrupt_encoding,
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmAiaInit {
+        tvm_id: 1,
+        params_addr: PhysAddr::new(0x1000),
+        len: Len::new(32),
+    }) => [1, 0x1000, 32, 0, 0, 0, 0, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuSetImsicAddr {
+        tvm_id: 1,
+        vcpu_id: 2,
+        imsic_addr: PhysAddr::new(0x2800_0000),
+    }) => [1, 2, 0x2800_0000, 0, 0, 0, 1, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TsmConvertImsic { imsic_addr: PhysAddr::new(0x2800_0000) })
+        => [0x2800_0000, 0, 0, 0, 0, 0, 2, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TsmReclaimImsic { imsic_addr: PhysAddr::new(0x2800_0000) })
+        => [0x2800_0000, 0, 0, 0, 0, 0, 3, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuBindImsic {
+        tvm_id: 1,
+        vcpu_id: 2,
+        imsic_mask: ImsicFileMask::from_raw_value(0b110).unwrap(),
+    }) => [1, 2, 0b110, 0, 0, 0, 4, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuUnbindImsicBegin { tvm_id: 1, vcpu_id: 2 })
+        => [1, 2, 0, 0, 0, 0, 5, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuUnbindImsicEnd { tvm_id: 1, vcpu_id: 2 })
+        => [1, 2, 0, 0, 0, 0, 6, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuInjectExternalInterrupt {
+        tvm_id: 1,
+        vcpu_id: 2,
+        interrupt_id: 9,
+    }) => [1, 2, 9, 0, 0, 0, 7, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuRebindImsicBegin {
+        tvm_id: 1,
+        vcpu_id: 2,
+        imsic_mask: ImsicFileMask::from_raw_value(0b110).unwrap(),
+    }) => [1, 2, 0b110, 0, 0, 0, 8, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuRebindImsicEnd { tvm_id: 1, vcpu_id: 2 })
+        => [1, 2, 0, 0, 0, 0, 9, EXT_TEE_INTERRUPT],
+    SbiMessage::TeeInterrupt(TeeInterruptFunction::TvmCpuRebindImsicClone { tvm_id: 1, vcpu_id: 2 })
+        => [1, 2, 0, 0, 0, 0, 10, EXT_TEE_INTERRUPT],
+}
+
+#[cfg(feature = "tee")]
+sbi_encoding_fixtures! {
+    name: tee_guest_encoding,
+    SbiMessage::TeeGuest(TeeGuestFunction::AddMmioRegion { addr: PhysAddr::new(0x1000_0000), len: Len::new(0x1000) })
+        => [0x1000_0000, 0x1000, 0, 0, 0, 0, 0, EXT_TEE_GUEST],
+    SbiMessage::TeeGuest(TeeGuestFunction::RemoveMmioRegion { addr: PhysAddr::new(0x1000_0000), len: Len::new(0x1000) })
+        => [0x1000_0000, 0x1000, 0, 0, 0, 0, 1, EXT_TEE_GUEST],
+    SbiMessage::TeeGuest(TeeGuestFunction::ShareMemory { addr: PhysAddr::new(0x8000_0000), len: Len::new(0x1000) })
+        => [0x8000_0000, 0x1000, 0, 0, 0, 0, 2, EXT_TEE_GUEST],
+    SbiMessage::TeeGuest(TeeGuestFunction::UnshareMemory { addr: PhysAddr::new(0x8000_0000), len: Len::new(0x1000) })
+        => [0x8000_0000, 0x1000, 0, 0, 0, 0, 3, EXT_TEE_GUEST],
+    SbiMessage::TeeGuest(TeeGuestFunction::AllowExternalInterrupt { id: -1 })
+        => [u64::MAX, 0, 0, 0, 0, 0, 4, EXT_TEE_GUEST],
+    SbiMessage::TeeGuest(TeeGuestFunction::DenyExternalInterrupt { id: 9 })
+        => [9, 0, 0, 0, 0, 0, 5, EXT_TEE_GUEST],
+}
+
+#[cfg(feature = "attestation")]
+sbi_encoding_fixtures! {
+    name: attestation_encoding,
+    SbiMessage::Attestation(AttestationFunction::GetCapabilities {
+        caps_addr_out: 0x1000,
+        caps_size: 64,
+    }) => [0x1000, 64, 0, 0, 0, 0, 0, EXT_ATTESTATION],
+    SbiMessage::Attestation(AttestationFunction::GetEvidence {
+        cert_request_addr: 0x1000,
+        cert_request_size: 64,
+        request_data_addr: 0x2000,
+        evidence_format: 1,
+        cert_addr_out: 0x3000,
+        cert_size: 4096,
+    }) => [0x1000, 64, 0x2000, 1, 0x3000, 4096, 1, EXT_ATTESTATION],
+    SbiMessage::Attestation(AttestationFunction::ExtendMeasurement {
+        measurement_data_addr: 0x1000,
+        measurement_data_size: 48,
+        measurement_index: 3,
+    }) => [0x1000, 48, 3, 0, 0, 0, 2, EXT_ATTESTATION],
+    SbiMessage::Attestation(AttestationFunction::ReadMeasurement {
+        measurement_data_addr_out: 0x1000,
+        measurement_data_size: 48,
+        measurement_index: 3,
+    }) => [0x1000, 48, 3, 0, 0, 0, 3, EXT_ATTESTATION],
+}
+
+// The `SBI_ERR_*` values of the SBI specification.
+const SPEC_ERROR_CODES: &[(Error, i64)] = &[
+    (Error::Failed, -1),
+    (Error::NotSupported, -2),
+    (Error::InvalidParam, -3),
+    (Error::Denied, -4),
+    (Error::InvalidAddress, -5),
+    (Error::AlreadyAvailable, -6),
+    (Error::AlreadyStarted, -7),
+    (Error::AlreadyStopped, -8),
+    (Error::NoShmem, -9),
+    (Error::InvalidState, -10),
+    (Error::BadRange, -11),
+    (Error::Timeout, -12),
+    (Error::Io, -13),
+    (Error::DeniedLocked, -14),
+];
+
+#[test]
+fn error_codes() {
+    for &(error, code) in SPEC_ERROR_CODES {
+        assert_eq!(error.code(), code, "code of {error:?}");
+        assert_eq!(Error::from_code(code), error, "error for code {code}");
+    }
+    let code = Error::InsufficientBufferCapacity.code();
+    assert!(SPEC_ERROR_CODES.iter().all(|&(_, c)| c != code));
+    assert_eq!(Error::from_code(code), Error::InsufficientBufferCapacity);
+    assert_eq!(Error::from_code(-15), Error::Unknown(-15));
+    assert_eq!(Error::Unknown(-15).code(), -15);
+}
+
+#[test]
+fn unknown_errors_never_encode_as_success() {
+    for value in [0, 1, i64::MAX] {
+        assert_eq!(Error::Unknown(value).code(), Error::Failed.code());
+        assert_ne!(
+            SbiReturn::from(Error::Unknown(value)).error_code,
+            SBI_SUCCESS
+        );
+    }
+}
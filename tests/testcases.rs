@@ -0,0 +1,166 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Golden request/response pairs for each extension, declared with `sbi_testcase!`.
+
+#![cfg(feature = "handler")]
+
+use sbi_rs::*;
+
+sbi_testcase! {
+    name: base_get_spec_version,
+    regs_in: [0, 0, 0, 0, 0, 0, 0, EXT_BASE],
+    message: "Base(GetSpecificationVersion)",
+    regs_out: [0, 0x0200_0000],
+    result: Ok(0x0200_0000),
+}
+
+sbi_testcase! {
+    name: base_probe_extension,
+    regs_in: [EXT_HART_STATE, 0, 0, 0, 0, 0, 3, EXT_BASE],
+    message: "Base(ProbeSbiExtension(4739917))",
+    regs_out: [0, 1],
+    result: Ok(1),
+}
+
+sbi_testcase! {
+    name: base_unknown_function,
+    regs_in: [0, 0, 0, 0, 0, 0, 7, EXT_BASE],
+    error: Error::NotSupported,
+}
+
+#[cfg(not(feature = "unknown"))]
+sbi_testcase! {
+    name: unknown_extension,
+    regs_in: [0, 0, 0, 0, 0, 0, 0, 0x1234_5678],
+    error: Error::NotSupported,
+}
+
+#[cfg(feature = "unknown")]
+sbi_testcase! {
+    name: unknown_extension,
+    regs_in: [1, 2, 3, 4, 5, 6, 7, 0x1234_5678],
+    message: "Unknown { eid: 305419896, fid: 7, args: [1, 2, 3, 4, 5, 6] }",
+    regs_out: [0, 9],
+    result: Ok(9),
+}
+
+#[cfg(all(not(feature = "pmu"), not(feature = "unknown")))]
+sbi_testcase! {
+    name: disabled_extension,
+    regs_in: [0, 0, 0, 0, 0, 0, 0, EXT_PMU],
+    error: Error::NotSupported,
+}
+
+#[cfg(all(not(feature = "pmu"), feature = "unknown"))]
+sbi_testcase! {
+    name: disabled_extension,
+    regs_in: [0, 0, 0, 0, 0, 0, 0, EXT_PMU],
+    message: "Unknown { eid: 5262677, fid: 0, args: [0, 0, 0, 0, 0, 0] }",
+    regs_out: [0, 9],
+    result: Ok(9),
+}
+
+sbi_testcase! {
+    name: time_set_timer,
+    regs_in: [0x1000, 0, 0, 0, 0, 0, 0, EXT_TIME],
+    message: "Time(SetTimer { stime_value: 4096 })",
+    regs_out: [0, 0],
+    result: Ok(0),
+}
+
+sbi_testcase! {
+    name: ipi_send_ipi,
+    regs_in: [0b11, 2, 0, 0, 0, 0, 0, EXT_IPI],
+    message: "Ipi(SendIpi { hart_mask: HartMask { mask: 3, base: 2 } })",
+    regs_out: [0, 0],
+    result: Ok(0),
+}
+
+sbi_testcase! {
+    name: rfence_remote_sfence_vma,
+    regs_in: [1, 0, 0x8000_0000, 0x1000, 0, 0, 1, EXT_RFENCE],
+    message: "Rfence(RemoteSfenceVma { hart_mask: HartMask { mask: 1, base: 0 }, \
+              start_addr: 2147483648, size: 4096 })",
+    regs_out: [0, 0],
+    result: Ok(0),
+}
+
+sbi_testcase! {
+    name: hsm_hart_start,
+    regs_in: [1, 0x8020_0000, 0, 0, 0, 0, 0, EXT_HART_STATE],
+    message: "HartState(HartStart { hart_id: 1, start_addr: 2149580800, opaque: 0 })",
+    regs_out: [0, 0],
+    result: Ok(0),
+}
+
+sbi_testcase! {
+    name: hsm_hart_start_already_started,
+    regs_in: [1, 0x8020_0000, 0, 0, 0, 0, 0, EXT_HART_STATE],
+    message: "HartState(HartStart { hart_id: 1, start_addr: 2149580800, opaque: 0 })",
+    regs_out: [-6i64 as u64, 0],
+    result: Err(Error::AlreadyAvailable),
+}
+
+sbi_testcase! {
+    name: hsm_hart_suspend_reserved_type,
+    regs_in: [0x1, 0, 0, 0, 0, 0, 3, EXT_HART_STATE],
+    error: Error::InvalidParam,
+}
+
+sbi_testcase! {
+    name: srst_shutdown,
+    regs_in: [0, 0, 0, 0, 0, 0, 0, EXT_RESET],
+    message: "Reset(Reset { reset_type: Shutdown, reason: NoReason })",
+    regs_out: [0, 0],
+    result: Ok(0),
+}
+
+sbi_testcase! {
+    name: srst_warm_reboot_failure,
+    regs_in: [2, 1, 0, 0, 0, 0, 0, EXT_RESET],
+    message: "Reset(Reset { reset_type: WarmReset, reason: SystemFailure })",
+    regs_out: [Error::Failed.code() as u64, 0],
+    result: Err(Error::Failed),
+}
+
+// Callers retrying incomplete operations poll for SBI_ERR_TIMEOUT.
+sbi_testcase! {
+    name: retry_error_is_timeout,
+    regs_in: [0, 0, 0, 0, 0, 0, 0, EXT_BASE],
+    message: "Base(GetSpecificationVersion)",
+    regs_out: [-12i64 as u64, 0],
+    result: Err(handler::RETRY_ERROR),
+}
+
+#[cfg(feature = "pmu")]
+sbi_testcase! {
+    name: pmu_config_matching_vsinh,
+    regs_in: [0, 1, 0x10, 1, 0, 0, 2, EXT_PMU],
+    message: "Pmu(ConfigureMatchingCounters { counter_index: 0, counter_mask: 1, config_flags: PmuCounterConfigFlags(16), event_type: Hardware(CpuCycles), event_data: 0 })",
+    regs_out: [0, 3],
+    result: Ok(3),
+}
+
+#[cfg(feature = "pmu")]
+sbi_testcase! {
+    name: pmu_config_matching_reserved_flag,
+    regs_in: [0, 1, 0x100, 1, 0, 0, 2, EXT_PMU],
+    error: Error::InvalidParam,
+}
+
+sbi_testcase! {
+    name: dbcn_console_write,
+    regs_in: [5, 0x8000_1000, 0, 0, 0, 0, 0, EXT_DBCN],
+    message: "DebugConsole(PutString { len: 5, addr: 0x80001000 })",
+    regs_out: [0, 5],
+    result: Ok(5),
+}
+
+#[cfg(feature = "tee")]
+sbi_testcase! {
+    name: teeh_convert_zero_pages,
+    regs_in: [0x1000, 0, 0, 0, 0, 0, 1, EXT_TEE_HOST],
+    error: Error::InvalidParam,
+}
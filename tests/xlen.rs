@@ -0,0 +1,69 @@
+// Copyright (c) 2022 by Rivos Inc.
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checks that the `usize` conversions of argument values fail, rather than truncate, on hosts
+//! whose `usize` is narrower than the 64-bit values held in registers. Only built for 32-bit
+//! targets; CI runs it with `--target i686-unknown-linux-gnu`.
+
+#![cfg(target_pointer_width = "32")]
+
+use sbi_rs::*;
+
+/// The smallest value that doesn't fit in a 32-bit `usize`.
+const TOO_WIDE: u64 = 1 << 32;
+
+#[test]
+fn values_wider_than_usize() {
+    assert_eq!(u64::from(u32::MAX).as_usize(), Ok(u32::MAX as usize));
+    assert_eq!(TOO_WIDE.as_usize(), Err(Error::InvalidParam));
+    assert_eq!(PhysAddr::new(TOO_WIDE).as_usize(), Err(Error::InvalidParam));
+    assert_eq!(Len::new(TOO_WIDE).as_usize(), Err(Error::InvalidParam));
+}
+
+#[test]
+fn debug_console() {
+    let f = DebugConsoleFunction::PutString {
+        len: Len::new(TOO_WIDE),
+        addr: PhysAddr::new(TOO_WIDE + 0x1000),
+    };
+    assert_eq!(f.len_usize(), Err(Error::InvalidParam));
+    assert_eq!(f.addr_usize(), Err(Error::InvalidParam));
+}
+
+#[cfg(feature = "tee")]
+#[test]
+fn tee_host() {
+    let f = TeeHostFunction::TsmConvertPages {
+        page_addr: PhysAddr::new(0x8000_0000),
+        num_pages: TOO_WIDE,
+    };
+    assert_eq!(f.num_pages_usize(), Some(Err(Error::InvalidParam)));
+    let f = TeeHostFunction::TvmAddMemoryRegion {
+        guest_id: 1,
+        guest_addr: PhysAddr::new(0x8000_0000),
+        len: Len::new(TOO_WIDE),
+    };
+    assert_eq!(f.len_usize(), Some(Err(Error::InvalidParam)));
+}
+
+#[cfg(feature = "tee")]
+#[test]
+fn tee_guest() {
+    let f = TeeGuestFunction::ShareMemory {
+        addr: PhysAddr::new(0x8000_0000),
+        len: Len::new(TOO_WIDE),
+    };
+    assert_eq!(f.len_usize(), Some(Err(Error::InvalidParam)));
+}
+
+#[cfg(feature = "tee")]
+#[test]
+fn tee_interrupt() {
+    let f = TeeInterruptFunction::TvmAiaInit {
+        tvm_id: 1,
+        params_addr: PhysAddr::new(0x1000),
+        len: Len::new(TOO_WIDE),
+    };
+    assert_eq!(f.len_usize(), Some(Err(Error::InvalidParam)));
+}